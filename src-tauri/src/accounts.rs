@@ -0,0 +1,230 @@
+//! GGG accounts (main, alts, other realms). Each account keeps its own OAuth token and trade
+//! session cookie; commands that talk to GGG use the active account unless told otherwise.
+//! The secrets live in the system credential store (Windows Credential Manager, the macOS
+//! keychain, the Secret Service on Linux), never in `accounts.json`; secrets found there from
+//! older versions are moved over on first load.
+
+use std::sync::Mutex;
+
+use crate::error::WarlordError;
+use crate::app_paths;
+
+const STATE_FILE: &str = "accounts.json";
+/// Service name of the credential store entries, one per account and secret
+const KEYRING_SERVICE: &str = "warlordtools";
+const TOKEN: &str = "token";
+const COOKIE: &str = "cookie";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    pub id: String,
+    pub name: String,
+    /// "intl" or "cn", as in the trade commands
+    pub server: String,
+    /// OAuth bearer token for api.pathofexile.com, kept in the credential store
+    #[serde(default, skip_serializing)]
+    pub access_token: String,
+    /// POESESSID cookie for the trade site, kept in the credential store
+    #[serde(default, skip_serializing)]
+    pub session_cookie: String,
+    pub added_at: u64,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct AccountsState {
+    active: Option<String>,
+    accounts: Vec<Account>,
+}
+
+/// What the frontend sees of an account; secrets stay in the backend.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountInfo {
+    pub id: String,
+    pub name: String,
+    pub server: String,
+    pub has_token: bool,
+    pub has_cookie: bool,
+    pub active: bool,
+}
+
+static STATE: Mutex<Option<AccountsState>> = Mutex::new(None);
+
+fn keyring_entry(id: &str, secret: &str) -> Result<keyring::Entry, WarlordError> {
+    keyring::Entry::new(KEYRING_SERVICE, &format!("{}:{}", id, secret)).map_err(|e| WarlordError::Other { message: format!("无法访问系统凭据存储: {}", e) })
+}
+
+/// Store `value` as the `secret` of account `id`; an empty value removes it.
+fn store_secret(id: &str, secret: &str, value: &str) -> Result<(), WarlordError> {
+    let entry = keyring_entry(id, secret)?;
+    let result = if value.is_empty() {
+        match entry.delete_credential() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            other => other,
+        }
+    } else {
+        entry.set_password(value)
+    };
+    result.map_err(|e| WarlordError::Other { message: format!("无法保存账号凭据: {}", e) })
+}
+
+fn load_secret(id: &str, secret: &str) -> String {
+    let loaded = keyring_entry(id, secret).and_then(|entry| match entry.get_password() {
+        Ok(value) => Ok(value),
+        Err(keyring::Error::NoEntry) => Ok(String::new()),
+        Err(e) => Err(WarlordError::Other { message: e.to_string() }),
+    });
+    loaded.unwrap_or_else(|e| {
+        eprintln!("[WarlordTools] Could not read the {} of account {}: {}", secret, id, e);
+        String::new()
+    })
+}
+
+/// `accounts.json` with the secrets filled in from the credential store. Plaintext secrets
+/// left in the file by older versions are moved into the store and the file is rewritten.
+fn load_state() -> AccountsState {
+    let mut state: AccountsState = app_paths::load_json(STATE_FILE);
+    let mut migrated = false;
+    for account in &mut state.accounts {
+        for (secret, value) in [(TOKEN, &mut account.access_token), (COOKIE, &mut account.session_cookie)] {
+            if value.is_empty() {
+                *value = load_secret(&account.id, secret);
+            } else {
+                match store_secret(&account.id, secret, value) {
+                    Ok(()) => migrated = true,
+                    Err(e) => eprintln!("[WarlordTools] Could not move the {} of account {} to the credential store: {}", secret, account.id, e),
+                }
+            }
+        }
+    }
+    if migrated {
+        if let Err(e) = app_paths::save_json(STATE_FILE, &state) {
+            eprintln!("[WarlordTools] Could not rewrite {} without secrets: {}", STATE_FILE, e);
+        }
+    }
+    state
+}
+
+fn with_state<R>(f: impl FnOnce(&mut AccountsState) -> R) -> R {
+    let mut guard = STATE.lock().unwrap();
+    let state = guard.get_or_insert_with(load_state);
+    f(state)
+}
+
+fn info(state: &AccountsState, account: &Account) -> AccountInfo {
+    AccountInfo {
+        id: account.id.clone(),
+        name: account.name.clone(),
+        server: account.server.clone(),
+        has_token: !account.access_token.is_empty(),
+        has_cookie: !account.session_cookie.is_empty(),
+        active: state.active.as_deref() == Some(account.id.as_str()),
+    }
+}
+
+pub fn list_accounts() -> Vec<AccountInfo> {
+    with_state(|state| state.accounts.iter().map(|a| info(state, a)).collect())
+}
+
+/// Add an account, or update the credentials of the account with the same name and server.
+/// The first account becomes the active one.
+pub fn save_account(name: &str, server: &str, access_token: Option<String>, session_cookie: Option<String>) -> Result<AccountInfo, WarlordError> {
+    if name.trim().is_empty() {
+        return Err(WarlordError::invalid("账号名称不能为空"));
+    }
+    if server != "intl" && server != "cn" {
+        return Err(WarlordError::invalid(format!("未知的服务器: {}", server)));
+    }
+    with_state(|state| {
+        let index = match state.accounts.iter().position(|a| a.name == name && a.server == server) {
+            Some(i) => i,
+            None => {
+                state.accounts.push(Account {
+                    id: app_paths::new_id(),
+                    name: name.to_string(),
+                    server: server.to_string(),
+                    access_token: String::new(),
+                    session_cookie: String::new(),
+                    added_at: app_paths::now_secs(),
+                });
+                state.accounts.len() - 1
+            }
+        };
+        let account = &mut state.accounts[index];
+        if let Some(token) = access_token {
+            store_secret(&account.id, TOKEN, token.trim())?;
+            account.access_token = token.trim().to_string();
+        }
+        if let Some(cookie) = session_cookie {
+            store_secret(&account.id, COOKIE, cookie.trim())?;
+            account.session_cookie = cookie.trim().to_string();
+        }
+        let id = account.id.clone();
+        state.active.get_or_insert(id);
+        app_paths::save_json(STATE_FILE, state)?;
+        Ok(info(state, &state.accounts[index]))
+    })
+}
+
+pub fn remove_account(id: &str) -> Result<(), WarlordError> {
+    with_state(|state| {
+        for secret in [TOKEN, COOKIE] {
+            store_secret(id, secret, "")?;
+        }
+        state.accounts.retain(|a| a.id != id);
+        if state.active.as_deref() == Some(id) {
+            state.active = state.accounts.first().map(|a| a.id.clone());
+        }
+        app_paths::save_json(STATE_FILE, state)
+    })
+}
+
+pub fn set_active_account(id: &str) -> Result<(), WarlordError> {
+    with_state(|state| {
+        if !state.accounts.iter().any(|a| a.id == id) {
+            return Err(WarlordError::invalid("账号不存在"));
+        }
+        state.active = Some(id.to_string());
+        app_paths::save_json(STATE_FILE, state)
+    })
+}
+
+/// The account a command should use: `id` when given, else the active one.
+pub fn resolve(id: Option<&str>) -> Result<Account, WarlordError> {
+    with_state(|state| {
+        let id = id.filter(|i| !i.is_empty()).or(state.active.as_deref()).ok_or_else(|| WarlordError::invalid("请先添加账号"))?;
+        state.accounts.iter().find(|a| a.id == id).cloned().ok_or_else(|| WarlordError::invalid("账号不存在"))
+    })
+}
+
+/// `given` if set, otherwise the active account's cookie when it is on `server`.
+pub fn session_cookie(server: &str, given: &str) -> String {
+    if !given.is_empty() {
+        return given.to_string();
+    }
+    resolve(None).ok().filter(|a| a.server == server).map(|a| a.session_cookie).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info_hides_credentials() {
+        let account = Account {
+            id: "a".to_string(),
+            name: "main".to_string(),
+            server: "intl".to_string(),
+            access_token: "secret".to_string(),
+            session_cookie: String::new(),
+            added_at: 0,
+        };
+        let state = AccountsState { active: Some("a".to_string()), accounts: vec![account.clone()] };
+        let json = serde_json::to_string(&info(&state, &account)).unwrap();
+        assert!(!json.contains("secret"));
+        assert!(json.contains("\"hasToken\":true") && json.contains("\"active\":true"));
+        assert!(!serde_json::to_string(&state).unwrap().contains("secret"));
+    }
+}
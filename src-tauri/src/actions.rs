@@ -0,0 +1,269 @@
+//! Backend action registry for the command palette: the user-facing commands, by id.
+//! Every entry can be listed with `list_actions` and run through the single `invoke` dispatcher;
+//! the entry holds the code it runs, so the list and the dispatcher cannot drift apart.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::error::WarlordError;
+use crate::{
+    app_trash, archive, audio, backup_schedule, block_edit, colorblind, dedupe, downgrade, duplicates, file_ops, filter_format, filter_link, filter_merge, filter_parser, filter_split, filter_transforms, game_migration, game_sync, ggg_api, journal, leveling,
+    library_export, library_git, patches, path_utils, poe_convert, powershell_opener, preprocessor, profile, provenance, quiet_hours, sandbox, search_replace, snippets, sound_profiles, strictness, temp_rules, versions, webdav_sync, workspace_health, workspace_rename,
+};
+
+/// What the action operates on.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Target {
+    /// No selection needed
+    None,
+    File,
+    Folder,
+    Block,
+}
+
+/// Current selection in the frontend.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ActionContext {
+    pub kind: Option<Target>,
+    pub path: Option<String>,
+    pub block_id: Option<usize>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionInfo {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub shortcut: Option<&'static str>,
+    pub targets: &'static [Target],
+    /// Whether the action applies to the context passed to `list_actions`
+    pub applicable: bool,
+}
+
+struct ActionDef {
+    id: &'static str,
+    title: &'static str,
+    shortcut: Option<&'static str>,
+    targets: &'static [Target],
+    /// Runs the action on its argument object; what changes files is one journal operation,
+    /// labelled like the matching command, so it is undone the same way
+    run: fn(&Value) -> Result<Value, WarlordError>,
+}
+
+const ACTIONS: &[ActionDef] = &[
+    // Files and folders
+    ActionDef { id: "file.open", title: "打开文件", shortcut: None, targets: &[Target::File], run: |args| powershell_opener::open_file(&arg_str(args, "path")?).map(|_| Value::Null) },
+    ActionDef { id: "folder.open", title: "打开文件夹", shortcut: None, targets: &[Target::Folder], run: |args| powershell_opener::open_folder(&arg_str(args, "path")?).map(|_| Value::Null) },
+    ActionDef { id: "folder.create", title: "新建文件夹", shortcut: None, targets: &[Target::Folder], run: |args| {
+        let path = arg_str(args, "path")?;
+        sandbox::check_write(&path)?;
+        fs::create_dir_all(path_utils::long_path(&path)).map_err(|e| WarlordError::io(e, &path))?;
+        Ok(Value::Null)
+    } },
+    ActionDef { id: "file.duplicate", title: "创建副本", shortcut: Some("Ctrl+D"), targets: &[Target::File], run: |args| to_value(journal::operation("duplicate", || file_ops::duplicate_filter(Path::new(&arg_str(args, "path")?)))?) },
+    ActionDef { id: "file.rename", title: "重命名并更新引用", shortcut: Some("F2"), targets: &[Target::File], run: |args| to_value(journal::operation("rename", || workspace_rename::rename_managed_file(&arg_str(args, "path")?, &arg_str(args, "newPath")?))?) },
+    ActionDef { id: "file.delete", title: "删除", shortcut: Some("Delete"), targets: &[Target::File, Target::Folder], run: |args| {
+        let paths = [arg_str(args, "path")?];
+        to_value(journal::operation("delete", || file_ops::delete_paths(&paths)))
+    } },
+    ActionDef { id: "file.clearReadonly", title: "清除只读属性", shortcut: None, targets: &[Target::File, Target::Folder], run: |args| to_value(file_ops::clear_readonly(Path::new(&arg_str(args, "path")?))?) },
+    ActionDef { id: "file.linkToGame", title: "链接到游戏文件夹", shortcut: None, targets: &[Target::File], run: |args| {
+        to_value(journal::operation("link", || filter_link::link_filter(Path::new(&arg_str(args, "path")?), Path::new(&arg_str(args, "gameDocumentsPath")?)))?)
+    } },
+    ActionDef { id: "file.versions", title: "查看历史版本", shortcut: None, targets: &[Target::File], run: |args| to_value(versions::list_versions(Path::new(&arg_str(args, "path")?))) },
+    ActionDef { id: "file.restoreVersion", title: "恢复历史版本", shortcut: None, targets: &[Target::File], run: |args| {
+        journal::operation("restore-version", || versions::restore_version(Path::new(&arg_str(args, "path")?), &arg_str(args, "versionId")?)).map(|_| Value::Null)
+    } },
+    ActionDef { id: "pack.export", title: "导出为过滤器包", shortcut: None, targets: &[Target::File], run: |args| to_value(archive::export_pack(&arg::<Vec<String>>(args, "paths")?, Path::new(&arg_str(args, "dest")?))?) },
+    // Editing a filter
+    ActionDef { id: "filter.format", title: "格式化过滤器", shortcut: Some("Shift+Alt+F"), targets: &[Target::File], run: |args| {
+        let options: filter_format::FormatOptions = arg_or_default(args, "options")?;
+        Ok(json!(journal::operation("format", || filter_format::format_file(&arg_str(args, "path")?, &options))?))
+    } },
+    ActionDef { id: "filter.canonicalize", title: "规范化过滤器", shortcut: None, targets: &[Target::File], run: |args| Ok(json!(filter_format::canonicalize_filter(&arg_str(args, "path")?)?)) },
+    ActionDef { id: "filter.minify", title: "压缩过滤器", shortcut: None, targets: &[Target::File], run: |args| {
+        let options: filter_format::MinifyOptions = arg_or_default(args, "options")?;
+        to_value(journal::operation("minify", || filter_format::minify_file(&arg_str(args, "src")?, &arg_str(args, "dest")?, &options))?)
+    } },
+    ActionDef { id: "filter.toJson", title: "导出为 JSON", shortcut: None, targets: &[Target::File], run: |args| to_value(filter_parser::parse_file(&arg_str(args, "path")?)?) },
+    ActionDef { id: "filter.dedupe", title: "删除重复规则块", shortcut: None, targets: &[Target::File], run: |args| to_value(journal::operation("dedupe", || dedupe::dedupe_filter(&arg_str(args, "path")?, arg_or_default(args, "apply")?))?) },
+    ActionDef { id: "filter.sortLists", title: "排序列表值", shortcut: None, targets: &[Target::File], run: |args| to_value(journal::operation("sort-lists", || filter_transforms::sort_value_lists(&arg_str(args, "path")?))?) },
+    ActionDef { id: "filter.replaceColor", title: "替换颜色", shortcut: None, targets: &[Target::File], run: |args| {
+        let scope: Option<String> = arg_or_default(args, "scope")?;
+        to_value(journal::operation("replace-color", || filter_transforms::replace_color(&arg_str(args, "path")?, &arg_str(args, "fromRgba")?, &arg_str(args, "toRgba")?, scope.as_deref()))?)
+    } },
+    ActionDef { id: "filter.swapSound", title: "替换提示音", shortcut: None, targets: &[Target::File], run: |args| {
+        let scope: Option<String> = arg_or_default(args, "scope")?;
+        to_value(journal::operation("swap-sound", || filter_transforms::swap_alert_sound(&arg_str(args, "path")?, &arg_str(args, "from")?, &arg_str(args, "to")?, scope.as_deref()))?)
+    } },
+    ActionDef { id: "filter.adjustVolumes", title: "调整提示音音量", shortcut: None, targets: &[Target::File], run: |args| {
+        let adjust: filter_transforms::VolumeAdjust = arg(args, "adjust")?;
+        to_value(journal::operation("adjust-volumes", || filter_transforms::adjust_alert_volumes(&arg_str(args, "path")?, &adjust))?)
+    } },
+    ActionDef { id: "filter.strictness", title: "设置严格度", shortcut: None, targets: &[Target::File], run: |args| to_value(journal::operation("strictness", || strictness::set_strictness(&arg_str(args, "path")?, arg(args, "level")?))?) },
+    ActionDef { id: "filter.colorblind", title: "生成色盲版本", shortcut: None, targets: &[Target::File], run: |args| {
+        to_value(journal::operation("colorblind", || colorblind::compile_colorblind_variant(&arg_str(args, "path")?, &arg_str(args, "deficiency")?))?)
+    } },
+    ActionDef { id: "filter.quietVariant", title: "生成静音版本", shortcut: None, targets: &[Target::File], run: |args| to_value(journal::operation("quiet-variant", || quiet_hours::compile_quiet_variant(&arg_str(args, "path")?))?) },
+    ActionDef { id: "filter.soundProfiles", title: "应用提示音方案", shortcut: None, targets: &[Target::File], run: |args| to_value(journal::operation("sound-profiles", || sound_profiles::compile_sound_profiles(&arg_str(args, "path")?))?) },
+    ActionDef { id: "filter.compile", title: "编译过滤器", shortcut: Some("Ctrl+Shift+B"), targets: &[Target::File], run: |args| to_value(journal::operation("compile", || preprocessor::compile_filter(&arg_str(args, "src")?, &arg_str(args, "dest")?))?) },
+    ActionDef { id: "filter.convertPoe1", title: "转换为 PoE2 过滤器", shortcut: None, targets: &[Target::File], run: |args| to_value(journal::operation("convert", || poe_convert::convert_poe1_filter(&arg_str(args, "src")?, &arg_str(args, "dest")?))?) },
+    ActionDef { id: "filter.downgrade", title: "降级到旧版本游戏", shortcut: None, targets: &[Target::File], run: |args| {
+        to_value(journal::operation("downgrade", || downgrade::downgrade_filter(&arg_str(args, "src")?, &arg_str(args, "dest")?, &arg_str(args, "version")?))?)
+    } },
+    ActionDef { id: "filter.split", title: "按章节拆分", shortcut: None, targets: &[Target::File], run: |args| to_value(journal::operation("split", || filter_split::split_filter(&arg_str(args, "path")?, &arg_str(args, "destDir")?))?) },
+    ActionDef { id: "filter.join", title: "合并拆分的章节", shortcut: None, targets: &[Target::Folder], run: |args| to_value(journal::operation("join", || filter_split::join_filter(&arg_str(args, "path")?, &arg_str(args, "dest")?))?) },
+    ActionDef { id: "filter.merge", title: "合并过滤器", shortcut: None, targets: &[Target::File], run: |args| {
+        to_value(journal::operation("merge", || filter_merge::merge_filters(&arg_str(args, "path")?, &arg_str(args, "addition")?, &arg_str(args, "dest")?))?)
+    } },
+    ActionDef { id: "filter.upload", title: "上传到游戏账号", shortcut: None, targets: &[Target::File], run: |args| {
+        let account: Option<String> = arg_or_default(args, "accountId")?;
+        Ok(json!(ggg_api::upload_filter(account.as_deref(), &arg_str(args, "path")?, &arg_str(args, "name")?)?))
+    } },
+    // The selected block
+    ActionDef { id: "block.moveUp", title: "上移规则块", shortcut: Some("Alt+Up"), targets: &[Target::Block], run: |args| move_block(args, true) },
+    ActionDef { id: "block.moveDown", title: "下移规则块", shortcut: Some("Alt+Down"), targets: &[Target::Block], run: |args| move_block(args, false) },
+    ActionDef { id: "block.provenance", title: "规则块来源", shortcut: None, targets: &[Target::Block], run: |args| {
+        let (doc, id) = (filter_parser::parse_file(&arg_str(args, "path")?)?, arg::<usize>(args, "blockId")?);
+        let block = doc.blocks.get(id).ok_or_else(|| WarlordError::invalid(format!("没有第 {} 个规则块", id)))?;
+        to_value(provenance::read(block))
+    } },
+    ActionDef { id: "snippet.insert", title: "插入片段", shortcut: None, targets: &[Target::File, Target::Block], run: |args| {
+        let position: snippets::SnippetPosition = arg(args, "position")?;
+        to_value(journal::operation("insert-snippet", || snippets::insert_snippet(&arg_str(args, "path")?, &arg_str(args, "snippetId")?, &position))?)
+    } },
+    // A workspace folder
+    ActionDef { id: "workspace.health", title: "检查工作区", shortcut: None, targets: &[Target::None, Target::Folder], run: |args| {
+        let workspace: Option<String> = arg_or_default(args, "path")?;
+        to_value(workspace_health::workspace_health(workspace.as_deref())?)
+    } },
+    ActionDef { id: "workspace.search", title: "在库中搜索", shortcut: Some("Ctrl+Shift+F"), targets: &[Target::Folder], run: |args| {
+        let options: search_replace::SearchOptions = arg_or_default(args, "options")?;
+        to_value(search_replace::search_library(&arg_str(args, "path")?, &arg_str(args, "query")?, &options)?)
+    } },
+    ActionDef { id: "workspace.searchReplace", title: "在库中替换", shortcut: Some("Ctrl+Shift+H"), targets: &[Target::Folder], run: |args| {
+        let options: search_replace::SearchOptions = arg_or_default(args, "options")?;
+        to_value(journal::operation("search-replace", || search_replace::search_replace(&arg_str(args, "path")?, &arg_str(args, "pattern")?, &arg_str(args, "replacement")?, &options))?)
+    } },
+    ActionDef { id: "workspace.renameBasetype", title: "重命名物品基底", shortcut: None, targets: &[Target::Folder], run: |args| {
+        to_value(journal::operation("rename-basetype", || filter_transforms::rename_basetype(&arg_str(args, "path")?, &arg_str(args, "from")?, &arg_str(args, "to")?))?)
+    } },
+    ActionDef { id: "workspace.duplicates", title: "查找重复文件", shortcut: None, targets: &[Target::Folder], run: |args| to_value(duplicates::find_duplicates(Path::new(&arg_str(args, "path")?))?) },
+    // History, trash and git
+    ActionDef { id: "history.undo", title: "撤销上一个操作", shortcut: Some("Ctrl+Z"), targets: &[Target::None], run: |_| to_value(journal::undo_last_operation()?) },
+    ActionDef { id: "history.list", title: "操作记录", shortcut: None, targets: &[Target::None], run: |args| to_value(journal::list_operations(arg_or_default::<Option<usize>>(args, "limit")?.unwrap_or(50))) },
+    ActionDef { id: "trash.list", title: "查看回收站", shortcut: None, targets: &[Target::None], run: |_| to_value(app_trash::list_trash()) },
+    ActionDef { id: "trash.restore", title: "从回收站恢复", shortcut: None, targets: &[Target::None], run: |args| Ok(json!(app_trash::restore_from_trash(&arg_str(args, "id")?)?)) },
+    ActionDef { id: "trash.empty", title: "清空回收站", shortcut: None, targets: &[Target::None], run: |args| to_value(app_trash::empty_trash(arg_or_default::<Option<Vec<String>>>(args, "ids")?.as_deref())?) },
+    ActionDef { id: "git.status", title: "库的 Git 状态", shortcut: None, targets: &[Target::None], run: |_| to_value(library_git::status()?) },
+    ActionDef { id: "git.init", title: "为库启用 Git", shortcut: None, targets: &[Target::None], run: |_| to_value(library_git::init()?) },
+    ActionDef { id: "git.commit", title: "提交库的修改", shortcut: None, targets: &[Target::None], run: |args| to_value(library_git::commit(&arg_str(args, "message")?)?) },
+    ActionDef { id: "git.switchLeague", title: "切换赛季分支", shortcut: None, targets: &[Target::None], run: |args| to_value(library_git::switch_branch(&library_git::league_branch(&arg_str(args, "league")?))?) },
+    // Backups, sync and the game folder
+    ActionDef { id: "backup.runNow", title: "立即备份", shortcut: None, targets: &[Target::None], run: |_| to_value(backup_schedule::run_now()?) },
+    ActionDef { id: "library.export", title: "导出整个库", shortcut: None, targets: &[Target::None], run: |args| {
+        let password: Option<String> = arg_or_default(args, "password")?;
+        to_value(library_export::export_library(Path::new(&arg_str(args, "dest")?), password.as_deref())?)
+    } },
+    ActionDef { id: "profile.export", title: "导出配置方案", shortcut: None, targets: &[Target::None], run: |args| to_value(profile::export_profile(Path::new(&arg_str(args, "dest")?))?) },
+    ActionDef { id: "webdav.sync", title: "WebDAV 同步", shortcut: None, targets: &[Target::None], run: |args| to_value(journal::operation("sync", || webdav_sync::sync(arg_or_default(args, "apply")?))?) },
+    ActionDef { id: "game.sync", title: "同步到游戏文件夹", shortcut: None, targets: &[Target::None], run: |args| {
+        let directions: HashMap<String, game_sync::Direction> = arg_or_default(args, "directions")?;
+        to_value(journal::operation("game-sync", || game_sync::sync_to_game(&arg_str(args, "profile")?, arg_or_default(args, "dryRun")?, &directions))?)
+    } },
+    ActionDef { id: "game.migrate", title: "从游戏文件夹迁移过滤器", shortcut: None, targets: &[Target::None, Target::Folder], run: |args| {
+        let game_dir: Option<String> = arg_or_default(args, "path")?;
+        to_value(journal::operation("migrate", || game_migration::migrate_from_game_folder(game_dir.as_deref().map(Path::new), arg_or_default(args, "replaceWithLinks")?))?)
+    } },
+    // Patches, temporary rules and leveling
+    ActionDef { id: "patch.apply", title: "应用补丁", shortcut: None, targets: &[Target::None], run: |args| to_value(journal::operation("apply-patch", || patches::apply_patch(&arg_str(args, "name")?))?) },
+    ActionDef { id: "patch.invert", title: "撤销补丁", shortcut: None, targets: &[Target::None], run: |args| to_value(journal::operation("invert-patch", || patches::invert_patch(&arg_str(args, "name")?))?) },
+    ActionDef { id: "tempRule.add", title: "添加临时规则", shortcut: None, targets: &[Target::File], run: |args| to_value(temp_rules::add_temp_rule(&arg_str(args, "filter")?, &arg_str(args, "rule")?, arg(args, "ttl")?)?) },
+    ActionDef { id: "tempRule.list", title: "查看临时规则", shortcut: None, targets: &[Target::None], run: |_| to_value(temp_rules::list_temp_rules()) },
+    ActionDef { id: "tempRule.remove", title: "移除临时规则", shortcut: None, targets: &[Target::None], run: |args| temp_rules::remove_temp_rule(&arg_str(args, "id")?).map(|_| Value::Null) },
+    ActionDef { id: "leveling.status", title: "升级阶段状态", shortcut: None, targets: &[Target::None], run: |_| to_value(leveling::get_leveling_status()) },
+    // Sounds
+    ActionDef { id: "sound.play", title: "试听", shortcut: Some("Space"), targets: &[Target::File], run: |args| audio::play_sound(Path::new(&arg_str(args, "path")?), arg_or_default(args, "volume")?).map(|_| Value::Null) },
+    ActionDef { id: "sound.stop", title: "停止播放", shortcut: Some("Escape"), targets: &[Target::None], run: |_| {
+        audio::stop_sound();
+        Ok(Value::Null)
+    } },
+    ActionDef { id: "quietHours.status", title: "静音时段状态", shortcut: None, targets: &[Target::None], run: |_| to_value(quiet_hours::status()) },
+];
+
+fn applies(def: &ActionDef, ctx: &ActionContext) -> bool {
+    let kind = ctx.kind.unwrap_or(Target::None);
+    def.targets.contains(&Target::None) || def.targets.contains(&kind)
+}
+
+pub fn list_actions(ctx: &ActionContext) -> Vec<ActionInfo> {
+    ACTIONS
+        .iter()
+        .map(|d| ActionInfo {
+            id: d.id,
+            title: d.title,
+            shortcut: d.shortcut,
+            targets: d.targets,
+            applicable: applies(d, ctx),
+        })
+        .collect()
+}
+
+fn arg<T: serde::de::DeserializeOwned>(args: &Value, key: &str) -> Result<T, WarlordError> {
+    match args.get(key) {
+        Some(v) if !v.is_null() => serde_json::from_value(v.clone()).map_err(|e| WarlordError::invalid(format!("参数 {} 无效: {}", key, e))),
+        _ => Err(WarlordError::invalid(format!("缺少参数: {}", key))),
+    }
+}
+
+fn arg_str(args: &Value, key: &str) -> Result<String, WarlordError> {
+    args[key].as_str().map(String::from).ok_or_else(|| WarlordError::invalid(format!("缺少参数: {}", key)))
+}
+
+fn arg_or_default<T: serde::de::DeserializeOwned + Default>(args: &Value, key: &str) -> Result<T, WarlordError> {
+    match args.get(key) {
+        Some(v) if !v.is_null() => serde_json::from_value(v.clone()).map_err(|e| WarlordError::invalid(format!("参数 {} 无效: {}", key, e))),
+        _ => Ok(T::default()),
+    }
+}
+
+fn to_value<T: serde::Serialize>(v: T) -> Result<Value, WarlordError> {
+    serde_json::to_value(v).map_err(|e| WarlordError::from(e.to_string()))
+}
+
+fn move_block(args: &Value, up: bool) -> Result<Value, WarlordError> {
+    let (path, id) = (arg_str(args, "path")?, arg::<usize>(args, "blockId")?);
+    to_value(journal::operation("edit-block", || block_edit::edit_file(&path, |doc| block_edit::move_block_by(doc, id, up)))?)
+}
+
+/// Run an action by id. `args` is the action's argument object (e.g. `{ "path": "..." }`;
+/// block actions also take `blockId`).
+pub fn invoke(id: &str, args: &Value) -> Result<Value, WarlordError> {
+    let def = ACTIONS.iter().find(|d| d.id == id).ok_or_else(|| WarlordError::invalid(format!("未知的操作: {}", id)))?;
+    (def.run)(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_each_action_once_for_its_selection() {
+        let mut ids: Vec<&str> = ACTIONS.iter().map(|d| d.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), ACTIONS.len());
+
+        let block = ActionContext { kind: Some(Target::Block), path: Some("a.filter".to_string()), block_id: Some(3) };
+        let applicable: Vec<&str> = list_actions(&block).into_iter().filter(|a| a.applicable).map(|a| a.id).collect();
+        assert!(applicable.contains(&"block.moveUp") && applicable.contains(&"history.undo"));
+        assert!(!applicable.contains(&"filter.format"));
+        assert_eq!(invoke("no.such", &json!({})).unwrap_err().code(), "invalidInput");
+        assert_eq!(invoke("block.moveUp", &json!({ "path": "a.filter" })).unwrap_err().to_string(), "缺少参数: blockId");
+    }
+}
@@ -0,0 +1,205 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::WarlordError;
+
+/// Name of the folder (under LocalAppData) shared with the frontend's ConfigManager.
+pub const CONFIG_DIR_NAME: &str = "WarlordToolsConfig";
+
+/// %LOCALAPPDATA%/WarlordToolsConfig, falling back to the working directory.
+pub fn config_dir() -> PathBuf {
+    // Keep state written by tests (backups, caches) out of the working directory, one folder
+    // per test (the harness names each test's thread after it) so parallel tests stay apart
+    if cfg!(test) {
+        let base = std::env::temp_dir().join("wt-test-config");
+        return match std::thread::current().name() {
+            Some(test) => base.join(test.replace("::", "-")),
+            None => base,
+        };
+    }
+    let base = std::env::var("LOCALAPPDATA")
+        .ok()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join(CONFIG_DIR_NAME)
+}
+
+/// Path of a file inside the config directory.
+pub fn config_file(name: &str) -> PathBuf {
+    config_dir().join(name)
+}
+
+/// Load a JSON state file from the config directory. Missing or unreadable files yield `T::default()`.
+pub fn load_json<T: serde::de::DeserializeOwned + Default>(name: &str) -> T {
+    fs::read_to_string(config_file(name))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Save a JSON state file into the config directory, creating the directory if needed.
+pub fn save_json<T: serde::Serialize>(name: &str, value: &T) -> Result<(), WarlordError> {
+    let path = config_file(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| WarlordError::io(e, parent))?;
+    }
+    let json = serde_json::to_string_pretty(value).map_err(|e| WarlordError::invalid(e.to_string()))?;
+    write_atomic(&path, json.as_bytes()).map_err(|e| WarlordError::io(e, &path))
+}
+
+/// Seconds since the unix epoch.
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Short unique id (hex of the current time in nanoseconds plus a process-wide counter).
+pub fn new_id() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    format!("{:x}{:x}", nanos, COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Hard links to the file at `path`; 1 when it cannot be told.
+#[cfg(unix)]
+fn link_count(path: &Path) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).map(|m| m.nlink()).unwrap_or(1)
+}
+
+#[cfg(windows)]
+fn link_count(path: &Path) -> u64 {
+    use std::os::windows::io::AsRawHandle;
+    #[repr(C)]
+    #[derive(Default)]
+    struct FileInformation {
+        attributes: u32,
+        creation_time: [u32; 2],
+        last_access_time: [u32; 2],
+        last_write_time: [u32; 2],
+        volume_serial_number: u32,
+        size_high: u32,
+        size_low: u32,
+        number_of_links: u32,
+        index_high: u32,
+        index_low: u32,
+    }
+    extern "system" {
+        fn GetFileInformationByHandle(file: *mut std::ffi::c_void, information: *mut FileInformation) -> i32;
+    }
+    let Ok(file) = fs::File::open(path) else { return 1 };
+    let mut information = FileInformation::default();
+    match unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut information) } {
+        0 => 1,
+        _ => u64::from(information.number_of_links),
+    }
+}
+
+/// Move `temp` over `path`. On Windows an existing target is replaced with ReplaceFileW,
+/// which keeps its attributes, ACLs and alternate data streams.
+#[cfg(windows)]
+fn replace(temp: &Path, path: &Path) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    extern "system" {
+        fn ReplaceFileW(replaced: *const u16, replacement: *const u16, backup: *const u16, flags: u32, exclude: *mut std::ffi::c_void, reserved: *mut std::ffi::c_void) -> i32;
+    }
+    const REPLACEFILE_IGNORE_MERGE_ERRORS: u32 = 0x2;
+    if !path.exists() {
+        return fs::rename(temp, path);
+    }
+    let wide = |p: &Path| p.as_os_str().encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+    let (replaced, replacement) = (wide(path), wide(temp));
+    let flags = REPLACEFILE_IGNORE_MERGE_ERRORS;
+    if unsafe { ReplaceFileW(replaced.as_ptr(), replacement.as_ptr(), std::ptr::null(), flags, std::ptr::null_mut(), std::ptr::null_mut()) } != 0 {
+        return Ok(());
+    }
+    let error = io::Error::last_os_error();
+    // Some file systems (FAT, some shares) cannot replace; the temporary file is still there
+    if temp.exists() { fs::rename(temp, path) } else { Err(error) }
+}
+
+#[cfg(not(windows))]
+fn replace(temp: &Path, path: &Path) -> io::Result<()> {
+    fs::rename(temp, path)
+}
+
+/// Replace `path` with `contents` through a temporary file in the same directory that is
+/// flushed to disk and then renamed over the target, so a crash leaves the old or the new
+/// file but never a truncated one. A symlinked target is followed, not replaced. The target
+/// keeps its permissions and owner (attributes and ACLs on Windows). A file with several hard
+/// links is rewritten in place instead, since a rename would split it from its other names.
+pub fn write_atomic(path: &Path, mut contents: &[u8]) -> io::Result<()> {
+    write_atomic_from(path, &mut contents).map(|_| ())
+}
+
+/// `write_atomic` with the content streamed from `source`; returns the bytes written.
+pub fn write_atomic_from(path: &Path, source: &mut impl io::Read) -> io::Result<u64> {
+    let is_link = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+    let path = if is_link { fs::canonicalize(path)? } else { path.to_path_buf() };
+    if link_count(&path) > 1 {
+        let mut file = fs::OpenOptions::new().write(true).truncate(true).open(&path)?;
+        let written = io::copy(source, &mut file)?;
+        file.sync_all()?;
+        return Ok(written);
+    }
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let temp = dir.join(format!(".{}.{}.tmp", name.to_string_lossy(), new_id()));
+    let result = (|| {
+        let mut file = fs::File::create(&temp)?;
+        let written = io::copy(source, &mut file)?;
+        #[cfg(unix)]
+        if let Ok(meta) = fs::metadata(&path) {
+            use std::os::unix::fs::MetadataExt;
+            use std::os::unix::io::AsRawFd;
+            file.set_permissions(meta.permissions())?;
+            // Only succeeds for root or when the owner stays the same; either is fine
+            unsafe { libc::fchown(file.as_raw_fd(), meta.uid(), meta.gid()) };
+        }
+        file.sync_all()?;
+        drop(file);
+        replace(&temp, &path).map(|_| written)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    let written = result?;
+    // Make the rename itself durable
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atomic_write_replaces_without_leftovers() {
+        let dir = std::env::temp_dir().join("wt-atomic-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.filter");
+        write_atomic(&file, b"Show\n").unwrap();
+        write_atomic(&file, b"Hide\n").unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "Hide\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        assert!(write_atomic(&dir.join("missing/a.filter"), b"").is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let linked = dir.join("installed.filter");
+        fs::hard_link(&file, &linked).unwrap();
+        write_atomic(&file, b"Show # again\n").unwrap();
+        assert_eq!(fs::read_to_string(&linked).unwrap(), "Show # again\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
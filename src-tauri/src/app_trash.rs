@@ -0,0 +1,215 @@
+//! The app's own trash, so deletions can be undone from inside the app. `sandbox::delete`
+//! moves files and folders to `trash/<id>/<name>` in the config folder and records where
+//! they came from in `trash.json`. Only a rename is done: something on another drive than
+//! the config folder is not copied over but left to the Recycle Bin. Entries older than
+//! `trash_days` go, and the oldest ones once the trash is over `trash_max_mb`. Emptying the
+//! trash sends them on to the Recycle Bin when `recycle_bin` is set, else removes them for
+//! good; an entry that cannot be removed stays in the trash.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+use crate::backups::{self, BackupConfig};
+use crate::{app_paths, file_ops, folder_size};
+
+const INDEX_FILE: &str = "trash.json";
+
+/// Serializes read-modify-write of the index.
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashEntry {
+    pub id: String,
+    /// Where it was deleted from, and is restored to
+    pub original_path: String,
+    pub is_dir: bool,
+    /// Bytes, all files for a folder
+    pub size: u64,
+    /// Seconds since the unix epoch
+    pub deleted_at: u64,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct TrashIndex {
+    entries: Vec<TrashEntry>,
+}
+
+fn trash_root() -> PathBuf {
+    app_paths::config_file("trash")
+}
+
+/// Where the entry's file or folder is kept.
+fn stored_path(entry: &TrashEntry) -> PathBuf {
+    let name = Path::new(&entry.original_path).file_name().map(|n| n.to_os_string()).unwrap_or_else(|| "item".into());
+    trash_root().join(&entry.id).join(name)
+}
+
+fn with_index<R>(f: impl FnOnce(&mut TrashIndex) -> Result<R, WarlordError>) -> Result<R, WarlordError> {
+    let _guard = INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut index: TrashIndex = app_paths::load_json(INDEX_FILE);
+    let result = f(&mut index)?;
+    app_paths::save_json(INDEX_FILE, &index)?;
+    Ok(result)
+}
+
+/// Move `path` into the trash. Callers check the write guard (`sandbox::delete`). None when
+/// it is on another drive than the trash, where it would have to be copied.
+pub fn move_to_trash(path: &Path) -> Result<Option<TrashEntry>, WarlordError> {
+    let long = long_path(path);
+    let meta = fs::symlink_metadata(&long).map_err(|e| WarlordError::io(e, path))?;
+    let absolute = std::path::absolute(path).map_err(|e| WarlordError::io(e, path))?;
+    let entry = TrashEntry {
+        id: app_paths::new_id(),
+        original_path: path_utils::short_path(&absolute),
+        is_dir: meta.is_dir(),
+        size: folder_size::size_of(path),
+        deleted_at: app_paths::now_secs(),
+    };
+    let stored = stored_path(&entry);
+    let folder = trash_root().join(&entry.id);
+    fs::create_dir_all(long_path(&folder)).map_err(|e| WarlordError::io(e, &folder))?;
+    if let Err(e) = fs::rename(&long, long_path(&stored)) {
+        let _ = fs::remove_dir(long_path(&folder));
+        if e.kind() == io::ErrorKind::CrossesDevices {
+            return Ok(None);
+        }
+        return Err(WarlordError::write_failed(e, path));
+    }
+    let config = backups::get_config();
+    with_index(|index| {
+        index.entries.push(entry.clone());
+        expire(index, &config, app_paths::now_secs());
+        Ok(())
+    })?;
+    Ok(Some(entry))
+}
+
+/// Remove the entries past `trash_days`, then the oldest until the rest fit in
+/// `trash_max_mb`; the newest entry always stays.
+fn expire(index: &mut TrashIndex, config: &BackupConfig, now: u64) {
+    let cutoff = now.saturating_sub(config.trash_days.saturating_mul(24 * 60 * 60));
+    let limit = config.trash_max_mb.saturating_mul(1024 * 1024);
+    index.entries.sort_by_key(|e| e.deleted_at);
+    let mut total: u64 = index.entries.iter().map(|e| e.size).fold(0, u64::saturating_add);
+    let newest = index.entries.len().saturating_sub(1);
+    let mut kept = Vec::new();
+    for (i, entry) in index.entries.drain(..).enumerate() {
+        let old = entry.deleted_at < cutoff || total > limit;
+        if i < newest && old && discard(&entry, config.recycle_bin).is_ok() {
+            total = total.saturating_sub(entry.size);
+        } else {
+            kept.push(entry);
+        }
+    }
+    index.entries = kept;
+}
+
+/// Send an entry's files to the Recycle Bin (`recycle`) or remove them.
+fn discard(entry: &TrashEntry, recycle: bool) -> Result<(), WarlordError> {
+    let stored = stored_path(entry);
+    let long = long_path(&stored);
+    if recycle && long.exists() {
+        trash::delete(&long).map_err(|e| WarlordError::Io { path: Some(path_utils::short_path(&stored)), message: format!("无法移到回收站: {}", e) })?;
+    }
+    let folder = trash_root().join(&entry.id);
+    match fs::remove_dir_all(long_path(&folder)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(WarlordError::io(e, &folder)),
+        _ => Ok(()),
+    }
+}
+
+/// Newest first.
+pub fn list_trash() -> Vec<TrashEntry> {
+    let mut entries = app_paths::load_json::<TrashIndex>(INDEX_FILE).entries;
+    entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then_with(|| b.id.cmp(&a.id)));
+    entries
+}
+
+/// Put an entry back where it was deleted from. Fails with `AlreadyExists` when something
+/// new is there now. Returns the restored path.
+pub fn restore_from_trash(id: &str) -> Result<String, WarlordError> {
+    let entry = list_trash().into_iter().find(|e| e.id == id).ok_or_else(|| WarlordError::invalid(format!("废纸篓中没有 {}", id)))?;
+    let original = PathBuf::from(&entry.original_path);
+    file_ops::move_path(&stored_path(&entry), &original, |_| {})?;
+    let _ = fs::remove_dir(long_path(trash_root().join(&entry.id)));
+    with_index(|index| {
+        index.entries.retain(|e| e.id != id);
+        Ok(())
+    })?;
+    Ok(entry.original_path)
+}
+
+/// Remove entries from the trash (all of them when `ids` is None), into the Recycle Bin
+/// if that setting is on. Entries that cannot be removed stay. Returns how many were removed.
+pub fn empty_trash(ids: Option<&[String]>) -> Result<usize, WarlordError> {
+    let recycle = backups::get_config().recycle_bin;
+    with_index(|index| {
+        let before = index.entries.len();
+        index.entries.retain(|entry| {
+            if ids.is_some_and(|ids| !ids.contains(&entry.id)) {
+                return true;
+            }
+            match discard(entry, recycle) {
+                Ok(()) => false,
+                Err(e) => {
+                    eprintln!("[WarlordTools] Could not remove {} from the trash: {}", entry.original_path, e);
+                    true
+                }
+            }
+        });
+        Ok(before - index.entries.len())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deleted_files_can_be_restored() {
+        let dir = std::env::temp_dir().join("wt-app-trash-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sounds")).unwrap();
+        fs::write(dir.join("sounds/alert.mp3"), b"ID3").unwrap();
+
+        let entry = move_to_trash(&dir.join("sounds")).unwrap().unwrap();
+        assert!(!dir.join("sounds").exists());
+        assert!(entry.is_dir && entry.size == 3);
+        assert!(list_trash().iter().any(|e| e.id == entry.id));
+        restore_from_trash(&entry.id).unwrap();
+        assert_eq!(fs::read(dir.join("sounds/alert.mp3")).unwrap(), b"ID3");
+        assert!(!list_trash().iter().any(|e| e.id == entry.id));
+
+        let again = move_to_trash(&dir.join("sounds/alert.mp3")).unwrap().unwrap();
+        assert_eq!(empty_trash(Some(std::slice::from_ref(&again.id))).unwrap(), 1);
+        assert!(!stored_path(&again).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn expires_old_and_oversized_entries() {
+        let day = 24 * 60 * 60;
+        let entry = |id: &str, days_ago: u64, mb: u64| TrashEntry {
+            id: format!("wt-expire-test-{}", id),
+            original_path: format!("{}.filter", id),
+            is_dir: false,
+            size: mb * 1024 * 1024,
+            deleted_at: 100 * day - days_ago * day,
+        };
+        let config = BackupConfig { trash_days: 30, trash_max_mb: 10, ..Default::default() };
+        let mut index = TrashIndex { entries: vec![entry("new", 0, 4), entry("stale", 40, 1), entry("big", 2, 6), entry("small", 1, 3)] };
+        expire(&mut index, &config, 100 * day);
+        let ids: Vec<&str> = index.entries.iter().map(|e| e.id.trim_start_matches("wt-expire-test-")).collect();
+        assert_eq!(ids, ["small", "new"]);
+
+        let mut huge = TrashIndex { entries: vec![entry("huge", 0, 50)] };
+        expire(&mut huge, &config, 100 * day);
+        assert_eq!(huge.entries.len(), 1);
+    }
+}
@@ -0,0 +1,665 @@
+//! Filter and sound packs as archives. Installing (zip, 7z or RAR, see `extractors`): entry
+//! names are checked before anything is written (absolute paths, `..`, drive letters and
+//! symbolic links are refused), so an archive cannot write outside the destination folder;
+//! files go through `sandbox::write_as`, with the free space checked up front like any
+//! large copy. Sharing: `export_pack` zips filters with the custom sounds they play and a
+//! manifest. Backups: `zip_files` writes a manifest of hashes that `verify_backup` checks;
+//! packs are written the same way, so they carry one too.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::WarlordError;
+use crate::extractors::{self, ArchiveEntry, Extractor};
+use crate::file_ops::{CopySummary, FailedFile, Overwrite, Progress};
+use crate::filter_parser::{unquote, Rule};
+use crate::path_utils::{self, long_path};
+use crate::{app_paths, disk_space, encoding, sandbox};
+
+/// Manifest at the root of an exported pack.
+pub const PACK_MANIFEST: &str = "warlordtools-pack.json";
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExtractOptions {
+    pub overwrite: Overwrite,
+}
+
+/// A file of the archive with where it extracts to; `path` is None when the name is unsafe.
+struct Entry {
+    entry: ArchiveEntry,
+    path: Option<PathBuf>,
+}
+
+/// Device names Windows reserves in every folder, with any extension ("nul.filter" too)
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn is_reserved(part: &str) -> bool {
+    let stem = part.split('.').next().unwrap_or(part).trim_end();
+    RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem))
+}
+
+/// Relative path for an archive entry name, or None if it would leave the destination:
+/// absolute, `..`, a drive or stream (`:`), characters Windows does not allow in names or a
+/// device name such as `CON` or `COM1`.
+pub fn entry_path(name: &str) -> Option<PathBuf> {
+    if name.starts_with(['/', '\\']) {
+        return None;
+    }
+    let mut path = PathBuf::new();
+    for part in name.split(['/', '\\']).filter(|p| !p.is_empty() && *p != ".") {
+        if part == ".." || part.contains([':', '<', '>', '"', '|', '?', '*']) || part.chars().any(char::is_control) || is_reserved(part) {
+            return None;
+        }
+        path.push(part);
+    }
+    path.components().next().is_some().then_some(path)
+}
+
+fn replaces(entry: &ArchiveEntry, target: &Path, overwrite: Overwrite) -> bool {
+    match overwrite {
+        _ if !target.exists() => true,
+        Overwrite::Skip => false,
+        Overwrite::Replace => true,
+        Overwrite::IfNewer => match (entry.modified, fs::metadata(target).and_then(|m| m.modified()).ok()) {
+            (Some(entry), Some(existing)) => entry > existing,
+            _ => true,
+        },
+    }
+}
+
+/// Extract `archive` (zip, 7z or RAR, see `extractors`) into `dest` (created if needed).
+/// Every file is attempted; the summary lists what was written, what `options.overwrite`
+/// skipped and what failed, including entries refused for their path.
+pub fn extract_archive(archive: &Path, dest: &Path, options: &ExtractOptions, progress: impl FnMut(&Progress)) -> Result<CopySummary, WarlordError> {
+    sandbox::check_write(dest)?;
+    let mut extractor = extractors::open(archive)?;
+    let entries: HashMap<String, Entry> = extractor.list()?.into_iter().map(|entry| (entry.name.clone(), Entry { path: entry_path(&entry.name), entry })).collect();
+    extract_entries(extractor.as_mut(), archive, &entries, dest, options.overwrite, progress)
+}
+
+/// Write `entries` (by entry name; files of the archive not in it are left out) below `dest`.
+fn extract_entries(extractor: &mut dyn Extractor, archive: &Path, entries: &HashMap<String, Entry>, dest: &Path, overwrite: Overwrite, mut progress: impl FnMut(&Progress)) -> Result<CopySummary, WarlordError> {
+    let target_of = |entry: &Entry| entry.path.as_ref().map(|p| long_path(dest.join(p)));
+    let bytes_total: u64 = entries.values().map(|e| e.entry.size).sum();
+    let to_write = entries.values().filter(|e| target_of(e).is_some_and(|t| replaces(&e.entry, &t, overwrite))).map(|e| e.entry.size).sum();
+    disk_space::check_disk_space(dest, to_write)?;
+
+    let mut summary = CopySummary::default();
+    let mut done = 0;
+    extractor.for_each(&mut |name, data| {
+        let Some(entry) = entries.get(name) else { return Ok(()) };
+        let shown = target_of(entry).map(path_utils::short_path).unwrap_or_else(|| name.to_string());
+        match target_of(entry) {
+            None => summary.failed.push(FailedFile { path: name.to_string(), error: WarlordError::invalid(format!("压缩包中的路径不安全, 已跳过: {}", name)) }),
+            Some(target) if !replaces(&entry.entry, &target, overwrite) => summary.skipped.push(shown.clone()),
+            Some(target) => match extract_one(data, &entry.entry, &target) {
+                Ok(bytes) => {
+                    summary.bytes += bytes;
+                    summary.copied.push(shown.clone());
+                }
+                Err(error) => summary.failed.push(FailedFile { path: name.to_string(), error }),
+            },
+        }
+        done += 1;
+        progress(&Progress { operation: "extract".to_string(), path: shown, done, total: entries.len(), bytes_done: summary.bytes, bytes_total });
+        Ok(())
+    })?;
+    eprintln!("[WarlordTools] Extracted {} files from {} ({} skipped, {} failed)", summary.copied.len(), archive.display(), summary.skipped.len(), summary.failed.len());
+    Ok(summary)
+}
+
+/// Stream one entry into place: the size in the archive's header is not trusted, what is
+/// written is what the entry decompresses to.
+fn extract_one(data: &mut dyn Read, entry: &ArchiveEntry, target: &Path) -> Result<u64, WarlordError> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| WarlordError::io(e, parent))?;
+    }
+    sandbox::write_stream(target, data, "extract").map_err(|e| match e {
+        WarlordError::Io { message, .. } => WarlordError::Io { path: Some(entry.name.clone()), message: format!("解压失败: {}", message) },
+        other => other,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedFile {
+    /// Name in the archive
+    pub entry: String,
+    /// "filter" / "sound" / "readme" / "other"
+    pub kind: String,
+    /// Where it is installed; None when it is not (readmes, pictures, unsafe names)
+    pub target: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPlan {
+    pub archive: String,
+    pub destination: String,
+    /// Wrapper folders of the archive that are not recreated, e.g. "NeverSink-8.5/"
+    pub stripped: String,
+    pub files: Vec<PlannedFile>,
+    /// Result of the install, when applied
+    pub summary: Option<CopySummary>,
+}
+
+/// "filter" / "sound" / "readme" / "other", by extension.
+pub fn kind_of(path: &Path) -> &'static str {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "filter" => "filter",
+        "mp3" | "ogg" | "wav" | "flac" => "sound",
+        "txt" | "md" | "pdf" => "readme",
+        _ if name.starts_with("readme") => "readme",
+        _ => "other",
+    }
+}
+
+/// Where each entry of a pack goes, relative to the game's filter folder. Folders wrapping
+/// the whole archive are dropped. The shallowest folder holding a filter is the pack's
+/// root: what is below it keeps its layout (so relative sound paths still work), filters
+/// elsewhere go next to the others. A pack without filters is a sound pack and goes into a
+/// folder named after the archive. Readmes and other files are not installed.
+fn plan_layout(names: &[String], pack_name: &str) -> (String, Vec<PlannedFile>) {
+    let parts: Vec<Option<Vec<String>>> = names.iter().map(|n| entry_path(n).map(|p| p.iter().map(|c| c.to_string_lossy().to_string()).collect())).collect();
+    let mut strip = 0;
+    let safe: Vec<&Vec<String>> = parts.iter().flatten().collect();
+    while !safe.is_empty() && safe.iter().all(|p| p.len() > strip + 1) && safe.windows(2).all(|w| w[0][strip] == w[1][strip]) {
+        strip += 1;
+    }
+    let stripped = safe.first().map(|p| p[..strip].iter().map(|c| format!("{}/", c)).collect()).unwrap_or_default();
+    let is_filter = |p: &[String]| p.last().is_some_and(|n| kind_of(Path::new(n)) == "filter");
+    let base: Option<&[String]> = safe.iter().map(|p| &p[strip..]).filter(|p| is_filter(p)).min_by_key(|p| p.len()).map(|p| &p[..p.len() - 1]);
+
+    let files = names
+        .iter()
+        .zip(&parts)
+        .map(|(name, parts)| {
+            let Some(parts) = parts else {
+                return PlannedFile { entry: name.clone(), kind: "other".to_string(), target: None };
+            };
+            let rest = &parts[strip..];
+            let kind = kind_of(Path::new(name));
+            let join = |p: &[String]| Some(p.join("/"));
+            let target = match (kind, base) {
+                ("filter" | "sound", Some(base)) if rest.starts_with(base) => join(&rest[base.len()..]),
+                ("filter", Some(_)) => join(&rest[rest.len() - 1..]),
+                ("sound", Some(_)) => join(rest),
+                ("sound", None) => Some(format!("{}/{}", pack_name, rest.join("/"))),
+                _ => None,
+            };
+            PlannedFile { entry: name.clone(), kind: kind.to_string(), target }
+        })
+        .collect();
+    (stripped, files)
+}
+
+/// Look at a downloaded pack and work out where its files belong (`plan_layout`) in
+/// `destination`, by default the game's filter folder (Path of Exile, then Path of Exile 2).
+/// With `apply` the files are installed, replacing older versions (they are backed up).
+pub fn import_pack(archive: &Path, destination: Option<&Path>, apply: bool, progress: impl FnMut(&Progress)) -> Result<ImportPlan, WarlordError> {
+    let destination = match destination {
+        Some(dest) => dest.to_path_buf(),
+        None => sandbox::game_documents().into_iter().find(|d| long_path(d).is_dir()).ok_or_else(|| WarlordError::invalid("找不到游戏的过滤器文件夹, 请选择安装位置"))?,
+    };
+    let mut extractor = extractors::open(archive)?;
+    let listed = extractor.list()?;
+    let pack_name = archive.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| "pack".to_string());
+    let names: Vec<String> = listed.iter().map(|e| e.name.clone()).collect();
+    let (stripped, files) = plan_layout(&names, &pack_name);
+    let mut plan = ImportPlan { archive: path_utils::short_path(archive), destination: path_utils::short_path(&destination), stripped, files, summary: None };
+    if !apply {
+        return Ok(plan);
+    }
+    sandbox::check_write(&destination)?;
+    let targets: HashMap<&str, &str> = plan.files.iter().filter_map(|f| Some((f.entry.as_str(), f.target.as_deref()?))).collect();
+    let entries: HashMap<String, Entry> = listed
+        .into_iter()
+        .filter_map(|entry| {
+            let path = entry_path(targets.get(entry.name.as_str())?)?;
+            Some((entry.name.clone(), Entry { entry, path: Some(path) }))
+        })
+        .collect();
+    plan.summary = Some(extract_entries(extractor.as_mut(), archive, &entries, &destination, Overwrite::Replace, progress)?);
+    Ok(plan)
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PackManifest {
+    pub created_at: u64,
+    /// Entry names of the filters, at the root of the archive
+    pub filters: Vec<String>,
+    /// Entry names of the sounds
+    pub sounds: Vec<String>,
+    /// Sounds the filters reference that were not found
+    pub missing: Vec<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportReport {
+    pub path: String,
+    pub manifest: PackManifest,
+    /// Size of the archive
+    pub bytes: u64,
+}
+
+/// Sounds referenced by a filter's text, with the text rewritten where needed. A sound
+/// below the filter's folder keeps its relative path in the pack; any other one (absolute,
+/// or `..` out of the folder) goes to `sounds/<name>` and its line is changed to play it
+/// from there. Returns the text (None if unchanged) and (file, entry name) per sound.
+fn pack_sounds(text: &str, base_dir: &Path) -> (Option<String>, Vec<(PathBuf, String)>) {
+    let mut sounds: Vec<(PathBuf, String)> = Vec::new();
+    let mut changed = false;
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let value = match Rule::parse(line.trim()) {
+            Some(rule) if rule.keyword.starts_with("CustomAlertSound") => rule.values.first().map(|v| unquote(v).to_string()),
+            _ => None,
+        };
+        let Some(value) = value.filter(|v| !v.is_empty()) else {
+            lines.push(line.to_string());
+            continue;
+        };
+        let (file, entry) = match entry_path(&value).filter(|_| !Path::new(&value).is_absolute()) {
+            Some(relative) => (base_dir.join(&relative), relative.to_string_lossy().replace('\\', "/")),
+            None => {
+                let file = if Path::new(&value).is_absolute() { PathBuf::from(&value) } else { base_dir.join(&value) };
+                let name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                (file, format!("sounds/{}", name))
+            }
+        };
+        if entry != value.replace('\\', "/") {
+            lines.push(line.replacen(&value, &entry, 1));
+            changed = true;
+        } else {
+            lines.push(line.to_string());
+        }
+        if !sounds.iter().any(|(_, e)| *e == entry) {
+            sounds.push((file, entry));
+        }
+    }
+    (changed.then(|| lines.join("\n")), sounds)
+}
+
+fn zip_write_error(error: zip::result::ZipError, dest: &Path) -> WarlordError {
+    WarlordError::Io { path: Some(path_utils::short_path(dest)), message: format!("无法写入压缩包: {}", error) }
+}
+
+/// Bundle the filters at `paths` and every custom sound they reference into the zip
+/// `dest`, with a `warlordtools-pack.json` manifest. Filters keep their bytes (encoding
+/// included) unless a sound reference had to be rewritten. Sounds that are missing are
+/// listed in the manifest instead of failing the export. Written by `zip_entries`, so
+/// sounds are streamed and an export cut short leaves only a `.part` file.
+pub fn export_pack(paths: &[String], dest: &Path) -> Result<ExportReport, WarlordError> {
+    if paths.is_empty() {
+        return Err(WarlordError::invalid("没有选择要导出的过滤器"));
+    }
+    let mut manifest = PackManifest { created_at: app_paths::now_secs(), ..Default::default() };
+    let mut entries: Vec<(String, ZipSource)> = Vec::new();
+    let mut sounds: Vec<(PathBuf, String)> = Vec::new();
+    for path in paths.iter().map(Path::new) {
+        let bytes = fs::read(long_path(path)).map_err(|e| WarlordError::io(e, path))?;
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).ok_or_else(|| WarlordError::invalid(format!("{} 不是文件", path.display())))?;
+        if manifest.filters.contains(&name) {
+            return Err(WarlordError::invalid(format!("有多个过滤器都叫 {}", name)));
+        }
+        let decoded = encoding::decode(&bytes);
+        let (rewritten, referenced) = pack_sounds(&decoded.text, path.parent().unwrap_or(Path::new("")));
+        let source = match rewritten {
+            Some(text) => ZipSource::Bytes(encoding::encode(&text, &decoded.encoding, decoded.bom)?),
+            None => ZipSource::File(path.to_path_buf()),
+        };
+        entries.push((name.clone(), source));
+        manifest.filters.push(name);
+        for (file, entry) in referenced {
+            if !sounds.iter().any(|(_, e)| *e == entry) {
+                sounds.push((file, entry));
+            }
+        }
+    }
+    for (file, entry) in sounds {
+        if long_path(&file).is_file() {
+            manifest.sounds.push(entry.clone());
+            entries.push((entry, ZipSource::File(file)));
+        } else {
+            manifest.missing.push(path_utils::short_path(&file));
+        }
+    }
+    let json = serde_json::to_vec_pretty(&manifest).map_err(|e| WarlordError::from(e.to_string()))?;
+    entries.push((PACK_MANIFEST.to_string(), ZipSource::Bytes(json)));
+    let (_, bytes) = zip_entries(&entries, dest, None)?;
+    eprintln!("[WarlordTools] Exported {} filters and {} sounds to {}", manifest.filters.len(), manifest.sounds.len(), dest.display());
+    Ok(ExportReport { path: path_utils::short_path(dest), manifest, bytes })
+}
+
+/// Manifest at the root of a library export or scheduled backup, listing every other file
+/// with its hash so the archive can be checked before it is relied on.
+pub const BACKUP_MANIFEST: &str = "manifest.json";
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestFile {
+    pub name: String,
+    pub size: u64,
+    /// Hex SHA-256 of the contents
+    pub sha256: String,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackupManifest {
+    /// Version of the app that wrote it
+    pub app_version: String,
+    /// Seconds since the unix epoch
+    pub created_at: u64,
+    pub files: Vec<ManifestFile>,
+}
+
+/// Archive entries for the files below `dir` that pass `keep`, named `<prefix><relative
+/// path>`. Links and `.git` folders are left out.
+pub fn tree_entries(dir: &Path, prefix: &str, keep: &dyn Fn(&Path) -> bool, out: &mut Vec<(String, PathBuf)>) {
+    let Ok(entries) = fs::read_dir(long_path(dir)) else { return };
+    for entry in entries.flatten() {
+        let (path, name) = (dir.join(entry.file_name()), entry.file_name().to_string_lossy().to_string());
+        let Ok(meta) = fs::symlink_metadata(entry.path()) else { continue };
+        if meta.is_dir() && name != ".git" {
+            tree_entries(&path, &format!("{}{}/", prefix, name), keep, out);
+        } else if meta.is_file() && keep(&path) {
+            out.push((format!("{}{}", prefix, name), path));
+        }
+    }
+}
+
+/// Contents of an archive entry: a file streamed from disk, or bytes built in memory (a
+/// filter whose sound paths were rewritten, a manifest).
+pub enum ZipSource {
+    File(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+impl ZipSource {
+    fn len(&self) -> u64 {
+        match self {
+            ZipSource::File(path) => fs::metadata(long_path(path)).map(|m| m.len()).unwrap_or(0),
+            ZipSource::Bytes(bytes) => bytes.len() as u64,
+        }
+    }
+}
+
+/// Options for an entry, AES-256 encrypted when there is a password.
+fn entry_options(method: zip::CompressionMethod, password: Option<&str>) -> zip::write::FileOptions<'_, ()> {
+    let options = zip::write::SimpleFileOptions::default().compression_method(method);
+    match password {
+        Some(password) => options.with_aes_encryption(zip::AesMode::Aes256, password),
+        None => options,
+    }
+}
+
+fn write_zip(file: fs::File, entries: &[(String, ZipSource)], dest: &Path, password: Option<&str>) -> Result<BackupManifest, WarlordError> {
+    use sha2::{Digest, Sha256};
+    let mut manifest = BackupManifest { app_version: env!("CARGO_PKG_VERSION").to_string(), created_at: app_paths::now_secs(), files: Vec::new() };
+    let mut zip = zip::ZipWriter::new(file);
+    let mut buffer = vec![0u8; 64 * 1024];
+    for (name, source) in entries {
+        // mp3/ogg/wav: compressing again gains little
+        let method = if kind_of(Path::new(name)) == "sound" { zip::CompressionMethod::Stored } else { zip::CompressionMethod::Deflated };
+        let (mut source, path): (Box<dyn Read>, &Path) = match source {
+            ZipSource::File(path) => (Box::new(fs::File::open(long_path(path)).map_err(|e| WarlordError::io(e, path))?), path),
+            ZipSource::Bytes(bytes) => (Box::new(bytes.as_slice()), dest),
+        };
+        zip.start_file(name.as_str(), entry_options(method, password)).map_err(|e| zip_write_error(e, dest))?;
+        let (mut hasher, mut size) = (Sha256::new(), 0u64);
+        loop {
+            let read = source.read(&mut buffer).map_err(|e| WarlordError::io(e, path))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            zip.write_all(&buffer[..read]).map_err(|e| WarlordError::io(e, dest))?;
+            size += read as u64;
+        }
+        let sha256 = format!("{:x}", hasher.finalize());
+        manifest.files.push(ManifestFile { name: name.clone(), size, sha256 });
+    }
+    let json = serde_json::to_vec_pretty(&manifest).map_err(|e| WarlordError::from(e.to_string()))?;
+    zip.start_file(BACKUP_MANIFEST, entry_options(zip::CompressionMethod::Deflated, password)).map_err(|e| zip_write_error(e, dest))?;
+    zip.write_all(&json).map_err(|e| WarlordError::io(e, dest))?;
+    zip.finish().map_err(|e| zip_write_error(e, dest))?;
+    Ok(manifest)
+}
+
+/// Zip `files` (name in the archive, file on disk) into `dest` with a `BACKUP_MANIFEST`,
+/// streamed through a `.part` file next to it, so an archive cut short never looks
+/// complete. With a password every entry (the manifest too) is AES-256 encrypted; the file
+/// names stay readable, as zip keeps them outside the encrypted data. Returns the manifest
+/// and the archive's size.
+pub fn zip_files(files: &[(String, PathBuf)], dest: &Path, password: Option<&str>) -> Result<(BackupManifest, u64), WarlordError> {
+    let entries: Vec<(String, ZipSource)> = files.iter().map(|(name, path)| (name.clone(), ZipSource::File(path.clone()))).collect();
+    zip_entries(&entries, dest, password)
+}
+
+/// `zip_files` for entries that need not all be on disk.
+pub fn zip_entries(entries: &[(String, ZipSource)], dest: &Path, password: Option<&str>) -> Result<(BackupManifest, u64), WarlordError> {
+    if password.is_some_and(str::is_empty) {
+        return Err(WarlordError::invalid("密码不能为空"));
+    }
+    sandbox::check_write(dest)?;
+    let total: u64 = entries.iter().map(|(_, source)| source.len()).sum();
+    disk_space::check_disk_space(dest, total)?;
+    let part = dest.with_extension("zip.part");
+    let file = fs::File::create(long_path(&part)).map_err(|e| WarlordError::io(e, &part))?;
+    let written = write_zip(file, entries, dest, password).and_then(|m| fs::rename(long_path(&part), long_path(dest)).map(|_| m).map_err(|e| WarlordError::io(e, dest)));
+    match written {
+        Ok(manifest) => Ok((manifest, fs::metadata(long_path(dest)).map(|m| m.len()).unwrap_or(0))),
+        Err(e) => {
+            let _ = fs::remove_file(long_path(&part));
+            Err(e)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EntryProblem {
+    /// Listed in the manifest, not in the archive
+    Missing,
+    /// Size or hash differ from the manifest
+    Corrupted,
+    /// Could not be decompressed
+    Unreadable,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BadEntry {
+    pub name: String,
+    pub problem: EntryProblem,
+    pub expected_sha256: String,
+    pub actual_sha256: Option<String>,
+    /// Why it could not be read
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupVerification {
+    pub archive: String,
+    pub app_version: String,
+    pub created_at: u64,
+    /// Entries that match the manifest
+    pub verified: usize,
+    pub bad: Vec<BadEntry>,
+    /// In the archive but not in the manifest
+    pub unlisted: Vec<String>,
+    /// Every listed file is there and intact
+    pub ok: bool,
+}
+
+/// Size and hash of each entry read, or why it could not be.
+type ReadEntries = HashMap<String, Result<(u64, String), String>>;
+
+fn check_manifest(manifest: &BackupManifest, mut found: ReadEntries) -> (usize, Vec<BadEntry>, Vec<String>) {
+    let mut verified = 0;
+    let mut bad = Vec::new();
+    for file in &manifest.files {
+        let entry = |problem, actual_sha256, error| BadEntry { name: file.name.clone(), problem, expected_sha256: file.sha256.clone(), actual_sha256, error };
+        match found.remove(&file.name) {
+            None => bad.push(entry(EntryProblem::Missing, None, None)),
+            Some(Err(error)) => bad.push(entry(EntryProblem::Unreadable, None, Some(error))),
+            Some(Ok((size, sha256))) if size == file.size && sha256.eq_ignore_ascii_case(&file.sha256) => verified += 1,
+            Some(Ok((_, sha256))) => bad.push(entry(EntryProblem::Corrupted, Some(sha256), None)),
+        }
+    }
+    let mut unlisted: Vec<String> = found.into_keys().collect();
+    unlisted.sort();
+    (verified, bad, unlisted)
+}
+
+/// Read every entry of a library export or scheduled backup and compare it with the
+/// archive's `BACKUP_MANIFEST`, so a backup can be trusted before the originals are deleted.
+/// An encrypted one needs its password.
+pub fn verify_backup(archive: &Path, password: Option<&str>) -> Result<BackupVerification, WarlordError> {
+    use sha2::{Digest, Sha256};
+    let mut extractor = extractors::open_with_password(archive, password)?;
+    let mut manifest = None;
+    let mut found = ReadEntries::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    extractor.for_each(&mut |name, data| {
+        if name == BACKUP_MANIFEST {
+            let mut json = Vec::new();
+            data.read_to_end(&mut json).map_err(|e| WarlordError::Io { path: Some(name.to_string()), message: format!("解压失败: {}", e) })?;
+            manifest = Some(serde_json::from_slice::<BackupManifest>(&json).map_err(|e| WarlordError::invalid(format!("{} 无法读取: {}", BACKUP_MANIFEST, e)))?);
+            return Ok(());
+        }
+        let (mut hasher, mut size) = (Sha256::new(), 0u64);
+        let read = loop {
+            match data.read(&mut buffer) {
+                Ok(0) => break Ok((size, format!("{:x}", hasher.finalize()))),
+                Ok(n) => {
+                    hasher.update(&buffer[..n]);
+                    size += n as u64;
+                }
+                Err(e) => break Err(e.to_string()),
+            }
+        };
+        found.insert(name.to_string(), read);
+        Ok(())
+    })?;
+    let manifest = manifest.ok_or_else(|| WarlordError::invalid(format!("{} 中没有 {}，不是 WarlordTools 备份", path_utils::short_path(archive), BACKUP_MANIFEST)))?;
+    let (verified, bad, unlisted) = check_manifest(&manifest, found);
+    eprintln!("[WarlordTools] Verified {}: {} intact, {} bad", archive.display(), verified, bad.len());
+    Ok(BackupVerification { archive: path_utils::short_path(archive), app_version: manifest.app_version, created_at: manifest.created_at, verified, ok: bad.is_empty(), bad, unlisted })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn refuses_entries_leaving_the_destination() {
+        assert_eq!(entry_path("Sounds/./alert.mp3"), Some(Path::new("Sounds").join("alert.mp3")));
+        assert_eq!(entry_path(r"pack\leveling.filter"), Some(Path::new("pack").join("leveling.filter")));
+        assert_eq!(entry_path("console.filter"), Some(PathBuf::from("console.filter")));
+        for unsafe_name in ["../evil.filter", "sounds/../../evil.dll", "/etc/passwd", r"\Windows\evil.dll", "C:/evil.filter", "a.filter:stream", "./", "", "sounds/CON", "nul.filter", "Com1.mp3"] {
+            assert_eq!(entry_path(unsafe_name), None, "{}", unsafe_name);
+        }
+    }
+
+    /// Archive in memory, with header sizes that need not match the contents
+    struct FakeArchive(Vec<(ArchiveEntry, Vec<u8>)>);
+
+    impl Extractor for FakeArchive {
+        fn list(&mut self) -> Result<Vec<ArchiveEntry>, WarlordError> {
+            Ok(self.0.iter().map(|(entry, _)| entry.clone()).collect())
+        }
+
+        fn for_each(&mut self, each: &mut dyn FnMut(&str, &mut dyn Read) -> Result<(), WarlordError>) -> Result<(), WarlordError> {
+            for (entry, contents) in &self.0 {
+                each(&entry.name, &mut Cursor::new(contents))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn extracts_entries_into_place() {
+        let dest = std::env::temp_dir().join("wt-archive-extract-test");
+        let _ = fs::remove_dir_all(&dest);
+        fs::create_dir_all(&dest).unwrap();
+        let entry = |name: &str, size| ArchiveEntry { name: name.to_string(), size, modified: None };
+        let mut archive = FakeArchive(vec![
+            (entry("pack/NeverSink.filter", 5), b"Show\n".to_vec()),
+            // The header says 10 bytes, all of the entry is written
+            (entry("pack/sounds/alert.mp3", 10), vec![1u8; 70_000]),
+            (entry("pack/NUL.mp3", 1), b"x".to_vec()),
+            (entry("../evil.filter", 1), b"x".to_vec()),
+        ]);
+        let entries: HashMap<String, Entry> = archive.list().unwrap().into_iter().map(|entry| (entry.name.clone(), Entry { path: entry_path(&entry.name), entry })).collect();
+
+        let summary = extract_entries(&mut archive, Path::new("pack.zip"), &entries, &dest, Overwrite::Replace, |_| {}).unwrap();
+        assert_eq!(summary.copied.len(), 2);
+        let mut failed: Vec<&str> = summary.failed.iter().map(|f| f.path.as_str()).collect();
+        failed.sort();
+        assert_eq!(failed, ["../evil.filter", "pack/NUL.mp3"]);
+        assert_eq!(summary.bytes, 70_005);
+        assert_eq!(fs::read(dest.join("pack/NeverSink.filter")).unwrap(), b"Show\n");
+        assert_eq!(fs::read(dest.join("pack/sounds/alert.mp3")).unwrap().len(), 70_000);
+        assert_eq!(fs::read_dir(dest.join("pack/sounds")).unwrap().count(), 1);
+        assert!(!dest.join("evil.filter").exists());
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn plans_pack_layouts() {
+        let names = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        fn targets(files: &[PlannedFile]) -> Vec<Option<&str>> {
+            files.iter().map(|f| f.target.as_deref()).collect()
+        }
+
+        let (stripped, files) = plan_layout(&names(&["NeverSink-8.5/filters/soft.filter", "NeverSink-8.5/filters/sounds/a.mp3", "NeverSink-8.5/filters/(STYLE) DARK/soft.filter", "NeverSink-8.5/README.md"]), "NeverSink-8.5");
+        assert_eq!(stripped, "NeverSink-8.5/");
+        assert_eq!(targets(&files), vec![Some("soft.filter"), Some("sounds/a.mp3"), Some("(STYLE) DARK/soft.filter"), None]);
+        assert_eq!(files[3].kind, "readme");
+
+        let (stripped, files) = plan_layout(&names(&["Pack/alerts/divine.mp3", "Pack/alerts/chaos.ogg", "Pack/preview.png", "../evil.mp3"]), "Alert Pack");
+        assert_eq!(stripped, "Pack/");
+        assert_eq!(targets(&files), vec![Some("Alert Pack/alerts/divine.mp3"), Some("Alert Pack/alerts/chaos.ogg"), None, None]);
+    }
+
+    #[test]
+    fn packs_sounds_next_to_filters() {
+        let base = Path::new("/lib/filters");
+        let text = "Show\n    CustomAlertSound \"sounds/divine.mp3\" 300\nShow\n    CustomAlertSoundOptional \"../shared/chime.ogg\"\nShow\n    CustomAlertSound \"sounds/divine.mp3\"";
+        let (rewritten, sounds) = pack_sounds(text, base);
+        assert_eq!(sounds, vec![(base.join("sounds").join("divine.mp3"), "sounds/divine.mp3".to_string()), (base.join("../shared/chime.ogg"), "sounds/chime.ogg".to_string())]);
+        assert_eq!(rewritten.unwrap().lines().nth(3), Some("    CustomAlertSoundOptional \"sounds/chime.ogg\""));
+        assert_eq!(pack_sounds("Show\n    CustomAlertSound \"a.mp3\"\n", base).0, None);
+    }
+
+    #[test]
+    fn reports_entries_that_differ_from_the_manifest() {
+        let file = |name: &str, size, sha256: &str| ManifestFile { name: name.to_string(), size, sha256: sha256.to_string() };
+        let manifest = BackupManifest { files: vec![file("library/a.filter", 4, "aa"), file("library/b.filter", 4, "bb"), file("library/c.filter", 4, "cc"), file("library/d.filter", 4, "dd")], ..Default::default() };
+        let found: ReadEntries = HashMap::from([
+            ("library/a.filter".to_string(), Ok((4, "AA".to_string()))),
+            ("library/b.filter".to_string(), Ok((4, "b0".to_string()))),
+            ("library/c.filter".to_string(), Err("invalid checksum".to_string())),
+            ("library/extra.filter".to_string(), Ok((1, "ee".to_string()))),
+        ]);
+        let (verified, bad, unlisted) = check_manifest(&manifest, found);
+        assert_eq!(verified, 1);
+        let problems: Vec<(&str, EntryProblem)> = bad.iter().map(|b| (b.name.as_str(), b.problem)).collect();
+        assert_eq!(problems, [("library/b.filter", EntryProblem::Corrupted), ("library/c.filter", EntryProblem::Unreadable), ("library/d.filter", EntryProblem::Missing)]);
+        assert_eq!(unlisted, ["library/extra.filter"]);
+    }
+}
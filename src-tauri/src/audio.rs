@@ -0,0 +1,254 @@
+//! Sound previews played by the app itself (rodio) rather than by the system's default
+//! player. The output stream cannot move between threads on every platform, so one player
+//! thread owns it and takes requests over a channel; it starts with the first preview and
+//! only one sound plays at a time. A queue of sounds (a whole pack) plays one after the
+//! other, and while something plays the position is reported every `PROGRESS_INTERVAL` to
+//! the listener set with `on_progress`. Previews follow the quiet hours volume.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+use crate::quiet_hours;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+enum Request {
+    Play { paths: Vec<PathBuf>, volume: f32, reply: Sender<Result<(), WarlordError>> },
+    Stop,
+    Seek { position: Duration, reply: Sender<Result<(), WarlordError>> },
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Progress {
+    pub path: String,
+    pub position_ms: u64,
+    /// None when the format does not tell (most mp3 files)
+    pub duration_ms: Option<u64>,
+    /// Sounds still waiting in the queue
+    pub queued: usize,
+    /// Played to the end or stopped; the last report for `path`
+    pub finished: bool,
+}
+
+type Listener = Box<dyn Fn(&Progress) + Send>;
+
+static PLAYER: Mutex<Option<Sender<Request>>> = Mutex::new(None);
+static LISTENER: Mutex<Option<Listener>> = Mutex::new(None);
+
+/// Receive the playback reports of every preview.
+pub fn on_progress(listener: impl Fn(&Progress) + Send + 'static) {
+    *LISTENER.lock().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(listener));
+}
+
+fn report(progress: &Progress) {
+    if let Some(listener) = LISTENER.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        listener(progress);
+    }
+}
+
+/// Hand `request` to the player thread, starting it when it is not running.
+fn send(request: Request) {
+    let mut player = PLAYER.lock().unwrap_or_else(|e| e.into_inner());
+    let request = match player.as_ref() {
+        Some(running) => match running.send(request) {
+            Ok(()) => return,
+            Err(mpsc::SendError(request)) => request,
+        },
+        None => request,
+    };
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || run(receiver));
+    let _ = sender.send(request);
+    *player = Some(sender);
+}
+
+fn ask(request: impl FnOnce(Sender<Result<(), WarlordError>>) -> Request) -> Result<(), WarlordError> {
+    let (reply, answer) = mpsc::channel();
+    send(request(reply));
+    answer.recv().unwrap_or_else(|_| Err(WarlordError::Other { message: "音频播放线程已退出".to_string() }))
+}
+
+struct Playing {
+    path: PathBuf,
+    duration: Option<Duration>,
+    sink: Sink,
+}
+
+#[derive(Default)]
+struct Player {
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    playing: Option<Playing>,
+    queue: VecDeque<PathBuf>,
+    volume: f32,
+}
+
+impl Player {
+    fn progress(&self, finished: bool) -> Option<Progress> {
+        let playing = self.playing.as_ref()?;
+        Some(Progress {
+            path: path_utils::short_path(&playing.path),
+            position_ms: playing.sink.get_pos().as_millis() as u64,
+            duration_ms: playing.duration.map(|d| d.as_millis() as u64),
+            queued: self.queue.len(),
+            finished,
+        })
+    }
+
+    /// Report the end of the current sound and drop it, which stops it.
+    fn finish(&mut self) {
+        if let Some(progress) = self.progress(true) {
+            report(&progress);
+        }
+        self.playing = None;
+    }
+
+    fn stop(&mut self) {
+        self.queue.clear();
+        self.finish();
+    }
+
+    /// Start the next sound of the queue, skipping the ones that cannot be played; the error
+    /// of the last one is returned when none could.
+    fn advance(&mut self) -> Result<(), WarlordError> {
+        let mut failure = None;
+        while let Some(path) = self.queue.pop_front() {
+            match self.start(&path) {
+                Ok(playing) => {
+                    self.playing = Some(playing);
+                    return Ok(());
+                }
+                Err(e) => {
+                    eprintln!("[WarlordTools] Skipping preview of {}: {}", path.display(), e);
+                    failure = Some(e);
+                }
+            }
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    fn start(&mut self, path: &Path) -> Result<Playing, WarlordError> {
+        let file = File::open(long_path(path)).map_err(|e| WarlordError::io(e, path))?;
+        let source = Decoder::new(BufReader::new(file)).map_err(|e| WarlordError::invalid(format!("无法播放 {}: {}", path_utils::short_path(path), e)))?;
+        let duration = source.total_duration();
+        if self.output.is_none() {
+            let opened = OutputStream::try_default().map_err(|e| WarlordError::Other { message: format!("找不到音频输出设备: {}", e) })?;
+            self.output = Some(opened);
+        }
+        let Some((_, handle)) = self.output.as_ref() else { unreachable!() };
+        let sink = Sink::try_new(handle).map_err(|e| WarlordError::Other { message: format!("无法打开音频输出: {}", e) })?;
+        sink.set_volume(self.volume);
+        sink.append(source);
+        eprintln!("[WarlordTools] Previewing {} at volume {:.2}", path.display(), self.volume);
+        Ok(Playing { path: path.to_path_buf(), duration, sink })
+    }
+
+    fn handle(&mut self, request: Request) {
+        match request {
+            Request::Play { paths, volume, reply } => {
+                self.stop();
+                self.queue = paths.into();
+                self.volume = volume;
+                let _ = reply.send(self.advance());
+            }
+            Request::Stop => self.stop(),
+            Request::Seek { position, reply } => {
+                let result = match &self.playing {
+                    Some(playing) => playing.sink.try_seek(position).map_err(|e| WarlordError::invalid(format!("无法跳转: {}", e))),
+                    None => Err(WarlordError::invalid("没有正在播放的声音")),
+                };
+                let _ = reply.send(result);
+            }
+        }
+    }
+
+    /// Report the position, or move on to the next sound when this one has ended.
+    fn tick(&mut self) {
+        match &self.playing {
+            Some(playing) if playing.sink.empty() => {
+                self.finish();
+                let _ = self.advance();
+            }
+            Some(_) => {
+                if let Some(progress) = self.progress(false) {
+                    report(&progress);
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+fn run(requests: Receiver<Request>) {
+    let mut player = Player::default();
+    loop {
+        // Idle until asked while nothing plays
+        let request = match player.playing {
+            Some(_) => requests.recv_timeout(PROGRESS_INTERVAL),
+            None => requests.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match request {
+            Ok(request) => player.handle(request),
+            Err(RecvTimeoutError::Timeout) => player.tick(),
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+/// Preview volume from the requested one (1.0 is the file's own loudness, at most 2.0) and
+/// the quiet hours factor.
+fn preview_volume(volume: Option<f32>, scale: f32) -> f32 {
+    volume.filter(|v| v.is_finite()).unwrap_or(1.0).clamp(0.0, 2.0) * scale
+}
+
+/// Play `path`, replacing the preview or queue that is playing.
+pub fn play_sound(path: &Path, volume: Option<f32>) -> Result<(), WarlordError> {
+    queue_sounds(vec![path.to_path_buf()], volume)
+}
+
+/// Play `paths` one after the other, replacing the preview or queue that is playing. Sounds
+/// that cannot be played are skipped; fails only when none can.
+pub fn queue_sounds(paths: Vec<PathBuf>, volume: Option<f32>) -> Result<(), WarlordError> {
+    if paths.is_empty() {
+        return Err(WarlordError::invalid("没有要播放的声音"));
+    }
+    let volume = preview_volume(volume, quiet_hours::status().volume_scale);
+    ask(|reply| Request::Play { paths, volume, reply })
+}
+
+/// Stop the preview and clear the queue.
+pub fn stop_sound() {
+    send(Request::Stop);
+}
+
+/// Jump to `position` in the preview that is playing.
+pub fn seek(position: Duration) -> Result<(), WarlordError> {
+    ask(|reply| Request::Seek { position, reply })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales_and_clamps_preview_volume() {
+        assert_eq!(preview_volume(None, 1.0), 1.0);
+        assert_eq!(preview_volume(Some(0.5), 0.3), 0.15);
+        assert_eq!(preview_volume(Some(9.0), 1.0), 2.0);
+        assert_eq!(preview_volume(Some(f32::NAN), 0.5), 0.5);
+        assert!(matches!(seek(Duration::from_secs(1)), Err(WarlordError::InvalidInput { .. })));
+
+        let missing = std::env::temp_dir().join("wt-audio-test-missing.ogg");
+        assert!(matches!(queue_sounds(Vec::new(), None), Err(WarlordError::InvalidInput { .. })));
+        assert!(matches!(queue_sounds(vec![missing.clone(), missing], None), Err(WarlordError::NotFound { .. })));
+    }
+}
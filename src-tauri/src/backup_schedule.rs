@@ -0,0 +1,221 @@
+//! Scheduled backups: every `interval_hours` a background thread zips the filter library and
+//! the filters and sounds in the game's documents folders into
+//! `<destination>/warlordtools-backup-<timestamp>.zip` (with a hash manifest, see
+//! `archive::zip_files`), keeping the newest `keep` archives.
+//! The outcome of the last run is kept in `backup_schedule_status.json`; a failed run is
+//! reported to the frontend and to `backup` webhooks.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+use crate::{app_paths, archive, library, power, sandbox, webhooks};
+
+const CONFIG_FILE: &str = "backup_schedule.json";
+const STATUS_FILE: &str = "backup_schedule_status.json";
+const ARCHIVE_PREFIX: &str = "warlordtools-backup-";
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScheduleConfig {
+    pub enabled: bool,
+    pub interval_hours: u64,
+    /// Folder the archives are written to
+    pub destination: String,
+    /// Archives kept in the destination, oldest removed first
+    pub keep: usize,
+    pub include_library: bool,
+    pub include_game: bool,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        ScheduleConfig { enabled: false, interval_hours: 24, destination: String::new(), keep: 10, include_library: true, include_game: true }
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScheduleStatus {
+    /// Seconds since the unix epoch
+    pub last_run: Option<u64>,
+    pub last_success: Option<u64>,
+    pub last_archive: Option<String>,
+    pub last_size: u64,
+    pub last_error: Option<String>,
+    pub next_run: Option<u64>,
+    pub running: bool,
+}
+
+pub fn get_config() -> ScheduleConfig {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+/// The destination must be inside the sandbox already (a folder picked through
+/// `set_destination`, or below one); it is created once the settings are valid.
+pub fn set_config(config: &ScheduleConfig) -> Result<(), WarlordError> {
+    if config.interval_hours == 0 || config.keep == 0 {
+        return Err(WarlordError::invalid("间隔和保留数量至少为 1"));
+    }
+    if config.enabled && config.destination.is_empty() {
+        return Err(WarlordError::invalid("请选择备份保存的文件夹"));
+    }
+    if !config.destination.is_empty() {
+        let destination = Path::new(&config.destination);
+        sandbox::check_write(destination)?;
+        fs::create_dir_all(long_path(destination)).map_err(|e| WarlordError::io(e, destination))?;
+    }
+    app_paths::save_json(CONFIG_FILE, config)
+}
+
+/// Use `folder`, which the user picked in a dialog, as the destination; it becomes a sandbox
+/// root so the archives can be written and pruned there.
+pub fn set_destination(folder: &Path) -> Result<ScheduleConfig, WarlordError> {
+    sandbox::add_root(&folder.display().to_string())?;
+    let config = ScheduleConfig { destination: folder.display().to_string(), ..get_config() };
+    app_paths::save_json(CONFIG_FILE, &config)?;
+    Ok(config)
+}
+
+/// When the run after `last_run` is due; a huge interval (hand-edited config) means never.
+fn next_run(last_run: Option<u64>, interval_hours: u64) -> u64 {
+    last_run.map_or(app_paths::now_secs(), |last| last.saturating_add(interval_hours.saturating_mul(3600)))
+}
+
+pub fn status() -> ScheduleStatus {
+    let mut status: ScheduleStatus = app_paths::load_json(STATUS_FILE);
+    let config = get_config();
+    status.next_run = config.enabled.then(|| next_run(status.last_run, config.interval_hours));
+    status.running = RUNNING.load(Ordering::Relaxed);
+    status
+}
+
+/// Files to back up: (name in the archive, file on disk).
+fn collect(config: &ScheduleConfig) -> Vec<(String, PathBuf)> {
+    let mut files = Vec::new();
+    if config.include_library {
+        if let Some(root) = library::library_root() {
+            archive::tree_entries(&root, "library/", &|_| true, &mut files);
+        }
+    }
+    if config.include_game {
+        for dir in sandbox::game_documents() {
+            let name = dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            archive::tree_entries(&dir, &format!("game/{}/", name), &|p| matches!(archive::kind_of(p), "filter" | "sound"), &mut files);
+        }
+    }
+    files
+}
+
+/// Archives in the destination, oldest first (the names start with the timestamp).
+fn archives(destination: &Path) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = fs::read_dir(long_path(destination))
+        .map(|entries| entries.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    found.retain(|p| p.extension().is_some_and(|e| e == "zip") && p.file_name().is_some_and(|n| n.to_string_lossy().starts_with(ARCHIVE_PREFIX)));
+    found.sort();
+    found
+}
+
+fn backup(config: &ScheduleConfig) -> Result<(PathBuf, u64), WarlordError> {
+    let destination = PathBuf::from(&config.destination);
+    fs::create_dir_all(long_path(&destination)).map_err(|e| WarlordError::io(e, &destination))?;
+    let mut files = collect(config);
+    // A destination inside the library would back up the earlier backups
+    files.retain(|(_, path)| !path.starts_with(&destination));
+    if files.is_empty() {
+        return Err(WarlordError::invalid("没有可备份的文件"));
+    }
+    let dest = destination.join(format!("{}{}.zip", ARCHIVE_PREFIX, chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    let (_, size) = archive::zip_files(&files, &dest, None)?;
+    // Our own archives, so removed for good rather than into the trash
+    let existing = archives(&destination);
+    for old in &existing[..existing.len().saturating_sub(config.keep.max(1))] {
+        if let Err(e) = fs::remove_file(long_path(old)) {
+            eprintln!("[WarlordTools] Could not remove old backup {}: {}", old.display(), e);
+        }
+    }
+    Ok((dest, size))
+}
+
+/// Back up now, whether or not the schedule is due, and record the outcome.
+pub fn run_now() -> Result<ScheduleStatus, WarlordError> {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return Err(WarlordError::Conflict { path: get_config().destination, message: "备份正在进行".to_string() });
+    }
+    let result = backup(&get_config());
+    RUNNING.store(false, Ordering::SeqCst);
+    let mut status: ScheduleStatus = app_paths::load_json(STATUS_FILE);
+    let now = app_paths::now_secs();
+    status.last_run = Some(now);
+    match &result {
+        Ok((archive, size)) => {
+            status.last_success = Some(now);
+            status.last_archive = Some(path_utils::short_path(archive));
+            status.last_size = *size;
+            status.last_error = None;
+        }
+        Err(e) => status.last_error = Some(e.to_string()),
+    }
+    app_paths::save_json(STATUS_FILE, &status)?;
+    result.map(|_| self::status())
+}
+
+/// Background thread that runs due backups, checking every few minutes (less often on
+/// battery). `on_failure` gets the status of each failed run.
+pub fn spawn_scheduler(on_failure: impl Fn(&ScheduleStatus) + Send + 'static) {
+    std::thread::spawn(move || loop {
+        let (config, status) = (get_config(), status());
+        if config.enabled && !status.running && status.next_run.is_some_and(|next| next <= app_paths::now_secs()) {
+            if let Err(e) = run_now() {
+                eprintln!("[WarlordTools] Scheduled backup failed: {}", e);
+                webhooks::notify(webhooks::EVENT_BACKUP, "定时备份失败", &e.to_string(), false);
+                on_failure(&self::status());
+            }
+        }
+        std::thread::sleep(power::interval(Duration::from_secs(300)));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_own_archives_in_order() {
+        let dir = std::env::temp_dir().join("wt-backup-schedule-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["warlordtools-backup-20240302-010000.zip", "warlordtools-backup-20240301-010000.zip", "other.zip", "warlordtools-backup-x.txt"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        let names: Vec<String> = archives(&dir).iter().map(|p| p.file_name().unwrap().to_string_lossy().to_string()).collect();
+        assert_eq!(names, ["warlordtools-backup-20240301-010000.zip", "warlordtools-backup-20240302-010000.zip"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn huge_intervals_never_come_due() {
+        assert_eq!(next_run(Some(1_000), 2), 1_000 + 7_200);
+        assert_eq!(next_run(Some(1_000), u64::MAX / 1_000), u64::MAX);
+    }
+
+    #[test]
+    fn destination_must_be_in_the_sandbox() {
+        let outside = ScheduleConfig { enabled: true, destination: "/wt-no-such-root/backups".to_string(), ..Default::default() };
+        assert!(matches!(set_config(&outside), Err(WarlordError::OutsideSandbox { .. })));
+        assert!(!Path::new(&outside.destination).exists());
+
+        let dir = std::env::temp_dir().join("wt-backup-destination-test");
+        let _ = fs::remove_dir_all(&dir);
+        let inside = ScheduleConfig { destination: dir.join("new").display().to_string(), ..Default::default() };
+        set_config(&inside).unwrap();
+        assert!(dir.join("new").is_dir());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,255 @@
+//! Copies of files taken before the app changes them, kept in the config folder:
+//! `backups/<path hash>/<timestamp>-<reason>.bak`, with `path.txt` naming the original file.
+//! `sandbox` takes one before every write and delete; only the newest `retention` copies of
+//! each file are kept, and the oldest copies of any file go once the folder is over
+//! `max_total_mb`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+use crate::{app_paths, folder_size, sandbox};
+
+const CONFIG_FILE: &str = "backups.json";
+const TIMESTAMP: &str = "%Y%m%d-%H%M%S%3f";
+
+/// Bytes in the backups folder, counted at the first snapshot and kept up to date after.
+static TOTAL_BYTES: Mutex<Option<u64>> = Mutex::new(None);
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackupConfig {
+    /// Snapshot files before every save and delete
+    pub enabled: bool,
+    /// Copies kept per file
+    pub retention: usize,
+    /// Size of the whole backups folder
+    pub max_total_mb: u64,
+    /// Deleting moves files to the Recycle Bin instead of removing them for good
+    pub recycle_bin: bool,
+    /// Deleting moves files to the app's own trash (`app_trash`), restorable from the app
+    pub app_trash: bool,
+    /// Days an entry stays in the app trash
+    pub trash_days: u64,
+    /// Size of the app trash
+    pub trash_max_mb: u64,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig { enabled: true, retention: 20, max_total_mb: 1024, recycle_bin: true, app_trash: true, trash_days: 30, trash_max_mb: 2048 }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupEntry {
+    pub backup: String,
+    pub original: String,
+    /// Seconds since the unix epoch
+    pub created_at: i64,
+    /// "save" / "delete" / "restore" / "search-replace" ...
+    pub reason: String,
+    pub size: u64,
+}
+
+pub fn get_config() -> BackupConfig {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+pub fn set_config(config: &BackupConfig) -> Result<(), WarlordError> {
+    if config.retention == 0 {
+        return Err(WarlordError::invalid("至少保留一个备份"));
+    }
+    if config.max_total_mb == 0 || config.trash_max_mb == 0 {
+        return Err(WarlordError::invalid("备份空间上限必须大于 0"));
+    }
+    if config.trash_days == 0 {
+        return Err(WarlordError::invalid("废纸篓至少保留一天"));
+    }
+    app_paths::save_json(CONFIG_FILE, config)
+}
+
+fn backups_root() -> PathBuf {
+    app_paths::config_file("backups")
+}
+
+/// Keyed by the path without `\\?\`, so both forms find the same backups.
+fn backup_dir(path: &Path) -> PathBuf {
+    let digest = Sha256::digest(path_utils::short_path(path).as_bytes());
+    backups_root().join(hex_prefix(&digest))
+}
+
+fn hex_prefix(digest: &[u8]) -> String {
+    digest.iter().take(8).map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Backup files in `dir`, oldest first (the names start with the timestamp).
+fn backup_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "bak")).collect())
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Returns the bytes freed.
+fn prune(dir: &Path, retention: usize) -> u64 {
+    let files = backup_files(dir);
+    let mut freed = 0;
+    for old in &files[..files.len().saturating_sub(retention)] {
+        let size = fs::metadata(old).map(|m| m.len()).unwrap_or(0);
+        if fs::remove_file(old).is_ok() {
+            freed += size;
+        }
+    }
+    freed
+}
+
+/// Remove the oldest backups below `root`, whatever file they are of, until the rest fit in
+/// `limit` bytes; `keep` (the one just taken) stays. Returns the bytes left.
+fn trim_to(root: &Path, limit: u64, keep: &Path) -> u64 {
+    let mut files: Vec<(std::ffi::OsString, PathBuf, u64)> = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .flat_map(|dir| backup_files(&dir.path()))
+        .map(|path| {
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            (path.file_name().unwrap_or_default().to_os_string(), path, size)
+        })
+        .collect();
+    // The names start with the timestamp
+    files.sort();
+    let mut total: u64 = files.iter().map(|(_, _, size)| size).sum();
+    for (_, path, size) in &files {
+        if total <= limit {
+            break;
+        }
+        if path != keep && fs::remove_file(path).is_ok() {
+            total -= size;
+        }
+    }
+    total
+}
+
+/// Account for a new backup of `added` bytes (and `freed` pruned ones), trimming the
+/// folder when it has grown past the configured size.
+fn account(added: u64, freed: u64, keep: &Path) {
+    let mut total = TOTAL_BYTES.lock().unwrap_or_else(|e| e.into_inner());
+    let bytes = total.get_or_insert_with(|| folder_size::folder_size(&backups_root()).bytes);
+    *bytes = bytes.saturating_add(added).saturating_sub(freed);
+    let limit = get_config().max_total_mb.max(1).saturating_mul(1024 * 1024);
+    if *bytes > limit {
+        *bytes = trim_to(&backups_root(), limit, keep);
+    }
+}
+
+/// Copy `path` into its backup folder. Returns the backup file.
+pub fn snapshot(path: &Path, reason: &str) -> Result<PathBuf, WarlordError> {
+    let dir = backup_dir(path);
+    fs::create_dir_all(&dir).map_err(|e| WarlordError::io(e, &dir))?;
+    fs::write(dir.join("path.txt"), path_utils::short_path(path).as_bytes()).map_err(|e| WarlordError::io(e, &dir))?;
+    let backup = dir.join(format!("{}-{}.bak", chrono::Local::now().format(TIMESTAMP), reason));
+    let size = fs::copy(long_path(path), &backup).map_err(|e| WarlordError::io(e, path))?;
+    let freed = prune(&dir, get_config().retention.max(1));
+    account(size, freed, &backup);
+    Ok(backup)
+}
+
+/// Largest folder `snapshot_tree` copies; a bigger one would crowd every other backup out.
+pub const TREE_LIMIT: u64 = 256 * 1024 * 1024;
+
+/// Snapshot every file below `dir` (before the folder is deleted). Refused for folders over
+/// `TREE_LIMIT`.
+pub fn snapshot_tree(dir: &Path, reason: &str) -> Result<usize, WarlordError> {
+    if folder_size::folder_size(dir).bytes > TREE_LIMIT {
+        return Err(WarlordError::invalid(format!("{} 超过 {} MB, 无法在删除前备份, 请开启回收站后再删除", path_utils::short_path(dir), TREE_LIMIT >> 20)));
+    }
+    snapshot_files(dir, reason)
+}
+
+fn snapshot_files(dir: &Path, reason: &str) -> Result<usize, WarlordError> {
+    let mut count = 0;
+    for entry in fs::read_dir(long_path(dir)).map_err(|e| WarlordError::io(e, dir))?.flatten() {
+        let path = dir.join(entry.file_name());
+        if entry.path().is_dir() {
+            count += snapshot_files(&path, reason)?;
+        } else {
+            snapshot(&path, reason)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+fn entry_of(backup: &Path, original: &str) -> Option<BackupEntry> {
+    let name = backup.file_stem()?.to_string_lossy().to_string();
+    // <date>-<time>-<reason>
+    let split = name.match_indices('-').nth(1)?.0;
+    let created_at = chrono::NaiveDateTime::parse_from_str(&name[..split], TIMESTAMP).ok()?.and_local_timezone(chrono::Local).earliest()?.timestamp();
+    Some(BackupEntry {
+        backup: backup.display().to_string(),
+        original: original.to_string(),
+        created_at,
+        reason: name[split + 1..].to_string(),
+        size: fs::metadata(backup).map(|m| m.len()).unwrap_or(0),
+    })
+}
+
+/// Backups of `path`, newest first.
+pub fn list(path: &Path) -> Vec<BackupEntry> {
+    let original = path.display().to_string();
+    let mut entries: Vec<BackupEntry> = backup_files(&backup_dir(path)).iter().filter_map(|b| entry_of(b, &original)).collect();
+    entries.reverse();
+    entries
+}
+
+/// Put `backup` back in place of its original file (which is itself backed up first).
+/// Returns the original path.
+pub fn restore(backup: &Path) -> Result<String, WarlordError> {
+    let dir = backup.parent().filter(|d| d.parent() == Some(backups_root().as_path())).ok_or_else(|| WarlordError::invalid("不是备份文件"))?;
+    let original = fs::read_to_string(dir.join("path.txt")).map_err(|_| WarlordError::invalid("备份缺少原始路径"))?;
+    let contents = fs::read(backup).map_err(|e| WarlordError::io(e, backup))?;
+    if let Some(parent) = Path::new(&original).parent() {
+        sandbox::check_write(parent)?;
+        fs::create_dir_all(long_path(parent)).map_err(|e| WarlordError::io(e, parent))?;
+    }
+    sandbox::write_as(&original, contents, "restore")?;
+    Ok(original)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_backup_names() {
+        let entry = entry_of(Path::new("backups/x/20240301-101502123-search-replace.bak"), "a.filter").unwrap();
+        assert_eq!(entry.reason, "search-replace");
+        let expected = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(10, 15, 2).unwrap();
+        assert_eq!(entry.created_at, expected.and_local_timezone(chrono::Local).unwrap().timestamp());
+        assert!(entry_of(Path::new("backups/x/notes.bak"), "a.filter").is_none());
+    }
+
+    #[test]
+    fn trims_the_oldest_backups_of_any_file() {
+        let root = std::env::temp_dir().join("wt-backups-trim-test");
+        let _ = fs::remove_dir_all(&root);
+        for (dir, name) in [("a", "20240301-100000000-save"), ("b", "20240302-100000000-save"), ("a", "20240303-100000000-save"), ("b", "20240304-100000000-save")] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            fs::write(root.join(dir).join(format!("{}.bak", name)), [0u8; 10]).unwrap();
+        }
+        let newest = root.join("b/20240304-100000000-save.bak");
+        assert_eq!(trim_to(&root, 25, &newest), 20);
+        assert_eq!(backup_files(&root.join("a")), [root.join("a/20240303-100000000-save.bak")]);
+        assert_eq!(backup_files(&root.join("b")), std::slice::from_ref(&newest));
+        assert_eq!(trim_to(&root, 5, &newest), 10);
+        assert!(newest.is_file());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
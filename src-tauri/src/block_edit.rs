@@ -0,0 +1,249 @@
+//! Structural edits on single blocks that leave the rest of the file byte-identical.
+
+use crate::error::WarlordError;
+use crate::filter_parser::{self, section_marker, Block, FilterDocument, Rule};
+
+/// Comment banner line such as "#=====" or "#-----".
+fn is_banner(line: &str) -> bool {
+    let text = line.trim().trim_start_matches('#').trim();
+    !text.is_empty() && text.chars().all(|c| matches!(c, '=' | '-' | '*' | '~'))
+}
+
+/// Split a block's leading lines into (prefix, attached): `attached` is the comment group
+/// directly above the header that belongs to the block; blank lines, section markers and
+/// banners stay in `prefix` so they keep their place when the block moves.
+pub fn split_leading(leading: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut start = leading.len();
+    while start > 0 {
+        let l = &leading[start - 1];
+        if l.trim().is_empty() || section_marker(l).is_some() || is_banner(l) {
+            break;
+        }
+        start -= 1;
+    }
+    (leading[..start].to_vec(), leading[start..].to_vec())
+}
+
+/// Where an inserted block lands relative to the comments/banners above the insertion point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    /// Right after the previous block (stays in the previous block's section)
+    AfterPrev,
+    /// Right above the next block's own comments (below any section banner)
+    BeforeNext,
+}
+
+/// Join two runs of separator lines without doubling the blank line between them.
+fn join_prefix(mut first: Vec<String>, second: Vec<String>) -> Vec<String> {
+    let mut second = second.into_iter().peekable();
+    if first.last().is_some_and(|l| l.trim().is_empty()) && second.peek().is_some_and(|l| l.trim().is_empty()) {
+        second.next();
+    }
+    first.extend(second);
+    first
+}
+
+/// Take a block out of the document. The separator lines above it (blank lines, banners)
+/// stay where they were by moving onto the next block, or into the trailer.
+fn detach(doc: &mut FilterDocument, index: usize) -> Block {
+    let mut block = doc.blocks.remove(index);
+    let (prefix, attached) = split_leading(&block.leading);
+    block.leading = attached;
+    match doc.blocks.get_mut(index) {
+        Some(next) => next.leading = join_prefix(prefix, std::mem::take(&mut next.leading)),
+        None => doc.trailer = join_prefix(prefix, std::mem::take(&mut doc.trailer)),
+    }
+    block
+}
+
+/// Insert a detached block so it ends up at `index`.
+pub fn attach(doc: &mut FilterDocument, index: usize, mut block: Block, side: Side) {
+    let attached = std::mem::take(&mut block.leading);
+    let side = if index == 0 { Side::BeforeNext } else { side };
+    match (side, doc.blocks.get_mut(index)) {
+        (Side::BeforeNext, Some(next)) => {
+            let (prefix, next_attached) = split_leading(&next.leading);
+            block.leading = join_prefix(prefix, attached);
+            next.leading = join_prefix(vec![String::new()], next_attached);
+        }
+        _ => block.leading = join_prefix(vec![String::new()], attached),
+    }
+    doc.blocks.insert(index.min(doc.blocks.len()), block);
+}
+
+/// Remove a block that was inserted with `attach`, handing its separator lines back to the
+/// block below and dropping the blank line `attach` gave that block.
+pub fn remove_attached(doc: &mut FilterDocument, index: usize) -> Block {
+    let (prefix, _) = split_leading(&doc.blocks[index].leading);
+    let block = doc.blocks.remove(index);
+    match doc.blocks.get_mut(index) {
+        Some(next) => {
+            let skip = next.leading.first().is_some_and(|l| l.trim().is_empty()) as usize;
+            next.leading = prefix.into_iter().chain(next.leading.drain(skip..)).collect();
+        }
+        None => doc.trailer = prefix.into_iter().chain(std::mem::take(&mut doc.trailer)).collect(),
+    }
+    block
+}
+
+/// Move block `from` to index `to` (counted after removal, like `Vec::insert`).
+pub fn move_block(doc: &mut FilterDocument, from: usize, to: usize, side: Side) -> Result<usize, WarlordError> {
+    if from >= doc.blocks.len() || to >= doc.blocks.len() {
+        return Err(WarlordError::invalid("Block index out of range"));
+    }
+    let block = detach(doc, from);
+    attach(doc, to, block, side);
+    doc.renumber();
+    Ok(to)
+}
+
+/// Move a block one step up or down. At a section boundary the block crosses the
+/// banner into the neighbouring section instead of swapping with that section's block.
+pub fn move_block_by(doc: &mut FilterDocument, id: usize, up: bool) -> Result<usize, WarlordError> {
+    let count = doc.blocks.len();
+    if id >= count {
+        return Err(WarlordError::invalid("Block index out of range"));
+    }
+    let neighbour = if up { id.checked_sub(1) } else { Some(id + 1).filter(|n| *n < count) };
+    let neighbour = neighbour.ok_or_else(|| WarlordError::invalid(if up { "Block is already first" } else { "Block is already last" }))?;
+    let same_section = doc.blocks[id].section == doc.blocks[neighbour].section;
+    match (up, same_section) {
+        (true, true) => move_block(doc, id, neighbour, Side::BeforeNext),
+        (true, false) => move_block(doc, id, id, Side::AfterPrev),
+        (false, true) => move_block(doc, id, neighbour, Side::AfterPrev),
+        (false, false) => move_block(doc, id, id, Side::BeforeNext),
+    }
+}
+
+/// Move a block to the start or end of another section (matched by its `[[NNNN]] Title`).
+pub fn move_block_to_section(doc: &mut FilterDocument, id: usize, section: &str, at_end: bool) -> Result<usize, WarlordError> {
+    if id >= doc.blocks.len() {
+        return Err(WarlordError::invalid("Block index out of range"));
+    }
+    let members: Vec<usize> = doc
+        .blocks
+        .iter()
+        .filter(|b| b.id != id && b.section.as_deref().is_some_and(|s| s.contains(section)))
+        .map(|b| b.id)
+        .collect();
+    let (first, last) = match (members.first(), members.last()) {
+        (Some(f), Some(l)) => (*f, *l),
+        _ => return Err(WarlordError::invalid(format!("Section not found: {}", section))),
+    };
+    // Indexes of the other blocks shift down by one once `id` is taken out
+    let shift = |i: usize| if i > id { i - 1 } else { i };
+    let block = detach(doc, id);
+    let to = if at_end { shift(last) + 1 } else { shift(first) };
+    attach(doc, to, block, if at_end { Side::AfterPrev } else { Side::BeforeNext });
+    doc.renumber();
+    Ok(to)
+}
+
+/// Rule to remove from a block. Without `operator` every rule with the keyword goes.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleRef {
+    pub keyword: String,
+    #[serde(default)]
+    pub operator: Option<String>,
+}
+
+/// Form edits on one block.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BlockChanges {
+    /// New block keyword ("Show", "Hide", "Minimal")
+    pub kind: Option<String>,
+    /// Rules to set. A condition replaces the one with the same keyword and operator (so
+    /// `ItemLevel >= 60` and `ItemLevel < 75` stay separate); an action replaces by keyword.
+    pub set: Vec<Rule>,
+    pub remove: Vec<RuleRef>,
+}
+
+fn same_slot(existing: &Rule, rule: &Rule) -> bool {
+    existing.keyword == rule.keyword && (rule.is_action() || existing.operator == rule.operator)
+}
+
+/// Apply `changes` to block `id`. Lines that are not changed keep their exact text.
+pub fn update_block(doc: &mut FilterDocument, id: usize, changes: &BlockChanges) -> Result<Block, WarlordError> {
+    let block = doc.blocks.get_mut(id).ok_or_else(|| WarlordError::invalid("Block index out of range"))?;
+    if let Some(kind) = &changes.kind {
+        if filter_parser::block_keyword(kind) != Some(kind.as_str()) {
+            return Err(WarlordError::invalid(format!("Unknown block keyword: {}", kind)));
+        }
+        block.kind = kind.clone();
+        block.sync_header();
+    }
+    for remove in &changes.remove {
+        block.lines.retain(|l| {
+            l.rule.as_ref().is_none_or(|r| r.keyword != remove.keyword || (remove.operator.is_some() && r.operator != remove.operator))
+        });
+    }
+    for rule in &changes.set {
+        let mut rule = rule.clone();
+        match block.lines.iter_mut().find(|l| l.rule.as_ref().is_some_and(|r| same_slot(r, &rule))) {
+            Some(line) => {
+                if line.rule.as_ref() == Some(&rule) {
+                    continue;
+                }
+                // Keep an inline comment the form does not know about
+                if rule.comment.is_none() {
+                    rule.comment = line.rule.as_ref().and_then(|r| r.comment.clone());
+                }
+                line.set_rule(rule);
+            }
+            None => block.push_rule(rule),
+        }
+    }
+    let updated = block.clone();
+    doc.renumber();
+    Ok(updated)
+}
+
+/// Load, edit and write back a filter file.
+pub fn edit_file<T>(path: &str, edit: impl FnOnce(&mut FilterDocument) -> Result<T, WarlordError>) -> Result<T, WarlordError> {
+    let mut doc = filter_parser::parse_file(path)?;
+    let result = edit(&mut doc)?;
+    filter_parser::write_file(path, &doc)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_only_affected_lines() {
+        let src = "Show # $tier->t1\n\tItemLevel >= 60 # min\n\tItemLevel < 75\n\tSetFontSize 40\n\tMinimapIcon 0 Red Star\n";
+        let mut doc = FilterDocument::parse(src);
+        let changes: BlockChanges = serde_json::from_str(
+            r#"{"kind": "Hide", "set": [{"keyword": "ItemLevel", "operator": ">=", "values": ["70"]}, {"keyword": "SetFontSize", "values": ["45"]}], "remove": [{"keyword": "MinimapIcon"}]}"#,
+        )
+        .unwrap();
+        update_block(&mut doc, 0, &changes).unwrap();
+        assert_eq!(doc.to_text(), "Hide # $tier->t1\n\tItemLevel >= 70 # min\n\tItemLevel < 75\n\tSetFontSize 45\n");
+    }
+
+    const SRC: &str = "# [[0100]] A\n\n# a1\nShow\n    BaseType \"A1\"\n\n# a2\nShow\n    BaseType \"A2\"\n\n# [[0200]] B\n\n# b1\nShow\n    BaseType \"B1\"\n";
+
+    #[test]
+    fn moves_block_with_its_comments() {
+        let mut doc = FilterDocument::parse(SRC);
+        assert_eq!(move_block_by(&mut doc, 0, false).unwrap(), 1);
+        assert_eq!(
+            doc.to_text(),
+            "# [[0100]] A\n\n# a2\nShow\n    BaseType \"A2\"\n\n# a1\nShow\n    BaseType \"A1\"\n\n# [[0200]] B\n\n# b1\nShow\n    BaseType \"B1\"\n"
+        );
+        move_block_by(&mut doc, 1, true).unwrap();
+        assert_eq!(doc.to_text(), SRC);
+    }
+
+    #[test]
+    fn moves_block_into_other_section() {
+        let mut doc = FilterDocument::parse(SRC);
+        let to = move_block_to_section(&mut doc, 0, "[[0200]]", true).unwrap();
+        assert_eq!(to, 2);
+        assert_eq!(doc.blocks[2].section.as_deref(), Some("[[0200]] B"));
+        assert_eq!(doc.blocks[2].leading, vec!["".to_string(), "# a1".to_string()]);
+    }
+}
@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use regex::Regex;
+
+use crate::error::WarlordError;
+
+/// Something interesting the game wrote to Client.txt.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum LogEvent {
+    /// "Generating level 12 area "G1_2" with seed ..." (language independent)
+    AreaGenerated { area_level: u32, area_id: String },
+    /// "You have entered Clearfell." / "你已进入：..."
+    AreaEntered { name: String },
+    /// "Name (Class) is now level 5"
+    LevelUp { character: String, class: String, level: u32 },
+}
+
+/// Latest known character/zone state, accumulated from events.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogState {
+    pub character: Option<String>,
+    pub level: u32,
+    pub area: Option<String>,
+    pub area_id: Option<String>,
+    pub area_level: u32,
+}
+
+impl LogState {
+    fn apply(&mut self, event: &LogEvent) {
+        match event {
+            LogEvent::AreaGenerated { area_level, area_id } => {
+                self.area_level = *area_level;
+                self.area_id = Some(area_id.clone());
+            }
+            LogEvent::AreaEntered { name } => self.area = Some(name.clone()),
+            LogEvent::LevelUp { character, level, .. } => {
+                self.character = Some(character.clone());
+                self.level = *level;
+            }
+        }
+    }
+}
+
+type Subscriber = Box<dyn Fn(&LogEvent, &LogState) + Send>;
+
+static STATE: Mutex<Option<LogState>> = Mutex::new(None);
+static SUBSCRIBERS: Mutex<Vec<Subscriber>> = Mutex::new(Vec::new());
+static WATCHED_PATH: Mutex<Option<String>> = Mutex::new(None);
+// Bumped whenever a new watcher starts so the old thread exits
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// Bytes of existing log read on start to recover the current level/zone
+const SEED_BYTES: u64 = 256 * 1024;
+
+fn patterns() -> &'static [Regex; 4] {
+    static RE: OnceLock<[Regex; 4]> = OnceLock::new();
+    RE.get_or_init(|| {
+        [
+            Regex::new(r#"Generating level (\d+) area "([^"]+)""#).unwrap(),
+            Regex::new(r": You have entered (.+?)\.?$").unwrap(),
+            Regex::new(r": 你已进入：(.+?)。?$").unwrap(),
+            Regex::new(r": (\S+) \(([^)]+)\) is now level (\d+)").unwrap(),
+        ]
+    })
+}
+
+/// Parse one Client.txt line.
+pub fn parse_line(line: &str) -> Option<LogEvent> {
+    let [generating, entered, entered_cn, level_up] = patterns();
+    let line = line.trim_end();
+    if let Some(c) = generating.captures(line) {
+        return Some(LogEvent::AreaGenerated {
+            area_level: c[1].parse().ok()?,
+            area_id: c[2].to_string(),
+        });
+    }
+    if let Some(c) = entered.captures(line).or_else(|| entered_cn.captures(line)) {
+        return Some(LogEvent::AreaEntered { name: c[1].to_string() });
+    }
+    if let Some(c) = level_up.captures(line) {
+        return Some(LogEvent::LevelUp {
+            character: c[1].to_string(),
+            class: c[2].to_string(),
+            level: c[3].parse().ok()?,
+        });
+    }
+    None
+}
+
+/// Register a callback invoked for every new event (after the state was updated).
+pub fn subscribe(callback: Subscriber) {
+    SUBSCRIBERS.lock().unwrap().push(callback);
+}
+
+pub fn current_state() -> LogState {
+    STATE.lock().unwrap().clone().unwrap_or_default()
+}
+
+pub fn watched_path() -> Option<String> {
+    WATCHED_PATH.lock().unwrap().clone()
+}
+
+fn dispatch(event: LogEvent) {
+    let state = {
+        let mut guard = STATE.lock().unwrap();
+        let state = guard.get_or_insert_with(LogState::default);
+        state.apply(&event);
+        state.clone()
+    };
+    for sub in SUBSCRIBERS.lock().unwrap().iter() {
+        sub(&event, &state);
+    }
+}
+
+/// Start tailing `path` (usually `<game dir>/logs/Client.txt`). Replaces any previous watcher.
+/// The tail of the existing log seeds the state without notifying subscribers.
+pub fn start_watcher(path: &str) -> Result<(), WarlordError> {
+    let mut file = File::open(path).map_err(|e| WarlordError::io(e, path))?;
+    let len = file.metadata().map_err(|e| WarlordError::io(e, path))?.len();
+
+    let seed_from = len.saturating_sub(SEED_BYTES);
+    file.seek(SeekFrom::Start(seed_from)).map_err(|e| WarlordError::io(e, path))?;
+    let mut seed = Vec::new();
+    file.read_to_end(&mut seed).map_err(|e| WarlordError::io(e, path))?;
+    let mut state = LogState::default();
+    for line in String::from_utf8_lossy(&seed).lines() {
+        if let Some(event) = parse_line(line) {
+            state.apply(&event);
+        }
+    }
+    *STATE.lock().unwrap() = Some(state);
+    *WATCHED_PATH.lock().unwrap() = Some(path.to_string());
+
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let path = path.to_string();
+    std::thread::spawn(move || tail(path, len, generation));
+    Ok(())
+}
+
+pub fn stop_watcher() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    *WATCHED_PATH.lock().unwrap() = None;
+}
+
+fn tail(path: String, mut pos: u64, generation: u64) {
+    let mut pending = String::new();
+    while GENERATION.load(Ordering::SeqCst) == generation {
+        std::thread::sleep(crate::power::interval(std::time::Duration::from_millis(500)));
+        let Ok(mut file) = File::open(&path) else { continue };
+        let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+        if len < pos {
+            // Log was truncated/rotated
+            pos = 0;
+            pending.clear();
+        }
+        if len == pos || file.seek(SeekFrom::Start(pos)).is_err() {
+            continue;
+        }
+        let mut buf = Vec::new();
+        if file.read_to_end(&mut buf).is_err() {
+            continue;
+        }
+        pos += buf.len() as u64;
+        pending.push_str(&String::from_utf8_lossy(&buf));
+        // Keep a trailing partial line for the next round
+        while let Some(idx) = pending.find('\n') {
+            let line: String = pending.drain(..=idx).collect();
+            if let Some(event) = parse_line(&line) {
+                dispatch(event);
+            }
+        }
+    }
+    eprintln!("[WarlordTools] log watcher for {} stopped", path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_known_lines() {
+        assert_eq!(
+            parse_line("2024/12/10 20:00:00 1 2 [DEBUG Client 10] Generating level 12 area \"G1_2\" with seed 1"),
+            Some(LogEvent::AreaGenerated { area_level: 12, area_id: "G1_2".to_string() })
+        );
+        assert_eq!(
+            parse_line("2024/12/10 20:00:01 1 2 [INFO Client 10] : You have entered Clearfell."),
+            Some(LogEvent::AreaEntered { name: "Clearfell".to_string() })
+        );
+        assert_eq!(
+            parse_line("2024/12/10 20:00:02 1 2 [INFO Client 10] : Bob (Warrior) is now level 5"),
+            Some(LogEvent::LevelUp { character: "Bob".to_string(), class: "Warrior".to_string(), level: 5 })
+        );
+        assert_eq!(parse_line("2024/12/10 20:00:03 1 2 [INFO Client 10] Connecting to instance"), None);
+    }
+}
@@ -0,0 +1,122 @@
+//! Conflict copies left by sync tools when the library lives in a synced folder and the same
+//! filter changed on two PCs: Dropbox and Nextcloud ("name (Jane's conflicted copy
+//! 2024-03-01).filter", "冲突副本" in Chinese clients), Syncthing
+//! ("name.sync-conflict-20240301-101500-ABCDEFG.filter") and OneDrive ("name-PCNAME.filter",
+//! with this PC's name). A scan marks each copy whose original is next to it, and
+//! `resolve` keeps one side or merges the copy's blocks into the original, so the copies do
+//! not pile up unnoticed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::WarlordError;
+use crate::filter_merge::{self, MergeReport};
+use crate::path_utils::{self, long_path};
+use crate::sandbox;
+
+/// What Dropbox and Nextcloud put in the parentheses, in the languages the clients use.
+const MARKERS: &[&str] = &["conflicted copy", "Conflicted copy", "冲突副本", "冲突的副本", "衝突複本"];
+const SYNCTHING_MARKER: &str = ".sync-conflict-";
+
+/// "Mine" is the file under the original name, "theirs" the conflict copy.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Resolution {
+    KeepMine,
+    /// The copy's content replaces the original
+    KeepTheirs,
+    /// Blocks only the copy has are added to the original (filters only)
+    Merge,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictResolution {
+    /// The file that remains
+    pub original: String,
+    pub merge: Option<MergeReport>,
+}
+
+fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(i) if i > 0 => name.split_at(i),
+        _ => (name, ""),
+    }
+}
+
+/// The name `name` is a conflict copy of, if it looks like one. `host` is this PC's name,
+/// which OneDrive appends.
+fn original_name_on(name: &str, host: Option<&str>) -> Option<String> {
+    let (stem, extension) = split_extension(name);
+    if let Some((base, _)) = stem.rsplit_once(SYNCTHING_MARKER).filter(|(base, _)| !base.is_empty()) {
+        return Some(format!("{}{}", base, extension));
+    }
+    if let Some(at) = MARKERS.iter().filter_map(|m| stem.find(m)).min() {
+        if let Some(start) = stem[..at].rfind(" (").filter(|&start| start > 0 && stem.ends_with(')')) {
+            return Some(format!("{}{}", &stem[..start], extension));
+        }
+    }
+    // name-PCNAME or name-PCNAME-2
+    let host = host.filter(|h| !h.is_empty())?;
+    let trimmed = match stem.rsplit_once('-') {
+        Some((rest, n)) if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => stem,
+    };
+    let split = trimmed.len().checked_sub(host.len() + 1)?;
+    let (base, suffix) = (trimmed.get(..split)?, trimmed.get(split..)?);
+    (!base.is_empty() && suffix.strip_prefix('-').is_some_and(|s| s.eq_ignore_ascii_case(host))).then(|| format!("{}{}", base, extension))
+}
+
+pub fn original_name(name: &str) -> Option<String> {
+    original_name_on(name, std::env::var("COMPUTERNAME").ok().as_deref())
+}
+
+/// The original `path` is a conflict copy of, when that file exists next to it.
+pub fn original_of(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let original = path.with_file_name(original_name(&name)?);
+    long_path(&original).is_file().then_some(original)
+}
+
+/// Settle the conflict copy at `copy` with its original; the copy goes to the trash either way.
+pub fn resolve(copy: &Path, resolution: Resolution) -> Result<ConflictResolution, WarlordError> {
+    let original = original_of(copy).ok_or_else(|| WarlordError::invalid(format!("{} 不是同步冲突副本", path_utils::short_path(copy))))?;
+    sandbox::check_write(&original)?;
+    let merge = match resolution {
+        Resolution::KeepMine => None,
+        Resolution::KeepTheirs => {
+            let contents = fs::read(long_path(copy)).map_err(|e| WarlordError::io(e, copy))?;
+            sandbox::write_as(&original, contents, "cloud-conflict")?;
+            None
+        }
+        Resolution::Merge => {
+            if !original.extension().is_some_and(|e| e.eq_ignore_ascii_case("filter")) {
+                return Err(WarlordError::invalid("只有过滤器可以合并"));
+            }
+            let (original, copy) = (original.display().to_string(), copy.display().to_string());
+            Some(filter_merge::merge_filters(&original, &copy, &original)?)
+        }
+    };
+    sandbox::delete(copy)?;
+    eprintln!("[WarlordTools] Resolved sync conflict {} ({:?})", copy.display(), resolution);
+    Ok(ConflictResolution { original: path_utils::short_path(&original), merge })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_conflict_copies() {
+        let original = |name| original_name_on(name, Some("GAMING-PC"));
+        assert_eq!(original("NeverSink (Jane Doe's conflicted copy 2024-03-01).filter").as_deref(), Some("NeverSink.filter"));
+        assert_eq!(original("NeverSink (Jane 的冲突副本 2024-03-01 (1)).filter").as_deref(), Some("NeverSink.filter"));
+        assert_eq!(original("a.b (conflicted copy 2024-03-01 101500).filter").as_deref(), Some("a.b.filter"));
+        assert_eq!(original("leveling.sync-conflict-20240301-101500-ABCDEFG.filter").as_deref(), Some("leveling.filter"));
+        assert_eq!(original("Strict-gaming-pc.filter").as_deref(), Some("Strict.filter"));
+        assert_eq!(original("Strict-GAMING-PC-2.filter").as_deref(), Some("Strict.filter"));
+        for name in ["Strict (copy).filter", "3-STRICT.filter", "GAMING-PC.filter", "(conflicted copy).filter"] {
+            assert_eq!(original(name), None, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Color-blind variants of a filter. Text/border/background colors go through a daltonize
+//! correction for the selected deficiency: the part of a color the viewer cannot see is moved
+//! into channels they can. The correction is a continuous function of the color, so tiers
+//! drawn as a gradient of similar colors stay a gradient. Palettes can pin exact colors and
+//! minimap icon / beam colors per deficiency.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::WarlordError;
+use crate::app_paths;
+use crate::filter_parser::{self, FilterDocument};
+use crate::filter_transforms::{self, FileChange};
+
+const CONFIG_FILE: &str = "colorblind.json";
+
+pub const DEFICIENCIES: &[&str] = &["protanopia", "deuteranopia", "tritanopia"];
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Palette {
+    /// "R G B" -> "R G B [A]", used instead of the computed correction
+    pub colors: BTreeMap<String, String>,
+    /// MinimapIcon / PlayEffect color names, e.g. "Green" -> "Blue"
+    pub named: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ColorBlindConfig {
+    /// Keyed by deficiency
+    pub palettes: BTreeMap<String, Palette>,
+}
+
+impl Default for ColorBlindConfig {
+    fn default() -> Self {
+        let named = |pairs: &[(&str, &str)]| Palette {
+            colors: BTreeMap::new(),
+            named: pairs.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect(),
+        };
+        let red_green = named(&[("Green", "Blue"), ("Brown", "Grey")]);
+        ColorBlindConfig {
+            palettes: BTreeMap::from([
+                ("protanopia".to_string(), red_green.clone()),
+                ("deuteranopia".to_string(), red_green),
+                ("tritanopia".to_string(), named(&[("Blue", "Pink"), ("Purple", "Red")])),
+            ]),
+        }
+    }
+}
+
+pub fn get_colorblind_config() -> ColorBlindConfig {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+pub fn set_colorblind_config(config: &ColorBlindConfig) -> Result<(), WarlordError> {
+    for palette in config.palettes.values() {
+        for (from, to) in &palette.colors {
+            filter_transforms::parse_rgba(from)?;
+            filter_transforms::parse_rgba(to)?;
+        }
+    }
+    app_paths::save_json(CONFIG_FILE, config)
+}
+
+type Matrix = [[f64; 3]; 3];
+
+const RGB_TO_LMS: Matrix = [[17.8824, 43.5161, 4.11935], [3.45565, 27.1554, 3.86714], [0.0299566, 0.184309, 1.46709]];
+const LMS_TO_RGB: Matrix = [
+    [0.0809444479, -0.130504409, 0.116721066],
+    [-0.0102485335, 0.0540193266, -0.113614708],
+    [-0.000365296938, -0.00412161469, 0.693511405],
+];
+/// Shifts the invisible error into the remaining channels
+const ERROR_SHIFT: Matrix = [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]];
+
+fn simulation(deficiency: &str) -> Result<Matrix, WarlordError> {
+    match deficiency {
+        "protanopia" => Ok([[0.0, 2.02344, -2.52581], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]),
+        "deuteranopia" => Ok([[1.0, 0.0, 0.0], [0.494207, 0.0, 1.24827], [0.0, 0.0, 1.0]]),
+        "tritanopia" => Ok([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [-0.395913, 0.801109, 0.0]]),
+        _ => Err(WarlordError::invalid(format!("未知的色觉类型: {}", deficiency))),
+    }
+}
+
+fn mul(m: &Matrix, v: [f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|r| m[r][0] * v[0] + m[r][1] * v[1] + m[r][2] * v[2])
+}
+
+/// Daltonized `rgb` for the deficiency with simulation matrix `sim`.
+pub fn correct(rgb: [u8; 3], sim: &Matrix) -> [u8; 3] {
+    let v = rgb.map(f64::from);
+    let seen = mul(&LMS_TO_RGB, mul(sim, mul(&RGB_TO_LMS, v)));
+    let shift = mul(&ERROR_SHIFT, [0, 1, 2].map(|i| v[i] - seen[i]));
+    [0, 1, 2].map(|i| (v[i] + shift[i]).round().clamp(0.0, 255.0) as u8)
+}
+
+/// Remap colors in `doc` for `deficiency`. Returns the rewritten line numbers.
+pub fn remap_document(doc: &mut FilterDocument, deficiency: &str, palette: &Palette) -> Result<Vec<usize>, WarlordError> {
+    let sim = simulation(deficiency)?;
+    let pinned: BTreeMap<[u8; 4], Vec<String>> = palette
+        .colors
+        .iter()
+        .filter_map(|(from, to)| Some((filter_transforms::parse_rgba(from).ok()?, to.split_whitespace().map(String::from).collect())))
+        .collect();
+    let mut lines = Vec::new();
+    for block in &mut doc.blocks {
+        let body_start = block.line + 1;
+        for (i, line) in block.lines.iter_mut().enumerate() {
+            let Some(rule) = line.rule.as_ref() else { continue };
+            let mut rule = rule.clone();
+            if filter_transforms::COLOR_KEYWORDS.contains(&rule.keyword.as_str()) {
+                let Ok(rgba) = filter_transforms::parse_rgba(&rule.values.join(" ")) else { continue };
+                let mut values = match pinned.get(&rgba).or_else(|| pinned.get(&[rgba[0], rgba[1], rgba[2], 255])) {
+                    Some(to) => to.clone(),
+                    None => correct([rgba[0], rgba[1], rgba[2]], &sim).iter().map(u8::to_string).collect(),
+                };
+                // Keep the block's own alpha unless the palette sets one
+                if values.len() == 3 && rule.values.len() == 4 {
+                    values.push(rgba[3].to_string());
+                }
+                rule.values = values;
+            } else if ["MinimapIcon", "PlayEffect"].contains(&rule.keyword.as_str()) {
+                for v in &mut rule.values {
+                    if let Some(to) = palette.named.get(v.as_str()) {
+                        *v = to.clone();
+                    }
+                }
+            } else {
+                continue;
+            }
+            if line.rule.as_ref() != Some(&rule) {
+                line.set_rule(rule);
+                lines.push(body_start + i);
+            }
+        }
+    }
+    Ok(lines)
+}
+
+/// Write `<stem>.<deficiency>.filter` next to `path`.
+pub fn compile_colorblind_variant(path: &str, deficiency: &str) -> Result<FileChange, WarlordError> {
+    let palette = get_colorblind_config().palettes.remove(deficiency).unwrap_or_default();
+    let mut doc = filter_parser::parse_file(path)?;
+    let lines = remap_document(&mut doc, deficiency, &palette)?;
+    let source = Path::new(path);
+    let stem = source.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let dest = source.with_file_name(format!("{}.{}.filter", stem, deficiency)).display().to_string();
+    filter_parser::write_file(&dest, &doc)?;
+    Ok(FileChange { path: dest, replacements: lines.len(), lines })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remaps_colors_and_icons() {
+        let sim = simulation("deuteranopia").unwrap();
+        let gray = correct([128, 128, 128], &sim);
+        assert!(gray.iter().all(|c| c.abs_diff(128) <= 1));
+        assert_ne!(correct([255, 0, 0], &sim), [255, 0, 0]);
+
+        let palette = ColorBlindConfig::default().palettes.remove("deuteranopia").unwrap();
+        let palette = Palette { colors: BTreeMap::from([("0 255 0".to_string(), "0 0 255".to_string())]), ..palette };
+        let mut doc = FilterDocument::parse("Show\n    SetTextColor 0 255 0 200\n    MinimapIcon 0 Green Star\n    SetFontSize 40\n");
+        assert_eq!(remap_document(&mut doc, "deuteranopia", &palette).unwrap(), vec![1, 2]);
+        assert_eq!(doc.to_text(), "Show\n    SetTextColor 0 0 255 200\n    MinimapIcon 0 Blue Star\n    SetFontSize 40\n");
+        assert!(remap_document(&mut doc, "achromatopsia", &palette).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Duplicate blocks. The game stops at the first block that matches an item, so a later block
+//! with the same conditions only ever matters after a `Continue`:
+//! - same conditions, visibility and actions with no `Continue` block in between: a duplicate,
+//!   removed; with one, the later block can undo what that one changed, so both are kept;
+//! - right after a block with the same conditions that ends in `Continue`: merged into it, its
+//!   actions overriding the earlier ones like they would in game;
+//! - otherwise it can never match and is reported as shadowed, but left alone.
+
+use crate::error::WarlordError;
+use crate::block_edit::{self, edit_file};
+use crate::filter_merge::signature;
+use crate::filter_parser::{self, FilterDocument};
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateBlock {
+    /// "duplicate" / "merged" / "shadowed"
+    pub kind: String,
+    /// 1-based header lines in the original file
+    pub kept_line: usize,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DedupeReport {
+    pub path: String,
+    pub applied: bool,
+    pub blocks: Vec<DuplicateBlock>,
+}
+
+fn has_continue(doc: &FilterDocument, i: usize) -> bool {
+    doc.blocks[i].rule("Continue").is_some()
+}
+
+/// Collapse duplicates in `doc`; returns what was found.
+pub fn dedupe_document(doc: &mut FilterDocument) -> Vec<DuplicateBlock> {
+    let mut found = Vec::new();
+    let mut removed = vec![false; doc.blocks.len()];
+    let mut last_kept: Option<usize> = None;
+    for j in 0..doc.blocks.len() {
+        let conditions = signature(&doc.blocks[j], false);
+        // Latest kept block with the same conditions
+        let earlier = (0..j).rev().find(|&k| !removed[k] && signature(&doc.blocks[k], false) == conditions);
+        let Some(k) = earlier else {
+            last_kept = Some(j);
+            continue;
+        };
+        let entry = |kind: &str| DuplicateBlock { kind: kind.to_string(), kept_line: doc.blocks[k].line + 1, line: doc.blocks[j].line + 1 };
+        let same = doc.blocks[k].kind == doc.blocks[j].kind && signature(&doc.blocks[k], true) == signature(&doc.blocks[j], true);
+        let continue_between = (k + 1..j).any(|m| !removed[m] && has_continue(doc, m));
+        if continue_between && has_continue(doc, k) {
+            // Reached through the `Continue` blocks, so it still does something
+            last_kept = Some(j);
+        } else if same && !continue_between {
+            found.push(entry("duplicate"));
+            removed[j] = true;
+        } else if last_kept == Some(k) && has_continue(doc, k) {
+            found.push(entry("merged"));
+            removed[j] = true;
+            let later = doc.blocks[j].clone();
+            let block = &mut doc.blocks[k];
+            for rule in later.actions().filter(|r| r.keyword != "Continue") {
+                block.set_rule(rule.clone());
+            }
+            if later.rule("Continue").is_none() {
+                block.remove_rule("Continue");
+            }
+            block.kind = later.kind.clone();
+            block.sync_header();
+        } else {
+            found.push(entry("shadowed"));
+            last_kept = Some(j);
+        }
+    }
+    for j in (0..removed.len()).rev().filter(|&j| removed[j]) {
+        block_edit::remove_attached(doc, j);
+    }
+    doc.renumber();
+    found
+}
+
+pub fn dedupe_filter(path: &str, apply: bool) -> Result<DedupeReport, WarlordError> {
+    let blocks = if apply {
+        edit_file(path, |doc| Ok(dedupe_document(doc)))?
+    } else {
+        dedupe_document(&mut filter_parser::parse_file(path)?)
+    };
+    Ok(DedupeReport { path: path.to_string(), applied: apply, blocks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapses_duplicates_and_continue_chains() {
+        let src = "Show\n    BaseType \"Divine Orb\"\n    SetFontSize 45\nShow\n    BaseType = \"Divine Orb\"\n    SetFontSize 45\n\
+Show\n    Class \"Rings\"\n    SetFontSize 30\n    Continue\nShow\n    Class \"Rings\"\n    SetTextColor 1 2 3\n\
+Hide\n    Class \"Rings\"\n";
+        let mut doc = FilterDocument::parse(src);
+        let found = dedupe_document(&mut doc);
+        let kinds: Vec<&str> = found.iter().map(|d| d.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["duplicate", "merged", "shadowed"]);
+        assert_eq!(
+            doc.to_text(),
+            "Show\n    BaseType \"Divine Orb\"\n    SetFontSize 45\nShow\n    Class \"Rings\"\n    SetFontSize 30\n    SetTextColor 1 2 3\nHide\n    Class \"Rings\"\n"
+        );
+    }
+
+    #[test]
+    fn keeps_duplicates_with_continue_in_between() {
+        let src = "Show\n    Class \"Rings\"\n    SetFontSize 30\n    Continue\nShow\n    Rarity Rare\n    SetFontSize 40\n    Continue\nShow\n    Class \"Rings\"\n    SetFontSize 30\n    Continue\n";
+        let mut doc = FilterDocument::parse(src);
+        let kinds: Vec<String> = dedupe_document(&mut doc).into_iter().map(|d| d.kind).collect();
+        assert!(kinds.is_empty());
+        assert_eq!(doc.to_text(), src);
+    }
+}
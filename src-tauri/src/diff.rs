@@ -0,0 +1,148 @@
+//! Line diff of two texts for the side-by-side viewer, computed with `similar` (patience
+//! algorithm, which keeps filter blocks together better than Myers). Either side can be a
+//! file, a saved version (`versions`) or a commit of the library (`library_git`), so the
+//! same view compares two filters, a filter with last week's version, or two versions.
+//! With `ignore_cosmetic` both sides are compared in their canonical form, so indentation,
+//! comments, blank lines and condition order do not show as changes; line numbers are then
+//! those of the canonical text.
+
+use std::path::Path;
+
+use similar::{Algorithm, ChangeTag, TextDiff};
+
+use crate::error::WarlordError;
+use crate::filter_parser::FilterDocument;
+use crate::{encoding, filter_format, library_git, path_utils, versions};
+
+/// Lines of unchanged context around each change.
+const DEFAULT_CONTEXT: usize = 3;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum DiffSource {
+    Path { path: String },
+    Version { path: String, version_id: String },
+    Commit { path: String, commit_id: String },
+    /// Unsaved text from the editor
+    Text { text: String, label: Option<String> },
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    /// "context" / "add" / "delete"
+    pub kind: &'static str,
+    /// 1-based, on the side the line is in
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    /// Without the line ending
+    pub text: String,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+    /// 1-based first line and line count on each side, as in a unified diff header
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextDiffResult {
+    pub old_label: String,
+    pub new_label: String,
+    pub hunks: Vec<DiffHunk>,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+/// Text and a label for the header of one side.
+fn load(source: &DiffSource) -> Result<(String, String), WarlordError> {
+    Ok(match source {
+        DiffSource::Path { path } => (encoding::read_to_string(path)?, path_utils::short_path(Path::new(path))),
+        DiffSource::Version { path, version_id } => (versions::get_version_content(Path::new(path), version_id)?, format!("{} @ {}", path, version_id)),
+        DiffSource::Commit { path, commit_id } => {
+            let bytes = library_git::file_at(commit_id, Path::new(path))?;
+            (encoding::decode(&bytes).text, format!("{} @ {}", path, &commit_id[..commit_id.len().min(8)]))
+        }
+        DiffSource::Text { text, label } => (text.clone(), label.clone().unwrap_or_else(|| "未保存".to_string())),
+    })
+}
+
+/// Hunks turning `old` into `new`, with `context` unchanged lines around each change.
+pub fn diff_strings(old: &str, new: &str, context: usize) -> Vec<DiffHunk> {
+    let diff = TextDiff::configure().algorithm(Algorithm::Patience).diff_lines(old, new);
+    let mut hunks = Vec::new();
+    for group in diff.grouped_ops(context) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else { continue };
+        let (old_range, new_range) = (first.old_range().start..last.old_range().end, first.new_range().start..last.new_range().end);
+        let mut lines = Vec::new();
+        for op in &group {
+            for change in diff.iter_changes(op) {
+                let kind = match change.tag() {
+                    ChangeTag::Equal => "context",
+                    ChangeTag::Insert => "add",
+                    ChangeTag::Delete => "delete",
+                };
+                lines.push(DiffLine {
+                    kind,
+                    old_line: change.old_index().map(|i| i + 1),
+                    new_line: change.new_index().map(|i| i + 1),
+                    text: change.value().trim_end_matches(['\r', '\n']).to_string(),
+                });
+            }
+        }
+        hunks.push(DiffHunk {
+            // An empty side starts at the line before, like `diff -u`
+            old_start: if old_range.is_empty() { old_range.start } else { old_range.start + 1 },
+            old_lines: old_range.len(),
+            new_start: if new_range.is_empty() { new_range.start } else { new_range.start + 1 },
+            new_lines: new_range.len(),
+            lines,
+        });
+    }
+    hunks
+}
+
+fn canonical(text: &str) -> String {
+    let mut doc = FilterDocument::parse(text);
+    filter_format::canonicalize_document(&mut doc);
+    doc.to_text()
+}
+
+pub fn diff_text(old: &DiffSource, new: &DiffSource, context: Option<usize>, ignore_cosmetic: bool) -> Result<TextDiffResult, WarlordError> {
+    let ((mut old_text, old_label), (mut new_text, new_label)) = (load(old)?, load(new)?);
+    if ignore_cosmetic {
+        (old_text, new_text) = (canonical(&old_text), canonical(&new_text));
+    }
+    let hunks = diff_strings(&old_text, &new_text, context.unwrap_or(DEFAULT_CONTEXT));
+    let count = |kind: &str| hunks.iter().flat_map(|h| &h.lines).filter(|l| l.kind == kind).count();
+    let (additions, deletions) = (count("add"), count("delete"));
+    Ok(TextDiffResult { old_label, new_label, hunks, additions, deletions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_deserialize_by_kind() {
+        let version: DiffSource = serde_json::from_str(r#"{"kind":"version","path":"a.filter","versionId":"20240301-101502123-save"}"#).unwrap();
+        assert!(matches!(version, DiffSource::Version { ref version_id, .. } if version_id == "20240301-101502123-save"));
+        let text = DiffSource::Text { text: "Show\n".to_string(), label: None };
+        assert_eq!(load(&text).unwrap(), ("Show\n".to_string(), "未保存".to_string()));
+    }
+
+    #[test]
+    fn cosmetic_changes_can_be_ignored() {
+        let old = "Show\n    BaseType \"Divine Orb\"\n    Class \"Currency\"\n";
+        let new = "# currency\nShow\n  Class \"Currency\"\n\n  BaseType \"Divine Orb\"\n";
+        assert_ne!(old, new);
+        assert_eq!(canonical(old), canonical(new));
+        assert_ne!(canonical(old), canonical("Hide\n    BaseType \"Divine Orb\"\n"));
+    }
+}
@@ -0,0 +1,206 @@
+//! Discord Rich Presence over Discord's local IPC socket (named pipe on Windows).
+//! The activity is built from backend state: the Client.txt watcher, the filter selected in
+//! the settings and the league last used for prices.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::error::WarlordError;
+use crate::{app_paths, client_log};
+
+const CONFIG_FILE: &str = "discord_rpc.json";
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RpcConfig {
+    pub enabled: bool,
+    /// Application id from the Discord developer portal
+    pub client_id: String,
+}
+
+/// What the user is doing, as far as the backend knows.
+#[derive(Clone, Debug, Default)]
+struct Activity {
+    editing: Option<String>,
+    league: Option<String>,
+    started_at: u64,
+}
+
+trait Pipe: Read + Write + Send {}
+impl<T: Read + Write + Send> Pipe for T {}
+
+static CONNECTION: Mutex<Option<Box<dyn Pipe>>> = Mutex::new(None);
+static ACTIVITY: Mutex<Option<Activity>> = Mutex::new(None);
+
+pub fn get_config() -> RpcConfig {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+#[cfg(windows)]
+fn open_pipe(n: u32) -> std::io::Result<Box<dyn Pipe>> {
+    let file = std::fs::OpenOptions::new().read(true).write(true).open(format!(r"\\.\pipe\discord-ipc-{}", n))?;
+    Ok(Box::new(file))
+}
+
+#[cfg(unix)]
+fn open_pipe(n: u32) -> std::io::Result<Box<dyn Pipe>> {
+    let dir = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .find_map(|v| std::env::var(v).ok())
+        .unwrap_or_else(|| "/tmp".to_string());
+    let stream = std::os::unix::net::UnixStream::connect(Path::new(&dir).join(format!("discord-ipc-{}", n)))?;
+    Ok(Box::new(stream))
+}
+
+fn write_frame(pipe: &mut dyn Pipe, op: u32, body: &serde_json::Value) -> std::io::Result<()> {
+    let data = body.to_string().into_bytes();
+    let mut frame = Vec::with_capacity(8 + data.len());
+    frame.extend_from_slice(&op.to_le_bytes());
+    frame.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&data);
+    pipe.write_all(&frame)
+}
+
+fn read_frame(pipe: &mut dyn Pipe) -> std::io::Result<serde_json::Value> {
+    let mut header = [0u8; 8];
+    pipe.read_exact(&mut header)?;
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let mut data = vec![0u8; len];
+    pipe.read_exact(&mut data)?;
+    serde_json::from_slice(&data).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+fn connect(client_id: &str) -> Result<Box<dyn Pipe>, WarlordError> {
+    let mut pipe = (0..10).find_map(|n| open_pipe(n).ok()).ok_or_else(|| WarlordError::invalid("未检测到正在运行的 Discord"))?;
+    write_frame(pipe.as_mut(), OP_HANDSHAKE, &serde_json::json!({ "v": 1, "client_id": client_id }))?;
+    let ready = read_frame(pipe.as_mut())?;
+    if ready["evt"] != "READY" {
+        return Err(WarlordError::invalid(format!("Discord 握手失败: {}", ready["message"])));
+    }
+    Ok(pipe)
+}
+
+fn file_stem(path: &str) -> String {
+    Path::new(path).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| path.to_string())
+}
+
+/// Activity JSON: the current zone while the game is running, otherwise the filter being edited.
+fn activity_json(activity: &Activity, log: &client_log::LogState, active_filter: Option<String>) -> serde_json::Value {
+    let league = activity.league.as_deref().map(|l| format!("赛季: {}", l));
+    let (details, state) = match &log.area {
+        Some(area) if client_log::watched_path().is_some() => {
+            let details = if log.area_level > 0 { format!("{} (区域等级 {})", area, log.area_level) } else { area.clone() };
+            let filter = active_filter.map(|f| format!("过滤器: {}", file_stem(&f)));
+            (details, filter.or(league))
+        }
+        _ => {
+            let details = match &activity.editing {
+                Some(path) => format!("编辑过滤器 {}", file_stem(path)),
+                None => "管理过滤器".to_string(),
+            };
+            (details, league)
+        }
+    };
+    let mut json = serde_json::json!({
+        "details": details,
+        "timestamps": { "start": activity.started_at },
+    });
+    if let Some(state) = state {
+        json["state"] = serde_json::json!(state);
+    }
+    json
+}
+
+fn active_filter() -> Option<String> {
+    let settings: serde_json::Value = app_paths::load_json("Settings.json");
+    settings["lastSelectedFilter"].as_str().filter(|s| !s.is_empty()).map(String::from)
+}
+
+/// Push the current activity to Discord. A broken connection is dropped and re-opened next time.
+fn push() {
+    let Some(activity) = ACTIVITY.lock().unwrap().clone() else { return };
+    let mut conn = CONNECTION.lock().unwrap();
+    if conn.is_none() {
+        match connect(&get_config().client_id) {
+            Ok(pipe) => *conn = Some(pipe),
+            Err(e) => {
+                eprintln!("[WarlordTools] discord rpc: {}", e);
+                return;
+            }
+        }
+    }
+    let body = serde_json::json!({
+        "cmd": "SET_ACTIVITY",
+        "args": {
+            "pid": std::process::id(),
+            "activity": activity_json(&activity, &client_log::current_state(), active_filter()),
+        },
+        "nonce": app_paths::new_id(),
+    });
+    let pipe = conn.as_mut().unwrap();
+    if let Err(e) = write_frame(pipe.as_mut(), OP_FRAME, &body).and_then(|_| read_frame(pipe.as_mut()).map(|_| ())) {
+        eprintln!("[WarlordTools] discord rpc: {}", e);
+        *conn = None;
+    }
+}
+
+/// Turn Rich Presence on or off and remember the choice.
+pub fn set_enabled(enabled: bool, client_id: Option<String>) -> Result<(), WarlordError> {
+    let mut config = get_config();
+    config.enabled = enabled;
+    if let Some(id) = client_id {
+        config.client_id = id;
+    }
+    if enabled && config.client_id.trim().is_empty() {
+        return Err(WarlordError::invalid("请先填写 Discord 应用 ID"));
+    }
+    app_paths::save_json(CONFIG_FILE, &config)?;
+    if enabled {
+        start();
+    } else {
+        *ACTIVITY.lock().unwrap() = None;
+        // Closing the socket makes Discord clear the activity
+        *CONNECTION.lock().unwrap() = None;
+    }
+    Ok(())
+}
+
+/// Start presence if it was enabled in a previous session.
+pub fn start() {
+    if !get_config().enabled {
+        return;
+    }
+    ACTIVITY.lock().unwrap().get_or_insert_with(|| Activity { started_at: app_paths::now_secs(), ..Default::default() });
+    std::thread::spawn(push);
+}
+
+/// Refresh the activity after a state change; does nothing while presence is off.
+pub fn update(editing: Option<&str>, league: Option<&str>) {
+    {
+        let mut guard = ACTIVITY.lock().unwrap();
+        let Some(activity) = guard.as_mut() else { return };
+        if let Some(path) = editing {
+            activity.editing = Some(path.to_string());
+        }
+        if let Some(league) = league {
+            activity.league = Some(league.to_string());
+        }
+    }
+    std::thread::spawn(push);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_editing_filter_outside_the_game() {
+        let activity = Activity { editing: Some("C:/f/NeverSink.filter".to_string()), league: Some("Settlers".to_string()), started_at: 1 };
+        let json = activity_json(&activity, &client_log::LogState::default(), None);
+        assert_eq!(json["details"], "编辑过滤器 NeverSink");
+        assert_eq!(json["state"], "赛季: Settlers");
+    }
+}
@@ -0,0 +1,67 @@
+//! Free space on the drive a path is on. Large copies (sound packs, moves across drives,
+//! archive extraction) check it first, so a full drive fails up front with `DiskFull` instead
+//! of leaving a half-written pack behind.
+
+use std::io;
+use std::path::Path;
+
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+
+#[cfg(windows)]
+fn free_bytes(dir: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    extern "system" {
+        fn GetDiskFreeSpaceExW(dir: *const u16, available: *mut u64, total: *mut u64, free: *mut u64) -> i32;
+    }
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available = 0u64;
+    // Space available to this user (quotas), not the drive's total free space
+    if unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, std::ptr::null_mut(), std::ptr::null_mut()) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(available)
+}
+
+#[cfg(unix)]
+fn free_bytes(dir: &Path) -> io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(dir.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Bytes free for the current user on the drive holding `path`. `path` does not have to
+/// exist yet (a copy's destination); its nearest existing ancestor is asked.
+pub fn available_space(path: &Path) -> Result<u64, WarlordError> {
+    let absolute = std::path::absolute(path).map_err(|e| WarlordError::io(e, path))?;
+    let existing = absolute.ancestors().find(|p| long_path(p).exists()).ok_or_else(|| WarlordError::not_found(path))?;
+    free_bytes(&long_path(existing)).map_err(|e| WarlordError::io(e, existing))
+}
+
+/// Fail with `DiskFull` when fewer than `required` bytes are free where `path` would be
+/// written. Returns the free bytes.
+pub fn check_disk_space(path: &Path, required: u64) -> Result<u64, WarlordError> {
+    let available = available_space(path)?;
+    if available < required {
+        return Err(WarlordError::DiskFull { path: path_utils::short_path(path), required, available });
+    }
+    Ok(available)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_space_for_paths_that_do_not_exist_yet() {
+        let missing = std::env::temp_dir().join("wt-disk-space-test/pack/alert.mp3");
+        let available = check_disk_space(&missing, 1).unwrap();
+        assert!(available > 0);
+        let err = check_disk_space(&missing, u64::MAX).unwrap_err();
+        assert_eq!(err.code(), "diskFull");
+    }
+}
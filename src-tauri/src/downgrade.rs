@@ -0,0 +1,125 @@
+//! Make a filter loadable by an older game client by taking out keywords added after the
+//! selected version. An unsupported action line is commented out on its own; a block with an
+//! unsupported condition is commented out whole, since it would match more items without it.
+
+use crate::error::WarlordError;
+use crate::filter_parser::{self, FilterDocument};
+use crate::{poe_convert, sandbox};
+
+const DISABLED_PREFIX: &str = "# [downgrade] ";
+
+/// Client versions in release order.
+pub const GAME_VERSIONS: &[&str] = &["0.1", "0.2", "0.3"];
+
+/// Keywords added after the first release and the version that introduced them.
+/// Keep in sync with the patch notes.
+const KEYWORD_SINCE: &[(&str, &str)] = &[
+    ("UnidentifiedItemTier", "0.2"),
+    ("BaseDefencePercentile", "0.2"),
+    ("TwiceCorrupted", "0.3"),
+    ("HasVaalUniqueMod", "0.3"),
+    ("IsVaalUnique", "0.3"),
+];
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemovedKeyword {
+    /// 1-based line of the rule
+    pub line: usize,
+    pub keyword: String,
+    pub since: String,
+    /// The whole block was commented out, not just the line
+    pub block_disabled: bool,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DowngradeReport {
+    pub dest: String,
+    pub version: String,
+    pub removed: Vec<RemovedKeyword>,
+}
+
+fn rank(version: &str) -> Result<usize, WarlordError> {
+    GAME_VERSIONS.iter().position(|v| *v == version).ok_or_else(|| WarlordError::invalid(format!("未知的游戏版本: {}", version)))
+}
+
+/// Version that introduced `keyword` when it is newer than `target`, by `since` (keyword,
+/// version) pairs.
+fn newer_than(since: &[(&str, &'static str)], keyword: &str, target: usize) -> Option<&'static str> {
+    since.iter().find(|(k, since)| *k == keyword && rank(since).is_ok_and(|r| r > target)).map(|(_, since)| *since)
+}
+
+/// Downgrade `doc` to `version`; returns the text and the report (with an empty `dest`).
+pub fn downgrade_document(doc: FilterDocument, version: &str) -> Result<(String, DowngradeReport), WarlordError> {
+    downgrade_by(doc, version, KEYWORD_SINCE)
+}
+
+fn downgrade_by(mut doc: FilterDocument, version: &str, since: &[(&str, &'static str)]) -> Result<(String, DowngradeReport), WarlordError> {
+    let target = rank(version)?;
+    let mut removed = Vec::new();
+    let mut disabled = Vec::new();
+    for (b, block) in doc.blocks.iter_mut().enumerate() {
+        let first_line = block.line + 2;
+        // (line index, keyword, since, is action) of each newer keyword
+        let newer: Vec<(usize, String, &str, bool)> = block
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                let rule = line.rule.as_ref()?;
+                newer_than(since, &rule.keyword, target).map(|since| (i, rule.keyword.clone(), since, rule.is_action()))
+            })
+            .collect();
+        // A newer condition disables the whole block, its newer actions with it; otherwise
+        // only those action lines are commented out
+        let disable = newer.iter().any(|(.., is_action)| !is_action);
+        for (i, keyword, since, _) in newer {
+            removed.push(RemovedKeyword { line: first_line + i, keyword, since: since.to_string(), block_disabled: disable });
+            if !disable {
+                let line = &mut block.lines[i];
+                line.raw = format!("{}{}", DISABLED_PREFIX, line.raw);
+                line.rule = None;
+            }
+        }
+        if disable {
+            disabled.push(b);
+        }
+    }
+    let text = poe_convert::comment_out_blocks(&doc, &disabled, DISABLED_PREFIX);
+    Ok((text, DowngradeReport { dest: String::new(), version: version.to_string(), removed }))
+}
+
+pub fn downgrade_filter(src: &str, dest: &str, version: &str) -> Result<DowngradeReport, WarlordError> {
+    let doc = filter_parser::parse_file(src)?;
+    let (text, mut report) = downgrade_document(doc, version)?;
+    sandbox::write(dest, text)?;
+    report.dest = dest.to_string();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_out_newer_keywords() {
+        let src = "Show\n    TwiceCorrupted True\n    SetFontSize 40\nShow\n    UnidentifiedItemTier 5\n";
+        let (text, report) = downgrade_document(FilterDocument::parse(src), "0.2").unwrap();
+        assert_eq!(text, "# [downgrade] Show\n# [downgrade]     TwiceCorrupted True\n# [downgrade]     SetFontSize 40\nShow\n    UnidentifiedItemTier 5\n");
+        assert_eq!(report.removed.len(), 1);
+        assert!(report.removed[0].block_disabled);
+        assert!(downgrade_document(FilterDocument::parse(src), "9.9").is_err());
+    }
+
+    #[test]
+    fn disabled_blocks_are_prefixed_once() {
+        let src = "Show\n    TwiceCorrupted True\n    PlayEffect Red\nShow\n    PlayEffect Blue\n";
+        // No action is newer than a release yet
+        let since = [("TwiceCorrupted", "0.3"), ("PlayEffect", "0.2")];
+        let (text, report) = downgrade_by(FilterDocument::parse(src), "0.1", &since).unwrap();
+        assert_eq!(text, "# [downgrade] Show\n# [downgrade]     TwiceCorrupted True\n# [downgrade]     PlayEffect Red\nShow\n# [downgrade]     PlayEffect Blue\n");
+        let disabled: Vec<(&str, bool)> = report.removed.iter().map(|r| (r.keyword.as_str(), r.block_disabled)).collect();
+        assert_eq!(disabled, [("TwiceCorrupted", true), ("PlayEffect", true), ("PlayEffect", false)]);
+    }
+}
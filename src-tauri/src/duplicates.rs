@@ -0,0 +1,93 @@
+//! Copies of the same filter piling up across league folders. Files with the same content
+//! hash are identical; files whose canonical form (`filter_format::canonicalize_document`)
+//! matches only differ in comments, formatting or rule order and are reported as equivalent.
+//! Links (`ScannedFile::is_link`) share their target's space and are left out.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::error::WarlordError;
+use crate::filter_format;
+use crate::filter_parser;
+use crate::scan::{self, ScanOptions, ScannedFile};
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    /// "identical" / "equivalent"
+    pub kind: String,
+    pub files: Vec<ScannedFile>,
+    /// Bytes freed by keeping one file of the group
+    pub wasted: u64,
+}
+
+fn group(kind: &str, mut files: Vec<ScannedFile>) -> DuplicateGroup {
+    files.sort_by_key(|f| Reverse(f.modified));
+    let wasted = files.iter().skip(1).map(|f| f.size).sum();
+    DuplicateGroup { kind: kind.to_string(), files, wasted }
+}
+
+fn canonical_hash(path: &str) -> Option<String> {
+    let mut doc = filter_parser::parse_file(path).ok()?;
+    filter_format::canonicalize_document(&mut doc);
+    Some(format!("{:x}", Sha256::digest(doc.to_text().as_bytes())))
+}
+
+/// Duplicate groups among the filters below `root`, the most wasted space first. A file is in
+/// at most one group (an equivalent group can hold identical files too); newest file first.
+pub fn find_duplicates(root: &Path) -> Result<Vec<DuplicateGroup>, WarlordError> {
+    let mut by_hash: BTreeMap<String, Vec<ScannedFile>> = BTreeMap::new();
+    for file in scan::refresh(root, &ScanOptions { hash: true, ..ScanOptions::default() })?.into_iter().filter(|f| !f.is_link) {
+        by_hash.entry(file.hash.clone()).or_default().push(file);
+    }
+    // One parse per distinct content
+    let distinct: Vec<(&String, &Vec<ScannedFile>)> = by_hash.iter().collect();
+    let canonical: Vec<(String, Option<String>)> = distinct.par_iter().map(|(hash, files)| (hash.to_string(), canonical_hash(&files[0].path))).collect();
+    let mut by_canonical: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (hash, canonical) in canonical {
+        if let Some(canonical) = canonical {
+            by_canonical.entry(canonical).or_default().push(hash);
+        }
+    }
+
+    let mut groups = Vec::new();
+    for hashes in by_canonical.values().filter(|h| h.len() > 1) {
+        let files = hashes.iter().filter_map(|h| by_hash.remove(h)).flatten().collect();
+        groups.push(group("equivalent", files));
+    }
+    for files in by_hash.into_values().filter(|f| f.len() > 1) {
+        groups.push(group("identical", files));
+    }
+    groups.sort_by_key(|g| Reverse(g.wasted));
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn groups_identical_and_equivalent_filters() {
+        let root = std::env::temp_dir().join("wt-duplicates-test");
+        let _ = fs::remove_dir_all(&root);
+        for league in ["settlers", "necropolis", "affliction"] {
+            fs::create_dir_all(root.join(league)).unwrap();
+        }
+        let neversink = "Show\n    Class \"Currency\"\n    SetFontSize 45\n";
+        fs::write(root.join("settlers/NeverSink.filter"), neversink).unwrap();
+        fs::write(root.join("necropolis/NeverSink.filter"), neversink).unwrap();
+        fs::write(root.join("affliction/NeverSink.filter"), "# mine\nShow\n  SetFontSize 45\n  Class Currency\n").unwrap();
+        fs::write(root.join("a.filter"), "Hide\n").unwrap();
+        fs::write(root.join("b.filter"), "Hide\n").unwrap();
+
+        let groups = find_duplicates(&root).unwrap();
+        let summary: Vec<(&str, usize)> = groups.iter().map(|g| (g.kind.as_str(), g.files.len())).collect();
+        assert_eq!(summary, vec![("equivalent", 3), ("identical", 2)]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use crate::error::WarlordError;
+use crate::app_paths;
+use crate::filter_parser::FilterDocument;
+
+const NINJA_API: &str = "https://poe.ninja/api/data";
+
+// poe.ninja overview types: (endpoint, type)
+const OVERVIEWS: &[(&str, &str)] = &[
+    ("currencyoverview", "Currency"),
+    ("currencyoverview", "Fragment"),
+    ("itemoverview", "DivinationCard"),
+    ("itemoverview", "UniqueWeapon"),
+    ("itemoverview", "UniqueArmour"),
+    ("itemoverview", "UniqueAccessory"),
+    ("itemoverview", "UniqueFlask"),
+    ("itemoverview", "UniqueJewel"),
+    ("itemoverview", "Essence"),
+    ("itemoverview", "Fossil"),
+    ("itemoverview", "Resonator"),
+    ("itemoverview", "Scarab"),
+    ("itemoverview", "Oil"),
+    ("itemoverview", "Incubator"),
+];
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceEntry {
+    pub name: String,
+    /// Item base type (same as `name` for currency and cards)
+    pub base_type: String,
+    /// poe.ninja overview type, e.g. "DivinationCard"
+    pub category: String,
+    pub chaos_value: f64,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceCache {
+    pub league: String,
+    pub fetched_at: u64,
+    pub entries: Vec<PriceEntry>,
+}
+
+/// Economy summary for one filter block.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockValue {
+    pub block_id: usize,
+    pub line: usize,
+    pub kind: String,
+    pub section: Option<String>,
+    pub base_types: Vec<String>,
+    /// BaseType values with no poe.ninja price
+    pub unpriced: Vec<String>,
+    pub priced_items: usize,
+    pub min_chaos: Option<f64>,
+    pub median_chaos: Option<f64>,
+    pub max_chaos: Option<f64>,
+}
+
+/// Max chaos value per base type at one point in time.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceSample {
+    pub at: u64,
+    pub prices: HashMap<String, f64>,
+}
+
+/// Daily price samples for uniques and divination cards, used by the pruning analysis.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceHistory {
+    pub league: String,
+    pub samples: Vec<PriceSample>,
+}
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+/// Samples older than this are dropped (half a year is longer than any league)
+const HISTORY_DAYS: u64 = 183;
+
+fn safe_league(league: &str) -> String {
+    league.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect()
+}
+
+fn cache_file(league: &str) -> String {
+    format!("economy_{}.json", safe_league(league))
+}
+
+fn history_file(league: &str) -> String {
+    format!("economy_history_{}.json", safe_league(league))
+}
+
+pub fn load_history(league: &str) -> PriceHistory {
+    app_paths::load_json(&history_file(league))
+}
+
+/// Add `cache` to the league's history, keeping at most one sample per day.
+fn record_history(cache: &PriceCache) -> Result<(), WarlordError> {
+    let mut prices: HashMap<String, f64> = HashMap::new();
+    let tracked = cache.entries.iter().filter(|e| e.category == "DivinationCard" || e.category.starts_with("Unique"));
+    for e in tracked {
+        let v = prices.entry(e.base_type.clone()).or_insert(e.chaos_value);
+        *v = v.max(e.chaos_value);
+    }
+    let mut history = load_history(&cache.league);
+    history.league = cache.league.clone();
+    let day = cache.fetched_at / DAY_SECS;
+    history.samples.retain(|s| s.at / DAY_SECS != day && s.at + HISTORY_DAYS * DAY_SECS >= cache.fetched_at);
+    history.samples.push(PriceSample { at: cache.fetched_at, prices });
+    history.samples.sort_by_key(|s| s.at);
+    app_paths::save_json(&history_file(&cache.league), &history)
+}
+
+pub fn load_cache(league: &str) -> Option<PriceCache> {
+    let cache: PriceCache = app_paths::load_json(&cache_file(league));
+    (!cache.entries.is_empty()).then_some(cache)
+}
+
+fn parse_overview(kind: &str, category: &str, body: &serde_json::Value) -> Vec<PriceEntry> {
+    let empty = vec![];
+    body["lines"]
+        .as_array()
+        .unwrap_or(&empty)
+        .iter()
+        .filter_map(|l| {
+            let (name, base_type, value) = if kind == "currencyoverview" {
+                let name = l["currencyTypeName"].as_str()?;
+                (name, name, l["chaosEquivalent"].as_f64()?)
+            } else {
+                let name = l["name"].as_str()?;
+                (name, l["baseType"].as_str().unwrap_or(name), l["chaosValue"].as_f64()?)
+            };
+            Some(PriceEntry {
+                name: name.to_string(),
+                base_type: base_type.to_string(),
+                category: category.to_string(),
+                chaos_value: value,
+            })
+        })
+        .collect()
+}
+
+/// Download every overview for `league` and store it in the app data cache.
+pub fn refresh_prices(league: &str) -> Result<PriceCache, WarlordError> {
+    let mut entries = Vec::new();
+    for (kind, category) in OVERVIEWS {
+        let url = format!("{}/{}", NINJA_API, kind);
+        let resp = ureq::get(&url)
+            .query("league", league)
+            .query("type", category)
+            .set("Accept", "application/json")
+            .call()
+            .map_err(|e| WarlordError::Network { message: format!("获取 poe.ninja 价格失败 ({}): {}", category, e) })?;
+        let body: serde_json::Value = resp.into_json().map_err(|e| WarlordError::Network { message: format!("poe.ninja 返回了无效的数据 ({}): {}", category, e) })?;
+        entries.extend(parse_overview(kind, category, &body));
+    }
+    let cache = PriceCache { league: league.to_string(), fetched_at: app_paths::now_secs(), entries };
+    app_paths::save_json(&cache_file(league), &cache)?;
+    if let Err(e) = record_history(&cache) {
+        eprintln!("[WarlordTools] economy history not saved: {}", e);
+    }
+    eprintln!("[WarlordTools] economy cache for {}: {} prices", league, cache.entries.len());
+    Ok(cache)
+}
+
+/// Lookup helper shared by the economy-driven analyses.
+pub struct PriceIndex<'a> {
+    by_base: HashMap<&'a str, Vec<&'a PriceEntry>>,
+    entries: &'a [PriceEntry],
+}
+
+impl<'a> PriceIndex<'a> {
+    pub fn new(cache: &'a PriceCache) -> PriceIndex<'a> {
+        let mut by_base: HashMap<&str, Vec<&PriceEntry>> = HashMap::new();
+        for e in &cache.entries {
+            by_base.entry(e.base_type.as_str()).or_default().push(e);
+        }
+        PriceIndex { by_base, entries: &cache.entries }
+    }
+
+    /// Entries a BaseType value matches: exact with `==`, otherwise substring like the game does.
+    pub fn matching(&self, value: &str, exact: bool) -> Vec<&'a PriceEntry> {
+        if exact {
+            self.by_base.get(value).cloned().unwrap_or_default()
+        } else {
+            self.entries.iter().filter(|e| e.base_type.contains(value)).collect()
+        }
+    }
+}
+
+pub fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) / 2.0 } else { values[mid] })
+}
+
+/// Annotate every block that has a BaseType condition with the value of the items it matches.
+pub fn tag_blocks(doc: &FilterDocument, cache: &PriceCache) -> Vec<BlockValue> {
+    let index = PriceIndex::new(cache);
+    let mut out = Vec::new();
+    for block in &doc.blocks {
+        let Some(rule) = block.rule("BaseType") else { continue };
+        let exact = rule.operator.as_deref() == Some("==");
+        let base_types: Vec<String> = rule.unquoted_values().into_iter().map(String::from).collect();
+
+        let mut values = Vec::new();
+        let mut unpriced = Vec::new();
+        for base in &base_types {
+            let matches = index.matching(base, exact);
+            if matches.is_empty() {
+                unpriced.push(base.clone());
+            }
+            values.extend(matches.iter().map(|e| e.chaos_value));
+        }
+        let min = values.iter().copied().reduce(f64::min);
+        let max = values.iter().copied().reduce(f64::max);
+        out.push(BlockValue {
+            block_id: block.id,
+            line: block.line,
+            kind: block.kind.clone(),
+            section: block.section.clone(),
+            base_types,
+            unpriced,
+            priced_items: values.len(),
+            min_chaos: min,
+            median_chaos: median(&mut values),
+            max_chaos: max,
+        });
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_overviews() {
+        let currency = serde_json::json!({ "lines": [
+            { "currencyTypeName": "Divine Orb", "chaosEquivalent": 180.5 },
+            { "currencyTypeName": "Unpriced Orb" },
+        ] });
+        let entries = parse_overview("currencyoverview", "Currency", &currency);
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].name.as_str(), entries[0].base_type.as_str(), entries[0].chaos_value), ("Divine Orb", "Divine Orb", 180.5));
+
+        let uniques = serde_json::json!({ "lines": [
+            { "name": "Headhunter", "baseType": "Leather Belt", "chaosValue": 9000.0 },
+            { "name": "The Doctor", "chaosValue": 800.0 },
+        ] });
+        let entries = parse_overview("itemoverview", "UniqueAccessory", &uniques);
+        let bases: Vec<&str> = entries.iter().map(|e| e.base_type.as_str()).collect();
+        assert_eq!(bases, vec!["Leather Belt", "The Doctor"]);
+        assert!(parse_overview("itemoverview", "Scarab", &serde_json::json!({})).is_empty());
+    }
+
+    #[test]
+    fn values_blocks_by_their_base_types() {
+        let entry = |name: &str, chaos_value: f64| PriceEntry { name: name.to_string(), base_type: name.to_string(), category: "Currency".to_string(), chaos_value };
+        let cache = PriceCache { entries: vec![entry("Chaos Orb", 1.0), entry("Divine Orb", 180.0), entry("Orb of Alchemy", 0.5), entry("Orb of Alteration", 0.2)], ..Default::default() };
+        let doc = FilterDocument::parse("Show\n    BaseType == \"Divine Orb\" \"Mirror of Kalandra\"\nShow\n    BaseType \"Orb of Al\" \"Chaos\"\nShow\n    Class \"Currency\"\n");
+        let values = tag_blocks(&doc, &cache);
+        assert_eq!(values.len(), 2);
+        assert_eq!((values[0].priced_items, values[0].max_chaos), (1, Some(180.0)));
+        assert_eq!(values[0].unpriced, vec!["Mirror of Kalandra".to_string()]);
+        assert_eq!((values[1].min_chaos, values[1].median_chaos, values[1].max_chaos), (Some(0.2), Some(0.5), Some(1.0)));
+        assert_eq!(median(&mut [4.0, 1.0, 3.0, 2.0]), Some(2.5));
+    }
+}
@@ -0,0 +1,139 @@
+//! Hide blocks for cheap divination cards, uniques and currency, generated from the cached
+//! poe.ninja prices. The blocks go to the top of a target section so they win over the
+//! section's Show blocks, and carry an `economy-hide` stamp so regenerating replaces them.
+
+use std::collections::BTreeMap;
+
+use crate::error::WarlordError;
+use crate::block_edit::{self, edit_file, Side};
+use crate::economy::PriceCache;
+use crate::filter_parser::{quote, Block, FilterDocument, Rule};
+use crate::provenance;
+
+const SOURCE: &str = "economy-hide";
+
+/// Item groups a Hide block can be generated for.
+pub const GROUPS: &[&str] = &["cards", "uniques", "currency"];
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HideReport {
+    pub path: String,
+    pub blocks: usize,
+    /// Hidden base types per group
+    pub hidden: BTreeMap<String, Vec<String>>,
+}
+
+fn group_of(category: &str) -> Option<&'static str> {
+    match category {
+        "DivinationCard" => Some("cards"),
+        "Currency" => Some("currency"),
+        c if c.starts_with("Unique") => Some("uniques"),
+        _ => None,
+    }
+}
+
+/// Base types per group worth less than `threshold` chaos. A unique base is only cheap when
+/// every unique on it is, since hiding works by base type.
+pub fn cheap_items(cache: &PriceCache, threshold: f64, groups: &[String]) -> BTreeMap<String, Vec<String>> {
+    let mut max: BTreeMap<(&str, &str), f64> = BTreeMap::new();
+    for e in &cache.entries {
+        let Some(group) = group_of(&e.category).filter(|g| groups.iter().any(|x| x == g)) else { continue };
+        let v = max.entry((group, e.base_type.as_str())).or_insert(e.chaos_value);
+        *v = v.max(e.chaos_value);
+    }
+    let mut cheap: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for ((group, base), value) in max {
+        if value < threshold {
+            cheap.entry(group.to_string()).or_default().push(base.to_string());
+        }
+    }
+    cheap
+}
+
+fn hide_block(group: &str, bases: &[String]) -> Block {
+    let mut block = Block::new("Hide");
+    let exact = |keyword: &str, values: Vec<String>| Rule { operator: Some("==".to_string()), ..Rule::new(keyword, values) };
+    match group {
+        "cards" => block.push_rule(exact("Class", vec![quote("Divination Cards")])),
+        "currency" => block.push_rule(exact("Class", vec![quote("Stackable Currency")])),
+        _ => block.push_rule(Rule::new("Rarity", vec!["Unique".to_string()])),
+    }
+    block.push_rule(exact("BaseType", bases.iter().map(|b| quote(b)).collect()));
+    provenance::stamp(&mut block, SOURCE);
+    block
+}
+
+/// Replace earlier generated blocks in `doc` with new ones at the top of `section`.
+/// Returns the number of blocks added.
+pub fn insert_hide_blocks(doc: &mut FilterDocument, section: &str, cheap: &BTreeMap<String, Vec<String>>) -> Result<usize, WarlordError> {
+    let mut i = 0;
+    while i < doc.blocks.len() {
+        if provenance::read(&doc.blocks[i]).is_some_and(|p| p.source == SOURCE) {
+            block_edit::remove_attached(doc, i);
+        } else {
+            i += 1;
+        }
+    }
+    doc.renumber();
+    let first = doc
+        .blocks
+        .iter()
+        .position(|b| b.section.as_deref().is_some_and(|s| s.contains(section)))
+        .ok_or_else(|| WarlordError::invalid(format!("Section not found: {}", section)))?;
+    let mut added = 0;
+    for (group, bases) in cheap.iter().filter(|(_, b)| !b.is_empty()) {
+        block_edit::attach(doc, first + added, hide_block(group, bases), Side::BeforeNext);
+        added += 1;
+    }
+    doc.renumber();
+    Ok(added)
+}
+
+pub fn generate_hide_blocks(path: &str, cache: &PriceCache, threshold: f64, groups: &[String], section: &str) -> Result<HideReport, WarlordError> {
+    if let Some(g) = groups.iter().find(|g| !GROUPS.contains(&g.as_str())) {
+        return Err(WarlordError::invalid(format!("未知的物品分组: {}", g)));
+    }
+    let hidden = cheap_items(cache, threshold, groups);
+    let blocks = edit_file(path, |doc| insert_hide_blocks(doc, section, &hidden))?;
+    Ok(HideReport { path: path.to_string(), blocks, hidden })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::economy::PriceEntry;
+
+    #[test]
+    fn hides_cheap_items_at_section_top() {
+        let entry = |name: &str, base: &str, category: &str, chaos_value| PriceEntry {
+            name: name.to_string(),
+            base_type: base.to_string(),
+            category: category.to_string(),
+            chaos_value,
+        };
+        let cache = PriceCache {
+            league: "Standard".to_string(),
+            fetched_at: 0,
+            entries: vec![
+                entry("Rain of Chaos", "Rain of Chaos", "DivinationCard", 0.5),
+                entry("The Doctor", "The Doctor", "DivinationCard", 900.0),
+                entry("Goldrim", "Leather Cap", "UniqueArmour", 1.0),
+                entry("Starkonja's Head", "Silken Hood", "UniqueArmour", 2.0),
+                entry("Fake", "Silken Hood", "UniqueArmour", 50.0),
+            ],
+        };
+        let groups: Vec<String> = GROUPS.iter().map(|g| g.to_string()).collect();
+        let cheap = cheap_items(&cache, 5.0, &groups);
+        assert_eq!(cheap["cards"], vec!["Rain of Chaos"]);
+        assert_eq!(cheap["uniques"], vec!["Leather Cap"]);
+
+        let mut doc = FilterDocument::parse("Show\n    Class \"Currency\"\n\n# [[0500]] Uniques\nShow\n    Rarity Unique\n");
+        assert_eq!(insert_hide_blocks(&mut doc, "0500", &cheap).unwrap(), 2);
+        assert_eq!(insert_hide_blocks(&mut doc, "0500", &cheap).unwrap(), 2);
+        assert_eq!(doc.blocks.len(), 4);
+        assert_eq!(doc.blocks[1].kind, "Hide");
+        assert!(doc.blocks[1].leading.iter().any(|l| l.contains("[[0500]]")));
+        assert_eq!(doc.blocks[2].rule("BaseType").unwrap().values, vec!["\"Leather Cap\""]);
+    }
+}
@@ -0,0 +1,143 @@
+//! Demoting uniques and divination cards that stopped being worth anything.
+//! Only generated blocks (stamped by us, or FilterBlade/NeverSink `$tier->` blocks) are
+//! touched; hand-written blocks are left alone even if they highlight a dead item.
+
+use crate::error::WarlordError;
+use crate::economy::PriceHistory;
+use crate::filter_parser::{self, quote, unquote, Block, FilterDocument};
+use crate::filter_transforms::FileChange;
+use crate::provenance;
+
+const WEEK_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// An item that has been below the threshold for the whole window.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadItem {
+    pub block_id: usize,
+    pub section: Option<String>,
+    pub base_type: String,
+    /// Highest price seen during the window
+    pub max_chaos: f64,
+    /// Block the item would move to, None when it is already in the lowest tier
+    pub target_block_id: Option<usize>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Demotion {
+    pub block_id: usize,
+    pub base_type: String,
+}
+
+fn is_generated(block: &Block) -> bool {
+    provenance::read(block).is_some() || block.header_comment().is_some_and(|c| c.contains("$tier->"))
+}
+
+/// Next generated block in the same section with a BaseType list (filters list tiers top-down).
+fn lower_tier(doc: &FilterDocument, block_id: usize) -> Option<usize> {
+    let section = &doc.blocks.get(block_id)?.section;
+    doc.blocks
+        .iter()
+        .skip(block_id + 1)
+        .take_while(|b| &b.section == section)
+        .find(|b| is_generated(b) && b.rule("BaseType").is_some())
+        .map(|b| b.id)
+}
+
+/// Highest price of `base_type` in the last `weeks`, None when the history is shorter than that.
+fn window_max(history: &PriceHistory, base_type: &str, weeks: u64, now: u64) -> Option<f64> {
+    let cutoff = now.saturating_sub(weeks * WEEK_SECS);
+    if history.samples.first().is_none_or(|s| s.at > cutoff) {
+        return None;
+    }
+    history
+        .samples
+        .iter()
+        .filter(|s| s.at >= cutoff)
+        .filter_map(|s| s.prices.get(base_type).copied())
+        .reduce(f64::max)
+}
+
+pub fn find_dead_items(doc: &FilterDocument, history: &PriceHistory, weeks: u64, max_chaos: f64, now: u64) -> Vec<DeadItem> {
+    let mut out = Vec::new();
+    for block in doc.blocks.iter().filter(|b| b.kind == "Show" && is_generated(b)) {
+        let Some(rule) = block.rule("BaseType") else { continue };
+        for base in rule.unquoted_values() {
+            let Some(seen) = window_max(history, base, weeks, now) else { continue };
+            if seen <= max_chaos {
+                out.push(DeadItem {
+                    block_id: block.id,
+                    section: block.section.clone(),
+                    base_type: base.to_string(),
+                    max_chaos: seen,
+                    target_block_id: lower_tier(doc, block.id),
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Move each item from its block's BaseType list to the next tier down. Items that are
+/// already in the lowest tier, or are the last value of their block, are skipped.
+pub fn demote_in(doc: &mut FilterDocument, items: &[Demotion]) -> Vec<usize> {
+    let mut touched = Vec::new();
+    for item in items {
+        let Some(target) = lower_tier(doc, item.block_id) else { continue };
+        let Some(rule) = doc.blocks[item.block_id].rule("BaseType") else { continue };
+        if rule.values.len() < 2 || !rule.values.iter().any(|v| unquote(v) == item.base_type) {
+            continue;
+        }
+        let mut rule = rule.clone();
+        rule.values.retain(|v| unquote(v) != item.base_type);
+        doc.blocks[item.block_id].set_rule(rule);
+
+        let Some(rule) = doc.blocks[target].rule("BaseType") else { continue };
+        let mut rule = rule.clone();
+        if !rule.values.iter().any(|v| unquote(v) == item.base_type) {
+            rule.values.push(quote(&item.base_type));
+        }
+        doc.blocks[target].set_rule(rule);
+        touched.extend([item.block_id, target]);
+    }
+    touched.sort_unstable();
+    touched.dedup();
+    touched
+}
+
+pub fn demote_items(path: &str, items: &[Demotion]) -> Result<FileChange, WarlordError> {
+    let mut doc = filter_parser::parse_file(path)?;
+    let touched = demote_in(&mut doc, items);
+    if !touched.is_empty() {
+        filter_parser::write_file(path, &doc)?;
+    }
+    let lines = touched.iter().map(|id| doc.blocks[*id].line).collect();
+    Ok(FileChange { path: path.to_string(), replacements: touched.len(), lines })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::economy::PriceSample;
+
+    #[test]
+    fn finds_and_demotes_dead_cards() {
+        let src = "Show # $type->divination $tier->t1\n    BaseType == \"Dead\" \"Alive\"\nShow # $tier->t2\n    BaseType == \"Other\"\nShow\n    BaseType == \"Dead\" \"Mine\"\n";
+        let mut doc = FilterDocument::parse(src);
+        let sample = |at, dead: f64| PriceSample {
+            at,
+            prices: [("Dead".to_string(), dead), ("Alive".to_string(), 50.0)].into_iter().collect(),
+        };
+        let now = 10 * WEEK_SECS;
+        let history = PriceHistory { league: "Std".to_string(), samples: vec![sample(0, 20.0), sample(7 * WEEK_SECS, 1.0), sample(now, 0.5)] };
+
+        let dead = find_dead_items(&doc, &history, 2, 2.0, now);
+        assert_eq!(dead.len(), 1);
+        assert_eq!((dead[0].block_id, dead[0].target_block_id), (0, Some(1)));
+
+        let items = [Demotion { block_id: 0, base_type: "Dead".to_string() }];
+        assert_eq!(demote_in(&mut doc, &items), vec![0, 1]);
+        assert!(doc.to_text().starts_with("Show # $type->divination $tier->t1\n    BaseType == \"Alive\"\nShow # $tier->t2\n    BaseType == \"Other\" \"Dead\"\n"));
+    }
+}
@@ -0,0 +1,167 @@
+//! Text encodings of filter files. Most are UTF-8, but Chinese-locale editors save GBK and
+//! Notepad can save UTF-16. Detection order: byte order mark, UTF-16 without a BOM (every
+//! other byte zero), valid UTF-8, then chardetng's guess. Writes through `write_preserving`
+//! keep a file's encoding, BOM and line endings so a round trip through the editor does not
+//! rewrite every line.
+
+use std::fs;
+use std::path::Path;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+use crate::error::WarlordError;
+use crate::path_utils::long_path;
+use crate::sandbox;
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedText {
+    /// Without the byte order mark
+    pub text: String,
+    /// encoding_rs name: "UTF-8", "GBK", "UTF-16LE", ...
+    pub encoding: String,
+    pub bom: bool,
+    /// Some bytes were not valid in the detected encoding and became U+FFFD
+    pub lossy: bool,
+}
+
+/// UTF-16 without a BOM: mostly ASCII text, so one byte of each pair is zero.
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 4 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let pairs = bytes.len() / 2;
+    let zeros = |offset: usize| bytes.iter().skip(offset).step_by(2).filter(|&&b| b == 0).count();
+    let (even, odd) = (zeros(0), zeros(1));
+    if odd * 10 >= pairs * 7 && even * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even * 10 >= pairs * 7 && odd * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+pub fn detect(bytes: &[u8]) -> (&'static Encoding, usize) {
+    if let Some(found) = Encoding::for_bom(bytes) {
+        return found;
+    }
+    // Before UTF-8: ASCII in UTF-16 is also valid UTF-8, full of NULs
+    if let Some(utf16) = sniff_utf16(bytes) {
+        return (utf16, 0);
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return (UTF_8, 0);
+    }
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    (detector.guess(None, false), 0)
+}
+
+pub fn decode(bytes: &[u8]) -> DecodedText {
+    let (encoding, bom_len) = detect(bytes);
+    let (text, lossy) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+    DecodedText { text: text.into_owned(), encoding: encoding.name().to_string(), bom: bom_len > 0, lossy }
+}
+
+pub fn read_file(path: impl AsRef<Path>) -> Result<DecodedText, WarlordError> {
+    let path = path.as_ref();
+    Ok(decode(&fs::read(long_path(path)).map_err(|e| WarlordError::io(e, path))?))
+}
+
+/// Text of a file in whatever encoding it was saved in.
+pub fn read_to_string(path: impl AsRef<Path>) -> Result<String, WarlordError> {
+    read_file(path).map(|d| d.text)
+}
+
+/// "\r\n" when the text has any CRLF line ending, else "\n".
+pub fn line_ending(text: &str) -> &'static str {
+    if text.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+pub fn with_line_endings(text: &str, ending: &str) -> String {
+    let lf = text.replace("\r\n", "\n");
+    if ending == "\n" {
+        lf
+    } else {
+        lf.replace('\n', ending)
+    }
+}
+
+/// `text` in `encoding` (an encoding_rs name), with a byte order mark if `bom`.
+pub fn encode(text: &str, encoding: &str, bom: bool) -> Result<Vec<u8>, WarlordError> {
+    let encoding = Encoding::for_label(encoding.as_bytes()).ok_or_else(|| WarlordError::invalid(format!("未知的编码: {}", encoding)))?;
+    let mut out = Vec::with_capacity(text.len() + 3);
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let little = encoding == UTF_16LE;
+        if bom {
+            out.extend(if little { [0xFF, 0xFE] } else { [0xFE, 0xFF] });
+        }
+        out.extend(text.encode_utf16().flat_map(|u| if little { u.to_le_bytes() } else { u.to_be_bytes() }));
+        return Ok(out);
+    }
+    if encoding == UTF_8 {
+        if bom {
+            out.extend([0xEF, 0xBB, 0xBF]);
+        }
+        out.extend(text.as_bytes());
+        return Ok(out);
+    }
+    let (bytes, _, unmappable) = encoding.encode(text);
+    if unmappable {
+        return Err(WarlordError::invalid(format!("文本中有 {} 无法表示的字符, 请改用 UTF-8 保存", encoding.name())));
+    }
+    out.extend(bytes.iter());
+    Ok(out)
+}
+
+/// Bytes to write `text` over `path` with: the file's current encoding, BOM and line endings,
+/// or UTF-8 as given for a new file.
+pub fn encode_like(path: impl AsRef<Path>, text: &str) -> Result<Vec<u8>, WarlordError> {
+    let (text, had_bom) = match text.strip_prefix('\u{feff}') {
+        Some(rest) => (rest, true),
+        None => (text, false),
+    };
+    match fs::read(long_path(path)) {
+        Ok(bytes) => {
+            let current = decode(&bytes);
+            let text = with_line_endings(text, line_ending(&current.text));
+            encode(&text, &current.encoding, current.bom)
+        }
+        Err(_) => encode(text, "UTF-8", had_bom),
+    }
+}
+
+/// `sandbox::write` that keeps the file's encoding and line endings.
+pub fn write_preserving(path: impl AsRef<Path>, text: &str) -> Result<(), WarlordError> {
+    let bytes = encode_like(&path, text)?;
+    sandbox::write(path, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_boms_and_utf16() {
+        let utf8 = decode("\u{feff}Show # 液化情感\n".as_bytes());
+        assert_eq!((utf8.text.as_str(), utf8.encoding.as_str(), utf8.bom), ("Show # 液化情感\n", "UTF-8", true));
+
+        let notepad: Vec<u8> = "Hide\r\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let decoded = decode(&notepad);
+        assert_eq!((decoded.text.as_str(), decoded.encoding.as_str(), decoded.bom), ("Hide\r\n", "UTF-16LE", false));
+        let with_bom: Vec<u8> = [0xFE, 0xFF].into_iter().chain("Hide".encode_utf16().flat_map(u16::to_be_bytes)).collect();
+        assert_eq!(decode(&with_bom).encoding, "UTF-16BE");
+
+        let file = std::env::temp_dir().join("wt-encoding-test.filter");
+        fs::write(&file, &notepad).unwrap();
+        write_preserving(&file, "Show\nHide\n").unwrap();
+        assert_eq!(decode(&fs::read(&file).unwrap()).text, "Show\r\nHide\r\n");
+        assert!(fs::read(&file).unwrap().starts_with(&[b'S', 0]));
+        fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+//! Error type of the commands. It reaches the frontend as `{ code, message, path }`: `code`
+//! (camelCase variant name) is what the UI branches on and localizes, `message` is the
+//! Chinese text shown as is, `path` the file or folder it is about, if any.
+//!
+//! The few platform helpers that still return `String` errors convert with `?` and arrive
+//! as `other`.
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::{file_lock, file_ops};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WarlordError {
+    NotFound { path: String },
+    PermissionDenied { path: Option<String>, message: String },
+    AlreadyExists { path: String },
+    /// Locked by another process (the game, OBS, an editor, antivirus); `processes` names them
+    /// when the system can tell (`file_lock::locking_processes`)
+    Busy { path: Option<String>, processes: Vec<String> },
+    /// Changed since it was loaded
+    Conflict { path: String, message: String },
+    /// Spectator workspace or network share (`sandbox::read_only_reason`)
+    ReadOnly { path: String, reason: String },
+    /// The file's read-only attribute is set; `clear_readonly` fixes it
+    ReadOnlyFile { path: String },
+    OutsideSandbox { path: String },
+    /// Not enough free space on the destination drive
+    DiskFull { path: String, required: u64, available: u64 },
+    InvalidInput { message: String },
+    Network { message: String },
+    Io { path: Option<String>, message: String },
+    Other { message: String },
+}
+
+/// Windows ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+const SHARING_VIOLATIONS: [i32; 2] = [32, 33];
+
+fn shown(path: &Path) -> String {
+    crate::path_utils::short_path(path)
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+impl WarlordError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            WarlordError::NotFound { .. } => "notFound",
+            WarlordError::PermissionDenied { .. } => "permissionDenied",
+            WarlordError::AlreadyExists { .. } => "alreadyExists",
+            WarlordError::Busy { .. } => "busy",
+            WarlordError::Conflict { .. } => "conflict",
+            WarlordError::ReadOnly { .. } => "readOnly",
+            WarlordError::ReadOnlyFile { .. } => "readOnlyFile",
+            WarlordError::OutsideSandbox { .. } => "outsideSandbox",
+            WarlordError::DiskFull { .. } => "diskFull",
+            WarlordError::InvalidInput { .. } => "invalidInput",
+            WarlordError::Network { .. } => "network",
+            WarlordError::Io { .. } => "io",
+            WarlordError::Other { .. } => "other",
+        }
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            WarlordError::NotFound { path }
+            | WarlordError::AlreadyExists { path }
+            | WarlordError::Conflict { path, .. }
+            | WarlordError::ReadOnly { path, .. }
+            | WarlordError::ReadOnlyFile { path }
+            | WarlordError::OutsideSandbox { path }
+            | WarlordError::DiskFull { path, .. } => Some(path),
+            WarlordError::PermissionDenied { path, .. } | WarlordError::Busy { path, .. } | WarlordError::Io { path, .. } => path.as_deref(),
+            WarlordError::InvalidInput { .. } | WarlordError::Network { .. } | WarlordError::Other { .. } => None,
+        }
+    }
+
+    /// `error` from an operation on `path`, by kind alone; a sharing violation is `Busy`.
+    pub fn io(error: io::Error, path: impl AsRef<Path>) -> Self {
+        let path = shown(path.as_ref());
+        let sharing_violation = error.raw_os_error().is_some_and(|code| cfg!(windows) && SHARING_VIOLATIONS.contains(&code));
+        if sharing_violation || error.kind() == io::ErrorKind::ResourceBusy {
+            return WarlordError::Busy { path: Some(path), processes: Vec::new() };
+        }
+        match error.kind() {
+            io::ErrorKind::NotFound => WarlordError::NotFound { path },
+            io::ErrorKind::AlreadyExists => WarlordError::AlreadyExists { path },
+            io::ErrorKind::PermissionDenied => WarlordError::PermissionDenied { path: Some(path), message: error.to_string() },
+            _ => WarlordError::Io { path: Some(path), message: error.to_string() },
+        }
+    }
+
+    /// `io` for a failed write, rename or delete of `path`, looked into further: an access
+    /// denied on a file another process holds open is `Busy`, on a read-only file
+    /// `ReadOnlyFile`, and `Busy` names the programs when the system can tell. This asks the
+    /// file system again, so it is only for the places where saving over a file fails.
+    pub fn write_failed(error: io::Error, path: impl AsRef<Path>) -> Self {
+        let on = path.as_ref();
+        match WarlordError::io(error, on) {
+            WarlordError::PermissionDenied { .. } if file_lock::is_file_locked(on) => WarlordError::Busy { path: Some(shown(on)), processes: file_lock::locking_processes(on) },
+            WarlordError::PermissionDenied { .. } if file_ops::is_readonly(on) => WarlordError::ReadOnlyFile { path: shown(on) },
+            WarlordError::Busy { path, .. } => WarlordError::Busy { path, processes: file_lock::locking_processes(on) },
+            other => other,
+        }
+    }
+
+    pub fn not_found(path: impl AsRef<Path>) -> Self {
+        WarlordError::NotFound { path: shown(path.as_ref()) }
+    }
+
+    pub fn already_exists(path: impl AsRef<Path>) -> Self {
+        WarlordError::AlreadyExists { path: shown(path.as_ref()) }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        WarlordError::InvalidInput { message: message.into() }
+    }
+}
+
+impl fmt::Display for WarlordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WarlordError::NotFound { path } => write!(f, "{} 不存在", path),
+            WarlordError::PermissionDenied { path: Some(path), message } => write!(f, "没有权限访问 {}: {}", path, message),
+            WarlordError::PermissionDenied { path: None, message } => write!(f, "没有权限: {}", message),
+            WarlordError::AlreadyExists { path } => write!(f, "{} 已存在", path),
+            WarlordError::Busy { path, processes } if processes.is_empty() => write!(f, "{} 正被其他程序占用", path.as_deref().unwrap_or("文件")),
+            WarlordError::Busy { path, processes } => write!(f, "{} 正被 {} 占用", path.as_deref().unwrap_or("文件"), processes.join(", ")),
+            WarlordError::Conflict { message, .. } => write!(f, "{}", message),
+            WarlordError::ReadOnly { path, reason } => write!(f, "{}, 无法修改 {}", reason, path),
+            WarlordError::ReadOnlyFile { path } => write!(f, "{} 被设为只读, 清除只读属性后才能保存", path),
+            WarlordError::OutsideSandbox { path } => write!(f, "{} 不在允许修改的文件夹内", path),
+            WarlordError::DiskFull { path, required, available } => write!(f, "{} 所在磁盘空间不足: 需要 {}, 剩余 {}", path, megabytes(*required), megabytes(*available)),
+            WarlordError::Io { path: Some(path), message } => write!(f, "{}: {}", path, message),
+            WarlordError::InvalidInput { message } | WarlordError::Network { message } | WarlordError::Io { path: None, message } | WarlordError::Other { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for WarlordError {}
+
+impl serde::Serialize for WarlordError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("WarlordError", 3)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("path", &self.path())?;
+        s.end()
+    }
+}
+
+impl From<io::Error> for WarlordError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => WarlordError::PermissionDenied { path: None, message: error.to_string() },
+            io::ErrorKind::ResourceBusy => WarlordError::Busy { path: None, processes: Vec::new() },
+            _ => WarlordError::Io { path: None, message: error.to_string() },
+        }
+    }
+}
+
+impl From<String> for WarlordError {
+    fn from(message: String) -> Self {
+        WarlordError::Other { message }
+    }
+}
+
+impl From<&str> for WarlordError {
+    fn from(message: &str) -> Self {
+        WarlordError::Other { message: message.to_string() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_code_message_and_path() {
+        let missing = WarlordError::io(io::Error::from(io::ErrorKind::NotFound), "C:/filters/a.filter");
+        let json = serde_json::to_value(&missing).unwrap();
+        assert_eq!(json["code"], "notFound");
+        assert_eq!(json["path"], "C:/filters/a.filter");
+        assert_eq!(json["message"], "C:/filters/a.filter 不存在");
+        assert_eq!(serde_json::to_value(WarlordError::from("网络错误")).unwrap()["path"], serde_json::Value::Null);
+    }
+}
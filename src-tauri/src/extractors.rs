@@ -0,0 +1,313 @@
+//! Archive formats for pack imports behind one trait, so `archive` does the path checks,
+//! overwrite policy and writing once for all of them: zip (zip crate), 7z (sevenz-rust) and
+//! RAR (unrar, the RARLAB library). The format is told by the file's signature rather than
+//! its extension, since downloads get renamed. Only zip entries carry a usable modification
+//! time; for the others `Overwrite::IfNewer` replaces. Password-protected (AES) zips, like
+//! encrypted backups, are read with `open_with_password`. No entry may decompress to more
+//! than `MAX_ENTRY_SIZE`, whatever its header says, so a crafted pack cannot fill the disk or,
+//! for RAR (whose entries come out whole), the memory.
+
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+
+/// A file in an archive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArchiveEntry {
+    /// Name as stored, `/`-separated
+    pub name: String,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+pub trait Extractor {
+    /// Files in the archive; folders and symbolic links are left out.
+    fn list(&mut self) -> Result<Vec<ArchiveEntry>, WarlordError>;
+
+    /// Hand each listed file to `each` with its name and contents, in archive order (solid
+    /// 7z blocks and RAR can only be read front to back). Stops at the first error `each`
+    /// returns.
+    fn for_each(&mut self, each: &mut dyn FnMut(&str, &mut dyn Read) -> Result<(), WarlordError>) -> Result<(), WarlordError>;
+}
+
+/// Largest file an entry may decompress to; nothing in a filter or sound pack comes near it.
+pub const MAX_ENTRY_SIZE: u64 = 512 * 1024 * 1024;
+
+fn too_large(name: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{} 解压后超过 {} MB, 已拒绝", name, MAX_ENTRY_SIZE / (1024 * 1024)))
+}
+
+/// An entry's contents, failing once more than `left` bytes came out.
+struct Limited<'a> {
+    inner: &'a mut dyn Read,
+    left: u64,
+    name: &'a str,
+}
+
+impl<'a> Limited<'a> {
+    fn new(inner: &'a mut dyn Read, name: &'a str) -> Limited<'a> {
+        Limited { inner, left: MAX_ENTRY_SIZE, name }
+    }
+}
+
+impl Read for Limited<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.left = self.left.checked_sub(read as u64).ok_or_else(|| too_large(self.name))?;
+        Ok(read)
+    }
+}
+
+/// Stands in for an entry refused before it was decompressed; the first read fails.
+struct Refused(Option<io::Error>);
+
+impl Read for Refused {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(self.0.take().unwrap_or_else(|| io::Error::from(io::ErrorKind::InvalidData)))
+    }
+}
+
+/// Whether an entry of `size` bytes (as its header says) may be decompressed at all.
+fn check_size(name: &str, size: u64) -> io::Result<()> {
+    if size > MAX_ENTRY_SIZE {
+        return Err(too_large(name));
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Zip,
+    SevenZ,
+    Rar,
+}
+
+/// Format from the first bytes of the file, else from the extension.
+pub fn detect(path: &Path) -> Option<Format> {
+    let mut magic = [0u8; 6];
+    let read = File::open(long_path(path)).and_then(|mut f| f.read(&mut magic)).unwrap_or(0);
+    match &magic[..read] {
+        [b'P', b'K', 3, 4, ..] | [b'P', b'K', 5, 6, ..] => return Some(Format::Zip),
+        [b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C] => return Some(Format::SevenZ),
+        [b'R', b'a', b'r', b'!', 0x1A, 0x07] => return Some(Format::Rar),
+        _ => {}
+    }
+    match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+        "zip" => Some(Format::Zip),
+        "7z" => Some(Format::SevenZ),
+        "rar" => Some(Format::Rar),
+        _ => None,
+    }
+}
+
+fn read_error(archive: &Path, error: impl std::fmt::Display) -> WarlordError {
+    WarlordError::Io { path: Some(path_utils::short_path(archive)), message: format!("无法读取压缩包: {}", error) }
+}
+
+/// Extractor for the archive at `path`.
+pub fn open(path: &Path) -> Result<Box<dyn Extractor>, WarlordError> {
+    open_with_password(path, None)
+}
+
+/// `open` for an archive that may be encrypted. An encrypted zip without the password, or
+/// with a wrong one, fails on its first entry with a message saying so.
+pub fn open_with_password(path: &Path, password: Option<&str>) -> Result<Box<dyn Extractor>, WarlordError> {
+    if !long_path(path).is_file() {
+        return Err(WarlordError::not_found(path));
+    }
+    match detect(path) {
+        Some(Format::Zip) => {
+            let file = File::open(long_path(path)).map_err(|e| WarlordError::io(e, path))?;
+            let zip = zip::ZipArchive::new(file).map_err(|e| zip_error(e, path))?;
+            Ok(Box::new(ZipExtractor { zip, path: path.to_path_buf(), password: password.map(String::from) }))
+        }
+        Some(Format::SevenZ) => Ok(Box::new(SevenZExtractor { path: path.to_path_buf() })),
+        Some(Format::Rar) => Ok(Box::new(RarExtractor { path: path.to_path_buf() })),
+        None => Err(WarlordError::invalid(format!("{} 不是支持的压缩包 (zip, 7z, rar)", path_utils::short_path(path)))),
+    }
+}
+
+fn zip_error(error: zip::result::ZipError, archive: &Path) -> WarlordError {
+    use zip::result::ZipError;
+    match error {
+        ZipError::Io(e) => WarlordError::io(e, archive),
+        ZipError::InvalidPassword => WarlordError::invalid(format!("{} 的密码错误", path_utils::short_path(archive))),
+        ZipError::UnsupportedArchive(message) if message == ZipError::PASSWORD_REQUIRED => WarlordError::invalid(format!("{} 已加密，请输入密码", path_utils::short_path(archive))),
+        other => read_error(archive, other),
+    }
+}
+
+/// Zip timestamps are local time without a zone.
+fn zip_time(time: zip::DateTime) -> Option<SystemTime> {
+    let date = chrono::NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())?;
+    let local = date.and_hms_opt(time.hour().into(), time.minute().into(), time.second().into())?.and_local_timezone(chrono::Local).earliest()?;
+    Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(local.timestamp().try_into().ok()?))
+}
+
+struct ZipExtractor {
+    zip: zip::ZipArchive<File>,
+    path: PathBuf,
+    password: Option<String>,
+}
+
+impl Extractor for ZipExtractor {
+    fn list(&mut self) -> Result<Vec<ArchiveEntry>, WarlordError> {
+        let mut out = Vec::new();
+        for index in 0..self.zip.len() {
+            // Decrypted when there is a password; plain entries ignore it
+            let file = match &self.password {
+                Some(password) => self.zip.by_index_decrypt(index, password.as_bytes()),
+                None => self.zip.by_index(index),
+            }
+            .map_err(|e| zip_error(e, &self.path))?;
+            if file.is_dir() || file.is_symlink() {
+                continue;
+            }
+            out.push(ArchiveEntry { name: file.name().to_string(), size: file.size(), modified: file.last_modified().and_then(zip_time) });
+        }
+        Ok(out)
+    }
+
+    fn for_each(&mut self, each: &mut dyn FnMut(&str, &mut dyn Read) -> Result<(), WarlordError>) -> Result<(), WarlordError> {
+        for index in 0..self.zip.len() {
+            let mut file = match &self.password {
+                Some(password) => self.zip.by_index_decrypt(index, password.as_bytes()),
+                None => self.zip.by_index(index),
+            }
+            .map_err(|e| zip_error(e, &self.path))?;
+            if file.is_dir() || file.is_symlink() {
+                continue;
+            }
+            let name = file.name().to_string();
+            each(&name, &mut Limited::new(&mut file, &name))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reopened per pass; sevenz-rust reads the whole header on open.
+struct SevenZExtractor {
+    path: PathBuf,
+}
+
+impl SevenZExtractor {
+    fn reader(&self) -> Result<sevenz_rust::SevenZReader<File>, WarlordError> {
+        sevenz_rust::SevenZReader::open(long_path(&self.path), sevenz_rust::Password::empty()).map_err(|e| read_error(&self.path, e))
+    }
+}
+
+impl Extractor for SevenZExtractor {
+    fn list(&mut self) -> Result<Vec<ArchiveEntry>, WarlordError> {
+        let reader = self.reader()?;
+        let files = reader.archive().files.iter().filter(|e| !e.is_directory() && !e.is_anti_item());
+        Ok(files.map(|e| ArchiveEntry { name: e.name().replace('\\', "/"), size: e.size(), modified: None }).collect())
+    }
+
+    fn for_each(&mut self, each: &mut dyn FnMut(&str, &mut dyn Read) -> Result<(), WarlordError>) -> Result<(), WarlordError> {
+        let mut failure = None;
+        self.reader()?
+            .for_each_entries(|entry, data| {
+                if entry.is_directory() || entry.is_anti_item() {
+                    return Ok(true);
+                }
+                let name = entry.name().replace('\\', "/");
+                if let Err(e) = each(&name, &mut Limited::new(data, &name)) {
+                    failure = Some(e);
+                    return Ok(false);
+                }
+                // A skipped file is still in the way of the next one in a solid block
+                io::copy(data, &mut io::sink())?;
+                Ok(true)
+            })
+            .map_err(|e| read_error(&self.path, e))?;
+        failure.map_or(Ok(()), Err)
+    }
+}
+
+struct RarExtractor {
+    path: PathBuf,
+}
+
+impl Extractor for RarExtractor {
+    fn list(&mut self) -> Result<Vec<ArchiveEntry>, WarlordError> {
+        let long = long_path(&self.path);
+        let archive = unrar::Archive::new(&long).open_for_listing().map_err(|e| read_error(&self.path, e))?;
+        let mut out = Vec::new();
+        for header in archive {
+            let header = header.map_err(|e| read_error(&self.path, e))?;
+            if header.is_file() {
+                out.push(ArchiveEntry { name: header.filename.to_string_lossy().replace('\\', "/"), size: header.unpacked_size, modified: None });
+            }
+        }
+        Ok(out)
+    }
+
+    fn for_each(&mut self, each: &mut dyn FnMut(&str, &mut dyn Read) -> Result<(), WarlordError>) -> Result<(), WarlordError> {
+        let long = long_path(&self.path);
+        let mut archive = unrar::Archive::new(&long).open_for_processing().map_err(|e| read_error(&self.path, e))?;
+        while let Some(header) = archive.read_header().map_err(|e| read_error(&self.path, e))? {
+            archive = if header.entry().is_file() {
+                let name = header.entry().filename.to_string_lossy().replace('\\', "/");
+                // unrar hands the entry over whole: one too large is refused from its header,
+                // before `read` allocates it
+                match check_size(&name, header.entry().unpacked_size) {
+                    Ok(()) => {
+                        let (data, rest) = header.read().map_err(|e| read_error(&self.path, e))?;
+                        each(&name, &mut Limited::new(&mut Cursor::new(data), &name))?;
+                        rest
+                    }
+                    Err(error) => {
+                        let rest = header.skip().map_err(|e| read_error(&self.path, e))?;
+                        each(&name, &mut Refused(Some(error)))?;
+                        rest
+                    }
+                }
+            } else {
+                header.skip().map_err(|e| read_error(&self.path, e))?
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_format_by_signature() {
+        let dir = std::env::temp_dir().join("wt-extractors-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        // A 7z renamed to .zip, and a zip without an extension
+        let renamed = dir.join("pack.zip");
+        std::fs::write(&renamed, [b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C, 0, 4]).unwrap();
+        assert_eq!(detect(&renamed), Some(Format::SevenZ));
+        let bare = dir.join("download");
+        std::fs::write(&bare, b"PK\x03\x04rest").unwrap();
+        assert_eq!(detect(&bare), Some(Format::Zip));
+        assert_eq!(detect(&dir.join("missing.rar")), Some(Format::Rar));
+        assert_eq!(detect(&dir.join("notes.txt")), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_entries_past_the_size_limit() {
+        // A RAR entry is refused from its header, before anything is decompressed
+        assert!(check_size("pack/alert.mp3", MAX_ENTRY_SIZE).is_ok());
+        let error = check_size("pack/bomb.mp3", MAX_ENTRY_SIZE + 1).unwrap_err();
+        let mut refused = Refused(Some(error));
+        let message = io::copy(&mut refused, &mut io::sink()).unwrap_err().to_string();
+        assert!(message.starts_with("pack/bomb.mp3 解压后超过"), "{}", message);
+
+        // The others are cut off as they stream, also when the header lies
+        let mut data = io::repeat(0).take(10);
+        let mut limited = Limited { inner: &mut data, left: 8, name: "pack/bomb.mp3" };
+        assert_eq!(io::copy(&mut limited, &mut io::sink()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let mut data = io::repeat(0).take(8);
+        assert_eq!(io::copy(&mut Limited { inner: &mut data, left: 8, name: "a" }, &mut io::sink()).unwrap(), 8);
+    }
+}
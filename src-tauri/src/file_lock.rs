@@ -0,0 +1,145 @@
+//! Files held open by another process. On Windows the game keeps its filter open and OBS its
+//! media sources, and saving over them fails with a bare "access denied";
+//! `WarlordError::write_failed` asks here so such failures come back as `Busy`, naming the
+//! programs when Windows can tell (Restart Manager).
+
+use std::fs;
+use std::path::Path;
+
+use crate::path_utils::long_path;
+
+/// Whether another process keeps `path` from being written (Windows: has it open without
+/// sharing write access; elsewhere: holds an exclusive `flock`). A viewer that only reads and
+/// shares the file does not count. False for files that do not exist.
+#[cfg(windows)]
+pub fn is_file_locked(path: &Path) -> bool {
+    use std::os::windows::fs::OpenOptionsExt;
+    const SHARING_VIOLATION: i32 = 32;
+    const LOCK_VIOLATION: i32 = 33;
+    const FILE_SHARE_ALL: u32 = 0x1 | 0x2 | 0x4;
+    // Asking for write access while sharing everything fails only if a holder denies writes
+    match fs::OpenOptions::new().write(true).share_mode(FILE_SHARE_ALL).open(long_path(path)) {
+        Ok(_) => false,
+        Err(e) => matches!(e.raw_os_error(), Some(SHARING_VIOLATION | LOCK_VIOLATION)),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn is_file_locked(path: &Path) -> bool {
+    use std::os::unix::io::AsRawFd;
+    let Ok(file) = fs::File::open(long_path(path)) else { return false };
+    let fd = file.as_raw_fd();
+    // A shared lock is only refused while someone holds an exclusive one; readers share
+    if unsafe { libc::flock(fd, libc::LOCK_SH | libc::LOCK_NB) } == 0 {
+        unsafe { libc::flock(fd, libc::LOCK_UN) };
+        false
+    } else {
+        std::io::Error::last_os_error().raw_os_error() == Some(libc::EWOULDBLOCK)
+    }
+}
+
+#[cfg(windows)]
+mod restart_manager {
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct UniqueProcess {
+        process_id: u32,
+        start_time: [u32; 2],
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct ProcessInfo {
+        process: UniqueProcess,
+        app_name: [u16; 256],
+        service_short_name: [u16; 64],
+        application_type: i32,
+        app_status: u32,
+        ts_session_id: u32,
+        restartable: i32,
+    }
+
+    #[link(name = "rstrtmgr")]
+    extern "system" {
+        fn RmStartSession(session: *mut u32, flags: u32, key: *mut u16) -> u32;
+        fn RmRegisterResources(session: u32, files: u32, names: *const *const u16, apps: u32, processes: *const UniqueProcess, services: u32, service_names: *const *const u16) -> u32;
+        fn RmGetList(session: u32, needed: *mut u32, count: *mut u32, info: *mut ProcessInfo, reasons: *mut u32) -> u32;
+        fn RmEndSession(session: u32) -> u32;
+    }
+
+    const ERROR_MORE_DATA: u32 = 234;
+
+    /// Names of the programs that have `path` open.
+    pub fn processes(path: &Path) -> Vec<String> {
+        let name: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut session = 0u32;
+        let mut key = [0u16; 33];
+        unsafe {
+            if RmStartSession(&mut session, 0, key.as_mut_ptr()) != 0 {
+                return Vec::new();
+            }
+            let names = [name.as_ptr()];
+            let mut found = Vec::new();
+            if RmRegisterResources(session, 1, names.as_ptr(), 0, std::ptr::null(), 0, std::ptr::null()) == 0 {
+                let mut info = vec![std::mem::zeroed::<ProcessInfo>(); 8];
+                let (mut needed, mut count, mut reasons) = (0u32, info.len() as u32, 0u32);
+                let status = RmGetList(session, &mut needed, &mut count, info.as_mut_ptr(), &mut reasons);
+                if status == 0 || status == ERROR_MORE_DATA {
+                    for process in &info[..(count as usize).min(info.len())] {
+                        let len = process.app_name.iter().position(|&c| c == 0).unwrap_or(process.app_name.len());
+                        found.push(String::from_utf16_lossy(&process.app_name[..len]));
+                    }
+                }
+            }
+            RmEndSession(session);
+            found
+        }
+    }
+}
+
+/// Programs holding `path` open, as far as the system can tell (empty off Windows).
+pub fn locking_processes(path: &Path) -> Vec<String> {
+    #[cfg(windows)]
+    {
+        // Restart Manager wants the plain form, not `\\?\`
+        restart_manager::processes(Path::new(&crate::path_utils::short_path(path)))
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = path;
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlocked_and_missing_files_are_not_locked() {
+        let file = std::env::temp_dir().join("wt-file-lock-test.filter");
+        fs::write(&file, "Show\n").unwrap();
+        assert!(!is_file_locked(&file));
+        fs::remove_file(&file).unwrap();
+        assert!(!is_file_locked(&file));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn readers_do_not_lock_writers_do() {
+        use std::os::unix::io::AsRawFd;
+        let file = std::env::temp_dir().join("wt-file-lock-flock-test.filter");
+        fs::write(&file, "Show\n").unwrap();
+        let holder = fs::File::open(&file).unwrap();
+        unsafe { libc::flock(holder.as_raw_fd(), libc::LOCK_SH) };
+        assert!(!is_file_locked(&file));
+        unsafe { libc::flock(holder.as_raw_fd(), libc::LOCK_EX) };
+        assert!(is_file_locked(&file));
+        drop(holder);
+        assert!(!is_file_locked(&file));
+        fs::remove_file(&file).unwrap();
+    }
+}
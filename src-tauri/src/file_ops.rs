@@ -0,0 +1,354 @@
+//! File operations on the library: copying folder trees (sound packs) and moving files or
+//! folders to another drive with progress and a per-file summary, duplicating filters and
+//! deleting in bulk. Paths are handled in extended-length form (`path_utils::long_path`)
+//! and reported without it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+use crate::{disk_space, sandbox, scan};
+
+/// What to do when a file already exists at the destination.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Overwrite {
+    #[default]
+    Skip,
+    Replace,
+    /// Replace only when the source was modified later
+    IfNewer,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Progress {
+    /// "copy" / "move"
+    pub operation: String,
+    /// File just handled
+    pub path: String,
+    pub done: usize,
+    pub total: usize,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedFile {
+    pub path: String,
+    pub error: WarlordError,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CopySummary {
+    /// Destination paths
+    pub copied: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failed: Vec<FailedFile>,
+    pub bytes: u64,
+}
+
+fn shown(path: &Path) -> String {
+    path_utils::short_path(path)
+}
+
+/// (source, relative path) of every file below `dir`, sorted.
+fn files_below(dir: &Path) -> Result<Vec<(PathBuf, PathBuf)>, WarlordError> {
+    fn visit(root: &Path, dir: &Path, out: &mut Vec<(PathBuf, PathBuf)>) -> std::io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                visit(root, &path, out)?;
+            } else {
+                let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                out.push((path, relative));
+            }
+        }
+        Ok(())
+    }
+    let mut out = Vec::new();
+    visit(dir, dir, &mut out).map_err(|e| WarlordError::io(e, dir))?;
+    out.sort();
+    Ok(out)
+}
+
+fn is_newer(src: &Path, dest: &Path) -> bool {
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    match (modified(src), modified(dest)) {
+        (Some(s), Some(d)) => s > d,
+        _ => true,
+    }
+}
+
+/// Bytes `copy_files` will write for `files` under `overwrite`.
+fn bytes_to_write(files: &[(PathBuf, PathBuf)], overwrite: Overwrite) -> u64 {
+    let size = |p: &Path| fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+    files.iter().filter(|(_, target)| overwrite != Overwrite::Skip || !target.exists()).map(|(file, _)| size(file)).sum()
+}
+
+/// (source, target) file pairs for copying `src` (a file or folder) to `dest`.
+fn copy_pairs(src: &Path, dest: &Path) -> Result<Vec<(PathBuf, PathBuf)>, WarlordError> {
+    if !src.is_dir() {
+        return Ok(vec![(src.to_path_buf(), dest.to_path_buf())]);
+    }
+    let src_full = fs::canonicalize(src).map_err(|e| WarlordError::io(e, src))?;
+    let dest_full = std::path::absolute(dest).map_err(|e| WarlordError::io(e, dest))?;
+    if dest_full.starts_with(&src_full) {
+        return Err(WarlordError::invalid("目标文件夹不能位于源文件夹内"));
+    }
+    Ok(files_below(src)?.into_iter().map(|(file, relative)| (file, dest.join(relative))).collect())
+}
+
+fn copy_files(files: &[(PathBuf, PathBuf)], overwrite: Overwrite, operation: &str, mut progress: impl FnMut(&Progress)) -> CopySummary {
+    let bytes_total = files.iter().map(|(f, _)| fs::metadata(f).map(|m| m.len()).unwrap_or(0)).sum();
+    let mut summary = CopySummary::default();
+    for (done, (file, target)) in files.iter().enumerate() {
+        let target_name = shown(target);
+        let replace = match overwrite {
+            _ if !target.exists() => true,
+            Overwrite::Skip => false,
+            Overwrite::Replace => true,
+            Overwrite::IfNewer => is_newer(file, target),
+        };
+        if !replace {
+            summary.skipped.push(target_name.clone());
+        } else {
+            let result = target.parent().map_or(Ok(()), |p| fs::create_dir_all(p).map_err(|e| WarlordError::io(e, p))).and_then(|_| sandbox::copy(file, target));
+            match result {
+                Ok(bytes) => {
+                    summary.bytes += bytes;
+                    summary.copied.push(target_name.clone());
+                }
+                Err(error) => summary.failed.push(FailedFile { path: shown(file), error }),
+            }
+        }
+        progress(&Progress {
+            operation: operation.to_string(),
+            path: target_name,
+            done: done + 1,
+            total: files.len(),
+            bytes_done: summary.bytes,
+            bytes_total,
+        });
+    }
+    summary
+}
+
+/// Copy the tree under `src` into `dest` (created if needed). Every file is attempted; the
+/// summary lists what was copied, skipped by `overwrite` and what failed.
+pub fn copy_folder(src: &Path, dest: &Path, overwrite: Overwrite, progress: impl FnMut(&Progress)) -> Result<CopySummary, WarlordError> {
+    if !src.is_dir() {
+        return Err(WarlordError::invalid(format!("{} 不是文件夹", src.display())));
+    }
+    sandbox::check_write(dest)?;
+    let (src, dest) = (&long_path(src), &long_path(dest));
+    let pairs = copy_pairs(src, dest)?;
+    disk_space::check_disk_space(dest, bytes_to_write(&pairs, overwrite))?;
+    Ok(copy_files(&pairs, overwrite, "copy", progress))
+}
+
+/// Move a file or folder to `dest`, which must not exist yet. A rename is tried first; across
+/// drives everything is copied, compared by hash and only then removed from `src`. On any
+/// failure the partial copy is removed and `src` is left as it was.
+pub fn move_path(src: &Path, dest: &Path, mut progress: impl FnMut(&Progress)) -> Result<CopySummary, WarlordError> {
+    if !long_path(src).exists() {
+        return Err(WarlordError::not_found(src));
+    }
+    if long_path(dest).exists() {
+        return Err(WarlordError::already_exists(dest));
+    }
+    sandbox::check_write(src)?;
+    sandbox::check_write(dest)?;
+    let (src, dest) = (&long_path(src), &long_path(dest));
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| WarlordError::io(e, parent))?;
+    }
+    let pairs = copy_pairs(src, dest)?;
+    if fs::rename(src, dest).is_ok() {
+        let bytes = pairs.iter().map(|(_, t)| fs::metadata(t).map(|m| m.len()).unwrap_or(0)).sum();
+        let path = shown(dest);
+        progress(&Progress { operation: "move".to_string(), path: path.clone(), done: pairs.len(), total: pairs.len(), bytes_done: bytes, bytes_total: bytes });
+        return Ok(CopySummary { copied: vec![path], bytes, ..Default::default() });
+    }
+
+    // Across drives: everything is copied before anything is removed
+    disk_space::check_disk_space(dest, bytes_to_write(&pairs, Overwrite::Skip))?;
+    let summary = copy_files(&pairs, Overwrite::Skip, "move", progress);
+    let undo = |error: WarlordError| {
+        let _ = if src.is_dir() { fs::remove_dir_all(dest) } else { fs::remove_file(dest) };
+        Err(error)
+    };
+    if let Some(failed) = summary.failed.first() {
+        return undo(failed.error.clone());
+    }
+    for (file, target) in &pairs {
+        let same = matches!((scan::hash_file(file), scan::hash_file(target)), (Ok(a), Ok(b)) if a == b);
+        if !same {
+            return undo(WarlordError::Io { path: Some(shown(file)), message: "移动校验失败".to_string() });
+        }
+    }
+    if src.is_dir() { fs::remove_dir_all(src) } else { fs::remove_file(src) }
+        .map_err(|e| WarlordError::Io { path: Some(shown(src)), message: format!("已复制到 {}, 但无法删除源: {}", shown(dest), e) })?;
+    Ok(summary)
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteResult {
+    pub path: String,
+    pub ok: bool,
+    pub error: Option<WarlordError>,
+}
+
+/// Delete every path (`sandbox::delete`), reporting each one instead of stopping at the first
+/// failure.
+pub fn delete_paths(paths: &[String]) -> Vec<DeleteResult> {
+    paths
+        .iter()
+        .map(|path| {
+            let error = sandbox::delete(path).err();
+            DeleteResult { path: path.clone(), ok: error.is_none(), error }
+        })
+        .collect()
+}
+
+/// Whether the file at `path` is read-only: the attribute on Windows (often set on downloaded
+/// filters), no owner write permission elsewhere.
+pub fn is_readonly(path: &Path) -> bool {
+    fs::metadata(long_path(path)).is_ok_and(|m| m.is_file() && m.permissions().readonly())
+}
+
+fn make_writable(path: &Path) -> std::io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // Owner only; `set_readonly(false)` would make it writable for everyone
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(path, permissions)
+}
+
+/// Clear the read-only attribute of `path`, or of every file below it for a folder. Only
+/// called after the user agreed to. Returns the files that were changed.
+pub fn clear_readonly(path: &Path) -> Result<Vec<String>, WarlordError> {
+    sandbox::check_write(path)?;
+    let long = long_path(path);
+    if !long.exists() {
+        return Err(WarlordError::not_found(path));
+    }
+    let files = if long.is_dir() { files_below(&long)?.into_iter().map(|(file, _)| file).collect() } else { vec![long] };
+    let mut cleared = Vec::new();
+    for file in files.iter().filter(|f| is_readonly(f)) {
+        make_writable(file).map_err(|e| WarlordError::io(e, file))?;
+        cleared.push(shown(file));
+    }
+    Ok(cleared)
+}
+
+/// Free sibling name for a copy of `path`: `name (copy).ext`, then `name (copy 2).ext`, ...
+/// Only the last extension is kept apart, so `a.ruthless.filter` becomes
+/// `a.ruthless (copy).filter`. Copying a copy replaces its suffix instead of stacking
+/// another; other parentheses (`Pack (2023)`) are part of the name.
+pub fn copy_name(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let base = match stem.rsplit_once(" (copy") {
+        Some((base, tail)) if tail.strip_suffix(')').is_some_and(|n| n.is_empty() || n.strip_prefix(' ').is_some_and(|n| n.parse::<u32>().is_ok())) => base,
+        _ => &stem,
+    };
+    (1..)
+        .map(|n| if n == 1 { format!("{} (copy){}", base, ext) } else { format!("{} (copy {}){}", base, n, ext) })
+        .map(|candidate| path.with_file_name(candidate))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+/// Copy `path` next to itself under a free name, keeping its modification time. Returns the
+/// new path.
+pub fn duplicate_filter(path: &Path) -> Result<String, WarlordError> {
+    if !long_path(path).is_file() {
+        return Err(WarlordError::not_found(path));
+    }
+    let path = &long_path(path);
+    let dest = copy_name(path);
+    sandbox::copy(path, &dest)?;
+    if let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) {
+        let _ = fs::File::options().write(true).open(&dest).and_then(|f| f.set_modified(modified));
+    }
+    Ok(shown(&dest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_tree_with_overwrite_policy() {
+        let root = std::env::temp_dir().join("wt-file-ops-test");
+        let _ = fs::remove_dir_all(&root);
+        let (src, dest) = (root.join("pack"), root.join("sounds"));
+        fs::create_dir_all(src.join("alerts")).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(src.join("a.mp3"), "new").unwrap();
+        fs::write(src.join("alerts/b.mp3"), "b").unwrap();
+        fs::write(dest.join("a.mp3"), "old").unwrap();
+
+        let mut calls = 0;
+        let summary = copy_folder(&src, &dest, Overwrite::Skip, |_| calls += 1).unwrap();
+        assert_eq!((summary.copied.len(), summary.skipped.len(), calls), (1, 1, 2));
+        assert_eq!(fs::read_to_string(dest.join("a.mp3")).unwrap(), "old");
+        assert_eq!(fs::read_to_string(dest.join("alerts/b.mp3")).unwrap(), "b");
+
+        let summary = copy_folder(&src, &dest, Overwrite::Replace, |_| {}).unwrap();
+        assert_eq!(summary.copied.len(), 2);
+        assert_eq!(fs::read_to_string(dest.join("a.mp3")).unwrap(), "new");
+        assert!(copy_folder(&root, &dest, Overwrite::Skip, |_| {}).is_err());
+
+        let moved = root.join("moved");
+        assert_eq!(move_path(&src, &moved, |_| {}).unwrap().copied.len(), 1);
+        assert!(!src.exists() && moved.join("alerts/b.mp3").exists());
+        assert!(move_path(&moved, &dest, |_| {}).is_err());
+
+        let filter = root.join("a.ruthless.filter");
+        fs::write(&filter, "Show\n").unwrap();
+        let first = duplicate_filter(&filter).unwrap();
+        assert!(first.ends_with("a.ruthless (copy).filter"));
+        assert!(duplicate_filter(Path::new(&first)).unwrap().ends_with("a.ruthless (copy 2).filter"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn copy_names_keep_other_parentheses() {
+        let dir = std::env::temp_dir().join("wt-copy-name-test");
+        let name = |file: &str| copy_name(&dir.join(file)).file_name().unwrap().to_string_lossy().to_string();
+        assert_eq!(name("Pack (2023).filter"), "Pack (2023) (copy).filter");
+        assert_eq!(name("Pack (2023) (copy 7).filter"), "Pack (2023) (copy).filter");
+        assert_eq!(name("Strict (2).filter"), "Strict (2) (copy).filter");
+        assert_eq!(name("sounds"), "sounds (copy)");
+        assert_eq!(name(".wtignore"), ".wtignore (copy)");
+    }
+
+    #[test]
+    fn clears_readonly_files_below_a_folder() {
+        let root = std::env::temp_dir().join("wt-readonly-test");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("pack")).unwrap();
+        let filter = root.join("pack/downloaded.filter");
+        fs::write(&filter, "Show\n").unwrap();
+        let mut permissions = fs::metadata(&filter).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&filter, permissions).unwrap();
+
+        assert!(is_readonly(&filter));
+        assert_eq!(clear_readonly(&root).unwrap(), vec![shown(&long_path(&filter))]);
+        assert!(!is_readonly(&filter));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,346 @@
+//! Large files sent to the frontend in pieces instead of one IPC string. `open` returns the
+//! id of a stream and `start` sets off its reader thread, once the frontend listens for
+//! that id, so no chunk goes out before anyone knows its id. Chunks are line-aligned and in
+//! order; the last one has `done` set. `close` stops a stream early (editor tab closed
+//! before the file finished loading).
+//! `read_file_range` fetches a window of lines for editors that only render what is
+//! visible, decoded from the file's own encoding.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+
+use crate::encoding;
+use crate::error::WarlordError;
+use crate::path_utils::long_path;
+
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamChunk {
+    pub id: u64,
+    pub seq: u64,
+    pub data: String,
+    /// Bytes read so far and the file size, for a progress bar
+    pub bytes: u64,
+    pub total: u64,
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+/// The reading of an opened stream, until `start` runs it.
+type Reader = Box<dyn FnOnce() + Send>;
+
+struct Stream {
+    cancelled: Arc<AtomicBool>,
+    reader: Option<Reader>,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static OPEN: Mutex<Option<HashMap<u64, Stream>>> = Mutex::new(None);
+
+fn with_open<R>(f: impl FnOnce(&mut HashMap<u64, Stream>) -> R) -> R {
+    let mut guard = OPEN.lock().unwrap();
+    f(guard.get_or_insert_with(HashMap::new))
+}
+
+/// Read `reader` in pieces of about `size` bytes that end on a line break (or at least on a
+/// UTF-8 character boundary), calling `f(text, bytes_read, eof)`. The call with `eof` set
+/// comes last and carries whatever was left. Stops early when `f` returns false.
+pub fn read_chunks(mut reader: impl Read, size: usize, mut f: impl FnMut(String, u64, bool) -> bool) -> io::Result<()> {
+    let mut pending: Vec<u8> = Vec::new();
+    let mut buf = vec![0u8; size];
+    let mut read_total = 0u64;
+    loop {
+        let n = reader.read(&mut buf)?;
+        read_total += n as u64;
+        pending.extend_from_slice(&buf[..n]);
+        if n == 0 || pending.len() >= size {
+            let cut = if n == 0 {
+                pending.len()
+            } else {
+                match pending.iter().rposition(|b| *b == b'\n') {
+                    Some(i) => i + 1,
+                    None => std::str::from_utf8(&pending).map_or_else(|e| e.valid_up_to(), |s| s.len()),
+                }
+            };
+            let rest = pending.split_off(cut);
+            let text = String::from_utf8_lossy(&pending).into_owned();
+            pending = rest;
+            let consumed = read_total - pending.len() as u64;
+            if (!text.is_empty() || n == 0) && !f(text, consumed, n == 0) {
+                return Ok(());
+            }
+        }
+        if n == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Open `path` for streaming; after `start`, chunks go to `emit` from a background thread.
+/// Returns the stream id.
+pub fn open(path: &str, emit: impl Fn(StreamChunk) + Send + 'static) -> Result<u64, WarlordError> {
+    let file = File::open(long_path(path)).map_err(|e| WarlordError::io(e, path))?;
+    let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let flag = cancelled.clone();
+    let reader: Reader = Box::new(move || {
+        let cancelled = flag;
+        let mut seq = 0;
+        let mut last_bytes = 0;
+        let result = read_chunks(file, CHUNK_SIZE, |data, bytes, done| {
+            if cancelled.load(Ordering::Relaxed) {
+                return false;
+            }
+            emit(StreamChunk { id, seq, data, bytes, total, done, error: None });
+            seq += 1;
+            last_bytes = bytes;
+            true
+        });
+        if let Err(e) = result {
+            if !cancelled.load(Ordering::Relaxed) {
+                emit(StreamChunk { id, seq, data: String::new(), bytes: last_bytes, total, done: true, error: Some(e.to_string()) });
+            }
+        }
+        with_open(|open| open.remove(&id));
+    });
+    with_open(|open| open.insert(id, Stream { cancelled, reader: Some(reader) }));
+    Ok(id)
+}
+
+/// Begin sending the chunks of an opened stream.
+pub fn start(id: u64) -> Result<(), WarlordError> {
+    let reader = with_open(|open| open.get_mut(&id).and_then(|s| s.reader.take()));
+    let reader = reader.ok_or_else(|| WarlordError::invalid(format!("没有待开始的文件流 {}", id)))?;
+    std::thread::spawn(reader);
+    Ok(())
+}
+
+/// Stop a stream, started or not. Closing one that already finished is not an error.
+pub fn close(id: u64) {
+    if let Some(stream) = with_open(|open| open.remove(&id)) {
+        stream.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineRange {
+    /// Lines `start_line..=end_line` (1-based) with their line breaks
+    pub text: String,
+    pub start_line: usize,
+    /// Last line returned; below `start_line` when the file is shorter
+    pub end_line: usize,
+    pub total_lines: usize,
+}
+
+/// Line feed as stored: one byte, or a UTF-16 code unit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Newline {
+    Byte,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Newline {
+    fn of(encoding: &'static Encoding) -> Newline {
+        if encoding == UTF_16LE {
+            Newline::Utf16Le
+        } else if encoding == UTF_16BE {
+            Newline::Utf16Be
+        } else {
+            Newline::Byte
+        }
+    }
+}
+
+/// Append the line at byte `pos` of `reader`, with its line break, to `line`. Returns the
+/// bytes read, 0 at the end. In UTF-16 a 0x0A byte only ends the line when it is the whole
+/// code unit of a line feed.
+fn read_line(reader: &mut impl BufRead, newline: Newline, pos: u64, line: &mut Vec<u8>) -> io::Result<usize> {
+    let start = line.len();
+    while reader.read_until(b'\n', line)? > 0 && line.ends_with(b"\n") {
+        let at = pos + (line.len() - start) as u64 - 1;
+        match newline {
+            Newline::Byte => break,
+            Newline::Utf16Le if at.is_multiple_of(2) => match reader.fill_buf()?.first() {
+                Some(0) => {
+                    reader.consume(1);
+                    line.push(0);
+                    break;
+                }
+                None => break,
+                Some(_) => {}
+            },
+            Newline::Utf16Be if !at.is_multiple_of(2) && line.len() - start >= 2 && line[line.len() - 2] == 0 => break,
+            _ => {}
+        }
+    }
+    Ok(line.len() - start)
+}
+
+/// Lines between two entries of `LineIndex::starts`.
+const INDEX_STEP: usize = 1000;
+/// Files whose line index is kept.
+const INDEXED_FILES: usize = 16;
+
+/// Where every `INDEX_STEP`th line of a file starts, so a window of lines is read from the
+/// nearest one instead of from the top. Valid while the file keeps its size and time.
+#[derive(Debug)]
+struct LineIndex {
+    len: u64,
+    modified: Option<SystemTime>,
+    encoding: &'static Encoding,
+    /// Byte offsets of lines 1, 1 + INDEX_STEP, 1 + 2 * INDEX_STEP ...
+    starts: Vec<u64>,
+    total_lines: usize,
+}
+
+static INDEXES: Mutex<Option<HashMap<PathBuf, Arc<LineIndex>>>> = Mutex::new(None);
+
+/// Detect the encoding from the start of `reader` and count its lines in one pass.
+fn build_index(reader: &mut (impl Read + Seek)) -> io::Result<LineIndex> {
+    let mut sample = Vec::new();
+    reader.by_ref().take(64 * 1024).read_to_end(&mut sample)?;
+    // A sample cut inside a UTF-8 character is still UTF-8
+    let sample = match std::str::from_utf8(&sample) {
+        Err(e) if e.error_len().is_none() => &sample[..e.valid_up_to()],
+        _ => &sample[..],
+    };
+    let (encoding, bom) = encoding::detect(sample);
+    let newline = Newline::of(encoding);
+    let mut pos = reader.seek(SeekFrom::Start(bom as u64))?;
+    let mut reader = BufReader::new(reader);
+    let (mut starts, mut total_lines, mut line) = (Vec::new(), 0usize, Vec::new());
+    loop {
+        if total_lines.is_multiple_of(INDEX_STEP) {
+            starts.push(pos);
+        }
+        line.clear();
+        let n = read_line(&mut reader, newline, pos, &mut line)?;
+        if n == 0 {
+            break;
+        }
+        pos += n as u64;
+        total_lines += 1;
+    }
+    Ok(LineIndex { len: pos, modified: None, encoding, starts, total_lines })
+}
+
+/// Lines `start_line..=end_line` (1-based) of the file `index` was built from.
+fn read_indexed(reader: &mut (impl Read + Seek), index: &LineIndex, start_line: usize, end_line: usize) -> io::Result<LineRange> {
+    let start_line = start_line.max(1);
+    let end = end_line.min(index.total_lines);
+    let mut bytes = Vec::new();
+    if start_line <= end {
+        let step = (start_line - 1) / INDEX_STEP;
+        let mut pos = reader.seek(SeekFrom::Start(index.starts[step]))?;
+        let mut reader = BufReader::new(reader);
+        let newline = Newline::of(index.encoding);
+        let mut line = Vec::new();
+        for number in step * INDEX_STEP + 1..=end {
+            line.clear();
+            pos += read_line(&mut reader, newline, pos, &mut line)? as u64;
+            if number >= start_line {
+                bytes.extend_from_slice(&line);
+            }
+        }
+    }
+    let (text, _) = index.encoding.decode_without_bom_handling(&bytes);
+    Ok(LineRange { text: text.into_owned(), start_line, end_line: end, total_lines: index.total_lines })
+}
+
+/// Lines `start_line..=end_line` (1-based) of `path` in its own encoding, plus the line
+/// count. The first call reads the whole file to index it; later ones read only the window.
+pub fn read_file_range(path: &str, start_line: usize, end_line: usize) -> Result<LineRange, WarlordError> {
+    let long = long_path(path);
+    let mut file = File::open(&long).map_err(|e| WarlordError::io(e, path))?;
+    let meta = file.metadata().map_err(|e| WarlordError::io(e, path))?;
+    let modified = meta.modified().ok();
+    let cached = INDEXES.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert_with(HashMap::new).get(&long).cloned();
+    let index = match cached.filter(|i| i.len == meta.len() && i.modified == modified) {
+        Some(index) => index,
+        None => {
+            let mut index = build_index(&mut file).map_err(|e| WarlordError::io(e, path))?;
+            index.modified = modified;
+            let index = Arc::new(index);
+            let mut indexes = INDEXES.lock().unwrap_or_else(|e| e.into_inner());
+            let indexes = indexes.get_or_insert_with(HashMap::new);
+            if indexes.len() >= INDEXED_FILES {
+                indexes.clear();
+            }
+            indexes.insert(long, index.clone());
+            index
+        }
+    };
+    read_indexed(&mut file, &index, start_line, end_line).map_err(|e| WarlordError::io(e, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_end_on_line_breaks() {
+        let text = "Show\n    BaseType \"神圣石\"\nHide\n";
+        let mut chunks = Vec::new();
+        let mut eof = Vec::new();
+        read_chunks(io::Cursor::new(text), 8, |chunk, _, done| {
+            chunks.push(chunk);
+            eof.push(done);
+            true
+        })
+        .unwrap();
+        assert_eq!(chunks.concat(), text);
+        assert_eq!(chunks[0], "Show\n");
+        assert!(chunks.iter().all(|c| !c.contains('\u{fffd}')));
+        assert_eq!(eof.iter().filter(|d| **d).count(), 1);
+        assert_eq!(eof.last(), Some(&true));
+
+    }
+
+    #[test]
+    fn streams_start_when_asked() {
+        let file = std::env::temp_dir().join("wt-file-stream-test.filter");
+        std::fs::write(&file, "Show\nHide\n").unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let id = open(&file.display().to_string(), move |chunk| tx.send(chunk).unwrap()).unwrap();
+        assert!(rx.recv_timeout(std::time::Duration::from_millis(50)).is_err());
+        start(id).unwrap();
+        let chunk = rx.recv().unwrap();
+        assert_eq!((chunk.id, chunk.seq, chunk.data.as_str(), chunk.done), (id, 0, "Show\nHide\n", true));
+        assert!(start(id).is_err());
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn reads_line_ranges_in_the_file_encoding() {
+        let lines: Vec<String> = (1..=2500).map(|n| format!("# {} 神圣石\n", n)).collect();
+        let text = lines.concat();
+        let window = |bytes: Vec<u8>, start: usize, end: usize| {
+            let mut reader = io::Cursor::new(bytes);
+            let index = build_index(&mut reader).unwrap();
+            read_indexed(&mut reader, &index, start, end).unwrap()
+        };
+        let range = window(text.clone().into_bytes(), 1999, 2001);
+        assert_eq!((range.text, range.end_line, range.total_lines), (lines[1998..2001].concat(), 2001, 2500));
+        assert_eq!(window(text.clone().into_bytes(), 2499, 9000).text, lines[2498..].concat());
+
+        // UTF-16 with a BOM, and "\u{0a0a}" whose bytes are both line feeds
+        let mut utf16 = vec![0xFF, 0xFE];
+        let tricky = "Show\n\u{0a0a}Hide\n    SetFontSize 45\n";
+        utf16.extend(tricky.encode_utf16().flat_map(u16::to_le_bytes));
+        let range = window(utf16, 2, 3);
+        assert_eq!((range.text.as_str(), range.total_lines), ("\u{0a0a}Hide\n    SetFontSize 45\n", 3));
+        assert!(window(Vec::new(), 1, 5).text.is_empty());
+    }
+}
@@ -0,0 +1,338 @@
+use crate::error::WarlordError;
+use crate::filter_parser::{self, quote, Block, BlockLine, FilterDocument, Rule};
+
+/// Conventional keyword order (conditions, then actions) used when sorting rule lines.
+pub const KEYWORD_ORDER: &[&str] = &[
+    "Class",
+    "BaseType",
+    "Rarity",
+    "ItemLevel",
+    "DropLevel",
+    "AreaLevel",
+    "Quality",
+    "Sockets",
+    "LinkedSockets",
+    "SocketGroup",
+    "Width",
+    "Height",
+    "StackSize",
+    "GemLevel",
+    "MapTier",
+    "WaystoneTier",
+    "UnidentifiedItemTier",
+    "Identified",
+    "Corrupted",
+    "Mirrored",
+    "AnyEnchantment",
+    "HasEnchantment",
+    "HasExplicitMod",
+    "HasImplicitMod",
+    "HasInfluence",
+    "FracturedItem",
+    "SynthesisedItem",
+    "ElderMap",
+    "ShapedMap",
+    "BlightedMap",
+    "UberBlightedMap",
+    "Replica",
+    "TransfiguredGem",
+    "SetFontSize",
+    "SetTextColor",
+    "SetBorderColor",
+    "SetBackgroundColor",
+    "PlayAlertSound",
+    "PlayAlertSoundPositional",
+    "CustomAlertSound",
+    "CustomAlertSoundOptional",
+    "DisableDropSound",
+    "EnableDropSound",
+    "DisableDropSoundIfAlertSound",
+    "EnableDropSoundIfAlertSound",
+    "MinimapIcon",
+    "PlayEffect",
+    "Continue",
+];
+
+/// Keywords whose values are strings and may be quoted.
+pub const STRING_KEYWORDS: &[&str] = &[
+    "Class",
+    "BaseType",
+    "HasExplicitMod",
+    "HasImplicitMod",
+    "HasEnchantment",
+    "EnchantmentPassiveNode",
+    "ArchnemesisMod",
+    "CustomAlertSound",
+    "CustomAlertSoundOptional",
+];
+
+/// Conditions whose values are an unordered set of names; no operator means `=`.
+pub const NAME_SET_KEYWORDS: &[&str] =
+    &["Class", "BaseType", "HasExplicitMod", "HasImplicitMod", "HasEnchantment", "EnchantmentPassiveNode", "ArchnemesisMod"];
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConditionOrder {
+    /// Keep lines where they are
+    Keep,
+    /// Conditions before actions, otherwise stable
+    ConditionsFirst,
+    /// Sort by `KEYWORD_ORDER`
+    Canonical,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FormatOptions {
+    /// Spaces per indentation level (ignored with `use_tabs`)
+    pub indent: usize,
+    pub use_tabs: bool,
+    /// Blank lines between the end of a block and the next block's comments
+    pub blank_lines_between_blocks: usize,
+    pub condition_order: ConditionOrder,
+    /// Quote every value of string keywords (BaseType, Class, ...)
+    pub normalize_quotes: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent: 4,
+            use_tabs: false,
+            blank_lines_between_blocks: 1,
+            condition_order: ConditionOrder::Keep,
+            normalize_quotes: true,
+        }
+    }
+}
+
+fn keyword_rank(keyword: &str) -> usize {
+    KEYWORD_ORDER.iter().position(|k| *k == keyword).unwrap_or(KEYWORD_ORDER.len())
+}
+
+/// Quote bare values of string keywords; numeric counts (`HasExplicitMod >=2 ...`) stay as-is.
+pub fn normalize_rule_quotes(rule: &mut Rule) {
+    if !STRING_KEYWORDS.contains(&rule.keyword.as_str()) {
+        return;
+    }
+    for v in &mut rule.values {
+        if !v.starts_with('"') && v.parse::<f64>().is_err() {
+            *v = quote(v);
+        }
+    }
+}
+
+/// `rule` with the shorthand spelled out: quoted names in sorted order without duplicates,
+/// the implied `=` operator, no comment. Leading counts (`HasExplicitMod >= 2 ...`) stay first.
+pub fn canonical_rule(rule: &Rule) -> Rule {
+    let mut rule = rule.clone();
+    normalize_rule_quotes(&mut rule);
+    rule.comment = None;
+    if NAME_SET_KEYWORDS.contains(&rule.keyword.as_str()) {
+        rule.operator.get_or_insert_with(|| "=".to_string());
+        let counts = rule.values.iter().take_while(|v| !v.starts_with('"')).count();
+        let mut names = rule.values.split_off(counts);
+        names.sort();
+        names.dedup();
+        rule.values.extend(names);
+    }
+    rule
+}
+
+/// Group body lines into (comments..., rule) units so comments move with the rule below them.
+fn rule_units(lines: Vec<BlockLine>) -> Vec<Vec<BlockLine>> {
+    let mut units = Vec::new();
+    let mut current = Vec::new();
+    for line in lines {
+        let is_rule = line.rule.is_some();
+        current.push(line);
+        if is_rule {
+            units.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        units.push(current);
+    }
+    units
+}
+
+/// Reorder rule lines of a block; trailing comments stay last.
+pub fn order_rules(block: &mut Block, order: ConditionOrder) {
+    if order == ConditionOrder::Keep {
+        return;
+    }
+    let mut units = rule_units(std::mem::take(&mut block.lines));
+    units.sort_by_key(|unit| match unit.last().and_then(|l| l.rule.as_ref()) {
+        None => (2, 0),
+        Some(rule) => match order {
+            ConditionOrder::Canonical => (0, keyword_rank(&rule.keyword)),
+            _ => (rule.is_action() as usize, 0),
+        },
+    });
+    block.lines = units.into_iter().flatten().collect();
+}
+
+fn format_block(block: &mut Block, indent: &str, opts: &FormatOptions, first: bool) {
+    // Blank lines before the block's comment group are normalized; single blanks inside it are kept
+    let mut leading: Vec<String> = Vec::new();
+    for l in block.leading.iter().map(|l| l.trim_end()) {
+        let blank = l.trim().is_empty();
+        if blank && leading.last().is_none_or(|p: &String| p.is_empty()) {
+            continue;
+        }
+        leading.push(if blank { String::new() } else { l.trim_start().to_string() });
+    }
+    if !first {
+        for _ in 0..opts.blank_lines_between_blocks {
+            leading.insert(0, String::new());
+        }
+    }
+    block.leading = leading;
+
+    block.header = match block.header_comment() {
+        Some(c) => format!("{} {}", block.kind, c),
+        None => block.kind.clone(),
+    };
+
+    block.lines.retain(|l| !l.raw.trim().is_empty());
+    for line in &mut block.lines {
+        match line.rule.as_mut() {
+            Some(rule) => {
+                if opts.normalize_quotes {
+                    normalize_rule_quotes(rule);
+                }
+                line.raw = format!("{}{}", indent, rule.render());
+            }
+            None => line.raw = format!("{}{}", indent, line.raw.trim()),
+        }
+    }
+    order_rules(block, opts.condition_order);
+}
+
+/// Pretty-print a document in place. Comments are kept and move with the line below them.
+pub fn format_document(doc: &mut FilterDocument, opts: &FormatOptions) {
+    let indent = if opts.use_tabs { "\t".to_string() } else { " ".repeat(opts.indent) };
+    for (i, block) in doc.blocks.iter_mut().enumerate() {
+        format_block(block, &indent, opts, i == 0);
+    }
+    let mut trailer: Vec<String> = doc.trailer.iter().map(|l| l.trim().to_string()).collect();
+    while trailer.last().is_some_and(|l| l.is_empty()) {
+        trailer.pop();
+    }
+    if !trailer.is_empty() {
+        trailer.insert(0, String::new());
+    }
+    doc.trailer = trailer;
+    doc.final_newline = true;
+    doc.renumber();
+}
+
+/// Normalized form for comparing filters: comments (except block headers) and blank lines
+/// dropped, rules canonicalized and in `KEYWORD_ORDER`, 4-space indent, `\n` endings. Two
+/// filters that only differ cosmetically canonicalize to the same text.
+pub fn canonicalize_document(doc: &mut FilterDocument) {
+    for block in &mut doc.blocks {
+        block.leading.clear();
+        block.lines.retain(|l| l.rule.is_some());
+        for line in &mut block.lines {
+            if let Some(rule) = line.rule.as_ref() {
+                line.set_rule(canonical_rule(rule));
+            }
+        }
+    }
+    let opts = FormatOptions { blank_lines_between_blocks: 0, condition_order: ConditionOrder::Canonical, ..Default::default() };
+    format_document(doc, &opts);
+    doc.trailer.clear();
+    doc.line_ending = "\n".to_string();
+    doc.bom = false;
+}
+
+/// Canonical text of a filter file; the file itself is not changed.
+pub fn canonicalize_filter(path: &str) -> Result<String, WarlordError> {
+    let mut doc = filter_parser::parse_file(path)?;
+    canonicalize_document(&mut doc);
+    Ok(doc.to_text())
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MinifyOptions {
+    /// Keep the comment header above the first block (author, version, links)
+    pub keep_header: bool,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MinifyReport {
+    pub original_bytes: usize,
+    pub minified_bytes: usize,
+}
+
+/// Strip comments, blank lines and indentation. Quotes are kept since multi-word values need them.
+pub fn minify_document(doc: &mut FilterDocument, opts: &MinifyOptions) {
+    for (i, block) in doc.blocks.iter_mut().enumerate() {
+        let keep = opts.keep_header && i == 0;
+        block.leading = if keep {
+            block.leading.iter().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect()
+        } else {
+            Vec::new()
+        };
+        block.header = block.kind.clone();
+        block.lines.retain(|l| l.rule.is_some());
+        for line in &mut block.lines {
+            if let Some(rule) = line.rule.as_mut() {
+                rule.comment = None;
+                line.raw = rule.render();
+            }
+        }
+    }
+    doc.trailer.clear();
+    doc.final_newline = true;
+    doc.renumber();
+}
+
+pub fn minify_file(src: &str, dest: &str, opts: &MinifyOptions) -> Result<MinifyReport, WarlordError> {
+    let mut doc = filter_parser::parse_file(src)?;
+    let original_bytes = doc.to_text().len();
+    minify_document(&mut doc, opts);
+    filter_parser::write_file(dest, &doc)?;
+    Ok(MinifyReport { original_bytes, minified_bytes: doc.to_text().len() })
+}
+
+/// Format a filter file in place and return the new text.
+pub fn format_file(path: &str, opts: &FormatOptions) -> Result<String, WarlordError> {
+    let mut doc = filter_parser::parse_file(path)?;
+    format_document(&mut doc, opts);
+    filter_parser::write_file(path, &doc)?;
+    Ok(doc.to_text())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_messy_filter() {
+        let src = "# 精华\nShow   # $tier->t1\n  SetFontSize 45  \n\tBaseType 精华 \"Divine Orb\"\n  # keep me\n    Class Currency\n\n\n\n# next\nHide\nRarity Normal\n";
+        let mut doc = FilterDocument::parse(src);
+        let opts = FormatOptions { condition_order: ConditionOrder::ConditionsFirst, ..Default::default() };
+        format_document(&mut doc, &opts);
+        assert_eq!(
+            doc.to_text(),
+            "# 精华\nShow # $tier->t1\n    BaseType \"精华\" \"Divine Orb\"\n    # keep me\n    Class \"Currency\"\n    SetFontSize 45\n\n# next\nHide\n    Rarity Normal\n"
+        );
+    }
+
+    #[test]
+    fn cosmetic_differences_canonicalize_away() {
+        let a = "# Currency\nShow # $tier->t1\n  SetFontSize 45 # big\n  BaseType == Divine \"Chaos Orb\"\n  Class Currency\n";
+        let b = "Show # $tier->t1\r\n\tClass = \"Currency\"\r\n\tBaseType == \"Chaos Orb\" \"Divine\" \"Divine\"\r\n\r\n\tSetFontSize 45\r\n";
+        let canon = |src: &str| {
+            let mut doc = FilterDocument::parse(src);
+            canonicalize_document(&mut doc);
+            doc.to_text()
+        };
+        assert_eq!(canon(a), "Show # $tier->t1\n    Class = \"Currency\"\n    BaseType == \"Chaos Orb\" \"Divine\"\n    SetFontSize 45\n");
+        assert_eq!(canon(a), canon(b));
+    }
+}
@@ -0,0 +1,205 @@
+//! Link install mode: instead of copying a library filter into the game's documents folder,
+//! put a link there, so what is saved in the library is what the game loads next. Folders
+//! get a junction on Windows (no admin rights needed), files a symbolic link where Windows
+//! allows one (Developer Mode or admin) and a hard link otherwise. Links are recorded in
+//! `filter_links.json`. A save keeps a hard link: `app_paths::write_atomic` rewrites a file
+//! with more than one link in place, and a save through the game-side name goes to the
+//! library file (`hard_link_source`).
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::app_paths;
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+use crate::sandbox;
+
+const LINKS_FILE: &str = "filter_links.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkKind {
+    Junction,
+    Symlink,
+    Hardlink,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterLink {
+    /// Filter or folder in the library
+    pub source: String,
+    /// Link in the game's documents folder
+    pub link: String,
+    pub kind: LinkKind,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct LinkRegistry {
+    links: Vec<FilterLink>,
+}
+
+fn shown(path: &Path) -> String {
+    path_utils::short_path(std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()))
+}
+
+/// Junction: an empty folder with a mount point reparse tag naming `target`. std has no
+/// call for it, and unlike a symbolic link it needs no privileges.
+#[cfg(windows)]
+fn junction(target: &Path, link: &Path) -> io::Result<()> {
+    use std::ffi::{c_void, OsStr};
+    use std::os::windows::ffi::OsStrExt;
+    const GENERIC_WRITE: u32 = 0x40000000;
+    const OPEN_EXISTING: u32 = 3;
+    const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x00200000;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x02000000;
+    const FSCTL_SET_REPARSE_POINT: u32 = 0x000900A4;
+    const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA0000003;
+    extern "system" {
+        fn CreateFileW(name: *const u16, access: u32, share: u32, security: *mut c_void, disposition: u32, flags: u32, template: isize) -> isize;
+        fn DeviceIoControl(handle: isize, code: u32, input: *const c_void, input_size: u32, output: *mut c_void, output_size: u32, returned: *mut u32, overlapped: *mut c_void) -> i32;
+        fn CloseHandle(handle: isize) -> i32;
+    }
+    let target = shown(&std::path::absolute(target)?);
+    let substitute: Vec<u16> = OsStr::new(&format!("\\??\\{}", target)).encode_wide().collect();
+    let print: Vec<u16> = OsStr::new(&target).encode_wide().collect();
+    // REPARSE_DATA_BUFFER: tag, data length, reserved, then the two names' offsets and
+    // lengths in bytes and the names themselves, each NUL-terminated
+    let mut data: Vec<u8> = Vec::new();
+    for field in [0, substitute.len() * 2, (substitute.len() + 1) * 2, print.len() * 2] {
+        data.extend((field as u16).to_le_bytes());
+    }
+    for unit in substitute.iter().chain(&[0]).chain(&print).chain(&[0]) {
+        data.extend(unit.to_le_bytes());
+    }
+    let mut buffer: Vec<u8> = IO_REPARSE_TAG_MOUNT_POINT.to_le_bytes().to_vec();
+    buffer.extend((data.len() as u16).to_le_bytes());
+    buffer.extend(0u16.to_le_bytes());
+    buffer.extend(data);
+
+    fs::create_dir(long_path(link))?;
+    let name: Vec<u16> = long_path(link).as_os_str().encode_wide().chain(Some(0)).collect();
+    let handle = unsafe { CreateFileW(name.as_ptr(), GENERIC_WRITE, 0, std::ptr::null_mut(), OPEN_EXISTING, FILE_FLAG_OPEN_REPARSE_POINT | FILE_FLAG_BACKUP_SEMANTICS, 0) };
+    if handle == -1 {
+        let error = io::Error::last_os_error();
+        let _ = fs::remove_dir(long_path(link));
+        return Err(error);
+    }
+    let mut returned = 0u32;
+    let ok = unsafe { DeviceIoControl(handle, FSCTL_SET_REPARSE_POINT, buffer.as_ptr().cast(), buffer.len() as u32, std::ptr::null_mut(), 0, &mut returned, std::ptr::null_mut()) };
+    let error = io::Error::last_os_error();
+    unsafe { CloseHandle(handle) };
+    if ok == 0 {
+        let _ = fs::remove_dir(long_path(link));
+        return Err(error);
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn create(source: &Path, link: &Path) -> io::Result<LinkKind> {
+    // ERROR_PRIVILEGE_NOT_HELD: symbolic links need Developer Mode or admin
+    const PRIVILEGE_NOT_HELD: i32 = 1314;
+    if source.is_dir() {
+        return junction(source, link).map(|_| LinkKind::Junction);
+    }
+    match std::os::windows::fs::symlink_file(long_path(source), long_path(link)) {
+        Ok(()) => Ok(LinkKind::Symlink),
+        Err(e) if e.raw_os_error() == Some(PRIVILEGE_NOT_HELD) => fs::hard_link(long_path(source), long_path(link)).map(|_| LinkKind::Hardlink),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(windows))]
+fn create(source: &Path, link: &Path) -> io::Result<LinkKind> {
+    let absolute = std::path::absolute(source)?;
+    std::os::unix::fs::symlink(absolute, link).map(|_| LinkKind::Symlink)
+}
+
+/// Remove a symbolic link or junction (not what it points to).
+fn remove_link(link: &Path) -> io::Result<()> {
+    let long = long_path(link);
+    // Directory links (junctions) are removed as directories on Windows
+    fs::remove_file(&long).or_else(|_| fs::remove_dir(&long))
+}
+
+/// Every link made by `link_filter`.
+pub fn links() -> Vec<FilterLink> {
+    app_paths::load_json::<LinkRegistry>(LINKS_FILE).links
+}
+
+fn save_registry(registry: &LinkRegistry) -> Result<(), WarlordError> {
+    app_paths::save_json(LINKS_FILE, registry)
+}
+
+/// Link `source` (a filter or folder in the library) into `destination`: a folder puts the
+/// link inside it under the source's name, anything else is the link's own path. A link
+/// already there is replaced; a real file or folder there (an earlier copy install) is
+/// deleted through `sandbox::delete`, so it is backed up first.
+pub fn link_filter(source: &Path, destination: &Path) -> Result<FilterLink, WarlordError> {
+    if !long_path(source).exists() {
+        return Err(WarlordError::not_found(source));
+    }
+    let link = match source.file_name() {
+        Some(name) if long_path(destination).is_dir() => destination.join(name),
+        _ => destination.to_path_buf(),
+    };
+    if path_utils::compare_paths(&shown(source), &shown(&link)) {
+        return Err(WarlordError::invalid(format!("{} 已经在游戏文件夹中", shown(source))));
+    }
+    sandbox::check_write(&link)?;
+    let mut registry: LinkRegistry = app_paths::load_json(LINKS_FILE);
+    let registered = |l: &FilterLink| path_utils::compare_paths(&l.link, &shown(&link));
+    if let Ok(meta) = fs::symlink_metadata(long_path(&link)) {
+        if meta.file_type().is_symlink() || registry.links.iter().any(registered) {
+            remove_link(&link).map_err(|e| WarlordError::io(e, &link))?;
+        } else {
+            sandbox::delete(&link)?;
+        }
+    }
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(long_path(parent)).map_err(|e| WarlordError::io(e, parent))?;
+    }
+    let kind = create(source, &link).map_err(|e| WarlordError::io(e, &link))?;
+    let entry = FilterLink { source: shown(source), link: shown(&link), kind };
+    registry.links.retain(|l| !registered(l));
+    registry.links.push(entry.clone());
+    save_registry(&registry)?;
+    eprintln!("[WarlordTools] Linked {} -> {} ({:?})", entry.link, entry.source, kind);
+    Ok(entry)
+}
+
+/// The library file behind `path` when it is a hard link made by `link_filter`, so a save
+/// through the game-side name goes to the library file.
+pub fn hard_link_source(path: &Path) -> Option<PathBuf> {
+    let shown = shown(path);
+    links().into_iter().find(|l| l.kind == LinkKind::Hardlink && path_utils::compare_paths(&l.link, &shown)).map(|l| PathBuf::from(l.source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linked_filter_follows_library_saves() {
+        let dir = std::env::temp_dir().join("wt-filter-link-test");
+        let _ = fs::remove_dir_all(&dir);
+        let (library, game) = (dir.join("library"), dir.join("game"));
+        fs::create_dir_all(&library).unwrap();
+        fs::create_dir_all(&game).unwrap();
+        let source = library.join("leveling.filter");
+        fs::write(&source, "Show\n").unwrap();
+
+        let entry = link_filter(&source, &game).unwrap();
+        let link = game.join("leveling.filter");
+        assert_eq!(fs::read_to_string(&link).unwrap(), "Show\n");
+        // Saved in place, so the hard link still shares the library file
+        sandbox::write(&source, "Hide\n").unwrap();
+        assert_eq!(fs::read_to_string(&link).unwrap(), "Hide\n");
+        // Linking again replaces the link
+        assert_eq!(link_filter(&source, &game).unwrap().link, entry.link);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+//! Layering an add-on filter onto a base filter (e.g. a personal filter onto NeverSink).
+//! Add-on blocks outside any section go to the top so they take priority, blocks in a
+//! section the base also has go to the end of that section, and other sections are appended.
+//! A block whose conditions already exist in the base is reported instead of duplicated.
+
+use std::path::Path;
+
+use crate::error::WarlordError;
+use crate::block_edit::{self, Side};
+use crate::filter_parser::{self, section_marker, unquote, Block, FilterDocument};
+use crate::{filter_format, provenance};
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeConflict {
+    /// Block index in the add-on
+    pub addition_block: usize,
+    pub addition_line: usize,
+    /// Block index in the base filter
+    pub base_block: usize,
+    pub base_line: usize,
+    pub section: Option<String>,
+    /// "duplicate" (same conditions and actions) or "conflict" (same conditions, other actions)
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeReport {
+    pub dest: String,
+    pub inserted: usize,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+pub type Signature = Vec<(String, Option<String>, Vec<String>)>;
+
+/// Order-insensitive view of a block's conditions or actions.
+pub fn signature(block: &Block, actions: bool) -> Signature {
+    let mut sig: Signature = block
+        .rules()
+        .filter(|r| r.is_action() == actions)
+        .map(|r| {
+            let r = filter_format::canonical_rule(r);
+            let mut values: Vec<String> = r.values.iter().map(|v| unquote(v).to_string()).collect();
+            values.sort();
+            (r.keyword.clone(), r.operator.clone(), values)
+        })
+        .collect();
+    sig.sort();
+    sig
+}
+
+pub fn merge_documents(base: &mut FilterDocument, addition: &FilterDocument, source: &str) -> (usize, Vec<MergeConflict>) {
+    let originals: Vec<(Signature, String, Signature, usize)> = base
+        .blocks
+        .iter()
+        .map(|b| (signature(b, false), b.kind.clone(), signature(b, true), b.line))
+        .collect();
+    let mut conflicts = Vec::new();
+    let mut inserted = 0;
+    let mut top = 0;
+
+    for block in &addition.blocks {
+        let conditions = signature(block, false);
+        if let Some((i, (_, kind, actions, line))) = originals.iter().enumerate().find(|(_, o)| o.0 == conditions) {
+            let same = *kind == block.kind && *actions == signature(block, true);
+            conflicts.push(MergeConflict {
+                addition_block: block.id,
+                addition_line: block.line,
+                base_block: i,
+                base_line: *line,
+                section: block.section.clone(),
+                reason: if same { "duplicate" } else { "conflict" }.to_string(),
+            });
+            continue;
+        }
+
+        let mut new = block.clone();
+        let (_, attached) = block_edit::split_leading(&new.leading);
+        let last_in_section = block
+            .section
+            .as_ref()
+            .and_then(|s| base.blocks.iter().rposition(|b| b.section.as_ref() == Some(s)));
+        match (&block.section, last_in_section) {
+            (None, _) => {
+                new.leading = attached;
+                provenance::stamp(&mut new, source);
+                let first_marker = base.blocks.first().and_then(|b| b.leading.iter().position(|l| section_marker(l).is_some()));
+                match first_marker.filter(|_| top == 0) {
+                    // Stay above the first section banner, below the file's own header comments
+                    Some(cut) => {
+                        let rest = base.blocks[0].leading.split_off(cut);
+                        let intro = std::mem::replace(&mut base.blocks[0].leading, rest);
+                        base.blocks[0].leading.insert(0, String::new());
+                        new.leading = intro.into_iter().chain(new.leading).collect();
+                        base.blocks.insert(0, new);
+                    }
+                    None => block_edit::attach(base, top, new, Side::AfterPrev),
+                }
+                top += 1;
+            }
+            (Some(_), Some(last)) => {
+                new.leading = attached;
+                provenance::stamp(&mut new, source);
+                block_edit::attach(base, last + 1, new, Side::AfterPrev);
+            }
+            (Some(_), None) => {
+                // New section: the block keeps its banner
+                provenance::stamp(&mut new, source);
+                if new.leading.first().is_none_or(|l| !l.trim().is_empty()) {
+                    new.leading.insert(0, String::new());
+                }
+                base.blocks.push(new);
+            }
+        }
+        base.renumber();
+        inserted += 1;
+    }
+    (inserted, conflicts)
+}
+
+pub fn merge_filters(base: &str, addition: &str, dest: &str) -> Result<MergeReport, WarlordError> {
+    let mut doc = filter_parser::parse_file(base)?;
+    let add = filter_parser::parse_file(addition)?;
+    let name = Path::new(addition).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let (inserted, conflicts) = merge_documents(&mut doc, &add, &format!("merge:{}", name));
+    filter_parser::write_file(dest, &doc)?;
+    eprintln!("[WarlordTools] merged {} into {}: {} blocks, {} conflicts", addition, dest, inserted, conflicts.len());
+    Ok(MergeReport { dest: dest.to_string(), inserted, conflicts })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_by_section_and_reports_conflicts() {
+        let base = "# [[0100]] Currency\nShow\n    BaseType \"Divine Orb\"\n    SetFontSize 45\n";
+        let addition = "# mine\nShow\n    BaseType \"Mirror of Kalandra\"\n\n# [[0100]] Currency\nShow\n    BaseType \"Chaos Orb\"\nShow\n    BaseType \"Divine Orb\"\n    SetFontSize 40\n";
+        let mut doc = FilterDocument::parse(base);
+        let (inserted, conflicts) = merge_documents(&mut doc, &FilterDocument::parse(addition), "merge:test");
+        assert_eq!(inserted, 2);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].reason, "conflict");
+        let bases: Vec<_> = doc.blocks.iter().map(|b| b.rule("BaseType").unwrap().values[0].clone()).collect();
+        assert_eq!(bases, vec!["\"Mirror of Kalandra\"", "\"Divine Orb\"", "\"Chaos Orb\""]);
+        assert_eq!(doc.blocks[0].section, None);
+        assert_eq!(doc.blocks[2].section.as_deref(), Some("[[0100]] Currency"));
+    }
+}
@@ -0,0 +1,532 @@
+//! Lossless item filter parser.
+//!
+//! Every source line is kept verbatim (`raw`) next to its parsed form, so writing a document
+//! back reproduces the original file exactly; edits only re-render the lines they touch.
+//!
+//! JSON schema (as produced by `to_json`, camelCase):
+//! ```text
+//! {
+//!   "schemaVersion": 1,
+//!   "lineEnding": "\n" | "\r\n",
+//!   "finalNewline": bool,              // file ended with a line break
+//!   "bom": bool,                       // file started with a UTF-8 BOM
+//!   "blocks": [{
+//!     "id": number,                    // position of the block in the file
+//!     "line": number,                  // 0-based line of the Show/Hide/Minimal keyword
+//!     "section": string | null,        // latest "[[NNNN]] Title" section marker above the block
+//!     "leading": [string],             // raw comment/blank lines between the previous block and this one
+//!     "header": string,                // raw keyword line, e.g. "Show # $tier->t1"
+//!     "kind": "Show" | "Hide" | "Minimal",
+//!     "lines": [{
+//!       "raw": string,                 // raw source line
+//!       "rule": null | {               // null for comments and blank lines inside the block
+//!         "keyword": string,           // e.g. "BaseType", "SetTextColor"
+//!         "operator": string | null,   // "==", ">=", ...
+//!         "values": [string],          // tokens, quotes preserved ("\"Divine Orb\"")
+//!         "comment": string | null     // trailing "# ..." on the same line
+//!       }
+//!     }]
+//!   }],
+//!   "trailer": [string]                // raw lines after the last block
+//! }
+//! ```
+//! On import, a line whose `rule` no longer matches its `raw` text is re-rendered from `rule`;
+//! everything else is written back untouched. `id`, `line` and `section` are recomputed.
+
+use crate::error::WarlordError;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+pub const BLOCK_KEYWORDS: &[&str] = &["Show", "Hide", "Minimal"];
+
+pub const OPERATORS: &[&str] = &["==", "!=", "<=", ">=", "<", ">", "=", "!"];
+
+/// Keywords that change how an item looks/sounds; every other keyword is a condition.
+pub const ACTION_KEYWORDS: &[&str] = &[
+    "SetTextColor",
+    "SetBorderColor",
+    "SetBackgroundColor",
+    "SetFontSize",
+    "PlayAlertSound",
+    "PlayAlertSoundPositional",
+    "CustomAlertSound",
+    "CustomAlertSoundOptional",
+    "DisableDropSound",
+    "EnableDropSound",
+    "DisableDropSoundIfAlertSound",
+    "EnableDropSoundIfAlertSound",
+    "MinimapIcon",
+    "PlayEffect",
+    "Continue",
+];
+
+const DEFAULT_INDENT: &str = "    ";
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    pub keyword: String,
+    #[serde(default)]
+    pub operator: Option<String>,
+    #[serde(default)]
+    pub values: Vec<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockLine {
+    pub raw: String,
+    #[serde(default)]
+    pub rule: Option<Rule>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Block {
+    #[serde(default)]
+    pub id: usize,
+    #[serde(default)]
+    pub line: usize,
+    #[serde(default)]
+    pub section: Option<String>,
+    #[serde(default)]
+    pub leading: Vec<String>,
+    pub header: String,
+    pub kind: String,
+    #[serde(default)]
+    pub lines: Vec<BlockLine>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterDocument {
+    #[serde(default = "schema_version")]
+    pub schema_version: u32,
+    #[serde(default = "default_line_ending")]
+    pub line_ending: String,
+    #[serde(default = "default_true")]
+    pub final_newline: bool,
+    #[serde(default)]
+    pub bom: bool,
+    pub blocks: Vec<Block>,
+    #[serde(default)]
+    pub trailer: Vec<String>,
+}
+
+fn schema_version() -> u32 {
+    SCHEMA_VERSION
+}
+
+fn default_line_ending() -> String {
+    "\n".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Split a line into whitespace separated tokens (quotes kept) and a trailing `# comment`.
+pub fn tokenize(line: &str) -> (Vec<String>, Option<String>) {
+    let mut tokens = Vec::new();
+    let mut buf = String::new();
+    let mut in_quote = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => {
+                in_quote = !in_quote;
+                buf.push(c);
+            }
+            '#' if !in_quote => {
+                if !buf.is_empty() {
+                    tokens.push(std::mem::take(&mut buf));
+                }
+                return (tokens, Some(line[i..].trim_end().to_string()));
+            }
+            c if c.is_whitespace() && !in_quote => {
+                if !buf.is_empty() {
+                    tokens.push(std::mem::take(&mut buf));
+                }
+            }
+            _ => buf.push(c),
+        }
+    }
+    if !buf.is_empty() {
+        tokens.push(buf);
+    }
+    (tokens, None)
+}
+
+/// Strip surrounding quotes from a value token.
+pub fn unquote(token: &str) -> &str {
+    token
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(token)
+}
+
+/// Wrap a value in quotes.
+pub fn quote(value: &str) -> String {
+    format!("\"{}\"", unquote(value))
+}
+
+fn is_indented(line: &str) -> bool {
+    line.starts_with(' ') || line.starts_with('\t')
+}
+
+fn indent_of(line: &str) -> &str {
+    let len = line.len() - line.trim_start().len();
+    &line[..len]
+}
+
+/// Keyword of a `Show`/`Hide`/`Minimal` line, if it is one.
+pub fn block_keyword(line: &str) -> Option<&'static str> {
+    let first = line.trim().split(|c: char| c.is_whitespace() || c == '#').next()?;
+    BLOCK_KEYWORDS.iter().copied().find(|k| *k == first)
+}
+
+/// Title of a NeverSink-style top-level section marker ("# [[0100]] Global overriding rules").
+pub fn section_marker(line: &str) -> Option<String> {
+    let text = line.trim().strip_prefix('#')?.trim();
+    let rest = text.strip_prefix("[[")?;
+    rest.contains("]]").then(|| text.to_string())
+}
+
+impl Rule {
+    /// Parse a body line; None for blank lines and comments.
+    pub fn parse(line: &str) -> Option<Rule> {
+        let (tokens, comment) = tokenize(line);
+        let mut iter = tokens.into_iter();
+        let keyword = iter.next()?;
+        let mut rest: Vec<String> = iter.collect();
+        let operator = match rest.first() {
+            Some(t) if OPERATORS.contains(&t.as_str()) => Some(rest.remove(0)),
+            // Operator glued to its value: "ItemLevel >=75"
+            Some(t) => match OPERATORS.iter().find(|op| t.starts_with(*op) && t.len() > op.len()) {
+                Some(op) => {
+                    rest[0] = t[op.len()..].to_string();
+                    Some(op.to_string())
+                }
+                None => None,
+            },
+            None => None,
+        };
+        Some(Rule { keyword, operator, values: rest, comment })
+    }
+
+    pub fn new(keyword: &str, values: Vec<String>) -> Rule {
+        Rule { keyword: keyword.to_string(), operator: None, values, comment: None }
+    }
+
+    pub fn is_action(&self) -> bool {
+        ACTION_KEYWORDS.contains(&self.keyword.as_str())
+    }
+
+    /// Values with quotes stripped.
+    pub fn unquoted_values(&self) -> Vec<&str> {
+        self.values.iter().map(|v| unquote(v)).collect()
+    }
+
+    /// Render without indentation.
+    pub fn render(&self) -> String {
+        let mut out = self.keyword.clone();
+        if let Some(op) = &self.operator {
+            out.push(' ');
+            out.push_str(op);
+        }
+        for v in &self.values {
+            out.push(' ');
+            out.push_str(v);
+        }
+        if let Some(c) = &self.comment {
+            out.push(' ');
+            out.push_str(c);
+        }
+        out
+    }
+}
+
+impl BlockLine {
+    pub fn parse(raw: &str) -> BlockLine {
+        let trimmed = raw.trim();
+        let rule = if trimmed.is_empty() || trimmed.starts_with('#') { None } else { Rule::parse(trimmed) };
+        BlockLine { raw: raw.to_string(), rule }
+    }
+
+    /// New rule line using the default indentation.
+    pub fn from_rule(rule: Rule) -> BlockLine {
+        BlockLine { raw: format!("{}{}", DEFAULT_INDENT, rule.render()), rule: Some(rule) }
+    }
+
+    /// Replace the rule and re-render this line only, keeping its indentation.
+    pub fn set_rule(&mut self, rule: Rule) {
+        let indent = if self.raw.trim().is_empty() { DEFAULT_INDENT } else { indent_of(&self.raw) };
+        self.raw = format!("{}{}", indent, rule.render());
+        self.rule = Some(rule);
+    }
+
+    /// Re-render `raw` when `rule` was changed without going through `set_rule`.
+    pub fn sync_raw(&mut self) {
+        if let Some(rule) = self.rule.clone() {
+            if BlockLine::parse(&self.raw).rule.as_ref() != Some(&rule) {
+                self.set_rule(rule);
+            }
+        }
+    }
+}
+
+impl Block {
+    pub fn new(kind: &str) -> Block {
+        Block {
+            id: 0,
+            line: 0,
+            section: None,
+            leading: Vec::new(),
+            header: kind.to_string(),
+            kind: kind.to_string(),
+            lines: Vec::new(),
+        }
+    }
+
+    /// Comment after the keyword on the header line (NeverSink puts `$type->... $tier->...` tags here).
+    pub fn header_comment(&self) -> Option<String> {
+        tokenize(&self.header).1
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &Rule> {
+        self.lines.iter().filter_map(|l| l.rule.as_ref())
+    }
+
+    pub fn rule(&self, keyword: &str) -> Option<&Rule> {
+        self.rules().find(|r| r.keyword == keyword)
+    }
+
+    pub fn conditions(&self) -> impl Iterator<Item = &Rule> {
+        self.rules().filter(|r| !r.is_action())
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &Rule> {
+        self.rules().filter(|r| r.is_action())
+    }
+
+    /// Set (replace in place, or append) the first rule with `rule.keyword`.
+    pub fn set_rule(&mut self, rule: Rule) {
+        match self.lines.iter_mut().find(|l| l.rule.as_ref().is_some_and(|r| r.keyword == rule.keyword)) {
+            Some(line) => line.set_rule(rule),
+            None => self.push_rule(rule),
+        }
+    }
+
+    /// Append a rule after the last rule line (before trailing indented comments).
+    pub fn push_rule(&mut self, rule: Rule) {
+        let pos = self.lines.iter().rposition(|l| l.rule.is_some()).map(|i| i + 1).unwrap_or(self.lines.len());
+        let indent = self.lines.iter().find(|l| l.rule.is_some()).map(|l| indent_of(&l.raw).to_string());
+        let mut line = BlockLine::from_rule(rule);
+        if let Some(indent) = indent {
+            let rule = line.rule.clone().unwrap();
+            line.raw = format!("{}{}", indent, rule.render());
+        }
+        self.lines.insert(pos, line);
+    }
+
+    /// Remove every rule with `keyword`. Returns how many lines were removed.
+    pub fn remove_rule(&mut self, keyword: &str) -> usize {
+        let before = self.lines.len();
+        self.lines.retain(|l| l.rule.as_ref().is_none_or(|r| r.keyword != keyword));
+        before - self.lines.len()
+    }
+
+    /// Keep `header` in sync with `kind` (keeps the header's trailing comment).
+    pub fn sync_header(&mut self) {
+        if block_keyword(&self.header) != Some(self.kind.as_str()) {
+            self.header = match self.header_comment() {
+                Some(c) => format!("{} {}", self.kind, c),
+                None => self.kind.clone(),
+            };
+        }
+    }
+
+    /// Source lines of the block (leading comments, header, body).
+    pub fn raw_lines(&self) -> impl Iterator<Item = &str> {
+        self.leading
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(self.header.as_str()))
+            .chain(self.lines.iter().map(|l| l.raw.as_str()))
+    }
+}
+
+impl FilterDocument {
+    pub fn parse(content: &str) -> FilterDocument {
+        let bom = content.starts_with('\u{feff}');
+        let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+        let line_ending = if content.contains("\r\n") { "\r\n" } else { "\n" };
+        let final_newline = content.ends_with('\n');
+
+        let body = content.strip_suffix('\n').unwrap_or(content);
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut pending: Vec<String> = Vec::new();
+        let mut current: Option<Block> = None;
+
+        if !content.is_empty() {
+            for raw in body.split('\n') {
+                let raw = raw.strip_suffix('\r').unwrap_or(raw);
+                let trimmed = raw.trim();
+                if let Some(kind) = block_keyword(raw) {
+                    if let Some(block) = current.take() {
+                        blocks.push(block);
+                    }
+                    let mut block = Block::new(kind);
+                    block.header = raw.to_string();
+                    block.leading = std::mem::take(&mut pending);
+                    current = Some(block);
+                } else if let Some(block) = current.as_mut() {
+                    if trimmed.is_empty() || (trimmed.starts_with('#') && !is_indented(raw)) {
+                        // Might be the next block's header comments; decided when the next line arrives
+                        pending.push(raw.to_string());
+                    } else {
+                        block.lines.extend(pending.drain(..).map(|l| BlockLine::parse(&l)));
+                        block.lines.push(BlockLine::parse(raw));
+                    }
+                } else {
+                    pending.push(raw.to_string());
+                }
+            }
+        }
+        if let Some(block) = current.take() {
+            blocks.push(block);
+        }
+
+        let mut doc = FilterDocument {
+            schema_version: SCHEMA_VERSION,
+            line_ending: line_ending.to_string(),
+            final_newline,
+            bom,
+            blocks,
+            trailer: pending,
+        };
+        doc.renumber();
+        doc
+    }
+
+    /// Recompute `id`, `line` and `section` after blocks were added, removed or moved.
+    pub fn renumber(&mut self) {
+        let mut line = 0;
+        let mut section: Option<String> = None;
+        for (id, block) in self.blocks.iter_mut().enumerate() {
+            for l in &block.leading {
+                if let Some(title) = section_marker(l) {
+                    section = Some(title);
+                }
+            }
+            block.id = id;
+            line += block.leading.len();
+            block.line = line;
+            block.section = section.clone();
+            line += 1 + block.lines.len();
+        }
+    }
+
+    /// All lines of the document without line terminators.
+    pub fn lines(&self) -> Vec<&str> {
+        let mut out: Vec<&str> = Vec::new();
+        for block in &self.blocks {
+            out.extend(block.raw_lines());
+        }
+        out.extend(self.trailer.iter().map(String::as_str));
+        out
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        if self.bom {
+            out.push('\u{feff}');
+        }
+        out.push_str(&self.lines().join(&self.line_ending));
+        if self.final_newline && !(self.blocks.is_empty() && self.trailer.is_empty()) {
+            out.push_str(&self.line_ending);
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Result<String, WarlordError> {
+        serde_json::to_string_pretty(self).map_err(|e| WarlordError::from(e.to_string()))
+    }
+
+    /// Read a document from JSON, re-rendering lines whose structured form was edited.
+    pub fn from_json(json: &str) -> Result<FilterDocument, WarlordError> {
+        let mut doc: FilterDocument = serde_json::from_str(json).map_err(|e| WarlordError::invalid(e.to_string()))?;
+        if doc.schema_version > SCHEMA_VERSION {
+            return Err(WarlordError::invalid(format!("Unsupported filter schema version {}", doc.schema_version)));
+        }
+        if doc.line_ending != "\r\n" {
+            doc.line_ending = "\n".to_string();
+        }
+        for block in &mut doc.blocks {
+            if !BLOCK_KEYWORDS.contains(&block.kind.as_str()) {
+                return Err(WarlordError::invalid(format!("Invalid block kind: {}", block.kind)));
+            }
+            block.sync_header();
+            for line in &mut block.lines {
+                line.sync_raw();
+            }
+        }
+        doc.renumber();
+        Ok(doc)
+    }
+}
+
+/// Parse a filter file from disk.
+pub fn parse_file(path: &str) -> Result<FilterDocument, crate::error::WarlordError> {
+    let decoded = crate::encoding::read_file(path)?;
+    let mut doc = FilterDocument::parse(&decoded.text);
+    doc.bom = decoded.bom && decoded.encoding == "UTF-8";
+    Ok(doc)
+}
+
+/// Write a document to disk.
+pub fn write_file(path: &str, doc: &FilterDocument) -> Result<(), crate::error::WarlordError> {
+    crate::encoding::write_preserving(path, &doc.to_text())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "#===\n# [[0100]] Currency\n#===\n\n# 液化情感\nShow # $tier->t1\n    BaseType == \"Divine Orb\" \"Exalted Orb\" # top\n    # inline note\n\n    SetTextColor 255 0 0 255\n\nHide\n    Class \"Flasks\"\n\n# end\n";
+
+    #[test]
+    fn round_trips_exactly() {
+        let doc = FilterDocument::parse(SAMPLE);
+        assert_eq!(doc.to_text(), SAMPLE);
+        assert_eq!(doc.blocks.len(), 2);
+        assert_eq!(doc.blocks[0].line, 5);
+        assert_eq!(doc.blocks[0].section.as_deref(), Some("[[0100]] Currency"));
+        assert_eq!(doc.trailer, vec!["".to_string(), "# end".to_string()]);
+
+        let crlf = SAMPLE.replace('\n', "\r\n");
+        assert_eq!(FilterDocument::parse(&crlf).to_text(), crlf);
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rule = Rule::parse("BaseType == \"Divine Orb\" \"Exalted Orb\" # top").unwrap();
+        assert_eq!(rule.operator.as_deref(), Some("=="));
+        assert_eq!(rule.unquoted_values(), vec!["Divine Orb", "Exalted Orb"]);
+        assert_eq!(rule.comment.as_deref(), Some("# top"));
+
+        let glued = Rule::parse("ItemLevel >=75").unwrap();
+        assert_eq!((glued.operator.as_deref(), glued.values[0].as_str()), (Some(">="), "75"));
+    }
+
+    #[test]
+    fn json_import_rerenders_edited_lines_only() {
+        let doc = FilterDocument::parse(SAMPLE);
+        let mut value: serde_json::Value = serde_json::from_str(&doc.to_json().unwrap()).unwrap();
+        value["blocks"][1]["kind"] = serde_json::json!("Show");
+        value["blocks"][1]["lines"][0]["rule"]["values"] = serde_json::json!(["\"Jewels\""]);
+        let edited = FilterDocument::from_json(&value.to_string()).unwrap();
+        assert_eq!(edited.to_text(), SAMPLE.replace("Hide\n    Class \"Flasks\"", "Show\n    Class \"Jewels\""));
+    }
+}
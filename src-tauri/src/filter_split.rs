@@ -0,0 +1,137 @@
+//! Split a filter into one file per top-level `[[NNNN]]` section and join it back.
+//! Pieces are cut from the raw text at line starts, so joining them reproduces the original
+//! byte for byte: the pieces keep the filter's encoding (GBK, UTF-16 ...), the first one its
+//! byte order mark. File names carry a sequence prefix that fixes the join order.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::WarlordError;
+use crate::filter_parser::section_marker;
+use crate::{encoding, sandbox};
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitReport {
+    pub dir: String,
+    /// Written files, in join order
+    pub files: Vec<String>,
+}
+
+fn slug(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').chars().take(40).collect()
+}
+
+/// Cut `content` into (name, text) pieces. The banner comment lines directly above a section
+/// marker go with the section they announce.
+pub fn split_text(content: &str) -> Vec<(String, String)> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut cuts = vec![(0usize, "header".to_string())];
+    for (i, line) in lines.iter().enumerate() {
+        let Some(title) = section_marker(line) else { continue };
+        let mut start = i;
+        while start > cuts.last().unwrap().0 && lines[start - 1].trim_start().starts_with('#') {
+            start -= 1;
+        }
+        let title = title.trim_start_matches('#').trim().trim_start_matches("[[");
+        cuts.push((start, slug(&title.replacen("]]", " ", 1))));
+    }
+    cuts.push((lines.len(), String::new()));
+
+    let width = (cuts.len() - 1).to_string().len().max(2);
+    cuts.windows(2)
+        .enumerate()
+        .map(|(n, w)| (format!("{:0width$}-{}.filter", n, w[0].1, width = width), lines[w[0].0..w[1].0].concat()))
+        .filter(|(_, text)| !text.is_empty())
+        .collect()
+}
+
+pub fn split_filter(path: &str, dest_dir: &str) -> Result<SplitReport, WarlordError> {
+    let decoded = encoding::read_file(path)?;
+    let dir = Path::new(dest_dir);
+    sandbox::check_write(dir)?;
+    fs::create_dir_all(dir).map_err(|e| WarlordError::io(e, dir))?;
+    // Leftover pieces from an earlier split would end up in the join
+    let existing = fs::read_dir(dir).map_err(|e| WarlordError::io(e, dir))?.flatten().any(|e| e.path().extension().is_some_and(|x| x == "filter"));
+    if existing {
+        return Err(WarlordError::invalid("目标文件夹中已有 .filter 文件, 请选择空文件夹"));
+    }
+    let mut files = Vec::new();
+    for (n, (name, text)) in split_text(&decoded.text).into_iter().enumerate() {
+        let file = dir.join(&name);
+        sandbox::write(&file, encoding::encode(&text, &decoded.encoding, decoded.bom && n == 0)?)?;
+        files.push(file.display().to_string());
+    }
+    Ok(SplitReport { dir: dest_dir.to_string(), files })
+}
+
+/// Concatenate the `.filter` files of `dir` in name order into `dest`, in the encoding of
+/// `dest` when it exists, else of the first piece.
+pub fn join_filter(dir: &str, dest: &str) -> Result<SplitReport, WarlordError> {
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| WarlordError::io(e, dir))?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|x| x == "filter"))
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(WarlordError::invalid("文件夹中没有 .filter 文件"));
+    }
+    let mut joined = String::new();
+    let mut first = None;
+    for file in &files {
+        let decoded = encoding::read_file(file)?;
+        let text = decoded.text.clone();
+        first.get_or_insert(decoded);
+        // Pieces edited by hand may have lost their final newline
+        if !joined.is_empty() && !joined.ends_with('\n') {
+            joined.push_str(if joined.contains("\r\n") { "\r\n" } else { "\n" });
+        }
+        joined.push_str(&text);
+    }
+    match first.filter(|_| !Path::new(dest).exists()) {
+        Some(first) => sandbox::write(dest, encoding::encode(&joined, &first.encoding, first.bom)?)?,
+        None => encoding::write_preserving(dest, &joined)?,
+    }
+    Ok(SplitReport { dir: dir.to_string(), files: files.iter().map(|f| f.display().to_string()).collect() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_round_trips() {
+        let src = "#version 1\n\n#=====\n# [[0100]] Global Rules\n#=====\nShow\n    Class \"Currency\"\n\n# [[0200]] 通货\nHide\n";
+        let pieces = split_text(src);
+        let names: Vec<&str> = pieces.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["00-header.filter", "01-0100-global-rules.filter", "02-0200-通货.filter"]);
+        assert!(pieces[1].1.starts_with("#=====\n# [[0100]]"));
+        assert_eq!(pieces.iter().map(|(_, t)| t.as_str()).collect::<String>(), src);
+    }
+
+    #[test]
+    fn split_and_join_keep_utf16() {
+        let dir = std::env::temp_dir().join("wt-split-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("pieces")).unwrap();
+        let utf16 = |text: &str| -> Vec<u8> { [0xFF, 0xFE].into_iter().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect() };
+        let original = utf16("#version 1\r\n# [[0100]] 通货\r\nShow\r\n");
+        fs::write(dir.join("notepad.filter"), &original).unwrap();
+        let pieces = dir.join("pieces").display().to_string();
+
+        split_filter(&dir.join("notepad.filter").display().to_string(), &pieces).unwrap();
+        join_filter(&pieces, &dir.join("joined.filter").display().to_string()).unwrap();
+        assert_eq!(fs::read(dir.join("joined.filter")).unwrap(), original);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
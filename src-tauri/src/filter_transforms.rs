@@ -0,0 +1,381 @@
+//! Bulk rewrites of rule values. Everything goes through the parser, so comments and
+//! commented-out blocks are never touched and only the edited lines are re-rendered.
+
+use std::path::Path;
+
+use crate::error::WarlordError;
+use crate::filter_parser::{self, quote, unquote, Block, FilterDocument, Rule};
+use crate::file_ops::FailedFile;
+use crate::{filter_format, library};
+
+/// Edits made to one file.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChange {
+    pub path: String,
+    pub replacements: usize,
+    /// 0-based lines that were rewritten
+    pub lines: Vec<usize>,
+}
+
+/// Keep the original quoting unless the new value needs quotes.
+fn requote(original: &str, value: &str) -> String {
+    if original.starts_with('"') || value.contains(char::is_whitespace) { quote(value) } else { value.to_string() }
+}
+
+/// Rename exact BaseType values `from` -> `to`. Returns the rewritten line numbers.
+pub fn rename_basetype_in(doc: &mut FilterDocument, from: &str, to: &str) -> Vec<usize> {
+    rename_basetypes_in(doc, &[(from, to)])
+}
+
+/// Rename exact BaseType values by `(from, to)` pairs in one pass. Every value is looked up
+/// by the name it had before, so "A" -> "B" next to "B" -> "C" turns A into B, not C.
+pub fn rename_basetypes_in(doc: &mut FilterDocument, renames: &[(&str, &str)]) -> Vec<usize> {
+    let renamed = |value: &str| renames.iter().find(|(from, _)| unquote(value) == *from).map(|(_, to)| *to);
+    let mut lines = Vec::new();
+    for block in &mut doc.blocks {
+        let body_start = block.line + 1;
+        for (i, line) in block.lines.iter_mut().enumerate() {
+            let Some(rule) = line.rule.as_ref() else { continue };
+            if rule.keyword != "BaseType" || !rule.values.iter().any(|v| renamed(v).is_some()) {
+                continue;
+            }
+            let mut rule = rule.clone();
+            for v in &mut rule.values {
+                if let Some(to) = renamed(v) {
+                    *v = requote(v, to);
+                }
+            }
+            line.set_rule(rule);
+            lines.push(body_start + i);
+        }
+    }
+    lines
+}
+
+/// Edits made across a workspace, and the filters that could not be read or written.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceChanges {
+    pub changed: Vec<FileChange>,
+    pub failed: Vec<FailedFile>,
+}
+
+/// Apply `edit` to every filter under `root`, writing only the files that changed. All files
+/// are edited in memory before the first write, so a filter that does not load is reported
+/// without leaving the workspace half rewritten; a failed write skips only that file.
+pub fn apply_to_workspace(
+    root: &str,
+    mut edit: impl FnMut(&mut FilterDocument) -> Vec<usize>,
+) -> Result<WorkspaceChanges, WarlordError> {
+    let mut report = WorkspaceChanges::default();
+    let mut planned = Vec::new();
+    for file in library::filter_files(Path::new(root)).map_err(|e| WarlordError::io(e, Path::new(root)))? {
+        let path = file.display().to_string();
+        match filter_parser::parse_file(&path) {
+            Ok(mut doc) => {
+                let lines = edit(&mut doc);
+                if !lines.is_empty() {
+                    planned.push((path, doc, lines));
+                }
+            }
+            Err(error) => report.failed.push(FailedFile { path, error }),
+        }
+    }
+    for (path, doc, lines) in planned {
+        match filter_parser::write_file(&path, &doc) {
+            Ok(()) => report.changed.push(FileChange { path, replacements: lines.len(), lines }),
+            Err(error) => report.failed.push(FailedFile { path, error }),
+        }
+    }
+    if !report.failed.is_empty() {
+        eprintln!("[WarlordTools] Workspace edit of {}: {} filters changed, {} failed", root, report.changed.len(), report.failed.len());
+    }
+    Ok(report)
+}
+
+pub fn rename_basetype(workspace: &str, from: &str, to: &str) -> Result<WorkspaceChanges, WarlordError> {
+    if from.is_empty() || to.is_empty() {
+        return Err(WarlordError::invalid("BaseType 不能为空"));
+    }
+    apply_to_workspace(workspace, |doc| rename_basetype_in(doc, from, to))
+}
+
+/// Blocks whose section title contains `scope` (every block when `scope` is None).
+fn in_scope(block: &Block, scope: Option<&str>) -> bool {
+    scope.is_none_or(|scope| block.section.as_deref().is_some_and(|s| s.contains(scope)))
+}
+
+pub const COLOR_KEYWORDS: &[&str] = &["SetTextColor", "SetBorderColor", "SetBackgroundColor"];
+
+/// Parse "R G B [A]"; a missing alpha is the game default of 255.
+pub fn parse_rgba(text: &str) -> Result<[u8; 4], WarlordError> {
+    let invalid = || WarlordError::invalid(format!("无效的颜色: {}", text));
+    let parts: Vec<u8> = text.split_whitespace().map(|p| p.parse::<u8>()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+    match parts[..] {
+        [r, g, b] => Ok([r, g, b, 255]),
+        [r, g, b, a] => Ok([r, g, b, a]),
+        _ => Err(invalid()),
+    }
+}
+
+/// Replace `from` with `to` in every color action, optionally only in blocks whose
+/// section title contains `scope`. Returns the rewritten line numbers.
+pub fn replace_color_in(doc: &mut FilterDocument, from: [u8; 4], to: &[String], scope: Option<&str>) -> Vec<usize> {
+    let mut lines = Vec::new();
+    for block in &mut doc.blocks {
+        if !in_scope(block, scope) {
+            continue;
+        }
+        let body_start = block.line + 1;
+        for (i, line) in block.lines.iter_mut().enumerate() {
+            let Some(rule) = line.rule.as_ref() else { continue };
+            if !COLOR_KEYWORDS.contains(&rule.keyword.as_str()) {
+                continue;
+            }
+            if parse_rgba(&rule.values.join(" ")).ok() != Some(from) {
+                continue;
+            }
+            let mut rule = rule.clone();
+            rule.values = to.to_vec();
+            line.set_rule(rule);
+            lines.push(body_start + i);
+        }
+    }
+    lines
+}
+
+pub fn replace_color(path: &str, from: &str, to: &str, scope: Option<&str>) -> Result<FileChange, WarlordError> {
+    let from = parse_rgba(from)?;
+    parse_rgba(to)?;
+    let to: Vec<String> = to.split_whitespace().map(String::from).collect();
+    let mut doc = filter_parser::parse_file(path)?;
+    let lines = replace_color_in(&mut doc, from, &to, scope.filter(|s| !s.is_empty()));
+    if !lines.is_empty() {
+        filter_parser::write_file(path, &doc)?;
+    }
+    Ok(FileChange { path: path.to_string(), replacements: lines.len(), lines })
+}
+
+/// True for values that name a sound file rather than a built-in id.
+fn is_sound_file(value: &str) -> bool {
+    value.contains(['/', '\\']) || Path::new(value).extension().is_some()
+}
+
+/// Replace an alert sound (built-in id or custom file) with another, keeping each block's
+/// volume. Swapping between an id and a file switches PlayAlertSound <-> CustomAlertSound.
+pub fn swap_alert_sound_in(doc: &mut FilterDocument, from: &str, to: &str, scope: Option<&str>) -> Vec<usize> {
+    let to_file = is_sound_file(to);
+    let mut lines = Vec::new();
+    for block in &mut doc.blocks {
+        if !in_scope(block, scope) {
+            continue;
+        }
+        let body_start = block.line + 1;
+        for (i, line) in block.lines.iter_mut().enumerate() {
+            let Some(rule) = line.rule.as_ref() else { continue };
+            let custom = rule.keyword.starts_with("CustomAlertSound");
+            if !custom && !rule.keyword.starts_with("PlayAlertSound") {
+                continue;
+            }
+            if rule.values.first().map(|v| unquote(v)) != Some(from) {
+                continue;
+            }
+            let keyword = match (custom, to_file) {
+                (true, true) | (false, false) => rule.keyword.clone(),
+                (false, true) => "CustomAlertSound".to_string(),
+                (true, false) => "PlayAlertSound".to_string(),
+            };
+            let mut values = vec![if to_file { quote(to) } else { to.to_string() }];
+            values.extend(rule.values.iter().skip(1).cloned());
+            let mut new_rule = Rule::new(&keyword, values);
+            new_rule.comment = rule.comment.clone();
+            line.set_rule(new_rule);
+            lines.push(body_start + i);
+        }
+    }
+    lines
+}
+
+pub fn swap_alert_sound(path: &str, from: &str, to: &str, scope: Option<&str>) -> Result<FileChange, WarlordError> {
+    if from.is_empty() || to.is_empty() {
+        return Err(WarlordError::invalid("提示音不能为空"));
+    }
+    let mut doc = filter_parser::parse_file(path)?;
+    let lines = swap_alert_sound_in(&mut doc, from, to, scope.filter(|s| !s.is_empty()));
+    if !lines.is_empty() {
+        filter_parser::write_file(path, &doc)?;
+    }
+    Ok(FileChange { path: path.to_string(), replacements: lines.len(), lines })
+}
+
+/// Sort the names of Class/BaseType/... lists alphabetically, quote them and drop repeats.
+/// The game reads each rule from a single line, so long lists stay on their line; a stable
+/// order is what keeps diffs between versions small. Returns the rewritten line numbers.
+pub fn sort_value_lists_in(doc: &mut FilterDocument) -> Vec<usize> {
+    let mut lines = Vec::new();
+    for block in &mut doc.blocks {
+        let body_start = block.line + 1;
+        for (i, line) in block.lines.iter_mut().enumerate() {
+            let Some(rule) = line.rule.as_ref().filter(|r| filter_format::NAME_SET_KEYWORDS.contains(&r.keyword.as_str())) else { continue };
+            let mut sorted = rule.clone();
+            filter_format::normalize_rule_quotes(&mut sorted);
+            let counts = sorted.values.iter().take_while(|v| !v.starts_with('"')).count();
+            let mut names = sorted.values.split_off(counts);
+            names.sort_by_cached_key(|v| (unquote(v).to_lowercase(), v.clone()));
+            names.dedup();
+            sorted.values.extend(names);
+            if &sorted != rule {
+                line.set_rule(sorted);
+                lines.push(body_start + i);
+            }
+        }
+    }
+    lines
+}
+
+pub fn sort_value_lists(path: &str) -> Result<FileChange, WarlordError> {
+    let mut doc = filter_parser::parse_file(path)?;
+    let lines = sort_value_lists_in(&mut doc);
+    if !lines.is_empty() {
+        filter_parser::write_file(path, &doc)?;
+    }
+    Ok(FileChange { path: path.to_string(), replacements: lines.len(), lines })
+}
+
+/// Volume the game uses for alert sounds without an explicit one
+pub const DEFAULT_ALERT_VOLUME: u32 = 100;
+pub const MAX_ALERT_VOLUME: u32 = 300;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VolumeAdjust {
+    /// Multiply every volume by this percentage (100 keeps them)
+    pub percent: u32,
+    /// Then clamp into `min..=max`
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+}
+
+impl Default for VolumeAdjust {
+    fn default() -> Self {
+        VolumeAdjust { percent: 100, min: None, max: None }
+    }
+}
+
+/// PlayAlertSound, PlayAlertSoundPositional, CustomAlertSound and CustomAlertSoundOptional.
+pub fn is_alert_sound(rule: &Rule) -> bool {
+    ["PlayAlertSound", "PlayAlertSoundPositional", "CustomAlertSound", "CustomAlertSoundOptional"].contains(&rule.keyword.as_str())
+}
+
+/// Copy of an alert sound `rule` (`PlayAlertSound <id> [volume]`, `CustomAlertSound "<file>"
+/// [volume]`) with its volume scaled by `percent`, clamped into `min..=max` and to what the
+/// game accepts. Computed in u64, so no percentage overflows.
+pub fn with_scaled_volume(rule: &Rule, adjust: &VolumeAdjust) -> Rule {
+    let volume = rule.values.get(1).and_then(|v| v.parse::<u32>().ok()).unwrap_or(DEFAULT_ALERT_VOLUME);
+    let mut scaled = u64::from(volume) * u64::from(adjust.percent) / 100;
+    if let Some(min) = adjust.min {
+        scaled = scaled.max(u64::from(min));
+    }
+    if let Some(max) = adjust.max {
+        scaled = scaled.min(u64::from(max));
+    }
+    let mut rule = rule.clone();
+    rule.values.truncate(1);
+    rule.values.push(scaled.min(u64::from(MAX_ALERT_VOLUME)).to_string());
+    rule
+}
+
+/// Scale every alert sound volume by `percent`. Sounds without a volume get the scaled
+/// default. Returns the rewritten line numbers.
+pub fn scale_volumes_in(doc: &mut FilterDocument, percent: u32) -> Vec<usize> {
+    adjust_volumes_in(doc, &VolumeAdjust { percent, ..Default::default() })
+}
+
+/// Scale, then clamp, every alert sound volume. Returns the rewritten line numbers.
+pub fn adjust_volumes_in(doc: &mut FilterDocument, adjust: &VolumeAdjust) -> Vec<usize> {
+    let mut lines = Vec::new();
+    for block in &mut doc.blocks {
+        let body_start = block.line + 1;
+        for (i, line) in block.lines.iter_mut().enumerate() {
+            let Some(rule) = line.rule.as_ref() else { continue };
+            if !is_alert_sound(rule) || rule.values.is_empty() {
+                continue;
+            }
+            let scaled = with_scaled_volume(rule, adjust);
+            if scaled.values == rule.values {
+                continue;
+            }
+            line.set_rule(scaled);
+            lines.push(body_start + i);
+        }
+    }
+    lines
+}
+
+pub fn adjust_alert_volumes(path: &str, adjust: &VolumeAdjust) -> Result<FileChange, WarlordError> {
+    if adjust.min.zip(adjust.max).is_some_and(|(min, max)| min > max) {
+        return Err(WarlordError::invalid("最小音量不能大于最大音量"));
+    }
+    let mut doc = filter_parser::parse_file(path)?;
+    let lines = adjust_volumes_in(&mut doc, adjust);
+    if !lines.is_empty() {
+        filter_parser::write_file(path, &doc)?;
+    }
+    Ok(FileChange { path: path.to_string(), replacements: lines.len(), lines })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renames_only_rule_values() {
+        let src = "# BaseType \"Old Name\"\nShow\n    BaseType == \"Old Name\" \"Other\"\n    # BaseType \"Old Name\"\n";
+        let mut doc = FilterDocument::parse(src);
+        assert_eq!(rename_basetype_in(&mut doc, "Old Name", "New Name"), vec![2]);
+        assert_eq!(doc.to_text(), src.replacen("== \"Old Name\"", "== \"New Name\"", 1));
+    }
+
+    #[test]
+    fn replaces_colors_in_scope() {
+        let src = "# [[0100]] A\nShow\n    SetTextColor 255 0 0\n    SetBorderColor 255 0 0 255\n# [[0200]] B\nShow\n    SetTextColor 255 0 0\n";
+        let mut doc = FilterDocument::parse(src);
+        let to = vec!["0".to_string(), "0".to_string(), "255".to_string()];
+        assert_eq!(replace_color_in(&mut doc, [255, 0, 0, 255], &to, Some("[[0100]]")), vec![2, 3]);
+        assert!(doc.to_text().ends_with("Show\n    SetTextColor 255 0 0\n"));
+    }
+
+    #[test]
+    fn swaps_sound_and_keeps_volume() {
+        let src = "Show\n    PlayAlertSound 1 300\nShow\n    CustomAlertSound \"old.mp3\" 150\n";
+        let mut doc = FilterDocument::parse(src);
+        assert_eq!(swap_alert_sound_in(&mut doc, "1", "pack/1.mp3", None), vec![1]);
+        assert_eq!(swap_alert_sound_in(&mut doc, "old.mp3", "6", None), vec![3]);
+        assert_eq!(doc.to_text(), "Show\n    CustomAlertSound \"pack/1.mp3\" 300\nShow\n    PlayAlertSound 6 150\n");
+    }
+
+    #[test]
+    fn sorts_name_lists() {
+        let src = "Show\n    BaseType == \"Exalted Orb\" chaos \"Chaos Orb\" \"Exalted Orb\" # tier 1\n    HasExplicitMod >= 2 \"b\" \"A\"\n    Class \"Currency\"\n";
+        let mut doc = FilterDocument::parse(src);
+        assert_eq!(sort_value_lists_in(&mut doc), vec![1, 2]);
+        assert_eq!(
+            doc.to_text(),
+            "Show\n    BaseType == \"chaos\" \"Chaos Orb\" \"Exalted Orb\" # tier 1\n    HasExplicitMod >= 2 \"A\" \"b\"\n    Class \"Currency\"\n"
+        );
+    }
+
+    #[test]
+    fn scales_then_clamps_volumes() {
+        let src = "Show\n    PlayAlertSound 1 300\nShow\n    PlayAlertSoundPositional 2 40\nShow\n    PlayAlertSound 3 100\n";
+        let mut doc = FilterDocument::parse(src);
+        let adjust = VolumeAdjust { percent: 50, min: Some(30), max: Some(120) };
+        assert_eq!(adjust_volumes_in(&mut doc, &adjust), vec![1, 3, 5]);
+        assert_eq!(doc.to_text(), "Show\n    PlayAlertSound 1 120\nShow\n    PlayAlertSoundPositional 2 30\nShow\n    PlayAlertSound 3 50\n");
+
+        let mut loud = FilterDocument::parse("Show\n    PlayAlertSound 1 4000000000\n");
+        assert_eq!(scale_volumes_in(&mut loud, u32::MAX), vec![1]);
+        assert_eq!(loud.to_text(), "Show\n    PlayAlertSound 1 300\n");
+    }
+}
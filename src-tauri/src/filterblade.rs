@@ -0,0 +1,149 @@
+//! Apply a FilterBlade customization export to a local copy of the base filter.
+//! FilterBlade addresses blocks by the NeverSink header tags (`Show # $type->currency $tier->t1`),
+//! so changes carry over to any version of the base filter that still has those tags:
+//!
+//! ```json
+//! {
+//!   "strictness": 3,
+//!   "changes": [
+//!     { "type": "currency", "tier": "t1", "visibility": "Show",
+//!       "actions": { "SetTextColor": "255 0 0 255", "MinimapIcon": null },
+//!       "addBaseTypes": ["Mirror Shard"], "removeBaseTypes": [] }
+//!   ]
+//! }
+//! ```
+//!
+//! `"tier": "*"` applies to every tier of a type; a `null` action removes it.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::error::WarlordError;
+use crate::filter_parser::{self, quote, unquote, Block, FilterDocument, Rule};
+use crate::{sandbox, strictness};
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TierChange {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub tier: String,
+    /// "Show" / "Hide" / "Minimal"
+    pub visibility: Option<String>,
+    pub actions: BTreeMap<String, Option<String>>,
+    pub add_base_types: Vec<String>,
+    pub remove_base_types: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CustomizationExport {
+    pub strictness: Option<u32>,
+    pub changes: Vec<TierChange>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyReport {
+    pub dest: String,
+    /// Blocks touched by at least one change
+    pub blocks: usize,
+    /// `type/tier` of changes that matched no block in the base filter
+    pub unmatched: Vec<String>,
+}
+
+/// `$type->` and `$tier->` tags of a block header.
+pub fn tags(block: &Block) -> (Option<String>, Option<String>) {
+    let comment = block.header_comment().unwrap_or_default();
+    let tag = |prefix: &str| comment.split_whitespace().find_map(|t| t.strip_prefix(prefix)).map(String::from);
+    (tag("$type->"), tag("$tier->"))
+}
+
+fn matches(block: &Block, change: &TierChange) -> bool {
+    let (kind, tier) = tags(block);
+    kind.as_deref() == Some(change.kind.as_str()) && (change.tier == "*" || tier.as_deref() == Some(change.tier.as_str()))
+}
+
+fn apply_change(block: &mut Block, change: &TierChange) {
+    if let Some(kind) = &change.visibility {
+        block.kind = kind.clone();
+        block.sync_header();
+    }
+    for (keyword, value) in &change.actions {
+        match value {
+            Some(value) => block.set_rule(Rule::new(keyword, filter_parser::tokenize(value).0)),
+            None => {
+                block.remove_rule(keyword);
+            }
+        }
+    }
+    if change.add_base_types.is_empty() && change.remove_base_types.is_empty() {
+        return;
+    }
+    let Some(rule) = block.rule("BaseType").cloned() else { return };
+    let mut rule = rule;
+    rule.values.retain(|v| !change.remove_base_types.iter().any(|r| r == unquote(v)));
+    for base in &change.add_base_types {
+        if !rule.values.iter().any(|v| unquote(v) == base) {
+            rule.values.push(quote(base));
+        }
+    }
+    block.set_rule(rule);
+}
+
+/// Apply `export` to `doc`; returns (touched blocks, unmatched changes).
+pub fn apply_export(doc: &mut FilterDocument, export: &CustomizationExport) -> (usize, Vec<String>) {
+    let mut touched = vec![false; doc.blocks.len()];
+    let mut unmatched = Vec::new();
+    for change in &export.changes {
+        let mut hit = false;
+        for (i, block) in doc.blocks.iter_mut().enumerate() {
+            if matches(block, change) {
+                apply_change(block, change);
+                touched[i] = true;
+                hit = true;
+            }
+        }
+        if !hit {
+            unmatched.push(format!("{}/{}", change.kind, change.tier));
+        }
+    }
+    (touched.iter().filter(|t| **t).count(), unmatched)
+}
+
+pub fn apply_filterblade_export(base: &str, export_path: &str, dest: &str) -> Result<ApplyReport, WarlordError> {
+    let json = fs::read_to_string(export_path).map_err(|e| WarlordError::io(e, export_path))?;
+    let export: CustomizationExport = serde_json::from_str(&json).map_err(|e| WarlordError::invalid(format!("FilterBlade 导出文件格式错误: {}", e)))?;
+    let mut doc = filter_parser::parse_file(base)?;
+    let (blocks, unmatched) = apply_export(&mut doc, &export);
+    let mut text = doc.to_text();
+    if let Some(level) = export.strictness {
+        text = strictness::apply_strictness(&text, level).0;
+    }
+    sandbox::write(dest, text)?;
+    Ok(ApplyReport { dest: dest.to_string(), blocks, unmatched })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_changes_by_tier_tag() {
+        let src = "Show # $type->currency $tier->t1\n    BaseType == \"Divine Orb\"\n    MinimapIcon 0 Red Star\nShow # $type->currency $tier->t2\n    BaseType == \"Chaos Orb\"\n";
+        let export: CustomizationExport = serde_json::from_str(
+            r#"{"changes": [
+                {"type": "currency", "tier": "t1", "actions": {"SetTextColor": "255 0 0", "MinimapIcon": null}, "addBaseTypes": ["Mirror Shard"]},
+                {"type": "currency", "tier": "t2", "visibility": "Hide"},
+                {"type": "maps", "tier": "*", "visibility": "Hide"}
+            ]}"#,
+        )
+        .unwrap();
+        let mut doc = FilterDocument::parse(src);
+        assert_eq!(apply_export(&mut doc, &export), (2, vec!["maps/*".to_string()]));
+        assert_eq!(
+            doc.to_text(),
+            "Show # $type->currency $tier->t1\n    BaseType == \"Divine Orb\" \"Mirror Shard\"\n    SetTextColor 255 0 0\nHide # $type->currency $tier->t2\n    BaseType == \"Chaos Orb\"\n"
+        );
+    }
+}
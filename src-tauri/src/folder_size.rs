@@ -0,0 +1,157 @@
+//! Total size of a folder (a sound pack, a league's backups) counted on a background thread,
+//! with running totals reported while it goes and a cancel, like `scan::start`. `prepare`
+//! returns the id and `start` sets the count off, once the frontend listens for that id.
+//! Links are counted as themselves and not followed, so a linked install is not counted twice.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+
+/// Running totals of a count, or its result when `done`.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderSize {
+    pub id: u64,
+    pub path: String,
+    pub bytes: u64,
+    pub files: u64,
+    pub dirs: u64,
+    pub done: bool,
+    pub cancelled: bool,
+    /// Folders that could not be read (counted as empty)
+    pub unreadable: u64,
+    pub error: Option<String>,
+}
+
+/// How often running totals are reported.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A prepared count, until `start` runs it.
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Count {
+    cancelled: Arc<AtomicBool>,
+    job: Option<Job>,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static RUNNING: Mutex<Option<HashMap<u64, Count>>> = Mutex::new(None);
+
+fn with_running<R>(f: impl FnOnce(&mut HashMap<u64, Count>) -> R) -> R {
+    let mut guard = RUNNING.lock().unwrap();
+    f(guard.get_or_insert_with(HashMap::new))
+}
+
+/// Count everything below `root`, calling `progress` at most every `PROGRESS_INTERVAL`.
+fn count(root: &Path, totals: &mut FolderSize, cancelled: &AtomicBool, progress: &mut dyn FnMut(&FolderSize)) {
+    let mut pending: Vec<PathBuf> = vec![long_path(root)];
+    let mut last = Instant::now();
+    while let Some(dir) = pending.pop() {
+        if cancelled.load(Ordering::Relaxed) {
+            totals.cancelled = true;
+            return;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            totals.unreadable += 1;
+            continue;
+        };
+        totals.dirs += 1;
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.path().symlink_metadata() else { continue };
+            if meta.is_dir() {
+                pending.push(entry.path());
+            } else {
+                totals.files += 1;
+                totals.bytes += meta.len();
+            }
+        }
+        if last.elapsed() >= PROGRESS_INTERVAL {
+            progress(totals);
+            last = Instant::now();
+        }
+    }
+}
+
+/// Size of `root` now, on this thread.
+pub fn folder_size(root: &Path) -> FolderSize {
+    let mut totals = FolderSize { path: path_utils::short_path(root), ..Default::default() };
+    count(root, &mut totals, &AtomicBool::new(false), &mut |_| {});
+    totals.done = true;
+    totals
+}
+
+/// Bytes of a file, or of everything below a folder.
+pub fn size_of(path: &Path) -> u64 {
+    match fs::symlink_metadata(long_path(path)) {
+        Ok(meta) if meta.is_dir() => folder_size(path).bytes,
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Count `root` on a background thread once `start` is called, handing running totals to
+/// `emit`; the last one has `done` set. Returns the id for `start` and `cancel`.
+pub fn prepare(root: PathBuf, emit: impl Fn(FolderSize) + Send + 'static) -> u64 {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let flag = cancelled.clone();
+    let job: Job = Box::new(move || {
+        let mut totals = FolderSize { id, path: path_utils::short_path(&root), ..Default::default() };
+        if long_path(&root).is_dir() {
+            count(&root, &mut totals, &flag, &mut |t| emit(t.clone()));
+        } else {
+            totals.error = Some(format!("{} 不是文件夹", totals.path));
+        }
+        totals.done = true;
+        emit(totals);
+        with_running(|running| running.remove(&id));
+    });
+    with_running(|running| running.insert(id, Count { cancelled, job: Some(job) }));
+    id
+}
+
+/// Set off a prepared count.
+pub fn start(id: u64) -> Result<(), WarlordError> {
+    let job = with_running(|running| running.get_mut(&id).and_then(|c| c.job.take()));
+    let job = job.ok_or_else(|| WarlordError::invalid(format!("没有待开始的统计 {}", id)))?;
+    std::thread::spawn(job);
+    Ok(())
+}
+
+/// Stop a count; its last report has `cancelled` set.
+pub fn cancel(id: u64) {
+    if let Some(count) = with_running(|running| running.remove(&id)) {
+        count.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_files_and_folders() {
+        let root = std::env::temp_dir().join("wt-folder-size-test");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("alerts/loud")).unwrap();
+        fs::write(root.join("a.mp3"), [0u8; 100]).unwrap();
+        fs::write(root.join("alerts/loud/b.mp3"), [0u8; 28]).unwrap();
+
+        let size = folder_size(&root);
+        assert_eq!((size.bytes, size.files, size.dirs, size.done), (128, 2, 3, true));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let id = prepare(root.clone(), move |totals| tx.send(totals).unwrap());
+        start(id).unwrap();
+        assert!(start(id).is_err());
+        let last = rx.iter().find(|t| t.done).unwrap();
+        assert_eq!((last.bytes, last.files), (128, 2));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+//! First-run migration for users who kept their filters in the game's documents folder: each
+//! `.filter` there is copied into the library under a folder named after the game folder
+//! ("Path of Exile/NeverSink.filter"), where it came from is recorded in `migrations.json`,
+//! and optionally the original is swapped for a link to the library copy (`filter_link`), so
+//! the game keeps loading the same file. Filters that already are links are left alone, and
+//! running it again skips what was migrated before.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::WarlordError;
+use crate::file_ops::{self, FailedFile};
+use crate::filter_link::{self, LinkKind};
+use crate::path_utils::{self, long_path};
+use crate::{app_paths, library, sandbox};
+
+const MIGRATIONS_FILE: &str = "migrations.json";
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigratedFilter {
+    /// Where the filter was in the game folder
+    pub origin: String,
+    pub library_path: String,
+    /// Seconds since the unix epoch
+    pub time: u64,
+    /// Set when the original was replaced by a link
+    pub link: Option<LinkKind>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Migrations {
+    filters: Vec<MigratedFilter>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationReport {
+    pub migrated: Vec<MigratedFilter>,
+    /// Links already, or migrated before
+    pub skipped: Vec<String>,
+    pub failed: Vec<FailedFile>,
+}
+
+/// Every filter migrated so far.
+pub fn migrations() -> Vec<MigratedFilter> {
+    app_paths::load_json::<Migrations>(MIGRATIONS_FILE).filters
+}
+
+/// The `.filter` files the game reads from `dir` (its top level only).
+fn game_filters(dir: &Path) -> Vec<PathBuf> {
+    let mut filters: Vec<PathBuf> = fs::read_dir(long_path(dir))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| dir.join(entry.file_name()))
+        .filter(|path| path.extension().is_some_and(|e| e.eq_ignore_ascii_case("filter")))
+        .collect();
+    filters.sort();
+    filters
+}
+
+/// Library path for `origin` under `folder`; None when the same content is there already,
+/// under its own name or where an earlier migration put it.
+fn destination(origin: &Path, folder: &Path, contents: &[u8], earlier: Option<&Path>) -> Option<PathBuf> {
+    if earlier.is_some_and(|path| fs::read(long_path(path)).is_ok_and(|existing| existing == contents)) {
+        return None;
+    }
+    let dest = folder.join(origin.file_name()?);
+    match fs::read(long_path(&dest)) {
+        Ok(existing) if existing == contents => None,
+        Ok(_) => Some(file_ops::copy_name(&dest)),
+        Err(_) => Some(dest),
+    }
+}
+
+fn migrate_one(origin: &Path, folder: &Path, earlier: Option<&Path>, replace_with_links: bool) -> Result<Option<MigratedFilter>, WarlordError> {
+    let contents = fs::read(long_path(origin)).map_err(|e| WarlordError::io(e, origin))?;
+    let Some(dest) = destination(origin, folder, &contents, earlier) else { return Ok(None) };
+    sandbox::check_write(folder)?;
+    fs::create_dir_all(long_path(folder)).map_err(|e| WarlordError::io(e, folder))?;
+    sandbox::write_as(&dest, &contents, "migrate")?;
+    let link = if replace_with_links { Some(filter_link::link_filter(&dest, origin)?.kind) } else { None };
+    Ok(Some(MigratedFilter { origin: path_utils::short_path(origin), library_path: path_utils::short_path(&dest), time: app_paths::now_secs(), link }))
+}
+
+fn migrate(dirs: &[PathBuf], root: &Path, replace_with_links: bool) -> Result<MigrationReport, WarlordError> {
+    let linked = filter_link::links();
+    let mut registry: Migrations = app_paths::load_json(MIGRATIONS_FILE);
+    let mut report = MigrationReport::default();
+    for dir in dirs {
+        let folder = root.join(dir.file_name().unwrap_or_else(|| "Game".as_ref()));
+        for origin in game_filters(dir) {
+            let shown = path_utils::short_path(&origin);
+            let is_link = fs::symlink_metadata(long_path(&origin)).is_ok_and(|m| m.file_type().is_symlink()) || linked.iter().any(|l| path_utils::compare_paths(&l.link, &shown));
+            if is_link {
+                report.skipped.push(shown);
+                continue;
+            }
+            let earlier = registry.filters.iter().find(|f| path_utils::compare_paths(&f.origin, &shown)).map(|f| PathBuf::from(&f.library_path));
+            match migrate_one(&origin, &folder, earlier.as_deref(), replace_with_links) {
+                Ok(Some(entry)) => {
+                    registry.filters.retain(|f| !path_utils::compare_paths(&f.origin, &entry.origin));
+                    registry.filters.push(entry.clone());
+                    report.migrated.push(entry);
+                }
+                Ok(None) => report.skipped.push(shown),
+                Err(error) => report.failed.push(FailedFile { path: shown, error }),
+            }
+        }
+    }
+    app_paths::save_json(MIGRATIONS_FILE, &registry)?;
+    eprintln!("[WarlordTools] Migrated {} filters from the game folder into {} ({} skipped, {} failed)", report.migrated.len(), root.display(), report.skipped.len(), report.failed.len());
+    Ok(report)
+}
+
+/// Copy the filters of `game_dir` (every game documents folder when None) into the library.
+pub fn migrate_from_game_folder(game_dir: Option<&Path>, replace_with_links: bool) -> Result<MigrationReport, WarlordError> {
+    let root = library::library_root().ok_or_else(|| WarlordError::invalid("尚未设置过滤器库文件夹"))?;
+    let dirs = match game_dir {
+        Some(dir) if !long_path(dir).is_dir() => return Err(WarlordError::not_found(dir)),
+        Some(dir) => vec![dir.to_path_buf()],
+        None => sandbox::game_documents().into_iter().filter(|d| long_path(d).is_dir()).collect(),
+    };
+    if dirs.is_empty() {
+        return Err(WarlordError::invalid("找不到游戏的文档文件夹"));
+    }
+    migrate(&dirs, &root, replace_with_links)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_game_filters_once() {
+        let root = std::env::temp_dir().join("wt-migration-test");
+        let _ = fs::remove_dir_all(&root);
+        let (game, library) = (root.join("Path of Exile"), root.join("library"));
+        fs::create_dir_all(&game).unwrap();
+        fs::create_dir_all(library.join("Path of Exile")).unwrap();
+        fs::write(game.join("NeverSink.filter"), "Show\n").unwrap();
+        fs::write(game.join("Strict.filter"), "Hide\n").unwrap();
+        fs::write(game.join("production_Config.ini"), "").unwrap();
+        fs::write(library.join("Path of Exile/Strict.filter"), "Show # mine\n").unwrap();
+
+        let report = migrate(std::slice::from_ref(&game), &library, false).unwrap();
+        let copied: Vec<&str> = report.migrated.iter().filter_map(|f| Path::new(&f.library_path).file_name()?.to_str()).collect();
+        assert_eq!(copied, ["NeverSink.filter", "Strict (copy).filter"]);
+        assert_eq!(fs::read_to_string(library.join("Path of Exile/Strict (copy).filter")).unwrap(), "Hide\n");
+        assert!(game.join("NeverSink.filter").is_file());
+
+        let again = migrate(std::slice::from_ref(&game), &library, false).unwrap();
+        assert!(again.migrated.is_empty());
+        assert_eq!(again.skipped.len(), 2);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,207 @@
+//! Sync profiles between the library and a game's filter folder: each profile names library
+//! filters to keep installed in a folder (by default the first game's documents folder),
+//! under their file name since the game only reads filters at its top level. Both sides are
+//! compared with the hashes of the last sync (`game_sync_state.json`), so a filter edited
+//! in the library goes to the game, one edited in the game (e.g. through the in-game reload
+//! workflow or an external editor) comes back, and one edited on both sides is left alone
+//! unless the caller picks a direction for it. Every write goes through the sandbox.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::WarlordError;
+use crate::file_ops::FailedFile;
+use crate::path_utils::{self, long_path};
+use crate::{app_paths, archive, library, sandbox, webhooks};
+
+const CONFIG_FILE: &str = "game_sync.json";
+const STATE_FILE: &str = "game_sync_state.json";
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SyncProfile {
+    pub name: String,
+    /// Folder the filters are installed in; empty for the game's documents folder
+    pub game_dir: String,
+    /// `/`-separated paths relative to the library root
+    pub filters: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GameSyncConfig {
+    pub profiles: Vec<SyncProfile>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+    ToGame,
+    FromGame,
+    Skip,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Change {
+    LibraryChanged,
+    GameChanged,
+    /// Changed on both sides since the last sync
+    BothChanged,
+    MissingInLibrary,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSyncItem {
+    /// As listed in the profile
+    pub filter: String,
+    pub library_path: String,
+    pub game_path: String,
+    pub change: Change,
+    /// What is done, or would be on a dry run
+    pub direction: Direction,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSyncReport {
+    pub profile: String,
+    pub items: Vec<GameSyncItem>,
+    pub dry_run: bool,
+    pub failed: Vec<FailedFile>,
+}
+
+/// Last synced hash by profile, then by filter.
+type SyncState = BTreeMap<String, BTreeMap<String, String>>;
+
+pub fn get_config() -> GameSyncConfig {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+pub fn set_config(config: &GameSyncConfig) -> Result<(), WarlordError> {
+    for (i, profile) in config.profiles.iter().enumerate() {
+        if profile.name.trim().is_empty() || config.profiles[..i].iter().any(|p| p.name == profile.name) {
+            return Err(WarlordError::invalid(format!("同步方案名称为空或重复: {}", profile.name)));
+        }
+        let mut names = BTreeMap::new();
+        for filter in &profile.filters {
+            let name = archive::entry_path(filter).and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_lowercase()));
+            let name = name.ok_or_else(|| WarlordError::invalid(format!("无效的过滤器路径: {}", filter)))?;
+            if let Some(other) = names.insert(name, filter) {
+                return Err(WarlordError::invalid(format!("{} 和 {} 在游戏文件夹中同名", other, filter)));
+            }
+        }
+    }
+    app_paths::save_json(CONFIG_FILE, config)
+}
+
+fn hash_of(path: &Path) -> Option<String> {
+    fs::read(long_path(path)).ok().map(|bytes| format!("{:x}", Sha256::digest(&bytes)))
+}
+
+/// What changed from the library, game and last synced hashes, and the default direction.
+fn decide(library: Option<&String>, game: Option<&String>, base: Option<&String>) -> Option<(Change, Direction)> {
+    Some(match (library, game) {
+        _ if library == game => return None,
+        // Never deletes from the game folder: uninstalling is the user's call
+        (None, _) => (Change::MissingInLibrary, Direction::Skip),
+        _ if game == base || game.is_none() => (Change::LibraryChanged, Direction::ToGame),
+        _ if library == base => (Change::GameChanged, Direction::FromGame),
+        _ => (Change::BothChanged, Direction::Skip),
+    })
+}
+
+fn game_dir(profile: &SyncProfile) -> Result<PathBuf, WarlordError> {
+    if !profile.game_dir.is_empty() {
+        return Ok(PathBuf::from(&profile.game_dir));
+    }
+    sandbox::game_documents().into_iter().next().ok_or_else(|| WarlordError::invalid("找不到游戏的文档文件夹"))
+}
+
+fn copy(from: &Path, to: &Path) -> Result<(), WarlordError> {
+    let bytes = fs::read(long_path(from)).map_err(|e| WarlordError::io(e, from))?;
+    if let Some(parent) = to.parent() {
+        sandbox::check_write(parent)?;
+        fs::create_dir_all(long_path(parent)).map_err(|e| WarlordError::io(e, parent))?;
+    }
+    sandbox::write_as(to, &bytes, "game-sync").map(|_| ())
+}
+
+/// Compare the library and game copies of `profile`'s filters and, unless `dry_run`, copy
+/// each one the way its item says. `directions` overrides the default per filter, which is
+/// how a conflict is settled.
+pub fn sync_to_game(profile: &str, dry_run: bool, directions: &HashMap<String, Direction>) -> Result<GameSyncReport, WarlordError> {
+    let config = get_config();
+    let profile = config.profiles.iter().find(|p| p.name == profile).ok_or_else(|| WarlordError::invalid(format!("没有同步方案 {}", profile)))?;
+    let root = library::library_root().ok_or_else(|| WarlordError::invalid("尚未设置过滤器库文件夹"))?;
+    let dir = game_dir(profile)?;
+    let mut state: SyncState = app_paths::load_json(STATE_FILE);
+    let synced = state.entry(profile.name.clone()).or_default();
+
+    let mut items = Vec::new();
+    let mut failed = Vec::new();
+    for filter in &profile.filters {
+        let Some(relative) = archive::entry_path(filter) else { continue };
+        let library_path = root.join(&relative);
+        let game_path = dir.join(relative.file_name().unwrap_or_default());
+        let (in_library, in_game) = (hash_of(&library_path), hash_of(&game_path));
+        let Some((change, default)) = decide(in_library.as_ref(), in_game.as_ref(), synced.get(filter)) else {
+            if let Some(hash) = in_library {
+                synced.insert(filter.clone(), hash);
+            }
+            continue;
+        };
+        let direction = directions.get(filter).copied().unwrap_or(default);
+        if !dry_run {
+            let result = match direction {
+                Direction::ToGame => copy(&library_path, &game_path).map(|_| in_library.clone()),
+                Direction::FromGame => copy(&game_path, &library_path).map(|_| in_game.clone()),
+                Direction::Skip => Ok(None),
+            };
+            match result {
+                Ok(Some(hash)) => {
+                    synced.insert(filter.clone(), hash);
+                }
+                Ok(None) => {}
+                Err(error) => failed.push(FailedFile { path: filter.clone(), error }),
+            }
+        }
+        let (library_path, game_path) = (path_utils::short_path(&library_path), path_utils::short_path(&game_path));
+        items.push(GameSyncItem { filter: filter.clone(), library_path, game_path, change, direction });
+    }
+    // Filters taken out of the profile are forgotten
+    synced.retain(|filter, _| profile.filters.contains(filter));
+    if !dry_run {
+        app_paths::save_json(STATE_FILE, &state)?;
+        eprintln!("[WarlordTools] Synced profile {} with {}: {} changes, {} failed", profile.name, dir.display(), items.len(), failed.len());
+        let installed: Vec<&str> = items.iter().filter(|i| i.direction == Direction::ToGame && !failed.iter().any(|f| f.path == i.filter)).map(|i| i.filter.as_str()).collect();
+        if !installed.is_empty() {
+            webhooks::notify(webhooks::EVENT_FILTER_UPDATE, &format!("过滤器已更新到游戏 ({})", profile.name), &installed.join("\n"), failed.is_empty());
+        }
+    }
+    Ok(GameSyncReport { profile: profile.name.clone(), items, dry_run, failed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decides_direction_by_last_synced_hash() {
+        let (a, b, c) = (Some("a".to_string()), Some("b".to_string()), Some("c".to_string()));
+        assert_eq!(decide(a.as_ref(), a.as_ref(), None), None);
+        assert_eq!(decide(b.as_ref(), a.as_ref(), a.as_ref()), Some((Change::LibraryChanged, Direction::ToGame)));
+        assert_eq!(decide(a.as_ref(), None, a.as_ref()), Some((Change::LibraryChanged, Direction::ToGame)));
+        assert_eq!(decide(a.as_ref(), b.as_ref(), a.as_ref()), Some((Change::GameChanged, Direction::FromGame)));
+        assert_eq!(decide(b.as_ref(), c.as_ref(), a.as_ref()), Some((Change::BothChanged, Direction::Skip)));
+        assert_eq!(decide(None, a.as_ref(), a.as_ref()), Some((Change::MissingInLibrary, Direction::Skip)));
+
+        let profile = |filters: &[&str]| GameSyncConfig { profiles: vec![SyncProfile { name: "main".to_string(), game_dir: String::new(), filters: filters.iter().map(|f| f.to_string()).collect() }] };
+        assert!(matches!(set_config(&profile(&["a/Strict.filter", "b/strict.filter"])), Err(WarlordError::InvalidInput { .. })));
+        assert!(matches!(set_config(&profile(&["../x.filter"])), Err(WarlordError::InvalidInput { .. })));
+    }
+}
@@ -0,0 +1,109 @@
+//! Calls to the official GGG API (api.pathofexile.com) with an account's OAuth token:
+//! characters, stash tabs and item filter upload.
+
+use std::fs;
+
+use serde_json::{json, Value};
+
+use crate::error::WarlordError;
+use crate::accounts::{self, Account};
+use crate::webhooks;
+
+const API_BASE: &str = "https://api.pathofexile.com";
+/// GGG requires an identifying user agent for OAuth clients
+const USER_AGENT: &str = concat!("OAuth warlordtools/", env!("CARGO_PKG_VERSION"));
+const REALM: &str = "poe2";
+
+fn token(account: &Account) -> Result<&str, WarlordError> {
+    if account.server != "intl" {
+        return Err(WarlordError::invalid("国服账号不支持官方 API"));
+    }
+    if account.access_token.is_empty() {
+        return Err(WarlordError::invalid(format!("账号 {} 未授权", account.name)));
+    }
+    Ok(&account.access_token)
+}
+
+fn request(method: &str, account: &Account, path: &str, body: Option<&Value>) -> Result<Value, WarlordError> {
+    let req = ureq::request(method, &format!("{}{}", API_BASE, path))
+        .set("Authorization", &format!("Bearer {}", token(account)?))
+        .set("User-Agent", USER_AGENT)
+        .set("Accept", "application/json");
+    let resp = match body {
+        Some(body) => req.send_json(body.clone()),
+        None => req.call(),
+    };
+    let resp = resp.map_err(|e| match e {
+        ureq::Error::Status(401, _) => WarlordError::PermissionDenied { path: None, message: format!("账号 {} 的授权已失效, 请重新登录", account.name) },
+        e => WarlordError::Network { message: format!("GGG API 请求失败: {}", e) },
+    })?;
+    resp.into_json().map_err(|e| WarlordError::Network { message: format!("GGG API 返回了无效的数据: {}", e) })
+}
+
+pub fn list_characters(account_id: Option<&str>) -> Result<Value, WarlordError> {
+    let account = accounts::resolve(account_id)?;
+    Ok(request("GET", &account, &format!("/character/{}", REALM), None)?["characters"].take())
+}
+
+pub fn list_stashes(account_id: Option<&str>, league: &str) -> Result<Value, WarlordError> {
+    let account = accounts::resolve(account_id)?;
+    Ok(request("GET", &account, &format!("/stash/{}/{}", REALM, league), None)?["stashes"].take())
+}
+
+/// Id of the filter called `name` in a `GET /item-filter` reply.
+fn find_filter_id(list: &Value, name: &str) -> Option<String> {
+    list["filters"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|f| f["filter_name"] == name && f["realm"] == REALM)
+        .and_then(|f| f["id"].as_str().map(String::from))
+}
+
+/// Id of the uploaded filter: the one in the reply, else the one that was updated.
+fn uploaded_id(reply: &Value, existing: Option<String>) -> Result<String, WarlordError> {
+    reply["filter"]["id"].as_str().map(String::from).or(existing).ok_or_else(|| WarlordError::Network { message: "GGG API 未返回过滤器 ID".to_string() })
+}
+
+/// Upload `path` as the account's item filter `name`, updating it when one with that name exists.
+/// Returns the filter id.
+pub fn upload_filter(account_id: Option<&str>, path: &str, name: &str) -> Result<String, WarlordError> {
+    let account = accounts::resolve(account_id)?;
+    let content = fs::read_to_string(path).map_err(|e| WarlordError::io(e, path))?;
+    let existing = request("GET", &account, "/item-filter", None)?;
+    let id = find_filter_id(&existing, name);
+
+    let body = json!({ "filter_name": name, "realm": REALM, "filter": content });
+    let reply = match &id {
+        Some(id) => request("POST", &account, &format!("/item-filter/{}", id), Some(&body))?,
+        None => {
+            let mut body = body;
+            body["type"] = json!("Normal");
+            body["public"] = json!(false);
+            request("POST", &account, "/item-filter", Some(&body))?
+        }
+    };
+    let id = uploaded_id(&reply, id)?;
+    webhooks::notify(webhooks::EVENT_FILTER_UPDATE, "过滤器已上传", &format!("{} 已上传到 {}", name, account.name), true);
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_filter_ids() {
+        let list = json!({ "filters": [
+            { "id": "a1", "filter_name": "Warlord", "realm": "pc" },
+            { "id": "b2", "filter_name": "Warlord", "realm": REALM },
+        ] });
+        assert_eq!(find_filter_id(&list, "Warlord"), Some("b2".to_string()));
+        assert_eq!(find_filter_id(&list, "Other"), None);
+        assert_eq!(find_filter_id(&json!({}), "Warlord"), None);
+
+        assert_eq!(uploaded_id(&json!({ "filter": { "id": "c3" } }), None).unwrap(), "c3");
+        assert_eq!(uploaded_id(&json!({}), Some("b2".to_string())).unwrap(), "b2");
+        assert_eq!(uploaded_id(&json!({ "filter": {} }), None).unwrap_err().code(), "network");
+    }
+}
@@ -0,0 +1,89 @@
+//! Wildcard patterns for scans and ignore files: `?` is one character, `*` any run of
+//! characters within one path component, `**` any run including `/`, and `**/` zero or more
+//! whole directories. Matching ignores case, like file names on Windows.
+
+#[derive(Clone, Copy, PartialEq)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    DoubleStar,
+    /// `**/`
+    Dirs,
+}
+
+fn tokens(pattern: &str) -> Vec<Token> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let token = match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') && chars.get(i + 2) == Some(&'/') => {
+                i += 2;
+                Token::Dirs
+            }
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                i += 1;
+                Token::DoubleStar
+            }
+            '*' => Token::Star,
+            '?' => Token::Any,
+            c => Token::Char(c),
+        };
+        out.push(token);
+        i += 1;
+    }
+    out
+}
+
+/// Whether `text` (with `/` separators) matches `pattern` as a whole.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern = tokens(&pattern.to_lowercase());
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (n, m) = (pattern.len(), text.len());
+    // dp[i][j]: pattern[i..] matches text[j..]
+    let mut dp = vec![vec![false; m + 1]; n + 1];
+    dp[n][m] = true;
+    for i in (0..n).rev() {
+        for j in (0..=m).rev() {
+            let next = text.get(j).copied();
+            dp[i][j] = match pattern[i] {
+                Token::Char(c) => next == Some(c) && dp[i + 1][j + 1],
+                Token::Any => next.is_some_and(|c| c != '/') && dp[i + 1][j + 1],
+                Token::Star => dp[i + 1][j] || (next.is_some_and(|c| c != '/') && dp[i][j + 1]),
+                Token::DoubleStar => dp[i + 1][j] || (next.is_some() && dp[i][j + 1]),
+                // Zero directories, or one whole `name/` and then more
+                Token::Dirs => dp[i + 1][j] || text[j..].iter().position(|&c| c == '/').is_some_and(|end| dp[i][j + end + 1]),
+            };
+        }
+    }
+    dp[0][0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(matches("*.filter", "NeverSink.FILTER"));
+        assert!(matches("*.ruthless.filter", "a.ruthless.filter"));
+        assert!(!matches("*.ruthless.filter", "a.filter"));
+        assert!(!matches("*.filter", "sub/a.filter"));
+        assert!(matches("**/*.filter", "a.filter"));
+        assert!(matches("**/*.filter", "x/y/a.filter"));
+        assert!(matches("backup/**", "backup/2024/a.filter"));
+        assert!(matches("alert?.ogg", "alert1.ogg"));
+        assert!(!matches("alert?.ogg", "alert10.ogg"));
+    }
+
+    #[test]
+    fn double_star_slash_takes_whole_directories() {
+        assert!(matches("**/b.filter", "b.filter"));
+        assert!(matches("**/b.filter", "x/b.filter"));
+        assert!(matches("a/**/b.filter", "a/x/y/b.filter"));
+        assert!(!matches("**/b.filter", "ab.filter"));
+        assert!(!matches("**/b.filter", "x/ab.filter"));
+        assert!(!matches("a/**/b.filter", "a/xb.filter"));
+    }
+}
@@ -0,0 +1,304 @@
+//! Journal of the file changes the app makes, so the last one can be undone, also after a
+//! restart. `sandbox::write_as` and `sandbox::delete` record each write and delete, renames
+//! and moves are recorded by their commands; what `operation` runs is grouped into one
+//! entry, so undoing a pack install or a folder copy reverts all of its files. The content a
+//! write replaced is kept under `journal/<sha256>` in the config folder, deleted files are
+//! brought back from the app's trash. The changes of an operation are written to the journal
+//! once, when it ends. The newest `MAX_OPERATIONS` entries are kept, fewer when the contents
+//! they keep add up to more than `MAX_BLOB_BYTES`.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+use crate::{app_paths, app_trash, file_ops, sandbox, scan};
+
+const JOURNAL_FILE: &str = "journal.json";
+const MAX_OPERATIONS: usize = 200;
+const MAX_BLOB_BYTES: u64 = 512 * 1024 * 1024;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    /// The `operation` running on this thread, with the changes it made so far
+    static CURRENT: RefCell<Option<Operation>> = const { RefCell::new(None) };
+    /// Set while undoing or in `unrecorded`, whose changes are not recorded
+    static PAUSED: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum Change {
+    /// `previous` is the hash of the replaced content, None for a new file
+    Write { path: String, previous: Option<String> },
+    /// `trash_id` is None when the file did not go to the app's trash
+    Delete { path: String, trash_id: Option<String> },
+    Rename { from: String, to: String },
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Operation {
+    pub id: String,
+    /// What the app was doing, e.g. "save", "delete", "import-pack"
+    pub label: String,
+    /// Seconds since the unix epoch
+    pub time: u64,
+    pub changes: Vec<Change>,
+    pub undone: bool,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Journal {
+    operations: Vec<Operation>,
+}
+
+fn blob_dir() -> PathBuf {
+    app_paths::config_file("journal")
+}
+
+fn with_journal<R>(f: impl FnOnce(&mut Journal) -> Result<R, WarlordError>) -> Result<R, WarlordError> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut journal: Journal = app_paths::load_json(JOURNAL_FILE);
+    let result = f(&mut journal)?;
+    trim(&mut journal);
+    app_paths::save_json(JOURNAL_FILE, &journal)?;
+    Ok(result)
+}
+
+fn blobs(operation: &Operation) -> impl Iterator<Item = &String> {
+    operation.changes.iter().filter_map(|c| match c {
+        Change::Write { previous, .. } => previous.as_ref(),
+        _ => None,
+    })
+}
+
+/// Drop the oldest entries past `MAX_OPERATIONS` or past `MAX_BLOB_BYTES` of kept contents;
+/// the newest entry always stays.
+fn trim(journal: &mut Journal) {
+    let mut seen = HashSet::new();
+    let mut total = 0u64;
+    let mut keep = 0;
+    for operation in journal.operations.iter().rev() {
+        for hash in blobs(operation) {
+            if seen.insert(hash) {
+                total = total.saturating_add(fs::metadata(blob_dir().join(hash)).map_or(0, |m| m.len()));
+            }
+        }
+        if keep > 0 && (keep == MAX_OPERATIONS || total > MAX_BLOB_BYTES) {
+            break;
+        }
+        keep += 1;
+    }
+    if keep < journal.operations.len() {
+        journal.operations.drain(..journal.operations.len() - keep);
+        prune_blobs(journal);
+    }
+}
+
+/// Remove stored contents no entry refers to any more.
+fn prune_blobs(journal: &Journal) {
+    let used: HashSet<&str> = journal.operations.iter().flat_map(blobs).map(String::as_str).collect();
+    for entry in fs::read_dir(blob_dir()).into_iter().flatten().flatten() {
+        if !entry.file_name().to_str().is_some_and(|name| used.contains(name)) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+fn new_operation(label: &str) -> Operation {
+    Operation { id: app_paths::new_id(), label: label.to_string(), time: app_paths::now_secs(), changes: Vec::new(), undone: false }
+}
+
+/// Add `operation` to the journal. Failures are logged; the changes themselves went through.
+fn save(operation: Operation) {
+    let result = with_journal(|journal| {
+        journal.operations.push(operation);
+        Ok(())
+    });
+    if let Err(e) = result {
+        eprintln!("[WarlordTools] Could not record a change in the journal: {}", e);
+    }
+}
+
+/// Run `f` as one operation: every change it makes is undone together. Nested calls join
+/// the outer operation.
+pub fn operation<R>(label: &str, f: impl FnOnce() -> R) -> R {
+    if CURRENT.with(|c| c.borrow().is_some()) {
+        return f();
+    }
+    CURRENT.with(|c| *c.borrow_mut() = Some(new_operation(label)));
+    let result = f();
+    if let Some(operation) = CURRENT.with(|c| c.borrow_mut().take()).filter(|o| !o.changes.is_empty()) {
+        save(operation);
+    }
+    result
+}
+
+/// Run `f` without recording its changes, for commands that record one change of their own
+/// for all of it (a move is one rename, not a copy and a delete of every file).
+pub fn unrecorded<R>(f: impl FnOnce() -> R) -> R {
+    let paused = PAUSED.with(|p| p.replace(true));
+    let result = f();
+    PAUSED.with(|p| p.set(paused));
+    result
+}
+
+/// Add `change` to the running operation, or as an operation of its own.
+pub fn record(label: &str, change: Change) {
+    if PAUSED.with(Cell::get) {
+        return;
+    }
+    let alone = CURRENT.with(|c| match c.borrow_mut().as_mut() {
+        Some(operation) => {
+            operation.changes.push(change);
+            None
+        }
+        None => Some(change),
+    });
+    if let Some(change) = alone {
+        save(Operation { changes: vec![change], ..new_operation(label) });
+    }
+}
+
+/// Record a write of `path`, keeping the content it replaced.
+pub fn record_write(path: &Path, previous: Option<&[u8]>, reason: &str) {
+    if PAUSED.with(Cell::get) {
+        return;
+    }
+    let previous = match previous.map(stash).transpose() {
+        Ok(previous) => previous,
+        Err(e) => return eprintln!("[WarlordTools] Could not keep the previous content of {} for undo: {}", path.display(), e),
+    };
+    record(reason, Change::Write { path: path_utils::short_path(path), previous });
+}
+
+/// `stash` for the file at `path`, streamed; for files too large to hold in memory. Call it
+/// before the file is replaced and record a `Change::Write` with the hash after.
+pub fn stash_file(path: &Path) -> Result<String, WarlordError> {
+    let hash = scan::hash_file(&long_path(path)).map_err(|e| WarlordError::io(e, path))?;
+    let blob = blob_dir().join(&hash);
+    if !blob.exists() {
+        fs::create_dir_all(blob_dir()).map_err(|e| WarlordError::io(e, blob_dir()))?;
+        let mut source = fs::File::open(long_path(path)).map_err(|e| WarlordError::io(e, path))?;
+        app_paths::write_atomic_from(&blob, &mut source).map_err(|e| WarlordError::io(e, &blob))?;
+    }
+    Ok(hash)
+}
+
+/// Keep `contents` under its hash; returns the hash.
+fn stash(contents: &[u8]) -> Result<String, WarlordError> {
+    let hash = format!("{:x}", Sha256::digest(contents));
+    let path = blob_dir().join(&hash);
+    if !path.exists() {
+        fs::create_dir_all(blob_dir()).map_err(|e| WarlordError::io(e, blob_dir()))?;
+        app_paths::write_atomic(&path, contents).map_err(|e| WarlordError::io(e, &path))?;
+    }
+    Ok(hash)
+}
+
+/// Newest first.
+pub fn list_operations(limit: usize) -> Vec<Operation> {
+    let journal: Journal = app_paths::load_json(JOURNAL_FILE);
+    journal.operations.into_iter().rev().take(limit).collect()
+}
+
+fn revert(change: &Change) -> Result<(), WarlordError> {
+    match change {
+        Change::Write { path, previous: Some(hash) } => {
+            let blob = blob_dir().join(hash);
+            let contents = fs::read(&blob).map_err(|_| WarlordError::invalid(format!("{} 的旧内容已不在撤销记录中", path)))?;
+            sandbox::write_as(path, contents, "undo").map(|_| ())
+        }
+        // Created by the operation; gone already is as good as undone
+        Change::Write { path, previous: None } if !long_path(path).exists() => Ok(()),
+        Change::Write { path, previous: None } => sandbox::delete(path),
+        Change::Delete { trash_id: Some(id), .. } => app_trash::restore_from_trash(id).map(|_| ()),
+        Change::Delete { path, trash_id: None } => Err(WarlordError::invalid(format!("{} 没有放入应用废纸篓，无法撤销删除", path))),
+        Change::Rename { from, to } => file_ops::move_path(Path::new(to), Path::new(from), |_| {}).map(|_| ()),
+    }
+}
+
+/// Revert the changes of operation `id`, newest first. When one fails the ones already
+/// reverted are dropped from the entry, so trying again continues where this stopped.
+fn undo(id: &str) -> Result<Operation, WarlordError> {
+    let operation = list_operations(usize::MAX).into_iter().find(|o| o.id == id).ok_or_else(|| WarlordError::invalid(format!("没有操作 {}", id)))?;
+    let mut reverted = 0;
+    let mut failure = None;
+    unrecorded(|| {
+        for change in operation.changes.iter().rev() {
+            if let Err(e) = revert(change) {
+                failure = Some(e);
+                break;
+            }
+            reverted += 1;
+        }
+    });
+    let remaining = operation.changes.len() - reverted;
+    let undone = with_journal(|journal| {
+        let entry = journal.operations.iter_mut().find(|o| o.id == id).ok_or_else(|| WarlordError::invalid(format!("没有操作 {}", id)))?;
+        entry.changes.truncate(remaining);
+        entry.undone = failure.is_none();
+        Ok(entry.clone())
+    })?;
+    match failure {
+        Some(e) => Err(e),
+        None => {
+            eprintln!("[WarlordTools] Undid {} ({} changes)", operation.label, operation.changes.len());
+            Ok(Operation { changes: operation.changes, ..undone })
+        }
+    }
+}
+
+/// Undo the newest operation that is not undone yet; returns it.
+pub fn undo_last_operation() -> Result<Operation, WarlordError> {
+    let last = list_operations(usize::MAX).into_iter().find(|o| !o.undone).ok_or_else(|| WarlordError::invalid("没有可撤销的操作"))?;
+    undo(&last.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undoes_a_grouped_operation() {
+        let dir = std::env::temp_dir().join("wt-journal-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (existing, created) = (dir.join("a.filter"), dir.join("b.filter"));
+        fs::write(&existing, "Show # old\n").unwrap();
+        operation("journal-test", || {
+            sandbox::write(&existing, "Show # new\n").unwrap();
+            sandbox::write(&created, "Hide\n").unwrap();
+        });
+        let entry = list_operations(usize::MAX).into_iter().find(|o| o.label == "journal-test").unwrap();
+        assert_eq!(entry.changes.len(), 2);
+        assert!(matches!(&entry.changes[1], Change::Write { previous: None, .. }));
+
+        assert!(undo(&entry.id).unwrap().undone);
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "Show # old\n");
+        assert!(!created.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn keeps_the_newest_operations() {
+        let mut journal = Journal { operations: (0..MAX_OPERATIONS + 5).map(|i| new_operation(&i.to_string())).collect() };
+        trim(&mut journal);
+        assert_eq!(journal.operations.len(), MAX_OPERATIONS);
+        assert_eq!(journal.operations[0].label, "5");
+
+        let dir = std::env::temp_dir().join("wt-journal-unrecorded-test");
+        fs::create_dir_all(&dir).unwrap();
+        operation("journal-unrecorded-test", || unrecorded(|| sandbox::write(dir.join("a.filter"), "Show\n").unwrap()));
+        assert!(!list_operations(usize::MAX).iter().any(|o| o.label == "journal-unrecorded-test"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
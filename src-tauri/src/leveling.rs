@@ -0,0 +1,236 @@
+//! Leveling plan: a list of pre-compiled filters, one per leveling phase, swapped in over the
+//! installed filter as the character levels. The level and zone level come from the game's
+//! Client.txt through `client_log`; phases only move forward, and the plan with its current
+//! phase is kept in leveling_plan.json.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::error::WarlordError;
+use crate::app_paths;
+use crate::library;
+use crate::sandbox;
+use crate::client_log::{self, LogState};
+
+const STATE_FILE: &str = "leveling_plan.json";
+
+/// One leveling phase: once the character reaches the thresholds its pre-compiled filter is installed.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelingPhase {
+    pub name: String,
+    /// Pre-compiled filter copied over the installed filter when this phase starts
+    pub source: String,
+    #[serde(default)]
+    pub min_level: u32,
+    /// Optional zone threshold from "Generating level N area" log lines (e.g. act 2 starts at 16)
+    #[serde(default)]
+    pub min_area_level: u32,
+}
+
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelingPlan {
+    pub enabled: bool,
+    /// Installed filter that gets overwritten on every phase change
+    pub filter: String,
+    /// Path of the game's Client.txt
+    pub client_log: String,
+    /// Phases in progression order
+    pub phases: Vec<LevelingPhase>,
+    /// Index of the phase currently installed (maintained by the backend)
+    #[serde(default)]
+    pub current_phase: Option<usize>,
+}
+
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelingStatus {
+    pub enabled: bool,
+    pub current_phase: Option<usize>,
+    pub phase_name: Option<String>,
+    pub level: u32,
+    pub area_level: u32,
+}
+
+static PLAN: Mutex<Option<LevelingPlan>> = Mutex::new(None);
+
+fn with_plan<R>(f: impl FnOnce(&mut LevelingPlan) -> R) -> R {
+    let mut guard = PLAN.lock().unwrap();
+    let plan = guard.get_or_insert_with(|| app_paths::load_json(STATE_FILE));
+    f(plan)
+}
+
+fn status_of(plan: &LevelingPlan, state: &LogState) -> LevelingStatus {
+    LevelingStatus {
+        enabled: plan.enabled,
+        current_phase: plan.current_phase,
+        phase_name: plan.current_phase.and_then(|i| plan.phases.get(i)).map(|p| p.name.clone()),
+        level: state.level,
+        area_level: state.area_level,
+    }
+}
+
+/// Last phase whose thresholds are all met.
+fn target_phase(plan: &LevelingPlan, state: &LogState) -> Option<usize> {
+    plan.phases
+        .iter()
+        .rposition(|p| state.level >= p.min_level && state.area_level >= p.min_area_level)
+}
+
+/// Install the phase matching `state`. Phases only move forward so a town portal back
+/// into a low-level zone doesn't revert the filter. Returns the new status if the phase changed.
+fn advance(plan: &mut LevelingPlan, state: &LogState) -> Result<Option<LevelingStatus>, WarlordError> {
+    if !plan.enabled {
+        return Ok(None);
+    }
+    let Some(target) = target_phase(plan, state) else { return Ok(None) };
+    if plan.current_phase.is_some_and(|cur| cur >= target) {
+        return Ok(None);
+    }
+    let phase = &plan.phases[target];
+    let text = fs::read(&phase.source).map_err(|e| WarlordError::io(e, &phase.source))?;
+    sandbox::write(&plan.filter, text)?;
+    eprintln!("[WarlordTools] leveling phase -> {} (level {}, area level {})", phase.name, state.level, state.area_level);
+    plan.current_phase = Some(target);
+    app_paths::save_json(STATE_FILE, plan)?;
+    Ok(Some(status_of(plan, state)))
+}
+
+/// Replace the plan, restart the log watcher and apply the matching phase right away.
+pub fn set_leveling_plan(mut plan: LevelingPlan) -> Result<LevelingStatus, WarlordError> {
+    plan.current_phase = None;
+    if plan.enabled
+        && !plan.client_log.is_empty()
+        && client_log::watched_path().as_deref() != Some(plan.client_log.as_str())
+    {
+        client_log::start_watcher(&plan.client_log)?;
+    }
+    let state = client_log::current_state();
+    with_plan(|current| {
+        *current = plan;
+        app_paths::save_json(STATE_FILE, current)?;
+        advance(current, &state)?;
+        Ok(status_of(current, &state))
+    })
+}
+
+pub fn get_leveling_plan() -> LevelingPlan {
+    with_plan(|plan| plan.clone())
+}
+
+pub fn get_leveling_status() -> LevelingStatus {
+    let state = client_log::current_state();
+    with_plan(|plan| status_of(plan, &state))
+}
+
+/// Log watcher hook: returns the new status when the installed phase changed.
+pub fn on_log_state(state: &LogState) -> Option<LevelingStatus> {
+    with_plan(|plan| match advance(plan, state) {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("[WarlordTools] {}", e);
+            None
+        }
+    })
+}
+
+fn remap_plan(plan: &mut LevelingPlan, old: &Path, new: &Path) -> usize {
+    let mut changed = 0;
+    let paths = std::iter::once(&mut plan.filter).chain(plan.phases.iter_mut().map(|p| &mut p.source));
+    for path in paths {
+        if let Some(p) = library::remap(Path::new(path.as_str()), old, new) {
+            *path = p.display().to_string();
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Point the plan at renamed/moved files. Returns how many paths changed.
+pub fn remap_paths(old: &Path, new: &Path) -> Result<usize, WarlordError> {
+    with_plan(|plan| {
+        let changed = remap_plan(plan, old, new);
+        if changed > 0 {
+            app_paths::save_json(STATE_FILE, plan)?;
+        }
+        Ok(changed)
+    })
+}
+
+/// Resume the saved plan at startup.
+pub fn resume() {
+    let plan = get_leveling_plan();
+    if plan.enabled && !plan.client_log.is_empty() {
+        match client_log::start_watcher(&plan.client_log) {
+            Ok(()) => {
+                on_log_state(&client_log::current_state());
+            }
+            Err(e) => eprintln!("[WarlordTools] failed to resume leveling plan: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phase(name: &str, source: &Path, min_level: u32, min_area_level: u32) -> LevelingPhase {
+        LevelingPhase { name: name.to_string(), source: source.display().to_string(), min_level, min_area_level }
+    }
+
+    fn at(level: u32, area_level: u32) -> LogState {
+        LogState { level, area_level, ..Default::default() }
+    }
+
+    #[test]
+    fn picks_the_last_phase_reached() {
+        let plan = LevelingPlan {
+            phases: vec![phase("act 1", Path::new("a"), 1, 0), phase("act 2", Path::new("b"), 12, 16), phase("maps", Path::new("c"), 65, 0)],
+            ..Default::default()
+        };
+        assert_eq!(target_phase(&plan, &at(0, 0)), None);
+        assert_eq!(target_phase(&plan, &at(14, 15)), Some(0));
+        assert_eq!(target_phase(&plan, &at(14, 16)), Some(1));
+        assert_eq!(target_phase(&plan, &at(70, 1)), Some(2));
+    }
+
+    #[test]
+    fn advances_but_never_goes_back() {
+        let dir = std::env::temp_dir().join("wt-leveling-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("early.filter"), "# early\n").unwrap();
+        fs::write(dir.join("late.filter"), "# late\n").unwrap();
+        let installed = dir.join("installed.filter");
+        let mut plan = LevelingPlan {
+            enabled: true,
+            filter: installed.display().to_string(),
+            phases: vec![phase("early", &dir.join("early.filter"), 1, 0), phase("late", &dir.join("late.filter"), 30, 0)],
+            ..Default::default()
+        };
+
+        assert_eq!(advance(&mut plan, &at(5, 5)).unwrap().and_then(|s| s.current_phase), Some(0));
+        assert_eq!(fs::read_to_string(&installed).unwrap(), "# early\n");
+        assert!(advance(&mut plan, &at(5, 5)).unwrap().is_none());
+        assert_eq!(advance(&mut plan, &at(31, 40)).unwrap().and_then(|s| s.phase_name), Some("late".to_string()));
+        // A later state below the threshold (another character, a log replay) keeps the late filter
+        assert!(advance(&mut plan, &at(2, 1)).unwrap().is_none());
+        assert_eq!((plan.current_phase, fs::read_to_string(&installed).unwrap().as_str()), (Some(1), "# late\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remaps_the_filter_and_phase_sources() {
+        let (old, new) = (Path::new("/lib/leveling"), Path::new("/lib/campaign"));
+        let mut plan = LevelingPlan {
+            filter: "/game/installed.filter".to_string(),
+            phases: vec![phase("act 1", &old.join("act1.filter"), 1, 0), phase("maps", Path::new("/lib/maps.filter"), 65, 0)],
+            ..Default::default()
+        };
+        assert_eq!(remap_plan(&mut plan, old, new), 1);
+        assert_eq!(plan.phases[0].source, new.join("act1.filter").display().to_string());
+        assert_eq!((plan.filter.as_str(), plan.phases[1].source.as_str()), ("/game/installed.filter", "/lib/maps.filter"));
+    }
+}
@@ -2,108 +2,1245 @@ use std::path::Path;
 use std::fs;
 use tauri::Manager;
 use tauri::Emitter;
+
+use error::WarlordError;
 pub mod powershell_opener;
 pub use powershell_opener::{open_file, open_folder, copy_file_powershell};
+pub mod app_paths;
+pub mod temp_rules;
+pub mod client_log;
+pub mod leveling;
+pub mod filter_parser;
+pub mod economy;
+pub mod provenance;
+pub mod filter_format;
+pub mod actions;
+pub mod library;
+pub mod workspace_rename;
+pub mod filter_transforms;
+pub mod block_edit;
+pub mod patch_migration;
+pub mod economy_prune;
+pub mod sound_profiles;
+pub mod strictness;
+pub mod manifest;
+pub mod pipelines;
+pub mod filter_merge;
+pub mod webhooks;
+pub mod discord_rpc;
+pub mod patches;
+pub mod preprocessor;
+pub mod obs;
+pub mod local_api;
+pub mod templates;
+pub mod quiet_hours;
+pub mod filter_split;
+pub mod filterblade;
+pub mod power;
+pub mod accounts;
+pub mod ggg_api;
+pub mod sandbox;
+pub mod backups;
+pub mod search_replace;
+pub mod poe_convert;
+pub mod workspace_health;
+pub mod downgrade;
+pub mod file_stream;
+pub mod colorblind;
+pub mod scan;
+pub mod dedupe;
+pub mod snippets;
+pub mod economy_hide;
+pub mod glob;
+pub mod wtignore;
+pub mod watcher;
+pub mod file_ops;
+pub mod duplicates;
+pub mod encoding;
+pub mod path_utils;
+pub mod error;
+pub mod disk_space;
+pub mod file_lock;
+pub mod archive;
+pub mod extractors;
+pub mod app_trash;
+pub mod folder_size;
+pub mod versions;
+pub mod library_git;
+pub mod diff;
+pub mod backup_schedule;
+pub mod library_export;
+pub mod webdav_sync;
+pub mod cloud_conflicts;
+pub mod game_sync;
+pub mod journal;
+pub mod profile;
+pub mod game_migration;
+pub mod audio;
+pub mod filter_link;
+
+#[tauri::command]
+fn open_folder_cmd(path: String) -> Result<(), WarlordError> {
+    open_folder(&path)
+}
+
+#[tauri::command]
+fn open_file_cmd(path: String) -> Result<(), WarlordError> {
+    open_file(&path)
+}
+
+#[tauri::command]
+fn copy_sound_file(src: String, dest: String) -> Result<(), WarlordError> {
+    sandbox::check_write(&dest)?;
+    let size = fs::metadata(path_utils::long_path(&src)).map_err(|e| WarlordError::io(e, &src))?.len();
+    disk_space::check_disk_space(Path::new(&dest), size)?;
+    journal::operation("copy-sound", || sandbox::copy(&src, &dest).map(|_| ()))
+}
+
+#[tauri::command]
+fn greet(name: &str) -> String {
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+#[tauri::command]
+fn scan_filter_files(path: String) -> Result<Vec<String>, WarlordError> {
+    let root = Path::new(&path);
+    if !root.exists() {
+        return Err(WarlordError::not_found(&path));
+    }
+    Ok(scan::refresh(root, &scan::ScanOptions::default())?.into_iter().map(|f| f.path).collect())
+}
+
+/// Last scan of `path` straight from the cache, for showing the library before a refresh.
+#[tauri::command]
+fn get_cached_scan(path: String, options: Option<scan::ScanOptions>) -> Vec<scan::ScannedFile> {
+    scan::cached(Path::new(&path), &options.unwrap_or_default())
+}
+
+/// `scan_filter_files` with size, modification time and content hash per file. `options`
+/// selects other file patterns (sounds, packs) and a depth limit.
+#[tauri::command]
+async fn scan_filter_files_v2(path: String, options: Option<scan::ScanOptions>) -> Result<Vec<scan::ScannedFile>, WarlordError> {
+    let root = Path::new(&path);
+    if !root.exists() {
+        return Err(WarlordError::not_found(&path));
+    }
+    scan::refresh(root, &options.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn hash_file(path: String, algo: Option<scan::HashAlgorithm>) -> Result<String, WarlordError> {
+    scan::hash_file_with(Path::new(&path), algo.unwrap_or_default()).map_err(|e| WarlordError::io(e, &path))
+}
+
+#[tauri::command]
+async fn find_duplicates(root: String) -> Result<Vec<duplicates::DuplicateGroup>, WarlordError> {
+    duplicates::find_duplicates(Path::new(&root))
+}
+
+/// Scan in the background, emitting `scan://batch` as each directory is done.
+#[tauri::command]
+fn start_scan(app: tauri::AppHandle, root: String, options: Option<scan::ScanOptions>) -> Result<u64, WarlordError> {
+    let root = std::path::PathBuf::from(root);
+    if !root.exists() {
+        return Err(WarlordError::not_found(&root));
+    }
+    Ok(scan::start(root, options.unwrap_or_default(), move |batch| {
+        let _ = app.emit("scan://batch", batch);
+    }))
+}
+
+#[tauri::command]
+fn cancel_scan(id: u64) {
+    scan::cancel(id);
+}
+
+/// Count a folder's size in the background, emitting `folder-size://progress` with running
+/// totals (the last has `done`) after `start_folder_size`. Returns the id for both it and
+/// `cancel_folder_size`.
+#[tauri::command]
+fn folder_size(app: tauri::AppHandle, path: String) -> u64 {
+    folder_size::prepare(std::path::PathBuf::from(path), move |totals| {
+        let _ = app.emit("folder-size://progress", totals);
+    })
+}
+
+#[tauri::command]
+fn start_folder_size(id: u64) -> Result<(), WarlordError> {
+    folder_size::start(id)
+}
+
+#[tauri::command]
+fn cancel_folder_size(id: u64) {
+    folder_size::cancel(id);
+}
+
+/// Emit `fs-change` for files created, modified or deleted below `root`.
+#[tauri::command]
+fn watch_directory(app: tauri::AppHandle, root: String) -> Result<(), WarlordError> {
+    Ok(watcher::watch(Path::new(&root), move |change| {
+        let _ = app.emit("fs-change", change);
+    })?)
+}
+
+#[tauri::command]
+fn unwatch_directory(root: String) {
+    watcher::unwatch(Path::new(&root));
+}
+
+#[tauri::command]
+fn read_file_content(path: String) -> Result<String, WarlordError> {
+    if path.ends_with(".filter") {
+        discord_rpc::update(Some(&path), None);
+    }
+    encoding::read_to_string(path)
+}
+
+/// `read_file_content` plus the detected encoding (GBK, UTF-16, BOM).
+#[tauri::command]
+fn read_file_with_encoding(path: String) -> Result<encoding::DecodedText, WarlordError> {
+    encoding::read_file(path)
+}
+
+/// Chunked alternative to `read_file_content` for very large filters; chunks arrive as
+/// `file-stream-chunk` events tagged with the returned id, after `start_stream`.
+#[tauri::command]
+fn open_stream(app: tauri::AppHandle, path: String) -> Result<u64, WarlordError> {
+    if path.ends_with(".filter") {
+        discord_rpc::update(Some(&path), None);
+    }
+    file_stream::open(&path, move |chunk| {
+        let _ = app.emit("file-stream-chunk", chunk);
+    })
+}
+
+/// Begin sending the chunks of a stream from `open_stream`, once its listener is set up.
+#[tauri::command]
+fn start_stream(id: u64) -> Result<(), WarlordError> {
+    file_stream::start(id)
+}
+
+#[tauri::command]
+fn close_stream(id: u64) {
+    file_stream::close(id);
+}
+
+/// Lines `start_line..=end_line` (1-based) of a file and its line count.
+#[tauri::command]
+async fn read_file_range(path: String, start_line: usize, end_line: usize) -> Result<file_stream::LineRange, WarlordError> {
+    file_stream::read_file_range(&path, start_line, end_line)
+}
+
+#[tauri::command]
+fn write_file_content(path: String, content: String) -> Result<(), WarlordError> {
+    encoding::write_preserving(path, &content)
+}
+
+/// `write_file_content` that fails with a conflict instead of overwriting a file changed on
+/// disk; returns the hash of the saved content.
+#[tauri::command]
+fn write_file_if_unchanged(path: String, content: String, expected_hash: String) -> Result<String, WarlordError> {
+    let bytes = encoding::encode_like(&path, &content)?;
+    sandbox::write_if_unchanged(path, bytes, &expected_hash)
+}
+
+#[tauri::command]
+fn delete_filter_file(path: String) -> Result<(), WarlordError> {
+    sandbox::delete(path)
+}
+
+#[tauri::command]
+fn delete_filter_folder(path: String) -> Result<(), WarlordError> {
+    sandbox::delete(path)
+}
+
+#[tauri::command]
+fn create_filter_folder(path: String) -> Result<(), WarlordError> {
+    sandbox::check_write(&path)?;
+    fs::create_dir_all(path_utils::long_path(&path)).map_err(|e| WarlordError::io(e, &path))
+}
+
+#[tauri::command]
+fn path_exists(path: String) -> Result<bool, WarlordError> {
+    Ok(path_utils::long_path(&path).exists())
+}
+
+#[tauri::command]
+fn rename_filter_file(old_path: String, new_path: String) -> Result<(), WarlordError> {
+    let new_path_ref = path_utils::long_path(&new_path);
+
+    if new_path_ref.exists() {
+        return Err(WarlordError::already_exists(&new_path));
+    }
+    sandbox::check_write(&old_path)?;
+    sandbox::check_write(&new_path)?;
+
+    fs::rename(path_utils::long_path(&old_path), new_path_ref).map_err(|e| WarlordError::write_failed(e, &old_path))?;
+    journal::record("rename", journal::Change::Rename { from: old_path, to: new_path });
+    Ok(())
+}
+
+// Rename and update every reference to the file (sounds, includes, backend state)
+#[tauri::command]
+fn rename_managed_file(old: String, new: String) -> Result<workspace_rename::RenameReport, WarlordError> {
+    journal::operation("rename", || workspace_rename::rename_managed_file(&old, &new))
+}
+
+/// Copy a folder tree, emitting `file-op-progress` per file.
+#[tauri::command]
+async fn copy_folder(app: tauri::AppHandle, src: String, dest: String, overwrite: Option<file_ops::Overwrite>) -> Result<file_ops::CopySummary, WarlordError> {
+    journal::operation("copy-folder", || {
+        file_ops::copy_folder(Path::new(&src), Path::new(&dest), overwrite.unwrap_or_default(), |progress| {
+            let _ = app.emit("file-op-progress", progress);
+        })
+    })
+}
+
+/// Move a file or folder, also to another drive; emits `file-op-progress`.
+#[tauri::command]
+async fn move_path(app: tauri::AppHandle, src: String, dest: String) -> Result<file_ops::CopySummary, WarlordError> {
+    journal::operation("move", || {
+        // One rename undoes it, also when it was copied across drives file by file
+        let summary = journal::unrecorded(|| {
+            file_ops::move_path(Path::new(&src), Path::new(&dest), |progress| {
+                let _ = app.emit("file-op-progress", progress);
+            })
+        })?;
+        journal::record("move", journal::Change::Rename { from: src, to: dest });
+        Ok(summary)
+    })
+}
+
+#[tauri::command]
+fn duplicate_filter(path: String) -> Result<String, WarlordError> {
+    journal::operation("duplicate", || file_ops::duplicate_filter(Path::new(&path)))
+}
+
+#[tauri::command]
+async fn delete_paths(paths: Vec<String>) -> Vec<file_ops::DeleteResult> {
+    journal::operation("delete", || file_ops::delete_paths(&paths))
+}
+
+/// Extract a zip, 7z or RAR filter or sound pack into `dest`, emitting `file-op-progress` per file.
+#[tauri::command]
+async fn extract_archive(app: tauri::AppHandle, zip_path: String, dest: String, options: Option<archive::ExtractOptions>) -> Result<file_ops::CopySummary, WarlordError> {
+    journal::operation("extract", || {
+        archive::extract_archive(Path::new(&zip_path), Path::new(&dest), &options.unwrap_or_default(), |progress| {
+            let _ = app.emit("file-op-progress", progress);
+        })
+    })
+}
+
+/// Where a downloaded pack's files belong; with `apply` they are installed there
+/// (`file-op-progress` per file).
+#[tauri::command]
+async fn import_pack(app: tauri::AppHandle, archive_path: String, apply: bool, destination: Option<String>) -> Result<archive::ImportPlan, WarlordError> {
+    journal::operation("import-pack", || {
+        archive::import_pack(Path::new(&archive_path), destination.as_deref().map(Path::new), apply, |progress| {
+            let _ = app.emit("file-op-progress", progress);
+        })
+    })
+}
+
+/// Zip filters together with the custom sounds they use, for sharing.
+#[tauri::command]
+fn export_pack(paths: Vec<String>, dest_zip: String) -> Result<archive::ExportReport, WarlordError> {
+    archive::export_pack(&paths, Path::new(&dest_zip))
+}
+
+/// Install a library filter into the game folder as a link instead of a copy.
+#[tauri::command]
+fn link_filter(library_path: String, game_documents_path: String) -> Result<filter_link::FilterLink, WarlordError> {
+    journal::operation("link", || filter_link::link_filter(Path::new(&library_path), Path::new(&game_documents_path)))
+}
+
+// ---- Paths ----
+
+#[tauri::command]
+fn normalize_path(path: String) -> String {
+    path_utils::normalize_path(&path)
+}
+
+#[tauri::command]
+fn join_paths(base: String, parts: Vec<String>) -> String {
+    path_utils::join_paths(&base, &parts)
+}
+
+#[tauri::command]
+fn relative_path(from: String, to: String) -> Result<String, WarlordError> {
+    path_utils::relative_path(&from, &to)
+}
+
+#[tauri::command]
+fn compare_paths(a: String, b: String) -> bool {
+    path_utils::compare_paths(&a, &b)
+}
+
+/// Free bytes where `path` would be written; fails with `diskFull` below `required_bytes`.
+#[tauri::command]
+fn check_disk_space(path: String, required_bytes: u64) -> Result<u64, WarlordError> {
+    disk_space::check_disk_space(Path::new(&path), required_bytes)
+}
+
+/// Clear the read-only attribute (of every file below a folder), once the user agreed to.
+#[tauri::command]
+fn clear_readonly(path: String) -> Result<Vec<String>, WarlordError> {
+    file_ops::clear_readonly(Path::new(&path))
+}
+
+/// Whether another program (the game, OBS) has the file open, so saving over it would fail.
+#[tauri::command]
+fn is_file_locked(path: String) -> bool {
+    file_lock::is_file_locked(Path::new(&path))
+}
+
+// ---- Backups ----
+
+#[tauri::command]
+fn list_backups(path: String) -> Vec<backups::BackupEntry> {
+    backups::list(Path::new(&path))
+}
+
+#[tauri::command]
+fn restore_backup(backup: String) -> Result<String, WarlordError> {
+    journal::operation("restore-backup", || backups::restore(Path::new(&backup)))
+}
+
+#[tauri::command]
+fn get_backup_config() -> backups::BackupConfig {
+    backups::get_config()
+}
+
+#[tauri::command]
+fn set_backup_config(config: backups::BackupConfig) -> Result<(), WarlordError> {
+    backups::set_config(&config)
+}
+
+// ---- Version history ----
+
+/// Saved versions of a filter, newest first.
+#[tauri::command]
+fn list_versions(path: String) -> Vec<versions::VersionEntry> {
+    versions::list_versions(Path::new(&path))
+}
+
+#[tauri::command]
+fn get_version_content(path: String, version_id: String) -> Result<String, WarlordError> {
+    versions::get_version_content(Path::new(&path), &version_id)
+}
+
+/// Save an earlier version over the filter; the current content stays in the history.
+#[tauri::command]
+fn restore_version(path: String, version_id: String) -> Result<(), WarlordError> {
+    journal::operation("restore-version", || versions::restore_version(Path::new(&path), &version_id))
+}
+
+#[tauri::command]
+fn get_version_retention() -> versions::RetentionPolicy {
+    versions::get_policy()
+}
+
+#[tauri::command]
+fn set_version_retention(policy: versions::RetentionPolicy) -> Result<(), WarlordError> {
+    versions::set_policy(&policy)
+}
+
+/// Apply the retention policy to the whole version history now.
+#[tauri::command]
+async fn prune_snapshots() -> versions::PruneReport {
+    versions::prune_snapshots()
+}
+
+/// Line diff between two files, versions or commits (or unsaved text), as hunks.
+#[tauri::command]
+fn diff_text(a: diff::DiffSource, b: diff::DiffSource, context: Option<usize>, ignore_cosmetic: Option<bool>) -> Result<diff::TextDiffResult, WarlordError> {
+    diff::diff_text(&a, &b, context, ignore_cosmetic.unwrap_or(false))
+}
+
+// ---- Library git history ----
+
+#[tauri::command]
+fn git_status() -> Result<library_git::GitStatus, WarlordError> {
+    library_git::status()
+}
+
+/// Make the library a git repository and turn on auto-commit.
+#[tauri::command]
+fn git_init_library() -> Result<library_git::GitStatus, WarlordError> {
+    library_git::init()
+}
+
+#[tauri::command]
+fn git_commit_library(message: String) -> Result<Option<library_git::GitCommit>, WarlordError> {
+    library_git::commit(&message)
+}
+
+#[tauri::command]
+fn git_log(limit: Option<usize>) -> Result<Vec<library_git::GitCommit>, WarlordError> {
+    library_git::log(limit.unwrap_or(200))
+}
+
+/// Restore the library, or only `path`, to a commit (as a new commit).
+#[tauri::command]
+fn git_checkout(commit_id: String, path: Option<String>) -> Result<(), WarlordError> {
+    journal::operation("git-checkout", || library_git::checkout(&commit_id, path.as_deref().map(Path::new)))
+}
+
+#[tauri::command]
+fn git_switch_branch(branch: String) -> Result<library_git::GitStatus, WarlordError> {
+    library_git::switch_branch(&branch)
+}
+
+/// Switch to the league's branch (`league/<name>`), created on first use.
+#[tauri::command]
+fn git_switch_league(league: String) -> Result<library_git::GitStatus, WarlordError> {
+    library_git::switch_branch(&library_git::league_branch(&league))
+}
+
+#[tauri::command]
+fn get_git_config() -> library_git::GitConfig {
+    library_git::get_config()
+}
+
+#[tauri::command]
+fn set_git_config(config: library_git::GitConfig) -> Result<(), WarlordError> {
+    library_git::set_config(&config)
+}
+
+#[tauri::command]
+fn get_backup_schedule() -> backup_schedule::ScheduleConfig {
+    backup_schedule::get_config()
+}
+
+#[tauri::command]
+fn set_backup_schedule(config: backup_schedule::ScheduleConfig) -> Result<(), WarlordError> {
+    backup_schedule::set_config(&config)
+}
+
+/// Ask for the folder scheduled backups go to; None when the dialog was cancelled.
+#[tauri::command]
+async fn pick_backup_destination(app: tauri::AppHandle) -> Result<Option<backup_schedule::ScheduleConfig>, WarlordError> {
+    use tauri_plugin_dialog::DialogExt;
+    let Some(picked) = app.dialog().file().set_title("选择备份保存的文件夹").blocking_pick_folder() else { return Ok(None) };
+    let folder = picked.into_path().map_err(|e| WarlordError::invalid(e.to_string()))?;
+    backup_schedule::set_destination(&folder).map(Some)
+}
+
+/// Last scheduled backup run and when the next one is due.
+#[tauri::command]
+fn backup_schedule_status() -> backup_schedule::ScheduleStatus {
+    backup_schedule::status()
+}
+
+#[tauri::command]
+async fn run_backup_now() -> Result<backup_schedule::ScheduleStatus, WarlordError> {
+    backup_schedule::run_now()
+}
+
+/// Library, settings and state in one zip with a hash manifest, encrypted when a password
+/// is given (it is not saved).
+#[tauri::command]
+async fn export_library(dest: String, password: Option<String>) -> Result<library_export::LibraryExport, WarlordError> {
+    library_export::export_library(Path::new(&dest), password.as_deref())
+}
+
+/// Restore a library export; `strategy` decides what happens to files that already exist.
+#[tauri::command]
+async fn import_library(archive: String, strategy: library_export::ImportStrategy, library_root: Option<String>, password: Option<String>) -> Result<library_export::LibraryImport, WarlordError> {
+    journal::operation("import-library", || library_export::import_library(Path::new(&archive), strategy, library_root.as_deref().map(Path::new), password.as_deref()))
+}
+
+/// Settings (not the library) in a small file, to set up another PC.
+#[tauri::command]
+fn export_profile(dest: String) -> Result<profile::Profile, WarlordError> {
+    profile::export_profile(Path::new(&dest))
+}
+
+#[tauri::command]
+fn import_profile(path: String) -> Result<profile::ProfileImport, WarlordError> {
+    journal::operation("import-profile", || profile::import_profile(Path::new(&path)))
+}
+
+/// Check a library export or scheduled backup against its manifest.
+#[tauri::command]
+async fn verify_backup(archive: String, password: Option<String>) -> Result<archive::BackupVerification, WarlordError> {
+    archive::verify_backup(Path::new(&archive), password.as_deref())
+}
+
+// ---- WebDAV sync ----
+
+#[tauri::command]
+fn get_webdav_config() -> webdav_sync::WebDavConfig {
+    webdav_sync::get_config()
+}
+
+#[tauri::command]
+fn set_webdav_config(config: webdav_sync::WebDavConfig) -> Result<(), WarlordError> {
+    webdav_sync::set_config(&config)
+}
+
+/// Differences between the library and the server; with `apply`, sync everything but conflicts.
+#[tauri::command]
+async fn webdav_sync(apply: bool) -> Result<webdav_sync::SyncReport, WarlordError> {
+    journal::operation("sync", || webdav_sync::sync(apply))
+}
+
+/// Forget the last synced state, after the server folder was moved or wiped on purpose.
+#[tauri::command]
+fn webdav_reset_state() -> Result<(), WarlordError> {
+    webdav_sync::reset_state()
+}
+
+#[tauri::command]
+async fn webdav_resolve_conflict(path: String, resolution: webdav_sync::Resolution) -> Result<(), WarlordError> {
+    journal::operation("resolve-conflict", || webdav_sync::resolve_conflict(&path, resolution))
+}
+
+/// Settle a conflict copy a sync tool left next to a file (see `ScannedFile::conflict_of`).
+#[tauri::command]
+fn resolve_cloud_conflict(path: String, resolution: cloud_conflicts::Resolution) -> Result<cloud_conflicts::ConflictResolution, WarlordError> {
+    journal::operation("resolve-conflict", || cloud_conflicts::resolve(Path::new(&path), resolution))
+}
+
+// ---- Game folder sync ----
+
+#[tauri::command]
+fn get_game_sync_config() -> game_sync::GameSyncConfig {
+    game_sync::get_config()
+}
+
+#[tauri::command]
+fn set_game_sync_config(config: game_sync::GameSyncConfig) -> Result<(), WarlordError> {
+    game_sync::set_config(&config)
+}
+
+/// Mirror a sync profile's filters between the library and the game folder; `directions`
+/// overrides the direction picked for a filter.
+#[tauri::command]
+async fn sync_to_game(profile: String, dry_run: bool, directions: Option<HashMap<String, game_sync::Direction>>) -> Result<game_sync::GameSyncReport, WarlordError> {
+    journal::operation("game-sync", || game_sync::sync_to_game(&profile, dry_run, &directions.unwrap_or_default()))
+}
+
+/// Copy the filters kept in the game folder (every game documents folder when `game_dir` is
+/// None) into the library; with `replace_with_links` the originals become links to the copies.
+#[tauri::command]
+async fn migrate_from_game_folder(game_dir: Option<String>, replace_with_links: bool) -> Result<game_migration::MigrationReport, WarlordError> {
+    journal::operation("migrate", || game_migration::migrate_from_game_folder(game_dir.as_deref().map(Path::new), replace_with_links))
+}
+
+#[tauri::command]
+fn list_migrations() -> Vec<game_migration::MigratedFilter> {
+    game_migration::migrations()
+}
+
+// ---- Undo ----
+
+/// Newest first.
+#[tauri::command]
+fn list_operations(limit: Option<usize>) -> Vec<journal::Operation> {
+    journal::list_operations(limit.unwrap_or(50))
+}
+
+/// Revert the newest file operation that is not undone yet, also from before a restart.
+#[tauri::command]
+fn undo_last_operation() -> Result<journal::Operation, WarlordError> {
+    journal::undo_last_operation()
+}
+
+// ---- Trash ----
+
+#[tauri::command]
+fn list_trash() -> Vec<app_trash::TrashEntry> {
+    app_trash::list_trash()
+}
+
+/// Put a deleted file or folder back; returns where it was restored to.
+#[tauri::command]
+fn restore_from_trash(id: String) -> Result<String, WarlordError> {
+    journal::operation("restore-trash", || app_trash::restore_from_trash(&id))
+}
+
+/// Empty the trash, or only the entries in `ids`.
+#[tauri::command]
+fn empty_trash(ids: Option<Vec<String>>) -> Result<usize, WarlordError> {
+    app_trash::empty_trash(ids.as_deref())
+}
+
+// ---- Structured filter access ----
+
+#[tauri::command]
+fn filter_to_json(path: String) -> Result<String, WarlordError> {
+    Ok(filter_parser::parse_file(&path)?.to_json()?)
+}
+
+#[tauri::command]
+fn json_to_filter(json: String, path: String) -> Result<(), WarlordError> {
+    let doc = filter_parser::FilterDocument::from_json(&json)?;
+    journal::operation("save", || filter_parser::write_file(&path, &doc))
+}
+
+#[tauri::command]
+fn format_filter(path: String, options: filter_format::FormatOptions) -> Result<String, WarlordError> {
+    journal::operation("format", || filter_format::format_file(&path, &options))
+}
+
+#[tauri::command]
+fn canonicalize_filter(path: String) -> Result<String, WarlordError> {
+    filter_format::canonicalize_filter(&path)
+}
+
+#[tauri::command]
+fn dedupe_filter(path: String, apply: bool) -> Result<dedupe::DedupeReport, WarlordError> {
+    journal::operation("dedupe", || dedupe::dedupe_filter(&path, apply))
+}
+
+#[tauri::command]
+fn minify_filter(src: String, dest: String, options: filter_format::MinifyOptions) -> Result<filter_format::MinifyReport, WarlordError> {
+    journal::operation("minify", || filter_format::minify_file(&src, &dest, &options))
+}
+
+#[tauri::command]
+fn rename_basetype(workspace: String, from: String, to: String) -> Result<filter_transforms::WorkspaceChanges, WarlordError> {
+    journal::operation("rename-basetype", || filter_transforms::rename_basetype(&workspace, &from, &to))
+}
+
+#[tauri::command]
+fn replace_color(path: String, from_rgba: String, to_rgba: String, scope: Option<String>) -> Result<filter_transforms::FileChange, WarlordError> {
+    journal::operation("replace-color", || filter_transforms::replace_color(&path, &from_rgba, &to_rgba, scope.as_deref()))
+}
+
+#[tauri::command]
+fn swap_alert_sound(path: String, from: String, to: String, scope: Option<String>) -> Result<filter_transforms::FileChange, WarlordError> {
+    journal::operation("swap-sound", || filter_transforms::swap_alert_sound(&path, &from, &to, scope.as_deref()))
+}
+
+#[tauri::command]
+fn sort_value_lists(path: String) -> Result<filter_transforms::FileChange, WarlordError> {
+    journal::operation("sort-lists", || filter_transforms::sort_value_lists(&path))
+}
+
+#[tauri::command]
+fn adjust_alert_volumes(path: String, adjust: filter_transforms::VolumeAdjust) -> Result<filter_transforms::FileChange, WarlordError> {
+    journal::operation("adjust-volumes", || filter_transforms::adjust_alert_volumes(&path, &adjust))
+}
+
+#[tauri::command]
+fn plan_patch_migration(workspace: String, patch_data: patch_migration::PatchData) -> Result<patch_migration::MigrationPlan, WarlordError> {
+    patch_migration::plan_patch_migration(&workspace, &patch_data)
+}
+
+#[tauri::command]
+fn apply_patch_migration(workspace: String, patch_data: patch_migration::PatchData) -> Result<filter_transforms::WorkspaceChanges, WarlordError> {
+    journal::operation("patch-migration", || patch_migration::apply_patch_migration(&workspace, &patch_data))
+}
+
+#[tauri::command]
+fn move_block(path: String, block_id: usize, direction: String) -> Result<usize, WarlordError> {
+    let up = match direction.as_str() {
+        "up" => true,
+        "down" => false,
+        _ => return Err(WarlordError::invalid(format!("未知的方向: {}", direction))),
+    };
+    journal::operation("edit-block", || block_edit::edit_file(&path, |doc| block_edit::move_block_by(doc, block_id, up)))
+}
+
+#[tauri::command]
+fn move_block_to(path: String, block_id: usize, index: usize) -> Result<usize, WarlordError> {
+    journal::operation("edit-block", || block_edit::edit_file(&path, |doc| block_edit::move_block(doc, block_id, index, block_edit::Side::AfterPrev)))
+}
+
+#[tauri::command]
+fn move_block_to_section(path: String, block_id: usize, section: String, at_end: bool) -> Result<usize, WarlordError> {
+    journal::operation("edit-block", || block_edit::edit_file(&path, |doc| block_edit::move_block_to_section(doc, block_id, &section, at_end)))
+}
+
+#[tauri::command]
+async fn search_replace(root: String, pattern: String, replacement: String, options: search_replace::SearchOptions) -> Result<search_replace::SearchReport, WarlordError> {
+    journal::operation("search-replace", || search_replace::search_replace(&root, &pattern, &replacement, &options))
+}
+
+#[tauri::command]
+async fn search_library(root: String, query: String, options: Option<search_replace::SearchOptions>) -> Result<search_replace::LibrarySearch, WarlordError> {
+    search_replace::search_library(&root, &query, &options.unwrap_or_default())
+}
+
+#[tauri::command]
+fn update_block(path: String, block_id: usize, changes: block_edit::BlockChanges) -> Result<filter_parser::Block, WarlordError> {
+    journal::operation("edit-block", || block_edit::edit_file(&path, |doc| block_edit::update_block(doc, block_id, &changes)))
+}
+
+#[tauri::command]
+fn compile_filter(src: String, dest: String) -> Result<preprocessor::CompileReport, WarlordError> {
+    journal::operation("compile", || preprocessor::compile_filter(&src, &dest))
+}
+
+#[tauri::command]
+fn convert_poe1_filter(src: String, dest: String) -> Result<poe_convert::ConversionReport, WarlordError> {
+    journal::operation("convert", || poe_convert::convert_poe1_filter(&src, &dest))
+}
+
+#[tauri::command]
+fn downgrade_filter(src: String, dest: String, version: String) -> Result<downgrade::DowngradeReport, WarlordError> {
+    journal::operation("downgrade", || downgrade::downgrade_filter(&src, &dest, &version))
+}
+
+#[tauri::command]
+fn apply_filterblade_export(base: String, export_path: String, dest: String) -> Result<filterblade::ApplyReport, WarlordError> {
+    journal::operation("filterblade", || filterblade::apply_filterblade_export(&base, &export_path, &dest))
+}
+
+#[tauri::command]
+fn split_filter(path: String, dest_dir: String) -> Result<filter_split::SplitReport, WarlordError> {
+    journal::operation("split", || filter_split::split_filter(&path, &dest_dir))
+}
+
+#[tauri::command]
+fn join_filter(dir: String, dest: String) -> Result<filter_split::SplitReport, WarlordError> {
+    journal::operation("join", || filter_split::join_filter(&dir, &dest))
+}
+
+#[tauri::command]
+fn generate_filter(template_path: String, params_json: String, dest: String) -> Result<templates::GenerateReport, WarlordError> {
+    journal::operation("generate", || templates::generate_filter(&template_path, &params_json, &dest))
+}
+
+#[tauri::command]
+fn build_filter(src: String, dest: String) -> Result<preprocessor::BuildReport, WarlordError> {
+    journal::operation("build", || preprocessor::build_filter(&src, &dest))
+}
+
+#[tauri::command]
+fn merge_filters(base: String, addition: String, dest: String) -> Result<filter_merge::MergeReport, WarlordError> {
+    journal::operation("merge", || filter_merge::merge_filters(&base, &addition, &dest))
+}
 
 #[tauri::command]
-fn open_folder_cmd(path: String) -> Result<(), String> {
-    open_folder(&path)
+fn get_block_provenance(path: String, block_id: usize) -> Result<Option<provenance::Provenance>, WarlordError> {
+    let doc = filter_parser::parse_file(&path)?;
+    let block = doc.blocks.get(block_id).ok_or_else(|| WarlordError::invalid(format!("没有第 {} 个规则块", block_id)))?;
+    Ok(provenance::read(block))
 }
 
 #[tauri::command]
-fn open_file_cmd(path: String) -> Result<(), String> {
-    open_file(&path)
+fn set_filter_strictness(path: String, level: u32) -> Result<strictness::StrictnessReport, WarlordError> {
+    journal::operation("strictness", || strictness::set_strictness(&path, level))
 }
 
+// ---- Snippets ----
+
 #[tauri::command]
-fn copy_sound_file(src: String, dest: String) -> Result<(), String> {
-    copy_file_powershell(&src, &dest)
+fn list_snippets() -> Vec<snippets::Snippet> {
+    snippets::list_snippets()
 }
 
 #[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
+fn save_snippet(id: Option<String>, name: String, description: Option<String>, text: String) -> Result<snippets::Snippet, WarlordError> {
+    snippets::save_snippet(id.as_deref(), &name, description.as_deref().unwrap_or(""), &text)
 }
 
 #[tauri::command]
-fn scan_filter_files(path: String) -> Result<Vec<String>, String> {
-    let mut filters = Vec::new();
-    let root = Path::new(&path);
+fn delete_snippet(id: String) -> Result<(), WarlordError> {
+    snippets::delete_snippet(&id)
+}
 
-    if !root.exists() {
-        return Err("Path does not exist".to_string());
-    }
+#[tauri::command]
+fn insert_snippet(filter_path: String, snippet_id: String, position: snippets::SnippetPosition) -> Result<usize, WarlordError> {
+    journal::operation("insert-snippet", || snippets::insert_snippet(&filter_path, &snippet_id, &position))
+}
 
-    fn visit_dirs(dir: &Path, filters: &mut Vec<String>) -> std::io::Result<()> {
-        if dir.is_dir() {
-            for entry in fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                if path.is_dir() {
-                    visit_dirs(&path, filters)?;
-                } else {
-                    if let Some(ext) = path.extension() {
-                        if ext == "filter" {
-                            if let Some(path_str) = path.to_str() {
-                                filters.push(path_str.to_string());
-                            }
-                        }
-                    }
-                }
-            }
+// ---- Export pipelines ----
+
+#[tauri::command]
+async fn run_pipeline(app: tauri::AppHandle, name: String, workspace: Option<String>) -> Result<pipelines::PipelineReport, WarlordError> {
+    let workspace = manifest::resolve_workspace(workspace.as_deref())?;
+    let report = journal::operation("pipeline", || pipelines::run_pipeline(&workspace, &name))?;
+    if !quiet_hours::mutes_notifications() {
+        for message in &report.notifications {
+            let _ = app.emit("pipeline-notification", message);
         }
-        Ok(())
     }
+    Ok(report)
+}
 
-    match visit_dirs(root, &mut filters) {
-        Ok(_) => Ok(filters),
-        Err(e) => Err(e.to_string()),
-    }
+#[tauri::command]
+async fn workspace_health(workspace: Option<String>) -> Result<workspace_health::HealthReport, WarlordError> {
+    workspace_health::workspace_health(workspace.as_deref())
 }
 
 #[tauri::command]
-fn read_file_content(path: String) -> Result<String, String> {
-    fs::read_to_string(path).map_err(|e| e.to_string())
+fn list_pipelines(workspace: Option<String>) -> Result<Vec<String>, WarlordError> {
+    let workspace = manifest::resolve_workspace(workspace.as_deref())?;
+    Ok(manifest::load(&workspace)?.pipelines.into_keys().collect())
+}
+
+// ---- Webhooks ----
+
+#[tauri::command]
+fn get_webhooks() -> Vec<webhooks::Webhook> {
+    webhooks::get_webhooks()
 }
 
 #[tauri::command]
-fn write_file_content(path: String, content: String) -> Result<(), String> {
-    fs::write(path, content).map_err(|e| e.to_string())
+fn set_webhooks(hooks: Vec<webhooks::Webhook>) -> Result<(), WarlordError> {
+    webhooks::set_webhooks(&hooks)
 }
 
 #[tauri::command]
-fn delete_filter_file(path: String) -> Result<(), String> {
-    fs::remove_file(path).map_err(|e| e.to_string())
+async fn test_webhook(url: String) -> Result<(), WarlordError> {
+    webhooks::post(&url, &webhooks::payload("test", "WarlordTools", "Webhook 测试消息", true))
 }
 
+// ---- Discord Rich Presence ----
+
 #[tauri::command]
-fn delete_filter_folder(path: String) -> Result<(), String> {
-    fs::remove_dir_all(path).map_err(|e| e.to_string())
+fn get_discord_presence() -> discord_rpc::RpcConfig {
+    discord_rpc::get_config()
 }
 
 #[tauri::command]
-fn create_filter_folder(path: String) -> Result<(), String> {
-    fs::create_dir_all(path).map_err(|e| e.to_string())
+fn set_discord_presence(enabled: bool, client_id: Option<String>) -> Result<(), WarlordError> {
+    discord_rpc::set_enabled(enabled, client_id)
 }
 
+// ---- OBS (obs-websocket) ----
+
 #[tauri::command]
-fn path_exists(path: String) -> Result<bool, String> {
-    Ok(Path::new(&path).exists())
+fn get_obs_config() -> obs::ObsConfig {
+    obs::get_config()
 }
 
 #[tauri::command]
-fn rename_filter_file(old_path: String, new_path: String) -> Result<(), String> {
-    let new_path_ref = Path::new(&new_path);
+fn set_obs_config(config: obs::ObsConfig) -> Result<(), WarlordError> {
+    obs::set_config(&config)
+}
 
-    if new_path_ref.exists() {
-        return Err("目标文件已存在".to_string());
+#[tauri::command]
+fn get_local_api_config() -> local_api::LocalApiConfig {
+    local_api::get_config()
+}
+
+#[tauri::command]
+fn set_local_api_config(config: local_api::LocalApiConfig) -> Result<local_api::LocalApiConfig, WarlordError> {
+    local_api::set_config(&config)
+}
+
+#[tauri::command]
+async fn test_obs_connection(config: obs::ObsConfig) -> Result<String, WarlordError> {
+    obs::test_connection(&config)
+}
+
+#[tauri::command]
+async fn obs_showcase(active: bool) -> Result<(), WarlordError> {
+    obs::showcase(active)
+}
+
+// ---- Spectator (read-only) workspaces ----
+
+#[tauri::command]
+fn get_path_capabilities(path: String) -> sandbox::Capabilities {
+    sandbox::capabilities(&path)
+}
+
+#[tauri::command]
+fn get_spectator_config() -> sandbox::SpectatorConfig {
+    sandbox::get_config()
+}
+
+#[tauri::command]
+fn set_spectator_config(config: sandbox::SpectatorConfig) -> Result<(), WarlordError> {
+    sandbox::set_config(&config)
+}
+
+/// Folders file changes are allowed under; anything else fails with `OUTSIDE_SANDBOX`.
+#[tauri::command]
+fn get_sandbox_roots() -> Vec<sandbox::SandboxRoot> {
+    sandbox::roots()
+}
+
+/// Let the user pick a folder to allow changes under; the webview never names the path, so
+/// it cannot widen the sandbox on its own. None when the dialog was cancelled.
+#[tauri::command]
+async fn add_sandbox_root(app: tauri::AppHandle) -> Result<Option<String>, WarlordError> {
+    use tauri_plugin_dialog::DialogExt;
+    let Some(picked) = app.dialog().file().set_title("选择允许修改的文件夹").blocking_pick_folder() else { return Ok(None) };
+    let path = picked.into_path().map_err(|e| WarlordError::invalid(e.to_string()))?;
+    let path = path.display().to_string();
+    sandbox::add_root(&path)?;
+    Ok(Some(path))
+}
+
+#[tauri::command]
+fn remove_sandbox_root(path: String) -> Result<(), WarlordError> {
+    sandbox::remove_root(&path)
+}
+
+// ---- GGG accounts ----
+
+#[tauri::command]
+fn list_accounts() -> Vec<accounts::AccountInfo> {
+    accounts::list_accounts()
+}
+
+#[tauri::command]
+fn save_account(name: String, server: String, access_token: Option<String>, session_cookie: Option<String>) -> Result<accounts::AccountInfo, WarlordError> {
+    accounts::save_account(&name, &server, access_token, session_cookie)
+}
+
+#[tauri::command]
+fn remove_account(id: String) -> Result<(), WarlordError> {
+    accounts::remove_account(&id)
+}
+
+#[tauri::command]
+fn set_active_account(id: String) -> Result<(), WarlordError> {
+    accounts::set_active_account(&id)
+}
+
+#[tauri::command]
+async fn list_characters(account_id: Option<String>) -> Result<serde_json::Value, WarlordError> {
+    ggg_api::list_characters(account_id.as_deref())
+}
+
+#[tauri::command]
+async fn list_stashes(league: String, account_id: Option<String>) -> Result<serde_json::Value, WarlordError> {
+    ggg_api::list_stashes(account_id.as_deref(), &league)
+}
+
+#[tauri::command]
+async fn upload_filter(path: String, name: String, account_id: Option<String>) -> Result<String, WarlordError> {
+    ggg_api::upload_filter(account_id.as_deref(), &path, &name)
+}
+
+// ---- Battery awareness ----
+
+#[tauri::command]
+fn get_power_status() -> power::PowerStatus {
+    power::status()
+}
+
+#[tauri::command]
+fn get_power_config() -> power::PowerConfig {
+    power::get_config()
+}
+
+#[tauri::command]
+fn set_power_config(config: power::PowerConfig) -> Result<(), WarlordError> {
+    power::set_config(&config)
+}
+
+// ---- Quiet hours ----
+
+#[tauri::command]
+fn get_quiet_hours() -> quiet_hours::QuietHours {
+    quiet_hours::get_quiet_hours()
+}
+
+#[tauri::command]
+fn set_quiet_hours(config: quiet_hours::QuietHours) -> Result<(), WarlordError> {
+    quiet_hours::set_quiet_hours(&config)
+}
+
+#[tauri::command]
+fn get_quiet_status() -> quiet_hours::QuietStatus {
+    quiet_hours::status()
+}
+
+#[tauri::command]
+fn get_colorblind_config() -> colorblind::ColorBlindConfig {
+    colorblind::get_colorblind_config()
+}
+
+#[tauri::command]
+fn set_colorblind_config(config: colorblind::ColorBlindConfig) -> Result<(), WarlordError> {
+    colorblind::set_colorblind_config(&config)
+}
+
+#[tauri::command]
+fn compile_colorblind_filter(path: String, deficiency: String) -> Result<filter_transforms::FileChange, WarlordError> {
+    journal::operation("colorblind", || colorblind::compile_colorblind_variant(&path, &deficiency))
+}
+
+#[tauri::command]
+fn compile_quiet_filter(path: String) -> Result<filter_transforms::FileChange, WarlordError> {
+    journal::operation("quiet-variant", || quiet_hours::compile_quiet_variant(&path))
+}
+
+// ---- Sound preview ----
+
+/// Preview a sound in the app; `volume` is a factor of the file's loudness (1.0 by default),
+/// turned down further during quiet hours.
+#[tauri::command]
+fn play_sound(path: String, volume: Option<f32>) -> Result<(), WarlordError> {
+    audio::play_sound(Path::new(&path), volume)
+}
+
+/// Play `paths` one after the other, e.g. every sound of a pack; progress arrives as
+/// `sound-progress` events.
+#[tauri::command]
+fn queue_sounds(paths: Vec<String>, volume: Option<f32>) -> Result<(), WarlordError> {
+    audio::queue_sounds(paths.into_iter().map(std::path::PathBuf::from).collect(), volume)
+}
+
+#[tauri::command]
+fn stop_sound() {
+    audio::stop_sound()
+}
+
+#[tauri::command]
+fn seek_sound(position_ms: u64) -> Result<(), WarlordError> {
+    audio::seek(std::time::Duration::from_millis(position_ms))
+}
+
+// ---- Per-area-tier sound profiles ----
+
+#[tauri::command]
+fn get_sound_profiles() -> sound_profiles::SoundProfiles {
+    sound_profiles::get_sound_profiles()
+}
+
+#[tauri::command]
+fn set_sound_profiles(profiles: sound_profiles::SoundProfiles) -> Result<(), WarlordError> {
+    sound_profiles::set_sound_profiles(&profiles)
+}
+
+#[tauri::command]
+fn compile_sound_profiles(path: String) -> Result<usize, WarlordError> {
+    journal::operation("sound-profiles", || sound_profiles::compile_sound_profiles(&path))
+}
+
+// ---- Command palette ----
+
+#[tauri::command]
+fn list_available_actions(context: actions::ActionContext) -> Vec<actions::ActionInfo> {
+    actions::list_actions(&context)
+}
+
+/// Async like the scans and syncs it can run, so a long action does not block the window.
+#[tauri::command]
+async fn invoke_action(id: String, args: serde_json::Value) -> Result<serde_json::Value, WarlordError> {
+    actions::invoke(&id, &args)
+}
+
+// ---- Economy (poe.ninja prices) ----
+
+#[tauri::command]
+async fn refresh_economy(league: String) -> Result<usize, WarlordError> {
+    discord_rpc::update(None, Some(&league));
+    Ok(economy::refresh_prices(&league)?.entries.len())
+}
+
+#[tauri::command]
+async fn tag_filter_economy(path: String, league: String) -> Result<Vec<economy::BlockValue>, WarlordError> {
+    let cache = match economy::load_cache(&league) {
+        Some(cache) => cache,
+        None => economy::refresh_prices(&league)?,
+    };
+    let doc = filter_parser::parse_file(&path)?;
+    Ok(economy::tag_blocks(&doc, &cache))
+}
+
+#[tauri::command]
+fn find_dead_economy(path: String, league: String, weeks: u64, max_chaos: f64) -> Result<Vec<economy_prune::DeadItem>, WarlordError> {
+    let history = economy::load_history(&league);
+    if history.samples.is_empty() {
+        return Err(WarlordError::invalid("没有价格历史, 请先刷新价格"));
     }
+    let doc = filter_parser::parse_file(&path)?;
+    Ok(economy_prune::find_dead_items(&doc, &history, weeks, max_chaos, app_paths::now_secs()))
+}
+
+#[tauri::command]
+fn generate_hide_blocks(path: String, league: String, threshold: f64, groups: Option<Vec<String>>, section: String) -> Result<economy_hide::HideReport, WarlordError> {
+    let cache = economy::load_cache(&league).ok_or_else(|| WarlordError::invalid("没有该赛区的价格缓存, 请先刷新价格"))?;
+    let groups = groups.unwrap_or_else(|| economy_hide::GROUPS.iter().map(|g| g.to_string()).collect());
+    journal::operation("hide-blocks", || economy_hide::generate_hide_blocks(&path, &cache, threshold, &groups, &section))
+}
+
+#[tauri::command]
+fn demote_dead_items(path: String, items: Vec<economy_prune::Demotion>) -> Result<filter_transforms::FileChange, WarlordError> {
+    journal::operation("demote", || economy_prune::demote_items(&path, &items))
+}
+
+// ---- Patch layers ----
+
+#[tauri::command]
+fn create_patch(name: String, target: String, ops: Vec<patches::PatchOp>) -> Result<patches::Patch, WarlordError> {
+    patches::create_patch(&name, &target, ops)
+}
+
+#[tauri::command]
+fn list_patches() -> Vec<patches::Patch> {
+    patches::list_patches()
+}
+
+#[tauri::command]
+fn apply_patch(name: String) -> Result<patches::ApplyReport, WarlordError> {
+    journal::operation("apply-patch", || patches::apply_patch(&name))
+}
+
+#[tauri::command]
+fn invert_patch(name: String) -> Result<patches::ApplyReport, WarlordError> {
+    journal::operation("invert-patch", || patches::invert_patch(&name))
+}
+
+/// Re-apply the patch in the background whenever its target is updated.
+#[tauri::command]
+fn set_patch_auto_reapply(name: String, auto_reapply: bool) -> Result<patches::Patch, WarlordError> {
+    patches::set_auto_reapply(&name, auto_reapply)
+}
+
+#[tauri::command]
+fn delete_patch(name: String) -> Result<(), WarlordError> {
+    patches::delete_patch(&name)
+}
+
+// ---- Temporary rules ----
+// ttl is in seconds
+
+#[tauri::command]
+fn add_temp_rule(filter: String, rule: String, ttl: u64) -> Result<temp_rules::TempRule, WarlordError> {
+    journal::operation("temp-rule", || temp_rules::add_temp_rule(&filter, &rule, ttl))
+}
+
+#[tauri::command]
+fn remove_temp_rule(id: String) -> Result<(), WarlordError> {
+    journal::operation("temp-rule", || temp_rules::remove_temp_rule(&id))
+}
+
+#[tauri::command]
+fn list_temp_rules() -> Vec<temp_rules::TempRule> {
+    temp_rules::list_temp_rules()
+}
 
-    fs::rename(&old_path, new_path_ref).map_err(|e| e.to_string())
+// ---- Client.txt watcher / leveling automation ----
+
+#[tauri::command]
+fn start_log_watcher(path: String) -> Result<(), WarlordError> {
+    client_log::start_watcher(&path)
+}
+
+#[tauri::command]
+fn get_log_state() -> client_log::LogState {
+    client_log::current_state()
+}
+
+#[tauri::command]
+fn set_leveling_plan(plan: leveling::LevelingPlan) -> Result<leveling::LevelingStatus, WarlordError> {
+    leveling::set_leveling_plan(plan)
 }
 
 #[tauri::command]
-async fn create_overlay_window(app: tauri::AppHandle, label: String, target_url: String) -> Result<(), String> {
+fn get_leveling_plan() -> leveling::LevelingPlan {
+    leveling::get_leveling_plan()
+}
+
+#[tauri::command]
+fn get_leveling_status() -> leveling::LevelingStatus {
+    leveling::get_leveling_status()
+}
+
+#[tauri::command]
+async fn create_overlay_window(app: tauri::AppHandle, label: String, target_url: String) -> Result<(), WarlordError> {
     if app.get_webview_window(&label).is_some() {
         return Ok(());
     }
@@ -130,11 +1267,15 @@ async fn create_overlay_window(app: tauri::AppHandle, label: String, target_url:
         .build()
         .map_err(|e| e.to_string())?;
 
+    if obs::is_showcase_window(&label) {
+        obs::showcase_in_background(true);
+    }
+
     Ok(())
 }
 
 #[tauri::command]
-fn get_clipboard_text() -> Result<String, String> {
+fn get_clipboard_text() -> Result<String, WarlordError> {
     let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
     clipboard.get_text().map_err(|e| e.to_string())
 }
@@ -146,11 +1287,12 @@ struct TradeSearchResult {
 }
 
 #[tauri::command]
-fn fetch_leagues(server: String, session_cookie: String) -> Result<Vec<String>, String> {
+fn fetch_leagues(server: String, session_cookie: String) -> Result<Vec<String>, WarlordError> {
+    let session_cookie = accounts::session_cookie(&server, &session_cookie);
     let api_base = match server.as_str() {
         "intl" => "https://www.pathofexile.com",
         "cn" => "https://poe.game.qq.com",
-        _ => return Err("Invalid server".to_string()),
+        _ => return Err(WarlordError::invalid("未知的服务器")),
     };
 
     let url = match server.as_str() {
@@ -166,7 +1308,7 @@ fn fetch_leagues(server: String, session_cookie: String) -> Result<Vec<String>,
         req = req.set("Cookie", &session_cookie);
     }
 
-    let resp = req.call().map_err(|e| format!("获取联赛列表失败: {}", e))?;
+    let resp = req.call().map_err(|e| WarlordError::Network { message: format!("获取联赛列表失败: {}", e) })?;
     let body: serde_json::Value = resp.into_json().map_err(|e| e.to_string())?;
 
     let leagues: Vec<String> = body["result"]
@@ -180,7 +1322,8 @@ fn fetch_leagues(server: String, session_cookie: String) -> Result<Vec<String>,
 }
 
 #[tauri::command]
-async fn search_trade(item_name: String, item_type: String, mod_text: String, league: String, server: String, session_cookie: String) -> Result<TradeSearchResult, String> {
+async fn search_trade(item_name: String, item_type: String, mod_text: String, league: String, server: String, session_cookie: String) -> Result<TradeSearchResult, WarlordError> {
+    let session_cookie = accounts::session_cookie(&server, &session_cookie);
     // Match EE2 exactly:
     // POST https://{host}/api/trade2/search/{league}
     // Headers: only Accept + Content-Type
@@ -194,7 +1337,7 @@ async fn search_trade(item_name: String, item_type: String, mod_text: String, le
             "https://poe.game.qq.com",
             "https://poe.game.qq.com/trade2/search/poe2",
         ),
-        _ => return Err("Invalid server".to_string()),
+        _ => return Err(WarlordError::invalid("未知的服务器")),
     };
 
     let api_url = match server.as_str() {
@@ -257,7 +1400,7 @@ async fn search_trade(item_name: String, item_type: String, mod_text: String, le
         .send_json(&query)
         .map_err(|e| {
             let err_str = e.to_string();
-            if err_str.contains("status 401") || err_str.contains("Unauthorized") {
+            let message = if err_str.contains("status 401") || err_str.contains("Unauthorized") {
                 format!(
                     "401 未登录。请按以下步骤获取 Cookie：\n\
                     1. 浏览器打开 {} 并登录\n\
@@ -273,7 +1416,8 @@ async fn search_trade(item_name: String, item_type: String, mod_text: String, le
                 "访问被拒绝，可能需要完成人机验证。请在浏览器中打开市集完成验证后再试".to_string()
             } else {
                 format!("市集搜索失败: {}", err_str)
-            }
+            };
+            WarlordError::Network { message }
         })?;
 
     let body: serde_json::Value = resp.into_json().map_err(|e| e.to_string())?;
@@ -288,12 +1432,12 @@ async fn search_trade(item_name: String, item_type: String, mod_text: String, le
 }
 
 #[tauri::command]
-async fn open_login_window(app: tauri::AppHandle, server: String) -> Result<(), String> {
+async fn open_login_window(app: tauri::AppHandle, server: String) -> Result<(), WarlordError> {
     let label = format!("login-{}", server);
     let url = match server.as_str() {
         "cn" => "https://poe.game.qq.com/trade2/",
         "intl" => "https://www.pathofexile.com/trade2/",
-        _ => return Err("Invalid server".to_string()),
+        _ => return Err(WarlordError::invalid("未知的服务器")),
     };
 
     // Close existing login window if any
@@ -315,10 +1459,10 @@ async fn open_login_window(app: tauri::AppHandle, server: String) -> Result<(),
 }
 
 #[tauri::command]
-async fn check_login_status(app: tauri::AppHandle, server: String) -> Result<bool, String> {
+async fn check_login_status(app: tauri::AppHandle, server: String) -> Result<bool, WarlordError> {
     let label = format!("login-{}", server);
     let window = app.get_webview_window(&label)
-        .ok_or("登录窗口未打开")?;
+        .ok_or_else(|| WarlordError::invalid("登录窗口未打开"))?;
     let current_url = window.url().map_err(|e| e.to_string())?;
     // Logged in = user is on the trade site (not a login/oauth page)
     let url_str = current_url.to_string();
@@ -327,7 +1471,7 @@ async fn check_login_status(app: tauri::AppHandle, server: String) -> Result<boo
 }
 
 #[tauri::command]
-async fn close_login_window(app: tauri::AppHandle, server: String) -> Result<(), String> {
+async fn close_login_window(app: tauri::AppHandle, server: String) -> Result<(), WarlordError> {
     let label = format!("login-{}", server);
     if let Some(window) = app.get_webview_window(&label) {
         window.destroy().map_err(|e| e.to_string())?;
@@ -342,7 +1486,7 @@ async fn open_trade_with_item(
     server: String,
     league: String,
     item_text: String,
-) -> Result<String, String> {
+) -> Result<String, WarlordError> {
     let label = format!("trade-paste-{}", server);
     // Close existing if any
     if let Some(w) = app.get_webview_window(&label) { let _ = w.destroy(); }
@@ -350,7 +1494,7 @@ async fn open_trade_with_item(
     let base_url = match server.as_str() {
         "cn" => format!("https://poe.game.qq.com/trade2/search/poe2/{}", league),
         "intl" => format!("https://www.pathofexile.com/trade2/search/poe2/{}", league),
-        _ => return Err("Invalid server".to_string()),
+        _ => return Err(WarlordError::invalid("未知的服务器")),
     };
 
     // Escape item text for JS
@@ -469,17 +1613,17 @@ fn parse_mod_filters(mod_text: &str) -> Vec<(String, f64)> {
 
 // Webview-based leagues fetch (for CN where direct API returns 401)
 #[tauri::command]
-async fn fetch_leagues_webview(app: tauri::AppHandle, server: String) -> Result<Vec<String>, String> {
+async fn fetch_leagues_webview(app: tauri::AppHandle, server: String) -> Result<Vec<String>, WarlordError> {
     let label = format!("login-{}", server);
     if app.get_webview_window(&label).is_none() {
-        return Err("请先登录市集".to_string());
+        return Err(WarlordError::invalid("请先登录市集"));
     }
     let window = app.get_webview_window(&label).unwrap();
 
     let api_url = match server.as_str() {
         "cn" => "https://poe.game.qq.com/api/trade2/data/leagues?realm=poe2",
         "intl" => "https://www.pathofexile.com/api/trade2/data/leagues",
-        _ => return Err("Invalid server".to_string()),
+        _ => return Err(WarlordError::invalid("未知的服务器")),
     };
 
     let js = format!(r#"
@@ -502,7 +1646,7 @@ async fn fetch_leagues_webview(app: tauri::AppHandle, server: String) -> Result<
     let start = std::time::Instant::now();
     loop {
         if start.elapsed() > std::time::Duration::from_secs(10) {
-            return Err("加载联赛超时".to_string());
+            return Err(WarlordError::Network { message: "加载联赛超时".to_string() });
         }
         std::thread::sleep(std::time::Duration::from_millis(300));
         let current_url = match window.url() { Ok(u) => u.to_string(), Err(_) => continue };
@@ -518,7 +1662,7 @@ async fn fetch_leagues_webview(app: tauri::AppHandle, server: String) -> Result<
         }
         if current_url.contains("__wr_err__=") { break; }
     }
-    Err("加载联赛失败".to_string())
+    Err(WarlordError::Network { message: "加载联赛失败".to_string() })
 }
 
 // Webview-based search: JS fetch() from webview sends ALL cookies (including HttpOnly)
@@ -531,14 +1675,14 @@ async fn search_trade_webview(
     item_name: String,
     item_type: String,
     mod_text: String,
-) -> Result<TradeSearchResult, String> {
+) -> Result<TradeSearchResult, WarlordError> {
     let label = format!("login-{}", server);
     // Re-open login window if closed (WebView2 cookies persist across sessions)
     if app.get_webview_window(&label).is_none() {
         let url = match server.as_str() {
             "cn" => "https://poe.game.qq.com/trade2/",
             "intl" => "https://www.pathofexile.com/trade2/",
-            _ => return Err("Invalid server".to_string()),
+            _ => return Err(WarlordError::invalid("未知的服务器")),
         };
         tauri::WebviewWindowBuilder::new(&app, &label, tauri::WebviewUrl::External(url.parse().map_err(|e: url::ParseError| e.to_string())?))
             .title("查价中...")
@@ -556,13 +1700,13 @@ async fn search_trade_webview(
     let api_url = match server.as_str() {
         "cn" => format!("https://poe.game.qq.com/api/trade2/search/poe2/{}", league),
         "intl" => format!("https://www.pathofexile.com/api/trade2/search/{}", league),
-        _ => return Err("Invalid server".to_string()),
+        _ => return Err(WarlordError::invalid("未知的服务器")),
     };
 
     let frontend_base = match server.as_str() {
         "cn" => format!("https://poe.game.qq.com/trade2/search/poe2/{}", league),
         "intl" => format!("https://www.pathofexile.com/trade2/search/poe2/{}", league),
-        _ => return Err("Invalid server".to_string()),
+        _ => return Err(WarlordError::invalid("未知的服务器")),
     };
 
     let status_opt = if server == "cn" { "securable" } else { "online" };
@@ -671,7 +1815,7 @@ async fn search_trade_webview(
     let start = std::time::Instant::now();
     loop {
         if start.elapsed() > std::time::Duration::from_secs(15) {
-            return Err("搜索超时，请检查网络或重试".to_string());
+            return Err(WarlordError::Network { message: "搜索超时，请检查网络或重试".to_string() });
         }
         std::thread::sleep(std::time::Duration::from_millis(300));
         let current_url = match window.url() {
@@ -704,7 +1848,7 @@ async fn search_trade_webview(
             let raw = base64_decode(&b64).unwrap_or_else(|_| format!("(base64解码失败) b64='{}' encoded='{}'", b64, encoded));
             eprintln!("[WarlordTools] ERROR raw: '{}'", raw);
             let _ = window.eval("history.back()");
-            return Err(format!("搜索失败: {}", raw));
+            return Err(WarlordError::Network { message: format!("搜索失败: {}", raw) });
         }
     }
 }
@@ -837,6 +1981,37 @@ fn simulate_copy() -> Result<(), String> {
     Ok(())
 }
 
+/// Focus the game and send `/reloaditemfilter` through the chat box.
+fn reload_filter_in_game() -> Result<(), WarlordError> {
+    #[cfg(target_os = "windows")]
+    {
+        extern "system" {
+            fn EnumWindows(callback: unsafe extern "system" fn(isize, isize) -> i32, lParam: isize) -> i32;
+            fn SetForegroundWindow(hWnd: isize) -> i32;
+            fn ShowWindow(hWnd: isize, nCmdShow: i32) -> i32;
+        }
+        const SW_RESTORE: i32 = 9;
+
+        unsafe {
+            POE_HWND = 0;
+            EnumWindows(poe_enum_callback, 0);
+            if POE_HWND == 0 {
+                return Err(WarlordError::invalid("未找到游戏窗口"));
+            }
+            ShowWindow(POE_HWND, SW_RESTORE);
+            SetForegroundWindow(POE_HWND);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+
+    use enigo::{Enigo, Key, Keyboard, Settings, Direction::Click};
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
+    enigo.key(Key::Return, Click).map_err(|e| e.to_string())?;
+    enigo.text("/reloaditemfilter").map_err(|e| e.to_string())?;
+    enigo.key(Key::Return, Click).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 // ---- Stat ID Database ----
 use std::sync::Mutex;
 use std::collections::HashMap;
@@ -850,7 +2025,7 @@ struct StatEntry {
 static STAT_DB: Mutex<Option<HashMap<String, Vec<StatEntry>>>> = Mutex::new(None);
 
 #[tauri::command]
-async fn get_stat_db(app: tauri::AppHandle, server: String) -> Result<usize, String> {
+async fn get_stat_db(app: tauri::AppHandle, server: String) -> Result<usize, WarlordError> {
     // Try to load from disk first
     let cache_path = get_stat_cache_path(&server);
     if let Ok(cached) = std::fs::read_to_string(&cache_path) {
@@ -867,28 +2042,22 @@ async fn get_stat_db(app: tauri::AppHandle, server: String) -> Result<usize, Str
     if let Some(ref db) = *STAT_DB.lock().unwrap() {
         if let Ok(json) = serde_json::to_string(db) {
             let _ = std::fs::create_dir_all(std::path::Path::new(&cache_path).parent().unwrap());
-            let _ = std::fs::write(&cache_path, json);
+            let _ = app_paths::write_atomic(Path::new(&cache_path), json.as_bytes());
         }
     }
     Ok(count)
 }
 
 fn get_stat_cache_path(server: &str) -> String {
-    let dir = dirs_next().unwrap_or_else(|| std::path::PathBuf::from("."));
-    format!("{}/WarlordToolsConfig/stats_{}.json", dir.display(), server)
-}
-
-fn dirs_next() -> Option<std::path::PathBuf> {
-    // Get LocalAppData equivalent
-    std::env::var("LOCALAPPDATA").ok().map(std::path::PathBuf::from)
+    app_paths::config_file(&format!("stats_{}.json", server)).display().to_string()
 }
 
 #[tauri::command]
-async fn fetch_stat_data_webview(app: tauri::AppHandle, server: String) -> Result<usize, String> {
+async fn fetch_stat_data_webview(app: tauri::AppHandle, server: String) -> Result<usize, WarlordError> {
     let api_url = match server.as_str() {
         "cn" => "https://poe.game.qq.com/api/trade/data/stats",
         "intl" => "https://www.pathofexile.com/api/trade/data/stats",
-        _ => return Err("Invalid server".to_string()),
+        _ => return Err(WarlordError::invalid("未知的服务器")),
     };
 
     let label = format!("login-{}", server);
@@ -905,7 +2074,7 @@ async fn fetch_stat_data_webview(app: tauri::AppHandle, server: String) -> Resul
             .build().map_err(|e| e.to_string())?;
         std::thread::sleep(std::time::Duration::from_millis(2000));
     }
-    let window = app.get_webview_window(&label).ok_or("no window")?;
+    let window = app.get_webview_window(&label).ok_or_else(|| WarlordError::invalid("窗口未打开"))?;
     eprintln!("[WarlordTools] fetch_stat_data: using window, fetching...");
 
     let js = format!(r#"
@@ -925,7 +2094,7 @@ async fn fetch_stat_data_webview(app: tauri::AppHandle, server: String) -> Resul
 
     let start = std::time::Instant::now();
     loop {
-        if start.elapsed() > std::time::Duration::from_secs(15) { return Err("超时".to_string()); }
+        if start.elapsed() > std::time::Duration::from_secs(15) { return Err(WarlordError::Network { message: "超时".to_string() }); }
         std::thread::sleep(std::time::Duration::from_millis(300));
         let cur = window.url().map(|u| u.to_string()).unwrap_or_default();
         if let Some(pos) = cur.find("__wr_ok__=") {
@@ -957,7 +2126,7 @@ async fn fetch_stat_data_webview(app: tauri::AppHandle, server: String) -> Resul
         }
         if cur.contains("__wr_err__=") { break; }
     }
-    Err("加载失败".to_string())
+    Err(WarlordError::Network { message: "加载失败".to_string() })
 }
 
 fn match_mods_to_stats(mod_text: &str, db: &HashMap<String, Vec<StatEntry>>, _server: &str) -> Vec<serde_json::Value> {
@@ -1037,7 +2206,75 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_opener::init())
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Destroyed = event {
+                if obs::is_showcase_window(window.label()) {
+                    obs::showcase_in_background(false);
+                }
+            }
+        })
         .setup(|app| {
+            temp_rules::spawn_expiry_watcher();
+            patches::spawn_reapply_watcher();
+            {
+                let handle = app.handle().clone();
+                backup_schedule::spawn_scheduler(move |status| {
+                    if !quiet_hours::mutes_notifications() {
+                        let _ = handle.emit("backup-failed", status);
+                    }
+                });
+            }
+            // Dashboard summary for the configured library, once per launch and not on battery
+            if power::background_allowed() {
+                let handle = app.handle().clone();
+                std::thread::spawn(move || match workspace_health::workspace_health(None) {
+                    Ok(report) => {
+                        let _ = handle.emit("workspace-health", report);
+                    }
+                    Err(e) => eprintln!("[WarlordTools] workspace health check skipped: {}", e),
+                });
+            }
+            {
+                let handle = app.handle().clone();
+                audio::on_progress(move |progress| {
+                    let _ = handle.emit("sound-progress", progress);
+                });
+            }
+            discord_rpc::start();
+            {
+                let handle = app.handle().clone();
+                local_api::set_host_handler(Box::new(move |action| match action {
+                    "toggle-overlay" => {
+                        for (label, window) in handle.webview_windows() {
+                            if label == "main" {
+                                continue;
+                            }
+                            let visible = window.is_visible().unwrap_or(false);
+                            let _ = if visible { window.hide() } else { window.show() };
+                        }
+                        Ok(serde_json::Value::Null)
+                    }
+                    "reload-filter" => reload_filter_in_game().map(|_| serde_json::Value::Null),
+                    _ => Err(WarlordError::invalid(format!("未知的快捷操作: {}", action))),
+                }));
+            }
+            local_api::start();
+
+            // Forward Client.txt events to the frontend and drive the leveling plan
+            {
+                let handle = app.handle().clone();
+                client_log::subscribe(Box::new(move |event, state| {
+                    let _ = handle.emit("client-log-event", event);
+                    if matches!(event, client_log::LogEvent::AreaEntered { .. }) {
+                        discord_rpc::update(None, None);
+                    }
+                    if let Some(status) = leveling::on_log_state(state) {
+                        let _ = handle.emit("leveling-phase-changed", status);
+                    }
+                }));
+            }
+            leveling::resume();
+
             #[cfg(desktop)]
             {
                 use tauri_plugin_global_shortcut::{Code, Modifiers, ShortcutState};
@@ -1126,7 +2363,185 @@ pub fn run() {
             search_trade_webview,
             open_trade_with_item,
             get_stat_db,
-            fetch_stat_data_webview
+            fetch_stat_data_webview,
+            add_temp_rule,
+            remove_temp_rule,
+            list_temp_rules,
+            start_log_watcher,
+            get_log_state,
+            set_leveling_plan,
+            get_leveling_plan,
+            get_leveling_status,
+            filter_to_json,
+            json_to_filter,
+            refresh_economy,
+            tag_filter_economy,
+            get_block_provenance,
+            format_filter,
+            list_available_actions,
+            invoke_action,
+            minify_filter,
+            rename_managed_file,
+            rename_basetype,
+            move_block,
+            move_block_to,
+            move_block_to_section,
+            replace_color,
+            plan_patch_migration,
+            apply_patch_migration,
+            swap_alert_sound,
+            find_dead_economy,
+            demote_dead_items,
+            get_sound_profiles,
+            set_sound_profiles,
+            compile_sound_profiles,
+            set_filter_strictness,
+            run_pipeline,
+            list_pipelines,
+            merge_filters,
+            get_webhooks,
+            set_webhooks,
+            test_webhook,
+            get_discord_presence,
+            set_discord_presence,
+            create_patch,
+            list_patches,
+            apply_patch,
+            invert_patch,
+            delete_patch,
+            compile_filter,
+            get_obs_config,
+            set_obs_config,
+            test_obs_connection,
+            obs_showcase,
+            build_filter,
+            get_local_api_config,
+            set_local_api_config,
+            generate_filter,
+            get_quiet_hours,
+            set_quiet_hours,
+            get_quiet_status,
+            compile_quiet_filter,
+            split_filter,
+            join_filter,
+            apply_filterblade_export,
+            get_power_status,
+            get_power_config,
+            set_power_config,
+            list_accounts,
+            save_account,
+            remove_account,
+            set_active_account,
+            list_characters,
+            list_stashes,
+            upload_filter,
+            update_block,
+            get_path_capabilities,
+            get_spectator_config,
+            set_spectator_config,
+            search_replace,
+            convert_poe1_filter,
+            workspace_health,
+            canonicalize_filter,
+            downgrade_filter,
+            open_stream,
+            close_stream,
+            adjust_alert_volumes,
+            get_colorblind_config,
+            set_colorblind_config,
+            compile_colorblind_filter,
+            get_cached_scan,
+            scan_filter_files_v2,
+            start_scan,
+            cancel_scan,
+            watch_directory,
+            unwatch_directory,
+            dedupe_filter,
+            sort_value_lists,
+            list_snippets,
+            save_snippet,
+            delete_snippet,
+            insert_snippet,
+            generate_hide_blocks,
+            write_file_if_unchanged,
+            list_backups,
+            restore_backup,
+            get_backup_config,
+            set_backup_config,
+            copy_folder,
+            move_path,
+            duplicate_filter,
+            delete_paths,
+            hash_file,
+            find_duplicates,
+            read_file_with_encoding,
+            read_file_range,
+            search_library,
+            normalize_path,
+            join_paths,
+            relative_path,
+            compare_paths,
+            get_sandbox_roots,
+            add_sandbox_root,
+            remove_sandbox_root,
+            check_disk_space,
+            is_file_locked,
+            link_filter,
+            extract_archive,
+            export_pack,
+            import_pack,
+            list_trash,
+            restore_from_trash,
+            empty_trash,
+            clear_readonly,
+            folder_size,
+            cancel_folder_size,
+            list_versions,
+            get_version_content,
+            restore_version,
+            git_status,
+            git_init_library,
+            git_commit_library,
+            git_log,
+            git_checkout,
+            git_switch_branch,
+            git_switch_league,
+            get_git_config,
+            set_git_config,
+            diff_text,
+            get_backup_schedule,
+            set_backup_schedule,
+            backup_schedule_status,
+            run_backup_now,
+            export_library,
+            import_library,
+            get_webdav_config,
+            set_webdav_config,
+            webdav_sync,
+            webdav_resolve_conflict,
+            webdav_reset_state,
+            resolve_cloud_conflict,
+            get_game_sync_config,
+            set_game_sync_config,
+            sync_to_game,
+            list_operations,
+            undo_last_operation,
+            get_version_retention,
+            set_version_retention,
+            prune_snapshots,
+            verify_backup,
+            export_profile,
+            import_profile,
+            migrate_from_game_folder,
+            list_migrations,
+            play_sound,
+            stop_sound,
+            seek_sound,
+            queue_sounds,
+            pick_backup_destination,
+            set_patch_auto_reapply,
+            start_stream,
+            start_folder_size
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
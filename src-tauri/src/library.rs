@@ -0,0 +1,39 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::app_paths;
+
+/// Filter storage folder chosen in the settings page (`filterStoragePath` in Settings.json).
+pub fn library_root() -> Option<PathBuf> {
+    let settings: serde_json::Value = app_paths::load_json("Settings.json");
+    settings["filterStoragePath"]
+        .as_str()
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Every `.filter` file under `root`, recursively.
+pub fn filter_files(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    fn visit(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                visit(&path, out)?;
+            } else if path.extension().is_some_and(|e| e == "filter") {
+                out.push(path);
+            }
+        }
+        Ok(())
+    }
+    let mut out = Vec::new();
+    if root.is_dir() {
+        visit(root, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Map `path` from under `old` to under `new` (exact match or a child of `old`).
+pub fn remap(path: &Path, old: &Path, new: &Path) -> Option<PathBuf> {
+    let rest = path.strip_prefix(old).ok()?;
+    Some(if rest.as_os_str().is_empty() { new.to_path_buf() } else { new.join(rest) })
+}
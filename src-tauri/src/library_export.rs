@@ -0,0 +1,263 @@
+//! The whole setup in one archive, to move to another PC or hand to a friend: every file of
+//! the filter library under `library/`, and the app's settings and state (patches, snippets,
+//! sound profiles, Settings.json ...) under `config/`, with `archive::BACKUP_MANIFEST`
+//! recording the app version and a hash of each file. Only the config files on
+//! `EXPORTED_CONFIG` go in: caches, run state, secrets (account tokens, the local API token,
+//! the WebDAV and OBS logins, webhook URLs) and anything that grants access (sandbox roots,
+//! link registries) stay behind, as does any config file added later until it is listed.
+//! `import_library` puts an export back, with a strategy for files that already exist, so
+//! restoring does not clobber newer work. An export can be encrypted with a password, which
+//! is only used for that export and import and never saved.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::archive::{self, BackupManifest};
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+use crate::{app_paths, extractors, file_ops, library, sandbox};
+
+pub const LIBRARY_PREFIX: &str = "library/";
+pub const CONFIG_PREFIX: &str = "config/";
+
+/// Config files that are exported, and the only ones an import writes: the user's own
+/// settings and presets. An archive is not trusted, so sandbox roots, link registries,
+/// webhooks and schedules in it are ignored.
+const EXPORTED_CONFIG: &[&str] = &[
+    "Settings.json",
+    "snippets.json",
+    "sound_profiles.json",
+    "colorblind.json",
+    "quiet_hours.json",
+    "power.json",
+    "versions.json",
+    "discord_rpc.json",
+    "patches.json",
+    "temp_rules.json",
+    "leveling_plan.json",
+    "game_sync.json",
+    "migrations.json",
+];
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryExport {
+    pub path: String,
+    pub manifest: BackupManifest,
+    pub bytes: u64,
+}
+
+/// The config folder's JSON files that go into an export.
+fn config_entries() -> Vec<(String, PathBuf)> {
+    let mut out: Vec<(String, PathBuf)> = EXPORTED_CONFIG
+        .iter()
+        .map(|name| (format!("{}{}", CONFIG_PREFIX, name), app_paths::config_file(name)))
+        .filter(|(_, path)| long_path(path).is_file())
+        .collect();
+    out.sort();
+    out
+}
+
+pub fn export_library(dest: &Path, password: Option<&str>) -> Result<LibraryExport, WarlordError> {
+    let root = library::library_root().filter(|r| long_path(r).is_dir()).ok_or_else(|| WarlordError::invalid("尚未设置过滤器库文件夹"))?;
+    let mut files = Vec::new();
+    archive::tree_entries(&root, LIBRARY_PREFIX, &|_| true, &mut files);
+    // An export saved into the library is not part of the next one
+    let absolute = std::path::absolute(dest).unwrap_or_else(|_| dest.to_path_buf());
+    files.retain(|(_, path)| !path_utils::compare_paths(&path_utils::short_path(path), &path_utils::short_path(&absolute)));
+    files.extend(config_entries());
+    let (manifest, bytes) = archive::zip_files(&files, dest, password)?;
+    eprintln!("[WarlordTools] Exported the library ({} files) to {}", manifest.files.len(), dest.display());
+    Ok(LibraryExport { path: path_utils::short_path(dest), manifest, bytes })
+}
+
+/// What happens to a file of the archive that already exists with other content.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportStrategy {
+    Overwrite,
+    SkipExisting,
+    /// Import as `name (copy).ext` next to it. Config files are kept as they are, since a
+    /// renamed one would not be read.
+    Rename,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ItemOutcome {
+    Created,
+    Overwritten,
+    Renamed,
+    Skipped,
+    /// Already there with the same content
+    Unchanged,
+    Failed,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportItem {
+    /// Name in the archive
+    pub entry: String,
+    pub target: Option<String>,
+    pub outcome: ItemOutcome,
+    pub error: Option<WarlordError>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryImport {
+    pub archive: String,
+    pub library_root: String,
+    pub items: Vec<ImportItem>,
+}
+
+/// Where an archive entry goes, and whether it is a config file; None for entries that are
+/// not imported.
+fn target_of(name: &str, root: &Path) -> Option<(PathBuf, bool)> {
+    if let Some(rest) = name.strip_prefix(LIBRARY_PREFIX) {
+        return archive::entry_path(rest).map(|p| (root.join(p), false));
+    }
+    let file = name.strip_prefix(CONFIG_PREFIX)?;
+    EXPORTED_CONFIG.contains(&file).then(|| (app_paths::config_file(file), true))
+}
+
+/// Settings.json names the library folder of the PC it came from; this PC's stays.
+fn merge_settings(incoming: &[u8], existing: Option<&[u8]>) -> Vec<u8> {
+    let (Ok(mut settings), Some(Ok(local))) = (serde_json::from_slice::<serde_json::Value>(incoming), existing.map(serde_json::from_slice::<serde_json::Value>)) else {
+        return incoming.to_vec();
+    };
+    if let Some(path) = local.get("filterStoragePath").filter(|p| p.as_str().is_some_and(|s| !s.is_empty())) {
+        settings["filterStoragePath"] = path.clone();
+    }
+    serde_json::to_vec_pretty(&settings).unwrap_or_else(|_| incoming.to_vec())
+}
+
+fn import_one(target: &Path, is_config: bool, mut contents: Vec<u8>, strategy: ImportStrategy) -> Result<(ItemOutcome, PathBuf), WarlordError> {
+    let existing = fs::read(long_path(target)).ok();
+    if is_config && target.file_name().is_some_and(|n| n == "Settings.json") {
+        contents = merge_settings(&contents, existing.as_deref());
+    }
+    let (outcome, target) = match (&existing, strategy) {
+        (None, _) => (ItemOutcome::Created, target.to_path_buf()),
+        (Some(old), _) if *old == contents => return Ok((ItemOutcome::Unchanged, target.to_path_buf())),
+        (Some(_), ImportStrategy::Overwrite) => (ItemOutcome::Overwritten, target.to_path_buf()),
+        (Some(_), ImportStrategy::Rename) if !is_config => (ItemOutcome::Renamed, file_ops::copy_name(target)),
+        (Some(_), _) => return Ok((ItemOutcome::Skipped, target.to_path_buf())),
+    };
+    if let Some(parent) = target.parent() {
+        sandbox::check_write(parent)?;
+        fs::create_dir_all(long_path(parent)).map_err(|e| WarlordError::io(e, parent))?;
+    }
+    sandbox::write_as(&target, &contents, "import")?;
+    Ok((outcome, target))
+}
+
+/// Put an `export_library` archive back, into `library_root` (default: the configured
+/// library). Each file is reported with what happened to it; one that fails does not stop
+/// the others. An encrypted export with a missing or wrong password fails before anything
+/// is written.
+pub fn import_library(archive: &Path, strategy: ImportStrategy, library_root: Option<&Path>, password: Option<&str>) -> Result<LibraryImport, WarlordError> {
+    let root = library_root.map(Path::to_path_buf).or_else(library::library_root).ok_or_else(|| WarlordError::invalid("请先设置过滤器库文件夹"))?;
+    sandbox::check_write(&root)?;
+    let mut extractor = extractors::open_with_password(archive, password)?;
+    let mut items = Vec::new();
+    extractor.for_each(&mut |name, data| {
+        if name == archive::BACKUP_MANIFEST {
+            return Ok(());
+        }
+        let Some((target, is_config)) = target_of(name, &root) else {
+            items.push(ImportItem { entry: name.to_string(), target: None, outcome: ItemOutcome::Skipped, error: None });
+            return Ok(());
+        };
+        let mut contents = Vec::new();
+        let result = data.read_to_end(&mut contents).map_err(|e| WarlordError::Io { path: Some(name.to_string()), message: format!("解压失败: {}", e) });
+        let item = match result.and_then(|_| import_one(&target, is_config, contents, strategy)) {
+            Ok((outcome, written)) => ImportItem { entry: name.to_string(), target: Some(path_utils::short_path(&written)), outcome, error: None },
+            Err(e) => ImportItem { entry: name.to_string(), target: Some(path_utils::short_path(&target)), outcome: ItemOutcome::Failed, error: Some(e) },
+        };
+        items.push(item);
+        Ok(())
+    })?;
+    let count = |o: ItemOutcome| items.iter().filter(|i| i.outcome == o).count();
+    eprintln!(
+        "[WarlordTools] Imported {}: {} created, {} overwritten, {} renamed, {} skipped, {} failed",
+        archive.display(),
+        count(ItemOutcome::Created),
+        count(ItemOutcome::Overwritten),
+        count(ItemOutcome::Renamed),
+        count(ItemOutcome::Skipped),
+        count(ItemOutcome::Failed)
+    );
+    Ok(LibraryImport { archive: path_utils::short_path(archive), library_root: path_utils::short_path(&root), items })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_listed_config_is_exported() {
+        fs::create_dir_all(app_paths::config_dir()).unwrap();
+        for name in ["accounts.json", "obs.json", "webhooks.json", "sandbox.json", "export_test.json", "snippets.json"] {
+            if !app_paths::config_file(name).exists() {
+                fs::write(app_paths::config_file(name), "{}").unwrap();
+            }
+        }
+        let names: Vec<String> = config_entries().into_iter().map(|(name, _)| name).collect();
+        assert!(names.contains(&"config/snippets.json".to_string()));
+        assert!(!names.iter().any(|n| ["accounts.json", "obs.json", "webhooks.json", "sandbox.json", "export_test.json"].iter().any(|secret| n.ends_with(secret))));
+    }
+
+    #[test]
+    fn import_strategies_keep_existing_work() {
+        let dir = std::env::temp_dir().join("wt-library-import-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (target, _) = target_of("library/leveling.filter", &dir).unwrap();
+        fs::write(&target, "Show # mine\n").unwrap();
+        let import = |strategy| import_one(&target, false, b"Show # backup\n".to_vec(), strategy).unwrap();
+
+        assert_eq!(import(ImportStrategy::SkipExisting).0, ItemOutcome::Skipped);
+        let (outcome, renamed) = import(ImportStrategy::Rename);
+        assert_eq!((outcome, renamed.file_name().unwrap().to_str().unwrap()), (ItemOutcome::Renamed, "leveling (copy).filter"));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "Show # mine\n");
+        assert_eq!(import(ImportStrategy::Overwrite).0, ItemOutcome::Overwritten);
+        assert_eq!(import(ImportStrategy::Overwrite).0, ItemOutcome::Unchanged);
+        assert!(target_of("library/../x", &dir).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn encrypted_exports_need_their_password() {
+        let dir = std::env::temp_dir().join("wt-library-password-test");
+        let _ = fs::remove_dir_all(&dir);
+        let (source, dest) = (dir.join("source"), dir.join("restored"));
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(source.join("leveling.filter"), "Show # leveling\n").unwrap();
+        let archive = dir.join("export.zip");
+        let files = vec![(format!("{}leveling.filter", LIBRARY_PREFIX), source.join("leveling.filter"))];
+        archive::zip_files(&files, &archive, Some("hunter2")).unwrap();
+
+        for password in [None, Some("wrong")] {
+            let error = import_library(&archive, ImportStrategy::Overwrite, Some(&dest), password).unwrap_err();
+            assert_eq!(error.code(), "invalidInput", "{:?}", password);
+            assert_eq!(fs::read_dir(&dest).unwrap().count(), 0);
+        }
+        let import = import_library(&archive, ImportStrategy::Overwrite, Some(&dest), Some("hunter2")).unwrap();
+        assert_eq!(import.items.iter().map(|i| i.outcome).collect::<Vec<_>>(), [ItemOutcome::Created]);
+        assert_eq!(fs::read_to_string(dest.join("leveling.filter")).unwrap(), "Show # leveling\n");
+        assert!(archive::verify_backup(&archive, Some("hunter2")).unwrap().ok);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn import_ignores_unlisted_config() {
+        let dir = std::env::temp_dir();
+        assert!(target_of("config/snippets.json", &dir).is_some_and(|(_, is_config)| is_config));
+        for name in ["config/accounts.json", "config/sandbox.json", "config/filter_links.json", "config/webhooks.json", "config/backup_schedule.json", "config/sub/snippets.json", "config/../snippets.json"] {
+            assert!(target_of(name, &dir).is_none(), "{}", name);
+        }
+    }
+}
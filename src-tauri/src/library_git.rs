@@ -0,0 +1,295 @@
+//! Optional git history of the filter library: the library root becomes a git repository
+//! (git2, no git install needed) and every save or delete the app makes in it is committed.
+//! Only filters and filter sources are tracked, so sounds and archives lying in the library
+//! do not bloat the history.
+//! Going back to an older state commits it on top instead of moving HEAD, so nothing is lost
+//! and the next save does not land on a detached head. Each league can have its own branch
+//! (`league/<name>`), switched to like any other branch.
+
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use git2::{BranchType, IndexAddOption, Oid, Repository, Signature};
+
+use crate::error::WarlordError;
+use crate::{app_paths, library, path_utils, sandbox};
+
+const CONFIG_FILE: &str = "library_git.json";
+const BRANCH_PREFIX: &str = "league/";
+
+/// One commit at a time; auto-commits come from whichever thread saved.
+static REPO_LOCK: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    /// Set while `checkout` writes a file, which it commits itself with its own message
+    static PAUSED: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GitConfig {
+    /// Commit every change the app makes in the library
+    pub enabled: bool,
+    pub author_name: String,
+    pub author_email: String,
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        GitConfig { enabled: false, author_name: "WarlordTools".to_string(), author_email: "warlordtools@localhost".to_string() }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitCommit {
+    pub id: String,
+    pub summary: String,
+    /// Seconds since the unix epoch
+    pub time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitStatus {
+    pub enabled: bool,
+    /// The library is a git repository
+    pub initialized: bool,
+    pub root: Option<String>,
+    pub branch: Option<String>,
+    pub branches: Vec<String>,
+    pub head: Option<GitCommit>,
+}
+
+pub fn get_config() -> GitConfig {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+pub fn set_config(config: &GitConfig) -> Result<(), WarlordError> {
+    app_paths::save_json(CONFIG_FILE, config)
+}
+
+fn git_error(error: git2::Error) -> WarlordError {
+    WarlordError::Other { message: format!("Git: {}", error.message()) }
+}
+
+fn root() -> Result<PathBuf, WarlordError> {
+    library::library_root().ok_or_else(|| WarlordError::invalid("尚未设置过滤器库文件夹"))
+}
+
+fn open() -> Result<(Repository, PathBuf), WarlordError> {
+    let root = root()?;
+    let repo = Repository::open(&root).map_err(|_| WarlordError::invalid(format!("{} 还不是 Git 仓库", path_utils::short_path(&root))))?;
+    Ok((repo, root))
+}
+
+fn commit_of(commit: &git2::Commit) -> GitCommit {
+    GitCommit { id: commit.id().to_string(), summary: commit.summary().unwrap_or_default().to_string(), time: commit.time().seconds() }
+}
+
+/// Filters and filter sources; everything else in the library is left out of the history.
+fn is_tracked(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("filter") || e.eq_ignore_ascii_case("filtersrc"))
+}
+
+/// Commit the tracked files below `pathspec` (a path relative to the root, "*" for all),
+/// deletions included. None when nothing changed.
+fn commit_path(repo: &Repository, pathspec: &str, message: &str) -> Result<Option<GitCommit>, git2::Error> {
+    let mut index = repo.index()?;
+    let mut only_tracked = |path: &Path, _: &[u8]| if is_tracked(path) { 0 } else { 1 };
+    index.add_all([pathspec], IndexAddOption::DEFAULT, Some(&mut only_tracked))?;
+    index.update_all([pathspec], None)?;
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+    if parent.as_ref().is_some_and(|p| p.tree_id() == tree.id()) {
+        return Ok(None);
+    }
+    let config = get_config();
+    let author = Signature::now(&config.author_name, &config.author_email)?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    let id = repo.commit(Some("HEAD"), &author, &author, message, &tree, &parents)?;
+    Ok(Some(commit_of(&repo.find_commit(id)?)))
+}
+
+fn commit_all(repo: &Repository, message: &str) -> Result<Option<GitCommit>, git2::Error> {
+    commit_path(repo, "*", message)
+}
+
+/// Make the library a git repository (if it is not one) and commit what is in it.
+pub fn init() -> Result<GitStatus, WarlordError> {
+    let root = root()?;
+    sandbox::check_write(&root)?;
+    {
+        let _guard = REPO_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let repo = Repository::open(&root).or_else(|_| Repository::init(&root)).map_err(git_error)?;
+        commit_all(&repo, "初始化过滤器库").map_err(git_error)?;
+    }
+    let mut config = get_config();
+    config.enabled = true;
+    set_config(&config)?;
+    status()
+}
+
+pub fn status() -> Result<GitStatus, WarlordError> {
+    let enabled = get_config().enabled;
+    let Ok((repo, root)) = open() else {
+        let root = library::library_root().map(path_utils::short_path);
+        return Ok(GitStatus { enabled, initialized: false, root, branch: None, branches: Vec::new(), head: None });
+    };
+    let head = repo.head().ok();
+    let mut branches = Vec::new();
+    for branch in repo.branches(Some(BranchType::Local)).map_err(git_error)? {
+        let (branch, _) = branch.map_err(git_error)?;
+        if let Some(name) = branch.name().map_err(git_error)? {
+            branches.push(name.to_string());
+        }
+    }
+    branches.sort();
+    Ok(GitStatus {
+        enabled,
+        initialized: true,
+        root: Some(path_utils::short_path(&root)),
+        branch: head.as_ref().and_then(|h| h.shorthand()).map(String::from),
+        branches,
+        head: head.and_then(|h| h.peel_to_commit().ok()).map(|c| commit_of(&c)),
+    })
+}
+
+/// Commit pending changes in the library (also ones made outside the app).
+pub fn commit(message: &str) -> Result<Option<GitCommit>, WarlordError> {
+    let (repo, _) = open()?;
+    let _guard = REPO_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    commit_all(&repo, message).map_err(git_error)
+}
+
+/// Called after the app changed `path`: commit it (only it) when it is in the library and
+/// history is on. Failures are logged; the change itself went through.
+pub fn auto_commit(path: &Path, reason: &str) {
+    if PAUSED.with(Cell::get) || !get_config().enabled {
+        return;
+    }
+    let Some(root) = library::library_root() else { return };
+    let Ok(relative) = Path::new(&path_utils::short_path(path)).strip_prefix(path_utils::short_path(&root)).map(Path::to_path_buf) else { return };
+    if relative.starts_with(".git") {
+        return;
+    }
+    let relative = relative.display().to_string().replace('\\', "/");
+    let message = format!("{}: {}", reason, relative);
+    let result = open().and_then(|(repo, _)| {
+        let _guard = REPO_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        commit_path(&repo, &relative, &message).map_err(git_error)
+    });
+    if let Err(e) = result {
+        eprintln!("[WarlordTools] Could not commit {} to the library history: {}", path.display(), e);
+    }
+}
+
+/// Newest first, on the current branch.
+pub fn log(limit: usize) -> Result<Vec<GitCommit>, WarlordError> {
+    let (repo, _) = open()?;
+    if repo.head().is_err() {
+        return Ok(Vec::new());
+    }
+    let mut walk = repo.revwalk().map_err(git_error)?;
+    walk.push_head().map_err(git_error)?;
+    walk.set_sorting(git2::Sort::TIME).map_err(git_error)?;
+    let mut out = Vec::new();
+    for id in walk.take(limit) {
+        let commit = repo.find_commit(id.map_err(git_error)?).map_err(git_error)?;
+        out.push(commit_of(&commit));
+    }
+    Ok(out)
+}
+
+fn find_commit<'r>(repo: &'r Repository, commit_id: &str) -> Result<git2::Commit<'r>, WarlordError> {
+    Oid::from_str(commit_id).and_then(|id| repo.find_commit(id)).map_err(|_| WarlordError::invalid(format!("没有提交 {}", commit_id)))
+}
+
+/// `path` in the library as it was in `commit`, with its path relative to the root.
+fn blob_at(repo: &Repository, root: &Path, commit: &git2::Commit, path: &Path) -> Result<(PathBuf, Vec<u8>), WarlordError> {
+    let relative = Path::new(&path_utils::short_path(path)).strip_prefix(path_utils::short_path(root)).map(Path::to_path_buf).map_err(|_| WarlordError::invalid("文件不在过滤器库中"))?;
+    let blob = commit.tree().and_then(|t| t.get_path(&relative)).and_then(|e| e.to_object(repo)).and_then(|o| o.peel_to_blob());
+    let blob = blob.map_err(|_| WarlordError::invalid(format!("提交 {} 中没有 {}", &commit.id().to_string()[..8], relative.display())))?;
+    Ok((relative, blob.content().to_vec()))
+}
+
+/// Contents of `path` at `commit_id`.
+pub fn file_at(commit_id: &str, path: &Path) -> Result<Vec<u8>, WarlordError> {
+    let (repo, root) = open()?;
+    let commit = find_commit(&repo, commit_id)?;
+    Ok(blob_at(&repo, &root, &commit, path)?.1)
+}
+
+/// Bring the library (or only `path` in it) back to how it was at `commit_id`, as a new
+/// commit. Pending changes are committed first.
+pub fn checkout(commit_id: &str, path: Option<&Path>) -> Result<(), WarlordError> {
+    let (repo, root) = open()?;
+    sandbox::check_write(&root)?;
+    let guard = REPO_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    commit_all(&repo, "保存未提交的更改").map_err(git_error)?;
+    let target = find_commit(&repo, commit_id)?;
+    let short = &commit_id[..commit_id.len().min(8)];
+    let message = match path {
+        Some(path) => {
+            let (relative, contents) = blob_at(&repo, &root, &target, path)?;
+            // Through the sandbox, so the file is backed up and versioned like a save; it is
+            // committed below with this message rather than auto-committed
+            drop(guard);
+            let paused = PAUSED.with(|p| p.replace(true));
+            let written = sandbox::write_as(root.join(&relative), contents, "git-checkout");
+            PAUSED.with(|p| p.set(paused));
+            written?;
+            format!("恢复 {} 到 {}", relative.display().to_string().replace('\\', "/"), short)
+        }
+        None => {
+            let mut options = git2::build::CheckoutBuilder::new();
+            options.force().remove_untracked(false);
+            repo.checkout_tree(target.as_object(), Some(&mut options)).map_err(git_error)?;
+            drop(guard);
+            format!("恢复过滤器库到 {}", short)
+        }
+    };
+    commit(&message).map(|_| ())
+}
+
+/// `league/<name>` with characters git refuses replaced.
+pub fn league_branch(league: &str) -> String {
+    let name: String = league.trim().chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' }).collect();
+    format!("{}{}", BRANCH_PREFIX, name.trim_matches('-'))
+}
+
+/// Switch the library to `branch`, creating it from the current state when it does not
+/// exist. Pending changes are committed on the branch being left.
+pub fn switch_branch(branch: &str) -> Result<GitStatus, WarlordError> {
+    {
+        let (repo, root) = open()?;
+        sandbox::check_write(&root)?;
+        let _guard = REPO_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let head = repo.head().ok().and_then(|h| h.shorthand().map(String::from));
+        commit_all(&repo, &format!("切换到 {} 前的更改", branch)).map_err(git_error)?;
+        if repo.find_branch(branch, BranchType::Local).is_err() {
+            let current = repo.head().and_then(|h| h.peel_to_commit()).map_err(|_| WarlordError::invalid("过滤器库还没有提交"))?;
+            repo.branch(branch, &current, false).map_err(git_error)?;
+        }
+        if head.as_deref() != Some(branch) {
+            repo.set_head(&format!("refs/heads/{}", branch)).map_err(git_error)?;
+            repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).map_err(git_error)?;
+        }
+    }
+    status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn league_branch_names() {
+        assert_eq!(league_branch("Settlers of Kalguur"), "league/Settlers-of-Kalguur");
+        assert_eq!(league_branch(" 凤凰 (HC) "), "league/凤凰--HC");
+        assert!(is_tracked(Path::new("a/NeverSink.FILTER")) && is_tracked(Path::new("main.filtersrc")));
+        assert!(!is_tracked(Path::new("sounds/alert.mp3")) && !is_tracked(Path::new("pack.zip")));
+    }
+}
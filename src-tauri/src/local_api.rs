@@ -0,0 +1,400 @@
+//! Local API for companion apps (trade macros, stream decks). Requests are JSON objects naming
+//! an action from the command palette registry:
+//!
+//! ```text
+//! {"id": 1, "action": "filter.format", "args": {"path": "C:/.../NeverSink.filter"}}
+//! {"id": 1, "ok": true, "result": ...}
+//! ```
+//!
+//! Served one request per line over the `\\.\pipe\warlordtools` named pipe (a unix socket
+//! elsewhere), which needs no network socket at all, and over HTTP on 127.0.0.1:
+//!
+//! - `POST /api` with a request as the body
+//! - `GET|POST /quick/<action>` runs a parameterless quick action, so a Stream Deck HTTP
+//!   plugin only needs one URL per button
+//!
+//! HTTP requests must carry the token as `?token=` or `Authorization: Bearer`.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::Mutex;
+
+use serde_json::{json, Value};
+
+use crate::error::WarlordError;
+use crate::{actions, app_paths, manifest, pipelines, strictness};
+
+const CONFIG_FILE: &str = "local_api.json";
+#[cfg(windows)]
+pub const PIPE_NAME: &str = r"\\.\pipe\warlordtools";
+
+/// Quick actions handled by the app shell (windows, game input) rather than this module
+pub const HOST_QUICK_ACTIONS: &[&str] = &["toggle-overlay", "reload-filter"];
+pub const QUICK_ACTIONS: &[&str] = &["toggle-overlay", "switch-strict", "run-sync", "reload-filter"];
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LocalApiConfig {
+    pub pipe_enabled: bool,
+    pub http_enabled: bool,
+    pub port: u16,
+    /// Generated when HTTP is first enabled
+    pub token: String,
+    /// Level `switch-strict` sets on the selected filter
+    pub strict_level: u32,
+    /// Manifest pipeline `run-sync` runs
+    pub sync_pipeline: String,
+}
+
+impl Default for LocalApiConfig {
+    fn default() -> Self {
+        LocalApiConfig {
+            pipe_enabled: false,
+            http_enabled: false,
+            port: 47823,
+            token: String::new(),
+            strict_level: 3,
+            sync_pipeline: "sync".to_string(),
+        }
+    }
+}
+
+type HostHandler = Box<dyn Fn(&str) -> Result<Value, WarlordError> + Send + Sync>;
+
+#[derive(Debug, serde::Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    action: String,
+    #[serde(default)]
+    args: Value,
+}
+
+/// Whether requests are answered; the listener itself stays up once started
+static PIPE_ENABLED: AtomicBool = AtomicBool::new(false);
+static PIPE_STARTED: AtomicBool = AtomicBool::new(false);
+/// Port the HTTP listener is bound to, 0 when it is not running
+static HTTP_PORT: AtomicU16 = AtomicU16::new(0);
+static HOST_HANDLER: Mutex<Option<HostHandler>> = Mutex::new(None);
+
+pub fn get_config() -> LocalApiConfig {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+/// 128 bits from the OS random number generator, hex encoded.
+fn new_token() -> Result<String, WarlordError> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(|e| WarlordError::Other { message: format!("无法生成令牌: {}", e) })?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Compare without stopping at the first difference, so the time taken does not tell how
+/// much of a guessed token was right.
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Save the settings and start newly enabled endpoints. Returns the saved config, which
+/// includes the token generated for HTTP.
+pub fn set_config(config: &LocalApiConfig) -> Result<LocalApiConfig, WarlordError> {
+    let mut config = config.clone();
+    if config.http_enabled && config.token.is_empty() {
+        config.token = new_token()?;
+    }
+    app_paths::save_json(CONFIG_FILE, &config)?;
+    PIPE_ENABLED.store(config.pipe_enabled, Ordering::SeqCst);
+    if config.pipe_enabled {
+        start_pipe();
+    }
+    if config.http_enabled {
+        start_http(config.port);
+    }
+    Ok(config)
+}
+
+/// Register the handler for `HOST_QUICK_ACTIONS`.
+pub fn set_host_handler(handler: HostHandler) {
+    *HOST_HANDLER.lock().unwrap() = Some(handler);
+}
+
+fn active_filter() -> Result<String, WarlordError> {
+    let settings: Value = app_paths::load_json("Settings.json");
+    settings["lastSelectedFilter"].as_str().filter(|s| !s.is_empty()).map(String::from).ok_or_else(|| WarlordError::invalid("未选择过滤器"))
+}
+
+/// Run a quick action by name.
+pub fn quick_action(name: &str) -> Result<Value, WarlordError> {
+    let config = get_config();
+    match name {
+        "switch-strict" => {
+            let path = active_filter()?;
+            let report = strictness::set_strictness(&path, config.strict_level)?;
+            serde_json::to_value(report).map_err(|e| WarlordError::from(e.to_string()))
+        }
+        "run-sync" => {
+            let workspace = manifest::resolve_workspace(None)?;
+            let report = pipelines::run_pipeline(&workspace, &config.sync_pipeline)?;
+            serde_json::to_value(report).map_err(|e| WarlordError::from(e.to_string()))
+        }
+        _ if HOST_QUICK_ACTIONS.contains(&name) => match HOST_HANDLER.lock().unwrap().as_ref() {
+            Some(handler) => handler(name),
+            None => Err(WarlordError::invalid("应用尚未就绪")),
+        },
+        _ => Err(WarlordError::invalid(format!("未知的快捷操作: {}", name))),
+    }
+}
+
+/// Run one request. `actions.list` lists the available actions.
+pub fn handle(request: &Value) -> Value {
+    let request: Request = match serde_json::from_value(request.clone()) {
+        Ok(r) => r,
+        Err(e) => return json!({ "id": Value::Null, "ok": false, "error": format!("无效的请求: {}", e) }),
+    };
+    let result = match request.action.as_str() {
+        "actions.list" => serde_json::to_value(actions::list_actions(&Default::default())).map_err(|e| WarlordError::from(e.to_string())),
+        id => actions::invoke(id, &request.args),
+    };
+    match result {
+        Ok(result) => json!({ "id": request.id, "ok": true, "result": result }),
+        Err(error) => json!({ "id": request.id, "ok": false, "error": error.to_string() }),
+    }
+}
+
+/// Answer one protocol line.
+pub fn handle_line(line: &str) -> String {
+    let reply = match serde_json::from_str::<Value>(line) {
+        Ok(request) => handle(&request),
+        Err(e) => json!({ "id": Value::Null, "ok": false, "error": format!("无效的 JSON: {}", e) }),
+    };
+    reply.to_string()
+}
+
+fn reply_to(line: &str) -> String {
+    if PIPE_ENABLED.load(Ordering::SeqCst) {
+        handle_line(line)
+    } else {
+        json!({ "id": Value::Null, "ok": false, "error": "本地接口已关闭" }).to_string()
+    }
+}
+
+#[cfg(windows)]
+async fn accept_pipe_clients() -> std::io::Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut server = ServerOptions::new().first_pipe_instance(true).create(PIPE_NAME)?;
+    loop {
+        server.connect().await?;
+        // Open the next instance before serving so other clients can connect meanwhile
+        let client = std::mem::replace(&mut server, ServerOptions::new().create(PIPE_NAME)?);
+        tokio::spawn(async move {
+            let (read, mut write) = tokio::io::split(client);
+            let mut lines = BufReader::new(read).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let reply = tokio::task::spawn_blocking(move || reply_to(&line)).await.unwrap_or_default();
+                if write.write_all(format!("{}\n", reply).as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(windows)]
+fn serve_pipe() -> std::io::Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_io().build()?;
+    runtime.block_on(accept_pipe_clients())
+}
+
+#[cfg(unix)]
+fn serve_pipe() -> std::io::Result<()> {
+    let dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    let path = std::path::Path::new(&dir).join("warlordtools.sock");
+    let _ = std::fs::remove_file(&path);
+    let listener = std::os::unix::net::UnixListener::bind(&path)?;
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { continue };
+        std::thread::spawn(move || {
+            let Ok(read) = stream.try_clone() else { return };
+            for line in BufReader::new(read).lines() {
+                let Ok(line) = line else { break };
+                if writeln!(stream, "{}", reply_to(&line)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    bearer: Option<String>,
+    body: Vec<u8>,
+}
+
+fn read_http(stream: &TcpStream) -> Option<HttpRequest> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?.to_string();
+
+    let (mut length, mut bearer) = (0usize, None);
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 || header.trim().is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':')?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value.parse().ok()?;
+        } else if name.eq_ignore_ascii_case("authorization") {
+            bearer = value.strip_prefix("Bearer ").map(String::from);
+        }
+    }
+    let mut body = vec![0u8; length.min(1 << 20)];
+    reader.read_exact(&mut body).ok()?;
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    // Tokens are hex, so values are taken as-is without percent-decoding
+    let query = query.split('&').filter_map(|pair| pair.split_once('=')).map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    Some(HttpRequest { method, path: path.to_string(), query, bearer, body })
+}
+
+/// Status and JSON body for one HTTP request.
+fn route(request: &HttpRequest, token: &str) -> (u16, Value) {
+    let given = request.bearer.as_deref().or_else(|| request.query.iter().find(|(k, _)| k == "token").map(|(_, v)| v.as_str()));
+    if token.is_empty() || !given.is_some_and(|given| same_token(given, token)) {
+        return (401, json!({ "ok": false, "error": "令牌无效" }));
+    }
+    if let Some(name) = request.path.strip_prefix("/quick/") {
+        return match quick_action(name) {
+            Ok(result) => (200, json!({ "ok": true, "result": result })),
+            Err(error) => (400, json!({ "ok": false, "error": error.to_string() })),
+        };
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/api") => match serde_json::from_slice::<Value>(&request.body) {
+            Ok(body) => (200, handle(&body)),
+            Err(e) => (400, json!({ "ok": false, "error": format!("无效的 JSON: {}", e) })),
+        },
+        _ => (404, json!({ "ok": false, "error": "未知的路径" })),
+    }
+}
+
+fn serve_http_client(mut stream: TcpStream) {
+    let _ = stream.set_read_timeout(Some(std::time::Duration::from_secs(5)));
+    let Some(request) = read_http(&stream) else { return };
+    let config = get_config();
+    let (status, body) = if config.http_enabled { route(&request, &config.token) } else { (503, json!({ "ok": false, "error": "本地接口已关闭" })) };
+    let body = body.to_string();
+    let reason = match status {
+        200 => "OK",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        503 => "Service Unavailable",
+        _ => "Bad Request",
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    );
+}
+
+/// Start the HTTP listener on `port`, moving it when it runs on another one. Only loopback
+/// connections are accepted.
+fn start_http(port: u16) {
+    let previous = HTTP_PORT.swap(port, Ordering::SeqCst);
+    if previous == port {
+        return;
+    }
+    if previous != 0 {
+        // Wake the old listener so it sees it was replaced
+        let _ = TcpStream::connect(("127.0.0.1", previous));
+    }
+    std::thread::spawn(move || match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => {
+            for stream in listener.incoming().flatten() {
+                if HTTP_PORT.load(Ordering::SeqCst) != port {
+                    return;
+                }
+                std::thread::spawn(move || serve_http_client(stream));
+            }
+        }
+        Err(e) => {
+            eprintln!("[WarlordTools] local api http: {}", e);
+            let _ = HTTP_PORT.compare_exchange(port, 0, Ordering::SeqCst, Ordering::SeqCst);
+        }
+    });
+}
+
+/// Start the pipe listener once, off the calling thread.
+fn start_pipe() {
+    if PIPE_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    std::thread::spawn(|| {
+        if let Err(e) = serve_pipe() {
+            eprintln!("[WarlordTools] local api pipe: {}", e);
+            PIPE_STARTED.store(false, Ordering::SeqCst);
+        }
+    });
+}
+
+/// Start the endpoints enabled in a previous session.
+pub fn start() {
+    let config = get_config();
+    PIPE_ENABLED.store(config.pipe_enabled, Ordering::SeqCst);
+    if config.pipe_enabled {
+        start_pipe();
+    }
+    if config.http_enabled {
+        start_http(config.port);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_with_request_id() {
+        let reply: Value = serde_json::from_str(&handle_line(r#"{"id": 7, "action": "actions.list"}"#)).unwrap();
+        assert_eq!((reply["id"].clone(), reply["ok"].clone()), (json!(7), json!(true)));
+        assert!(reply["result"].as_array().is_some_and(|a| !a.is_empty()));
+
+        let reply: Value = serde_json::from_str(&handle_line(r#"{"id": "x", "action": "nope"}"#)).unwrap();
+        assert_eq!(reply["ok"], false);
+        assert_eq!(reply["id"], "x");
+        assert_eq!(serde_json::from_str::<Value>(&handle_line("{")).unwrap()["ok"], false);
+    }
+
+    #[test]
+    fn http_requires_token() {
+        let request = |query: &str| HttpRequest {
+            method: "GET".to_string(),
+            path: "/quick/nope".to_string(),
+            query: vec![("token".to_string(), query.to_string())],
+            bearer: None,
+            body: Vec::new(),
+        };
+        assert_eq!(route(&request("wrong"), "secret").0, 401);
+        assert_eq!(route(&request(""), "").0, 401);
+        assert_eq!(route(&request("secret"), "secret").0, 400);
+        assert_eq!(route(&request("secre"), "secret").0, 401);
+
+        let token = new_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, new_token().unwrap());
+    }
+}
@@ -0,0 +1,69 @@
+//! Per-workspace configuration, `warlordtools.json` at the root of a filter workspace.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::WarlordError;
+
+pub const MANIFEST_FILE: &str = "warlordtools.json";
+
+/// One step of an export pipeline.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum PipelineStep {
+    /// Produce `output` from `source` (`.filtersrc` sources go through the preprocessor)
+    Compile,
+    /// Fail the pipeline on lines the parser does not understand
+    Lint,
+    Minify {
+        #[serde(default, rename = "keepHeader")]
+        keep_header: bool,
+    },
+    /// Write `<output>.sha256` next to the output
+    Sign,
+    /// Copy the output into another folder (the game's filter folder by default)
+    Install {
+        #[serde(default)]
+        dest: Option<String>,
+    },
+    Notify {
+        #[serde(default)]
+        message: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pipeline {
+    /// Relative to the workspace root
+    pub source: String,
+    /// Relative to the workspace root
+    pub output: String,
+    pub steps: Vec<PipelineStep>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Manifest {
+    pub pipelines: BTreeMap<String, Pipeline>,
+}
+
+/// Manifest of `workspace`; an empty one when the file does not exist.
+pub fn load(workspace: &Path) -> Result<Manifest, WarlordError> {
+    let path = workspace.join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(Manifest::default());
+    }
+    let content = fs::read_to_string(&path).map_err(|e| WarlordError::io(e, &path))?;
+    serde_json::from_str(&content).map_err(|e| WarlordError::invalid(format!("{} 格式错误: {}", MANIFEST_FILE, e)))
+}
+
+/// Workspace given by the frontend, or the filter storage folder from the settings.
+pub fn resolve_workspace(workspace: Option<&str>) -> Result<PathBuf, WarlordError> {
+    workspace
+        .filter(|w| !w.is_empty())
+        .map(PathBuf::from)
+        .or_else(crate::library::library_root)
+        .ok_or_else(|| WarlordError::invalid("未设置过滤器存储路径"))
+}
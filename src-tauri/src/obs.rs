@@ -0,0 +1,184 @@
+//! obs-websocket (v5) integration: switch to a "filter showcase" scene and/or show a source
+//! while an overlay window (the market or poedb overlay) is open, and put things back when
+//! it closes.
+
+use std::sync::Mutex;
+
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use tungstenite::{Message, WebSocket};
+
+use crate::error::WarlordError;
+use crate::app_paths;
+
+const CONFIG_FILE: &str = "obs.json";
+/// Label suffix of the windows that trigger the showcase (`market-overlay`, `poedb-overlay`)
+const SHOWCASE_LABEL_SUFFIX: &str = "-overlay";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ObsConfig {
+    pub enabled: bool,
+    pub url: String,
+    pub password: String,
+    /// Scene to switch to while previewing
+    pub scene: Option<String>,
+    /// Source (e.g. a browser source) to show in the current scene while previewing
+    pub source: Option<String>,
+}
+
+impl Default for ObsConfig {
+    fn default() -> Self {
+        ObsConfig {
+            enabled: false,
+            url: "ws://127.0.0.1:4455".to_string(),
+            password: String::new(),
+            scene: None,
+            source: None,
+        }
+    }
+}
+
+/// Scene that was live before the showcase started, restored afterwards
+static PREVIOUS_SCENE: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn get_config() -> ObsConfig {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+pub fn set_config(config: &ObsConfig) -> Result<(), WarlordError> {
+    app_paths::save_json(CONFIG_FILE, config)
+}
+
+type Socket = WebSocket<tungstenite::stream::MaybeTlsStream<std::net::TcpStream>>;
+
+/// obs-websocket auth string: base64(sha256(base64(sha256(password + salt)) + challenge)).
+fn auth_string(password: &str, salt: &str, challenge: &str) -> String {
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let secret = b64.encode(Sha256::digest(format!("{}{}", password, salt)));
+    b64.encode(Sha256::digest(format!("{}{}", secret, challenge)))
+}
+
+fn read_json(socket: &mut Socket) -> Result<serde_json::Value, WarlordError> {
+    loop {
+        match socket.read().map_err(|e| WarlordError::Network { message: format!("OBS 连接中断: {}", e) })? {
+            Message::Text(text) => return serde_json::from_str(&text).map_err(|e| WarlordError::Network { message: format!("OBS 返回了无效的数据: {}", e) }),
+            Message::Close(_) => return Err(WarlordError::invalid("OBS 关闭了连接 (密码错误?)")),
+            _ => continue,
+        }
+    }
+}
+
+fn send_json(socket: &mut Socket, body: serde_json::Value) -> Result<(), WarlordError> {
+    socket.send(Message::Text(body.to_string())).map_err(|e| WarlordError::Network { message: format!("OBS 连接中断: {}", e) })
+}
+
+/// Connect and identify. No event subscriptions, we only send requests.
+fn connect(config: &ObsConfig) -> Result<Socket, WarlordError> {
+    let (mut socket, _) = tungstenite::connect(config.url.as_str()).map_err(|e| WarlordError::Network { message: format!("无法连接 OBS: {}", e) })?;
+    let hello = read_json(&mut socket)?;
+    let mut identify = serde_json::json!({ "rpcVersion": 1, "eventSubscriptions": 0 });
+    let auth = &hello["d"]["authentication"];
+    if let (Some(challenge), Some(salt)) = (auth["challenge"].as_str(), auth["salt"].as_str()) {
+        identify["authentication"] = serde_json::json!(auth_string(&config.password, salt, challenge));
+    }
+    send_json(&mut socket, serde_json::json!({ "op": 1, "d": identify }))?;
+    let identified = read_json(&mut socket)?;
+    if identified["op"] != 2 {
+        return Err(WarlordError::invalid("OBS 认证失败"));
+    }
+    Ok(socket)
+}
+
+fn request(socket: &mut Socket, request_type: &str, data: serde_json::Value) -> Result<serde_json::Value, WarlordError> {
+    let id = app_paths::new_id();
+    send_json(
+        socket,
+        serde_json::json!({ "op": 6, "d": { "requestType": request_type, "requestId": id, "requestData": data } }),
+    )?;
+    loop {
+        let msg = read_json(socket)?;
+        if msg["op"] != 7 || msg["d"]["requestId"] != id.as_str() {
+            continue;
+        }
+        let status = &msg["d"]["requestStatus"];
+        if status["result"] != true {
+            return Err(WarlordError::invalid(format!("OBS {} 失败: {}", request_type, status["comment"])));
+        }
+        return Ok(msg["d"]["responseData"].clone());
+    }
+}
+
+fn set_source_visible(socket: &mut Socket, scene: &str, source: &str, visible: bool) -> Result<(), WarlordError> {
+    let item = request(socket, "GetSceneItemId", serde_json::json!({ "sceneName": scene, "sourceName": source }))?;
+    request(
+        socket,
+        "SetSceneItemEnabled",
+        serde_json::json!({ "sceneName": scene, "sceneItemId": item["sceneItemId"], "sceneItemEnabled": visible }),
+    )?;
+    Ok(())
+}
+
+/// Start (`active`) or end the showcase. Does nothing when the integration is off.
+pub fn showcase(active: bool) -> Result<(), WarlordError> {
+    let config = get_config();
+    if !config.enabled {
+        return Ok(());
+    }
+    let mut socket = connect(&config)?;
+    let current = request(&mut socket, "GetCurrentProgramScene", serde_json::json!({}))?;
+    let current = current["currentProgramSceneName"].as_str().unwrap_or_default().to_string();
+
+    if let Some(scene) = config.scene.as_deref().filter(|s| !s.is_empty()) {
+        if active {
+            if current != scene {
+                *PREVIOUS_SCENE.lock().unwrap() = Some(current.clone());
+            }
+            request(&mut socket, "SetCurrentProgramScene", serde_json::json!({ "sceneName": scene }))?;
+        } else if let Some(previous) = PREVIOUS_SCENE.lock().unwrap().take() {
+            request(&mut socket, "SetCurrentProgramScene", serde_json::json!({ "sceneName": previous }))?;
+        }
+    }
+    if let Some(source) = config.source.as_deref().filter(|s| !s.is_empty()) {
+        let scene = config.scene.as_deref().filter(|s| !s.is_empty()).unwrap_or(&current);
+        set_source_visible(&mut socket, scene, source, active)?;
+    }
+    let _ = socket.close(None);
+    Ok(())
+}
+
+/// Whether opening or closing the window `label` starts or ends the showcase.
+pub fn is_showcase_window(label: &str) -> bool {
+    label.ends_with(SHOWCASE_LABEL_SUFFIX)
+}
+
+/// Run `showcase` off the calling thread and log failures.
+pub fn showcase_in_background(active: bool) {
+    std::thread::spawn(move || {
+        if let Err(e) = showcase(active) {
+            eprintln!("[WarlordTools] obs: {}", e);
+        }
+    });
+}
+
+/// Check the connection and credentials.
+pub fn test_connection(config: &ObsConfig) -> Result<String, WarlordError> {
+    let mut socket = connect(config)?;
+    let version = request(&mut socket, "GetVersion", serde_json::json!({}))?;
+    let _ = socket.close(None);
+    Ok(format!("OBS {} / obs-websocket {}", version["obsVersion"], version["obsWebSocketVersion"]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlay_windows_trigger_the_showcase() {
+        assert!(is_showcase_window("market-overlay"));
+        assert!(is_showcase_window("poedb-overlay"));
+        assert!(!is_showcase_window("main"));
+        assert!(!is_showcase_window("login-intl"));
+        assert!(!is_showcase_window("trade-paste-cn"));
+    }
+}
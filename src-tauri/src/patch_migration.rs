@@ -0,0 +1,205 @@
+//! Migrating a filter library to a new game patch from a machine-readable list of
+//! basetype changes. `plan_patch_migration` reports what would change, then
+//! `apply_patch_migration` applies the renames through the batch engine; removed and newly
+//! added items are only reported, since what to do with them is a judgement call.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::WarlordError;
+use crate::file_ops::FailedFile;
+use crate::filter_parser::{self, quote, unquote, Block, FilterDocument, Rule};
+use crate::filter_transforms::{self, FileChange, WorkspaceChanges};
+use crate::library;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenamedBaseType {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddedBaseType {
+    pub base_type: String,
+    /// Item class of the new base, used to group suggested rules
+    #[serde(default)]
+    pub class: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PatchData {
+    /// Patch label, e.g. "3.26"
+    pub patch: String,
+    pub renamed: Vec<RenamedBaseType>,
+    pub removed: Vec<String>,
+    pub added: Vec<AddedBaseType>,
+}
+
+/// A rule that still references a basetype removed by the patch.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemovedRef {
+    pub path: String,
+    /// 0-based line of the BaseType rule
+    pub line: usize,
+    pub block_id: usize,
+    pub base_type: String,
+}
+
+/// Rule text for new items, grouped by class.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestedRule {
+    pub class: Option<String>,
+    pub base_types: Vec<String>,
+    pub rule: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationPlan {
+    pub patch: String,
+    /// Rewrites `apply_patch_migration` would make
+    pub renames: Vec<FileChange>,
+    /// Filters that could not be read
+    pub failed: Vec<FailedFile>,
+    pub removed_refs: Vec<RemovedRef>,
+    pub suggestions: Vec<SuggestedRule>,
+}
+
+/// BaseType values of `doc` that the patch removed.
+fn find_removed(path: &str, doc: &FilterDocument, removed: &[String]) -> Vec<RemovedRef> {
+    let mut refs = Vec::new();
+    for block in &doc.blocks {
+        for (i, line) in block.lines.iter().enumerate() {
+            let Some(rule) = line.rule.as_ref().filter(|r| r.keyword == "BaseType") else { continue };
+            for value in rule.values.iter().map(|v| unquote(v)) {
+                if removed.iter().any(|r| r == value) {
+                    refs.push(RemovedRef {
+                        path: path.to_string(),
+                        line: block.line + 1 + i,
+                        block_id: block.id,
+                        base_type: value.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    refs
+}
+
+/// The renames of `patch` as `(from, to)` pairs for `rename_basetypes_in`.
+fn renames(patch: &PatchData) -> Result<Vec<(&str, &str)>, WarlordError> {
+    let mut pairs: Vec<(&str, &str)> = Vec::new();
+    for rename in &patch.renamed {
+        if rename.from.is_empty() || rename.to.is_empty() {
+            return Err(WarlordError::invalid("BaseType 不能为空"));
+        }
+        match pairs.iter().find(|(from, _)| *from == rename.from) {
+            Some((_, to)) if *to != rename.to => return Err(WarlordError::invalid(format!("{} 被改名了两次", rename.from))),
+            Some(_) => {}
+            None => pairs.push((&rename.from, &rename.to)),
+        }
+    }
+    Ok(pairs)
+}
+
+fn suggest(patch: &str, added: &[AddedBaseType]) -> Vec<SuggestedRule> {
+    let mut groups: BTreeMap<Option<String>, Vec<String>> = BTreeMap::new();
+    for item in added {
+        groups.entry(item.class.clone()).or_default().push(item.base_type.clone());
+    }
+    groups
+        .into_iter()
+        .map(|(class, base_types)| {
+            let mut block = Block::new("Show");
+            block.leading.push(format!("# New in {}", patch));
+            if let Some(class) = &class {
+                block.push_rule(Rule::new("Class", vec![quote(class)]));
+            }
+            let mut rule = Rule::new("BaseType", base_types.iter().map(|b| quote(b)).collect());
+            rule.operator = Some("==".to_string());
+            block.push_rule(rule);
+            let rule = block.raw_lines().collect::<Vec<_>>().join("\n");
+            SuggestedRule { class, base_types, rule }
+        })
+        .collect()
+}
+
+/// What migrating `workspace` to `patch` would change, without writing anything.
+pub fn plan_patch_migration(workspace: &str, patch: &PatchData) -> Result<MigrationPlan, WarlordError> {
+    let pairs = renames(patch)?;
+    let mut plan = MigrationPlan { patch: patch.patch.clone(), renames: Vec::new(), failed: Vec::new(), removed_refs: Vec::new(), suggestions: suggest(&patch.patch, &patch.added) };
+    for file in library::filter_files(Path::new(workspace)).map_err(|e| WarlordError::io(e, Path::new(workspace)))? {
+        let path = file.display().to_string();
+        let mut doc = match filter_parser::parse_file(&path) {
+            Ok(doc) => doc,
+            Err(error) => {
+                plan.failed.push(FailedFile { path, error });
+                continue;
+            }
+        };
+        plan.removed_refs.extend(find_removed(&path, &doc, &patch.removed));
+        let lines = filter_transforms::rename_basetypes_in(&mut doc, &pairs);
+        if !lines.is_empty() {
+            plan.renames.push(FileChange { path, replacements: lines.len(), lines });
+        }
+    }
+    Ok(plan)
+}
+
+/// Apply the renames of `patch` to every filter under `workspace`.
+pub fn apply_patch_migration(workspace: &str, patch: &PatchData) -> Result<WorkspaceChanges, WarlordError> {
+    let pairs = renames(patch)?;
+    filter_transforms::apply_to_workspace(workspace, |doc| filter_transforms::rename_basetypes_in(doc, &pairs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn groups_suggestions_by_class() {
+        let added = vec![
+            AddedBaseType { base_type: "New Ring".to_string(), class: Some("Rings".to_string()) },
+            AddedBaseType { base_type: "Other Ring".to_string(), class: Some("Rings".to_string()) },
+        ];
+        let suggestions = suggest("3.26", &added);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].rule, "# New in 3.26\nShow\n    Class \"Rings\"\n    BaseType == \"New Ring\" \"Other Ring\"");
+    }
+
+    #[test]
+    fn plans_before_renaming_chains_once() {
+        let root = std::env::temp_dir().join("wt-patch-migration-test");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let original = "Show\n    BaseType \"A\" \"B\"\nShow\n    BaseType \"Gone\"\n";
+        fs::write(root.join("a.filter"), original).unwrap();
+        let patch = PatchData {
+            patch: "3.26".to_string(),
+            renamed: vec![
+                RenamedBaseType { from: "A".to_string(), to: "B".to_string() },
+                RenamedBaseType { from: "B".to_string(), to: "C".to_string() },
+            ],
+            removed: vec!["Gone".to_string()],
+            added: Vec::new(),
+        };
+        let workspace = root.display().to_string();
+
+        let plan = plan_patch_migration(&workspace, &patch).unwrap();
+        assert_eq!(plan.renames.len(), 1);
+        assert_eq!(plan.renames[0].lines, vec![1]);
+        assert_eq!(plan.removed_refs.len(), 1);
+        assert_eq!(fs::read_to_string(root.join("a.filter")).unwrap(), original);
+
+        let applied = apply_patch_migration(&workspace, &patch).unwrap();
+        assert_eq!(applied.changed.len(), 1);
+        assert!(fs::read_to_string(root.join("a.filter")).unwrap().contains("BaseType \"B\" \"C\""));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
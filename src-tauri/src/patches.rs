@@ -0,0 +1,358 @@
+//! User customizations kept apart from the upstream filter and re-applied after it updates.
+//! A patch is a list of operations on one target filter. Applying is idempotent: blocks added
+//! by a patch carry a `patch:<name>` provenance stamp and are replaced on every apply. The
+//! rule lines an apply changed are kept with their previous text, so inverting puts back
+//! exactly those lines and leaves lines that already had the new value alone. Patches are
+//! re-applied after upstream updates only when they opt in with `auto_reapply`.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::WarlordError;
+use crate::filter_parser::{self, Block, BlockLine, FilterDocument};
+use crate::{app_paths, block_edit, filter_merge, filter_transforms, library, provenance};
+
+const STATE_FILE: &str = "patches.json";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum PatchOp {
+    /// Replace a color in every color action ("R G B [A]")
+    #[serde(rename_all = "camelCase")]
+    Color { from: String, to: String, scope: Option<String> },
+    /// Replace an alert sound id or file
+    #[serde(rename_all = "camelCase")]
+    Sound { from: String, to: String, scope: Option<String> },
+    /// Rename an exact BaseType value
+    #[serde(rename_all = "camelCase")]
+    Rename { from: String, to: String },
+    /// Extra blocks, placed like `merge_filters` places add-on blocks
+    #[serde(rename_all = "camelCase")]
+    Blocks { text: String },
+}
+
+/// A rule line changed by an apply, to put back when the patch is inverted.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineChange {
+    /// Index among the blocks no patch added
+    pub block: usize,
+    /// Index in the block's lines
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Patch {
+    pub name: String,
+    /// Filter the patch applies to
+    pub target: String,
+    pub ops: Vec<PatchOp>,
+    pub enabled: bool,
+    pub created_at: u64,
+    /// SHA-256 of the target right after the last apply; a different hash means it was updated
+    #[serde(default)]
+    pub applied_hash: Option<String>,
+    /// Lines the applies since the target last changed upstream have changed, in order
+    #[serde(default)]
+    pub changed_lines: Vec<LineChange>,
+    /// Re-apply in the background after the target was updated
+    #[serde(default)]
+    pub auto_reapply: bool,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyReport {
+    pub name: String,
+    pub target: String,
+    /// Lines/blocks changed per operation, in order; for an invert the lines put back and
+    /// the blocks removed
+    pub changes: Vec<usize>,
+}
+
+static PATCHES: Mutex<Option<Vec<Patch>>> = Mutex::new(None);
+
+fn with_patches<R>(f: impl FnOnce(&mut Vec<Patch>) -> R) -> R {
+    let mut guard = PATCHES.lock().unwrap();
+    let patches = guard.get_or_insert_with(|| app_paths::load_json(STATE_FILE));
+    f(patches)
+}
+
+fn source(name: &str) -> String {
+    format!("patch:{}", name)
+}
+
+fn file_hash(path: &str) -> Option<String> {
+    fs::read(path).ok().map(|bytes| format!("{:x}", Sha256::digest(&bytes)))
+}
+
+/// Take out blocks added by an earlier apply of `name`.
+fn remove_blocks(doc: &mut FilterDocument, name: &str) -> usize {
+    let source = source(name);
+    let mut removed = 0;
+    let mut i = 0;
+    while i < doc.blocks.len() {
+        if provenance::read(&doc.blocks[i]).is_some_and(|p| p.source == source) {
+            block_edit::remove_attached(doc, i);
+            removed += 1;
+        } else {
+            i += 1;
+        }
+    }
+    doc.renumber();
+    removed
+}
+
+fn added_by_patch(block: &Block) -> bool {
+    provenance::read(block).is_some_and(|p| p.source.starts_with("patch:"))
+}
+
+/// Where `doc` line `number` is: the block index, the index among the blocks no patch added,
+/// and the index in that block.
+fn locate(doc: &FilterDocument, number: usize) -> Option<(usize, usize, usize)> {
+    let index = doc.blocks.iter().position(|b| number > b.line && number <= b.line + b.lines.len())?;
+    let upstream = doc.blocks[..index].iter().filter(|b| !added_by_patch(b)).count();
+    Some((index, upstream, number - doc.blocks[index].line - 1))
+}
+
+/// The block at `upstream` among the blocks no patch added.
+fn upstream_block(doc: &mut FilterDocument, upstream: usize) -> Option<&mut Block> {
+    doc.blocks.iter_mut().filter(|b| !added_by_patch(b)).nth(upstream)
+}
+
+/// Apply `ops` to `doc`; returns the number of changes per op and the rule lines changed.
+fn apply_recording(doc: &mut FilterDocument, name: &str, ops: &[PatchOp]) -> Result<(Vec<usize>, Vec<LineChange>), WarlordError> {
+    let mut changes = Vec::new();
+    let mut changed_lines = Vec::new();
+    for op in ops {
+        let before: Vec<Vec<String>> = doc.blocks.iter().map(|b| b.lines.iter().map(|l| l.raw.clone()).collect()).collect();
+        let lines = match op {
+            PatchOp::Color { from, to, scope } => {
+                let from = filter_transforms::parse_rgba(from)?;
+                filter_transforms::parse_rgba(to)?;
+                let to: Vec<String> = to.split_whitespace().map(String::from).collect();
+                filter_transforms::replace_color_in(doc, from, &to, scope.as_deref())
+            }
+            PatchOp::Sound { from, to, scope } => filter_transforms::swap_alert_sound_in(doc, from, to, scope.as_deref()),
+            PatchOp::Rename { from, to } => filter_transforms::rename_basetype_in(doc, from, to),
+            PatchOp::Blocks { text } => {
+                let removed = remove_blocks(doc, name);
+                let extra = FilterDocument::parse(text);
+                let (inserted, _) = filter_merge::merge_documents(doc, &extra, &source(name));
+                changes.push(removed.max(inserted));
+                continue;
+            }
+        };
+        for &number in &lines {
+            let Some((index, block, line)) = locate(doc, number) else { continue };
+            changed_lines.push(LineChange { block, line, before: before[index][line].clone(), after: doc.blocks[index].lines[line].raw.clone() });
+        }
+        changes.push(lines.len());
+    }
+    Ok((changes, changed_lines))
+}
+
+/// Apply `ops` to `doc`; returns the number of changes per op.
+pub fn apply_ops(doc: &mut FilterDocument, name: &str, ops: &[PatchOp]) -> Result<Vec<usize>, WarlordError> {
+    apply_recording(doc, name, ops).map(|(changes, _)| changes)
+}
+
+/// Undo what `patch` did to `doc`: its blocks are removed and the lines it changed get their
+/// previous text back, unless they were edited since. Returns the lines put back and the
+/// blocks removed.
+fn revert(doc: &mut FilterDocument, patch: &Patch) -> (usize, usize) {
+    let removed = remove_blocks(doc, &patch.name);
+    let mut restored = 0;
+    for change in patch.changed_lines.iter().rev() {
+        let Some(line) = upstream_block(doc, change.block).and_then(|b| b.lines.get_mut(change.line)) else { continue };
+        if line.raw == change.after {
+            *line = BlockLine::parse(&change.before);
+            restored += 1;
+        }
+    }
+    (restored, removed)
+}
+
+pub fn create_patch(name: &str, target: &str, ops: Vec<PatchOp>) -> Result<Patch, WarlordError> {
+    if name.trim().is_empty() {
+        return Err(WarlordError::invalid("补丁名称不能为空"));
+    }
+    let patch = Patch {
+        name: name.to_string(),
+        target: target.to_string(),
+        ops,
+        enabled: true,
+        created_at: app_paths::now_secs(),
+        applied_hash: None,
+        changed_lines: Vec::new(),
+        auto_reapply: false,
+    };
+    with_patches(|patches| {
+        patches.retain(|p| p.name != name);
+        patches.push(patch.clone());
+        app_paths::save_json(STATE_FILE, patches)
+    })?;
+    Ok(patch)
+}
+
+pub fn list_patches() -> Vec<Patch> {
+    with_patches(|patches| patches.clone())
+}
+
+pub fn delete_patch(name: &str) -> Result<(), WarlordError> {
+    with_patches(|patches| {
+        patches.retain(|p| p.name != name);
+        app_paths::save_json(STATE_FILE, patches)
+    })
+}
+
+fn find(name: &str) -> Result<Patch, WarlordError> {
+    with_patches(|patches| patches.iter().find(|p| p.name == name).cloned()).ok_or_else(|| WarlordError::invalid("补丁不存在"))
+}
+
+fn save_patch(name: &str, update: impl FnOnce(&mut Patch)) -> Result<(), WarlordError> {
+    with_patches(|patches| {
+        if let Some(p) = patches.iter_mut().find(|p| p.name == name) {
+            update(p);
+        }
+        app_paths::save_json(STATE_FILE, patches)
+    })
+}
+
+pub fn apply_patch(name: &str) -> Result<ApplyReport, WarlordError> {
+    let patch = find(name)?;
+    // Applied again over its own result: the lines changed before still count for an invert
+    let reapplied = patch.applied_hash.is_some() && file_hash(&patch.target) == patch.applied_hash;
+    let mut doc = filter_parser::parse_file(&patch.target)?;
+    let (changes, mut changed_lines) = apply_recording(&mut doc, name, &patch.ops)?;
+    filter_parser::write_file(&patch.target, &doc)?;
+    if reapplied {
+        changed_lines.splice(0..0, patch.changed_lines.iter().cloned());
+    }
+    let hash = file_hash(&patch.target);
+    save_patch(name, |p| {
+        p.enabled = true;
+        p.applied_hash = hash;
+        p.changed_lines = changed_lines;
+    })?;
+    Ok(ApplyReport { name: name.to_string(), target: patch.target, changes })
+}
+
+/// Undo a patch on its target and disable it.
+pub fn invert_patch(name: &str) -> Result<ApplyReport, WarlordError> {
+    let patch = find(name)?;
+    let mut doc = filter_parser::parse_file(&patch.target)?;
+    let (restored, removed) = revert(&mut doc, &patch);
+    filter_parser::write_file(&patch.target, &doc)?;
+    // An inverted patch stays off so it is not re-applied behind the user's back
+    save_patch(name, |p| {
+        p.enabled = false;
+        p.applied_hash = None;
+        p.changed_lines.clear();
+    })?;
+    Ok(ApplyReport { name: name.to_string(), target: patch.target, changes: vec![restored, removed] })
+}
+
+/// Turn background re-applying after upstream updates on or off for a patch.
+pub fn set_auto_reapply(name: &str, auto_reapply: bool) -> Result<Patch, WarlordError> {
+    find(name)?;
+    save_patch(name, |p| p.auto_reapply = auto_reapply)?;
+    find(name)
+}
+
+/// Enabled patches whose target changed since they were last applied.
+pub fn outdated() -> Vec<Patch> {
+    with_patches(|patches| {
+        patches
+            .iter()
+            .filter(|p| p.enabled && Path::new(&p.target).exists() && file_hash(&p.target) != p.applied_hash)
+            .cloned()
+            .collect()
+    })
+}
+
+/// Re-apply enabled `auto_reapply` patches whose target changed since they were last applied.
+pub fn reapply_outdated() -> Vec<ApplyReport> {
+    let mut reports = Vec::new();
+    for name in outdated().into_iter().filter(|p| p.auto_reapply).map(|p| p.name) {
+        match apply_patch(&name) {
+            Ok(report) => reports.push(report),
+            Err(e) => eprintln!("[WarlordTools] failed to re-apply patch {}: {}", name, e),
+        }
+    }
+    reports
+}
+
+/// Background thread that re-applies opted-in patches after upstream updates, once a minute
+/// (less often on battery).
+pub fn spawn_reapply_watcher() {
+    std::thread::spawn(|| loop {
+        for report in reapply_outdated() {
+            eprintln!("[WarlordTools] re-applied patch {} to {}", report.name, report.target);
+        }
+        std::thread::sleep(crate::power::interval(std::time::Duration::from_secs(60)));
+    });
+}
+
+/// Point patches at a renamed/moved filter. Returns the names that were updated.
+pub fn remap_paths(old: &Path, new: &Path) -> Result<Vec<String>, WarlordError> {
+    with_patches(|patches| {
+        let mut changed = Vec::new();
+        for patch in patches.iter_mut() {
+            if let Some(p) = library::remap(Path::new(&patch.target), old, new) {
+                patch.target = p.display().to_string();
+                changed.push(patch.name.clone());
+            }
+        }
+        if !changed.is_empty() {
+            app_paths::save_json(STATE_FILE, patches)?;
+        }
+        Ok(changed)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_is_idempotent_and_invertible() {
+        let src = "Show\n    BaseType \"Divine Orb\"\n    SetTextColor 255 0 0\n";
+        let ops = vec![
+            PatchOp::Color { from: "255 0 0".to_string(), to: "0 255 0".to_string(), scope: None },
+            PatchOp::Blocks { text: "Show\n    BaseType \"Mirror of Kalandra\"\n".to_string() },
+        ];
+        let mut doc = FilterDocument::parse(src);
+        let (_, mut changed_lines) = apply_recording(&mut doc, "mine", &ops).unwrap();
+        let (_, again) = apply_recording(&mut doc, "mine", &ops).unwrap();
+        changed_lines.extend(again);
+        assert_eq!(doc.blocks.len(), 2);
+        assert_eq!(doc.blocks[1].rule("SetTextColor").unwrap().values, vec!["0", "255", "0"]);
+
+        let patch = Patch { name: "mine".to_string(), target: String::new(), ops, enabled: true, created_at: 0, applied_hash: None, changed_lines, auto_reapply: false };
+        assert_eq!(revert(&mut doc, &patch), (1, 1));
+        assert_eq!(doc.to_text(), src);
+    }
+
+    #[test]
+    fn invert_keeps_lines_that_had_the_new_value() {
+        let src = "Show\n    SetTextColor 0 255 0\nShow\n    SetTextColor 255 0 0\n    PlayAlertSound 1 300\n";
+        let ops = vec![
+            PatchOp::Color { from: "255 0 0".to_string(), to: "0 255 0".to_string(), scope: None },
+            PatchOp::Sound { from: "1".to_string(), to: "2".to_string(), scope: None },
+            PatchOp::Blocks { text: "Show\n    BaseType \"Mirror of Kalandra\"\n".to_string() },
+        ];
+        let mut doc = FilterDocument::parse(src);
+        let (changes, changed_lines) = apply_recording(&mut doc, "mine", &ops).unwrap();
+        assert_eq!(changes, [1, 1, 1]);
+        let patch = Patch { name: "mine".to_string(), target: String::new(), ops, enabled: true, created_at: 0, applied_hash: None, changed_lines, auto_reapply: false };
+        revert(&mut doc, &patch);
+        assert_eq!(doc.to_text(), src);
+    }
+}
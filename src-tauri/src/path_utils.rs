@@ -0,0 +1,210 @@
+//! Path handling for the frontend, so it does not build paths by gluing strings with `/`.
+//! Everything is lexical (no disk access). On Windows both separators are accepted and `\`
+//! is written, drive letters are upper-cased, UNC (`\\server\share`) and `\\?\` prefixes are
+//! kept, and comparisons ignore case. `long_path` gives the extended-length form the
+//! filesystem commands hand to Windows so deep sound-pack folders work past MAX_PATH.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::WarlordError;
+
+/// A path split into prefix (drive, UNC share, `\\?\`), rootedness and components with `.`
+/// and `..` resolved.
+#[derive(Debug, PartialEq)]
+struct Parsed {
+    prefix: String,
+    absolute: bool,
+    parts: Vec<String>,
+}
+
+fn is_sep(c: char, windows: bool) -> bool {
+    c == '/' || (windows && c == '\\')
+}
+
+fn parse(path: &str, windows: bool) -> Parsed {
+    let mut rest = path;
+    let mut prefix = String::new();
+    let mut absolute = false;
+    if windows {
+        if let Some(r) = rest.strip_prefix(r"\\?\") {
+            prefix.push_str(r"\\?\");
+            rest = r;
+        } else if rest.len() > 2 && rest.chars().take(2).all(|c| is_sep(c, true)) {
+            let mut it = rest[2..].splitn(3, |c| is_sep(c, true));
+            let (server, share) = (it.next().unwrap_or(""), it.next().unwrap_or(""));
+            prefix = format!(r"\\{}\{}", server, share);
+            rest = it.next().unwrap_or("");
+            absolute = true;
+        }
+        let bytes = rest.as_bytes();
+        if bytes.len() >= 2 && bytes[1] == b':' && bytes[0].is_ascii_alphabetic() {
+            prefix.push((bytes[0] as char).to_ascii_uppercase());
+            prefix.push(':');
+            rest = &rest[2..];
+        }
+    }
+    absolute |= rest.starts_with(|c| is_sep(c, windows));
+    let mut parts: Vec<String> = Vec::new();
+    for part in rest.split(|c| is_sep(c, windows)).filter(|p| !p.is_empty() && *p != ".") {
+        if part == ".." {
+            match parts.last() {
+                Some(last) if last != ".." => {
+                    parts.pop();
+                }
+                // `..` above the root is the root
+                _ if absolute => {}
+                _ => parts.push(part.to_string()),
+            }
+        } else {
+            parts.push(part.to_string());
+        }
+    }
+    Parsed { prefix, absolute, parts }
+}
+
+fn render(parsed: &Parsed, windows: bool) -> String {
+    let sep = if windows { "\\" } else { "/" };
+    let mut out = parsed.prefix.clone();
+    if parsed.absolute && !out.ends_with('\\') {
+        out.push_str(sep);
+    }
+    out.push_str(&parsed.parts.join(sep));
+    if out.is_empty() {
+        out.push('.');
+    }
+    out
+}
+
+fn same_part(a: &str, b: &str, windows: bool) -> bool {
+    if windows {
+        a.to_lowercase() == b.to_lowercase()
+    } else {
+        a == b
+    }
+}
+
+fn normalize_for(path: &str, windows: bool) -> String {
+    render(&parse(path, windows), windows)
+}
+
+/// Join `parts` onto `base`; an absolute part starts over, like `Path::join`.
+fn join_for(base: &str, parts: &[String], windows: bool) -> String {
+    let mut joined = parse(base, windows);
+    for part in parts {
+        let next = parse(part, windows);
+        if next.absolute || !next.prefix.is_empty() {
+            joined = next;
+        } else {
+            joined = parse(&format!("{}/{}", render(&joined, windows), part), windows);
+        }
+    }
+    render(&joined, windows)
+}
+
+/// `to` relative to the directory `from`; None when they are on different drives or shares.
+fn relative_for(from: &str, to: &str, windows: bool) -> Option<String> {
+    let (from, to) = (parse(from, windows), parse(to, windows));
+    if !same_part(&from.prefix, &to.prefix, windows) || from.absolute != to.absolute {
+        return None;
+    }
+    let common = from.parts.iter().zip(&to.parts).take_while(|(a, b)| same_part(a, b, windows)).count();
+    let mut parts: Vec<String> = from.parts[common..].iter().map(|_| "..".to_string()).collect();
+    parts.extend(to.parts[common..].iter().cloned());
+    Some(render(&Parsed { prefix: String::new(), absolute: false, parts }, windows))
+}
+
+fn compare_for(a: &str, b: &str, windows: bool) -> bool {
+    same_part(&normalize_for(a, windows), &normalize_for(b, windows), windows)
+}
+
+/// `\\?\` form of an absolute path. The prefix turns off `/`, `.` and `..` handling in
+/// Windows, so the path is normalized first. Relative and device paths are left alone.
+fn long_for(path: &str, windows: bool) -> String {
+    if !windows || path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return path.to_string();
+    }
+    let normalized = normalize_for(path, true);
+    if let Some(unc) = normalized.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{}", unc)
+    } else if normalized.as_bytes().get(1..3) == Some(&b":\\"[..]) {
+        format!(r"\\?\{}", normalized)
+    } else {
+        path.to_string()
+    }
+}
+
+fn short_for(path: &str, windows: bool) -> String {
+    if !windows {
+        return path.to_string();
+    }
+    if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else {
+        path.strip_prefix(r"\\?\").unwrap_or(path).to_string()
+    }
+}
+
+/// Path to hand to `std::fs`: extended-length on Windows, so files deeper than MAX_PATH
+/// (260 characters) can be read, copied and deleted. Unchanged on other systems.
+pub fn long_path(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    PathBuf::from(long_for(&absolute.to_string_lossy(), true))
+}
+
+/// `path` without the extended-length prefix, for showing and comparing.
+pub fn short_path(path: impl AsRef<Path>) -> String {
+    short_for(&path.as_ref().to_string_lossy(), cfg!(windows))
+}
+
+pub fn normalize_path(path: &str) -> String {
+    normalize_for(path, cfg!(windows))
+}
+
+pub fn join_paths(base: &str, parts: &[String]) -> String {
+    join_for(base, parts, cfg!(windows))
+}
+
+pub fn relative_path(from: &str, to: &str) -> Result<String, WarlordError> {
+    relative_for(from, to, cfg!(windows)).ok_or_else(|| WarlordError::invalid(format!("{} 与 {} 不在同一个驱动器上", from, to)))
+}
+
+/// Whether both name the same path (case-insensitive on Windows).
+pub fn compare_paths(a: &str, b: &str) -> bool {
+    compare_for(a, b, cfg!(windows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_semantics() {
+        assert_eq!(normalize_for("c:/Users//me/./Documents/../Filters/", true), r"C:\Users\me\Filters");
+        assert_eq!(normalize_for(r"\\nas\poe/guild\..\leader.filter", true), r"\\nas\poe\leader.filter");
+        assert_eq!(normalize_for(r"\\?\C:\very\long", true), r"\\?\C:\very\long");
+        assert_eq!(normalize_for("../a/../../b", true), r"..\..\b");
+        assert_eq!(normalize_for("/a/../../b", false), "/b");
+        assert_eq!(join_for(r"C:\Filters", &["sounds".to_string(), "alert.mp3".to_string()], true), r"C:\Filters\sounds\alert.mp3");
+        assert_eq!(join_for(r"C:\Filters", &[r"D:\other".to_string()], true), r"D:\other");
+        assert_eq!(relative_for(r"C:\Filters\league", r"c:\filters\Sounds\a.mp3", true).unwrap(), r"..\Sounds\a.mp3");
+        assert!(relative_for(r"C:\Filters", r"D:\Filters", true).is_none());
+        assert!(compare_for(r"C:\Filters\A.filter", "c:/filters/a.FILTER", true));
+        assert!(!compare_for("/filters/A.filter", "/filters/a.filter", false));
+    }
+
+    #[test]
+    fn extended_length_paths() {
+        assert_eq!(long_for("c:/Sounds/pack/../alert.mp3", true), r"\\?\C:\Sounds\alert.mp3");
+        assert_eq!(long_for(r"\\nas\poe\sounds\a.mp3", true), r"\\?\UNC\nas\poe\sounds\a.mp3");
+        assert_eq!(long_for(r"\\?\C:\already", true), r"\\?\C:\already");
+        assert_eq!(long_for(r"sounds\a.mp3", true), r"sounds\a.mp3");
+        assert_eq!(long_for("/home/me/a.mp3", false), "/home/me/a.mp3");
+        for path in [r"C:\Sounds\alert.mp3", r"\\nas\poe\sounds\a.mp3"] {
+            assert_eq!(short_for(&long_for(path, true), true), path);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Named export pipelines declared in the workspace manifest (compile, lint, minify, sign,
+//! install, notify). Steps run in order and the pipeline stops at the first failure.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::WarlordError;
+use crate::filter_format::{self, MinifyOptions};
+use crate::filter_parser::{self, FilterDocument};
+use crate::library;
+use crate::manifest::{self, PipelineStep};
+use crate::preprocessor;
+use crate::sandbox;
+use crate::webhooks;
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepResult {
+    pub kind: String,
+    pub ok: bool,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineReport {
+    pub name: String,
+    pub output: String,
+    pub ok: bool,
+    pub steps: Vec<StepResult>,
+    /// Messages of notify steps, shown by the frontend
+    pub notifications: Vec<String>,
+}
+
+fn step_kind(step: &PipelineStep) -> &'static str {
+    match step {
+        PipelineStep::Compile => "compile",
+        PipelineStep::Lint => "lint",
+        PipelineStep::Minify { .. } => "minify",
+        PipelineStep::Sign => "sign",
+        PipelineStep::Install { .. } => "install",
+        PipelineStep::Notify { .. } => "notify",
+    }
+}
+
+/// Conditions missing from the formatter's ordering list.
+const EXTRA_KEYWORDS: &[&str] = &[
+    "EnchantmentPassiveNode",
+    "EnchantmentPassiveNum",
+    "ArchnemesisMod",
+    "AlternateQuality",
+    "Scourged",
+    "HasSearingExarchImplicit",
+    "HasEaterOfWorldsImplicit",
+    "HasCruciblePassiveTree",
+    "ZanaMemory",
+    "MemoryStrands",
+    "BaseDefencePercentile",
+    "BaseArmour",
+    "BaseEvasion",
+    "BaseEnergyShield",
+    "BaseWard",
+    "CorruptedMods",
+];
+
+fn is_known_keyword(keyword: &str) -> bool {
+    [filter_format::KEYWORD_ORDER, filter_format::STRING_KEYWORDS, filter_parser::ACTION_KEYWORDS, EXTRA_KEYWORDS]
+        .iter()
+        .any(|list| list.contains(&keyword))
+}
+
+/// Body lines that are neither comments nor rules with a known keyword.
+pub fn lint_document(doc: &FilterDocument) -> Vec<String> {
+    let mut problems = Vec::new();
+    for block in &doc.blocks {
+        for (i, line) in block.lines.iter().enumerate() {
+            let text = line.raw.trim();
+            let known = line.rule.as_ref().is_some_and(|r| is_known_keyword(&r.keyword));
+            if !known && !text.is_empty() && !text.starts_with('#') {
+                problems.push(format!("第 {} 行: 无法识别 \"{}\"", block.line + 2 + i, text));
+            }
+        }
+        if block.rules().any(|r| r.keyword == "BaseType" && r.values.is_empty()) {
+            problems.push(format!("第 {} 行: BaseType 没有值", block.line + 1));
+        }
+    }
+    problems
+}
+
+/// Where an `Install` step copies `output`.
+pub fn install_target(dest: Option<&str>, output: &Path) -> Result<PathBuf, WarlordError> {
+    let dest = dest.map(PathBuf::from).or_else(library::library_root).ok_or_else(|| WarlordError::invalid("未设置安装目录"))?;
+    Ok(dest.join(output.file_name().ok_or_else(|| WarlordError::invalid("无效的输出文件"))?))
+}
+
+fn run_step(step: &PipelineStep, source: &Path, output: &Path, notifications: &mut Vec<String>) -> Result<String, WarlordError> {
+    let out = output.display().to_string();
+    match step {
+        PipelineStep::Compile => {
+            sandbox::check_write(output)?;
+            if let Some(dir) = output.parent() {
+                fs::create_dir_all(dir).map_err(|e| WarlordError::io(e, dir))?;
+            }
+            let src = source.display().to_string();
+            if source.extension().is_some_and(|e| e == preprocessor::SOURCE_EXTENSION) {
+                let report = preprocessor::compile_filter(&src, &out)?;
+                return Ok(format!("{} variables, {} expansions", report.variables, report.expansions));
+            }
+            let doc = filter_parser::parse_file(&src)?;
+            filter_parser::write_file(&out, &doc)?;
+            Ok(format!("{} blocks", doc.blocks.len()))
+        }
+        PipelineStep::Lint => {
+            let problems = lint_document(&filter_parser::parse_file(&out)?);
+            if problems.is_empty() { Ok("no problems".to_string()) } else { Err(WarlordError::invalid(problems.join("\n"))) }
+        }
+        PipelineStep::Minify { keep_header } => {
+            let report = filter_format::minify_file(&out, &out, &MinifyOptions { keep_header: *keep_header })?;
+            Ok(format!("{} -> {} bytes", report.original_bytes, report.minified_bytes))
+        }
+        PipelineStep::Sign => {
+            let bytes = fs::read(output).map_err(|e| WarlordError::io(e, output))?;
+            let digest = format!("{:x}", Sha256::digest(&bytes));
+            let name = output.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            sandbox::write(format!("{}.sha256", out), format!("{}  {}\n", digest, name))?;
+            Ok(digest)
+        }
+        PipelineStep::Install { dest } => {
+            let target = install_target(dest.as_deref(), output)?;
+            if target != output {
+                sandbox::write(&target, fs::read(output).map_err(|e| WarlordError::io(e, output))?)?;
+            }
+            Ok(target.display().to_string())
+        }
+        PipelineStep::Notify { message } => {
+            let message = message.clone().unwrap_or_else(|| format!("{} 已发布", out));
+            notifications.push(message.clone());
+            Ok(message)
+        }
+    }
+}
+
+pub fn run_pipeline(workspace: &Path, name: &str) -> Result<PipelineReport, WarlordError> {
+    let manifest = manifest::load(workspace)?;
+    let pipeline = manifest.pipelines.get(name).ok_or_else(|| WarlordError::invalid(format!("未找到流水线: {}", name)))?;
+    let (source, output) = (workspace.join(&pipeline.source), workspace.join(&pipeline.output));
+
+    let mut report = PipelineReport {
+        name: name.to_string(),
+        output: output.display().to_string(),
+        ok: true,
+        steps: Vec::new(),
+        notifications: Vec::new(),
+    };
+    for step in &pipeline.steps {
+        let result = run_step(step, &source, &output, &mut report.notifications);
+        let ok = result.is_ok();
+        report.steps.push(StepResult {
+            kind: step_kind(step).to_string(),
+            ok,
+            message: result.unwrap_or_else(|e| e.to_string()),
+        });
+        if !ok {
+            report.ok = false;
+            break;
+        }
+    }
+    eprintln!("[WarlordTools] pipeline {}: {}", name, if report.ok { "ok" } else { "failed" });
+    let summary: Vec<String> = report
+        .steps
+        .iter()
+        .map(|s| format!("{} {}: {}", if s.ok { "✔" } else { "✘" }, s.kind, s.message))
+        .chain(report.notifications.iter().cloned())
+        .collect();
+    let title = format!("{} {}", name, if report.ok { "完成" } else { "失败" });
+    webhooks::notify(webhooks::EVENT_PIPELINE, &title, &summary.join("\n"), report.ok);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lints_unknown_lines() {
+        let doc = FilterDocument::parse("Show\n    BaseType \"Orb\"\n    # fine\n    \"stray\"\n");
+        assert_eq!(lint_document(&doc).len(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+//! PoE1 -> PoE2 filter conversion. Keywords and classes with a PoE2 counterpart are
+//! renamed; blocks that depend on a PoE1-only mechanic are commented out with a `# [PoE1] `
+//! prefix, since dropping their condition would make them match far more items.
+
+use crate::error::WarlordError;
+use crate::filter_parser::{self, quote, unquote, Block, FilterDocument, Rule};
+use crate::sandbox;
+
+pub const DISABLED_PREFIX: &str = "# [PoE1] ";
+
+const KEYWORD_RENAMES: &[(&str, &str)] = &[("MapTier", "WaystoneTier")];
+
+const CLASS_RENAMES: &[(&str, &str)] = &[("Maps", "Waystones"), ("Map", "Waystone")];
+
+/// Conditions on mechanics that do not exist in PoE2
+const UNSUPPORTED_CONDITIONS: &[&str] = &[
+    "LinkedSockets",
+    "SocketGroup",
+    "ShaperItem",
+    "ElderItem",
+    "HasInfluence",
+    "FracturedItem",
+    "SynthesisedItem",
+    "ShapedMap",
+    "ElderMap",
+    "BlightedMap",
+    "UberBlightedMap",
+    "Replica",
+    "GemQualityType",
+    "AlternateQuality",
+    "TransfiguredGem",
+    "HasEnchantment",
+    "AnyEnchantment",
+    "EnchantmentPassiveNode",
+    "EnchantmentPassiveNum",
+    "Scourged",
+    "HasSearingExarchImplicit",
+    "HasEaterOfWorldsImplicit",
+    "ArchnemesisMod",
+    "HasCruciblePassiveTree",
+    "ZanaMemory",
+    "MemoryStrands",
+];
+
+const UNSUPPORTED_CLASSES: &[&str] = &[
+    "Divination Cards",
+    "Heist Blueprints",
+    "Heist Contracts",
+    "Heist Brooches",
+    "Heist Cloaks",
+    "Heist Gear",
+    "Heist Tools",
+    "Blueprints",
+    "Contracts",
+    "Incubators",
+    "Abyss Jewels",
+    "Sentinels",
+    "Memories",
+    "Atlas Upgrade Items",
+    "Expedition Logbooks",
+];
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisabledBlock {
+    /// 1-based line of the block header
+    pub line: usize,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionReport {
+    pub dest: String,
+    /// Lines rewritten to PoE2 syntax
+    pub converted: usize,
+    pub disabled: Vec<DisabledBlock>,
+}
+
+/// Convert one block in place. Returns (changed lines, reason to disable it).
+fn convert_block(block: &mut Block) -> (usize, Option<String>) {
+    let mut converted = 0;
+    for line in &mut block.lines {
+        let Some(rule) = line.rule.as_ref() else { continue };
+        if UNSUPPORTED_CONDITIONS.contains(&rule.keyword.as_str()) {
+            return (converted, Some(format!("PoE2 不支持 {}", rule.keyword)));
+        }
+        let mut new_rule: Rule = rule.clone();
+        if let Some((_, to)) = KEYWORD_RENAMES.iter().find(|(from, _)| *from == rule.keyword) {
+            new_rule.keyword = to.to_string();
+        }
+        if rule.keyword == "Class" {
+            let before = new_rule.values.len();
+            new_rule.values.retain(|v| !UNSUPPORTED_CLASSES.contains(&unquote(v)));
+            if new_rule.values.is_empty() && before > 0 {
+                return (converted, Some(format!("PoE2 中没有物品类别 {}", rule.values.join(" "))));
+            }
+            for v in &mut new_rule.values {
+                if let Some((_, to)) = CLASS_RENAMES.iter().find(|(from, _)| *from == unquote(v)) {
+                    *v = quote(to);
+                }
+            }
+        }
+        if &new_rule != rule {
+            line.set_rule(new_rule);
+            converted += 1;
+        }
+    }
+    (converted, None)
+}
+
+/// Text of `doc` with the header and body lines of `blocks` (indices) prefixed by `prefix`.
+pub fn comment_out_blocks(doc: &FilterDocument, blocks: &[usize], prefix: &str) -> String {
+    let ranges: Vec<_> = blocks.iter().filter_map(|&i| doc.blocks.get(i)).map(|b| b.line..=b.line + b.lines.len()).collect();
+    let text = doc.to_text();
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        if ranges.iter().any(|r| r.contains(&i)) {
+            out.push_str(prefix);
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+/// Convert `doc`; returns the PoE2 text and the report (with an empty `dest`).
+pub fn convert_document(mut doc: FilterDocument) -> (String, ConversionReport) {
+    let mut report = ConversionReport { dest: String::new(), converted: 0, disabled: Vec::new() };
+    let mut disabled = Vec::new();
+    for (i, block) in doc.blocks.iter_mut().enumerate() {
+        let (converted, reason) = convert_block(block);
+        report.converted += converted;
+        if let Some(reason) = reason {
+            disabled.push(i);
+            report.disabled.push(DisabledBlock { line: block.line + 1, reason });
+        }
+    }
+    (comment_out_blocks(&doc, &disabled, DISABLED_PREFIX), report)
+}
+
+pub fn convert_poe1_filter(src: &str, dest: &str) -> Result<ConversionReport, WarlordError> {
+    let doc = filter_parser::parse_file(src)?;
+    let (text, mut report) = convert_document(doc);
+    sandbox::write(dest, text)?;
+    report.dest = dest.to_string();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renames_and_disables() {
+        let src = "Show\n    Class \"Maps\"\n    MapTier >= 15\nShow\n    LinkedSockets 6\n\nShow\n    Class \"Divination Cards\" \"Currency\"\n";
+        let (text, report) = convert_document(FilterDocument::parse(src));
+        assert_eq!(
+            text,
+            "Show\n    Class \"Waystones\"\n    WaystoneTier >= 15\n# [PoE1] Show\n# [PoE1]     LinkedSockets 6\n\nShow\n    Class \"Currency\"\n"
+        );
+        assert_eq!(report.converted, 3);
+        assert_eq!(report.disabled.len(), 1);
+        assert_eq!(report.disabled[0].line, 4);
+    }
+}
@@ -0,0 +1,122 @@
+//! Battery awareness. While the machine runs on battery, background loops poll less often and
+//! optional background work is skipped: the launch scan of the library for the dashboard, and
+//! file watcher reports (changes still mark the scan cache, so the next refresh sees them).
+//! `battery_saver = false` turns this off for users who would rather have full speed.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::WarlordError;
+use crate::app_paths;
+
+const CONFIG_FILE: &str = "power.json";
+/// Polling intervals are multiplied by this on battery
+const BATTERY_STRETCH: u32 = 4;
+const RECHECK: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PowerConfig {
+    pub battery_saver: bool,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        PowerConfig { battery_saver: true }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerStatus {
+    pub on_battery: bool,
+    /// Battery saving is in effect (on battery and not overridden)
+    pub saving: bool,
+}
+
+/// Last reading and when it was taken; the power state is re-read at most every `RECHECK`
+static CACHE: Mutex<Option<(Instant, PowerStatus)>> = Mutex::new(None);
+
+pub fn get_config() -> PowerConfig {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+pub fn set_config(config: &PowerConfig) -> Result<(), WarlordError> {
+    app_paths::save_json(CONFIG_FILE, config)?;
+    *CACHE.lock().unwrap() = None;
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn on_battery() -> bool {
+    #[repr(C)]
+    #[derive(Default)]
+    struct SystemPowerStatus {
+        ac_line_status: u8,
+        battery_flag: u8,
+        battery_life_percent: u8,
+        system_status_flag: u8,
+        battery_life_time: u32,
+        battery_full_life_time: u32,
+    }
+    extern "system" {
+        fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+    }
+    let mut status = SystemPowerStatus::default();
+    // ACLineStatus: 0 offline, 1 online, 255 unknown
+    unsafe { GetSystemPowerStatus(&mut status) != 0 && status.ac_line_status == 0 }
+}
+
+/// Linux (Steam Deck): on battery when a battery exists and no mains supply is online.
+#[cfg(not(target_os = "windows"))]
+fn on_battery() -> bool {
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else { return false };
+    let (mut battery, mut mains_online) = (false, false);
+    for entry in entries.flatten() {
+        let read = |name: &str| std::fs::read_to_string(entry.path().join(name)).unwrap_or_default().trim().to_string();
+        match read("type").as_str() {
+            "Battery" => battery = true,
+            "Mains" | "USB" => mains_online |= read("online") == "1",
+            _ => {}
+        }
+    }
+    battery && !mains_online
+}
+
+pub fn status() -> PowerStatus {
+    let mut cache = CACHE.lock().unwrap();
+    if let Some((at, status)) = cache.as_ref() {
+        if at.elapsed() < RECHECK {
+            return status.clone();
+        }
+    }
+    let on_battery = on_battery();
+    let status = PowerStatus { on_battery, saving: on_battery && get_config().battery_saver };
+    *cache = Some((Instant::now(), status.clone()));
+    status
+}
+
+/// Polling interval to use right now for a loop that normally waits `base`.
+pub fn interval(base: Duration) -> Duration {
+    stretch(base, status().saving)
+}
+
+fn stretch(base: Duration, saving: bool) -> Duration {
+    if saving { base * BATTERY_STRETCH } else { base }
+}
+
+/// Whether optional background work should run right now.
+pub fn background_allowed() -> bool {
+    !status().saving
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stretches_only_while_saving() {
+        assert_eq!(stretch(Duration::from_millis(500), false), Duration::from_millis(500));
+        assert_eq!(stretch(Duration::from_millis(500), true), Duration::from_secs(2));
+    }
+}
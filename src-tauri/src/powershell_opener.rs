@@ -3,6 +3,8 @@ use std::process::Command;
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 
+use crate::error::WarlordError;
+
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
@@ -12,7 +14,7 @@ fn escape_single_quotes(s: &str) -> String {
 
 /// Open a folder using PowerShell -> Start-Process (hidden)
 /// Returns Err(String) on failure.
-pub fn open_folder(path: &str) -> Result<(), String> {
+pub fn open_folder(path: &str) -> Result<(), WarlordError> {
     #[cfg(windows)]
     {
         let p = escape_single_quotes(path);
@@ -24,11 +26,11 @@ pub fn open_folder(path: &str) -> Result<(), String> {
         // prevent flashing console window
         cmd.creation_flags(CREATE_NO_WINDOW);
 
-        let status = cmd.spawn().map_err(|e| e.to_string())?.wait().map_err(|e| e.to_string())?;
+        let status = cmd.spawn()?.wait()?;
         if status.success() {
             Ok(())
         } else {
-            Err(format!("process exited with {}", status))
+            Err(WarlordError::invalid(format!("process exited with {}", status)))
         }
     }
 
@@ -39,19 +41,18 @@ pub fn open_folder(path: &str) -> Result<(), String> {
             Command::new("open").arg(path).status()
         } else {
             Command::new("xdg-open").arg(path).status()
-        }
-        .map_err(|e| e.to_string())?;
+        }?;
 
         if status.success() {
             Ok(())
         } else {
-            Err(format!("process exited with {}", status))
+            Err(WarlordError::invalid(format!("process exited with {}", status)))
         }
     }
 }
 
 /// Open a file using PowerShell -> Start-Process (hidden)
-pub fn open_file(path: &str) -> Result<(), String> {
+pub fn open_file(path: &str) -> Result<(), WarlordError> {
     #[cfg(windows)]
     {
         let p = escape_single_quotes(path);
@@ -61,11 +62,11 @@ pub fn open_file(path: &str) -> Result<(), String> {
         cmd.arg("-NoProfile").arg("-NonInteractive").arg("-Command").arg(ps_cmd);
         cmd.creation_flags(CREATE_NO_WINDOW);
 
-        let status = cmd.spawn().map_err(|e| e.to_string())?.wait().map_err(|e| e.to_string())?;
+        let status = cmd.spawn()?.wait()?;
         if status.success() {
             Ok(())
         } else {
-            Err(format!("process exited with {}", status))
+            Err(WarlordError::invalid(format!("process exited with {}", status)))
         }
     }
 
@@ -75,32 +76,32 @@ pub fn open_file(path: &str) -> Result<(), String> {
             Command::new("open").arg(path).status()
         } else {
             Command::new("xdg-open").arg(path).status()
-        }
-        .map_err(|e| e.to_string())?;
+        }?;
 
         if status.success() {
             Ok(())
         } else {
-            Err(format!("process exited with {}", status))
+            Err(WarlordError::invalid(format!("process exited with {}", status)))
         }
     }
 }
 
 /// Copy file using PowerShell (Hidden) to bypass some permission issues or just use native shell
-pub fn copy_file_powershell(src: &str, dest: &str) -> Result<(), String> {
+pub fn copy_file_powershell(src: &str, dest: &str) -> Result<(), WarlordError> {
     #[cfg(windows)]
     {
         let s = escape_single_quotes(src);
         let d = escape_single_quotes(dest);
         
+        // Parent taken here so no part of the path is read as a wildcard pattern
+        let parent = std::path::Path::new(dest).parent().map(|p| escape_single_quotes(&p.to_string_lossy())).unwrap_or_default();
+
         // Ensure directory exists then copy
-        // $d is the full file path. We need to create the parent directory.
-        // PowerShell: New-Item -ItemType Directory -Force -Path (Split-Path -Path 'dest' -Parent); Copy-Item -Path 'src' -Destination 'dest' -Force
-        
-        // Note: We use Split-Path to get parent dir from the destination file path
+        // -LiteralPath: sound names contain [ ] (wildcards to -Path), and `\\?\` long paths
+        // pass through untouched
         let ps_cmd = format!(
-            "New-Item -ItemType Directory -Force -Path (Split-Path -Path '{}' -Parent); Copy-Item -Path '{}' -Destination '{}' -Force", 
-            d, s, d
+            "New-Item -ItemType Directory -Force -Path '{}' | Out-Null; Copy-Item -LiteralPath '{}' -Destination '{}' -Force",
+            parent, s, d
         );
 
         let mut cmd = Command::new("powershell");
@@ -108,11 +109,11 @@ pub fn copy_file_powershell(src: &str, dest: &str) -> Result<(), String> {
         // Hide window
         cmd.creation_flags(CREATE_NO_WINDOW);
 
-        let status = cmd.spawn().map_err(|e| e.to_string())?.wait().map_err(|e| e.to_string())?;
+        let status = cmd.spawn()?.wait()?;
         if status.success() {
             Ok(())
         } else {
-            Err(format!("Copy process exited with {}", status))
+            Err(WarlordError::invalid(format!("Copy process exited with {}", status)))
         }
     }
     #[cfg(not(windows))]
@@ -122,9 +123,9 @@ pub fn copy_file_powershell(src: &str, dest: &str) -> Result<(), String> {
          use std::path::Path;
          
          if let Some(parent) = Path::new(dest).parent() {
-             fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+             fs::create_dir_all(parent).map_err(|e| WarlordError::io(e, parent))?;
          }
-         fs::copy(src, dest).map_err(|e| e.to_string())?;
+         fs::copy(src, dest).map_err(|e| WarlordError::io(e, src))?;
          Ok(())
     }
 }
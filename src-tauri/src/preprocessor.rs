@@ -0,0 +1,238 @@
+//! `.filtersrc` preprocessor. A source file is a normal filter plus variable definitions:
+//!
+//! ```text
+//! $T1Color = 255 0 0 255
+//! $T1Sound = 6 300
+//! Show
+//!     SetTextColor $T1Color
+//!     PlayAlertSound $T1Sound
+//! ```
+//!
+//! Definition lines are dropped from the output and `$Name` is replaced outside comments,
+//! so NeverSink-style `# $type->...` tags in comments are left alone. `#include "path"`
+//! (relative to the including file) pulls in another source or filter file in place.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::error::WarlordError;
+use crate::filter_parser::FilterDocument;
+use crate::{encoding, pipelines};
+
+pub const SOURCE_EXTENSION: &str = "filtersrc";
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompileReport {
+    pub dest: String,
+    pub variables: usize,
+    pub expansions: usize,
+}
+
+fn definition_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^\s*\$([A-Za-z_][A-Za-z0-9_]*)\s*=\s*(.*?)\s*$").unwrap())
+}
+
+fn reference_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\$([A-Za-z_][A-Za-z0-9_]*)").unwrap())
+}
+
+/// Byte offset of the `#` starting a comment, ignoring `#` inside quotes.
+fn comment_start(line: &str) -> Option<usize> {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Replace references in `code`; unknown names are collected into `missing`.
+fn substitute(code: &str, vars: &HashMap<String, String>, missing: &mut Vec<String>, count: &mut usize) -> String {
+    reference_re()
+        .replace_all(code, |c: &regex::Captures| match vars.get(&c[1]) {
+            Some(value) => {
+                *count += 1;
+                value.clone()
+            }
+            None => {
+                missing.push(c[0].to_string());
+                c[0].to_string()
+            }
+        })
+        .into_owned()
+}
+
+fn include_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"^\s*#include\s+"([^"]+)"\s*$"#).unwrap())
+}
+
+/// Expansion state shared across included files: variables defined in an include are
+/// visible to the lines after it.
+#[derive(Default)]
+struct Expander {
+    vars: HashMap<String, String>,
+    out: Vec<String>,
+    errors: Vec<String>,
+    expansions: usize,
+    /// Every file read, in include order
+    files: Vec<String>,
+    /// Files currently being expanded, to detect include cycles
+    stack: Vec<PathBuf>,
+}
+
+impl Expander {
+    fn error(&mut self, origin: Option<&Path>, line: usize, message: String) {
+        match origin {
+            Some(path) => self.errors.push(format!("{} 第 {} 行: {}", path.display(), line, message)),
+            None => self.errors.push(format!("第 {} 行: {}", line, message)),
+        }
+    }
+
+    fn file(&mut self, path: &Path, nested: bool) {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.stack.contains(&key) {
+            let message = format!("循环引用 {}", path.display());
+            self.errors.push(message);
+            return;
+        }
+        let content = match encoding::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => {
+                self.errors.push(format!("无法读取 {}: {}", path.display(), e));
+                return;
+            }
+        };
+        self.files.push(path.display().to_string());
+        self.stack.push(key);
+        // An included file's final newline would otherwise add a blank line at the join
+        let text = if nested { content.strip_suffix('\n').unwrap_or(&content) } else { &content };
+        self.text(text, Some(path));
+        self.stack.pop();
+    }
+
+    fn text(&mut self, src: &str, origin: Option<&Path>) {
+        let base_dir = origin.and_then(Path::parent).map(Path::to_path_buf);
+        for (idx, raw) in src.split('\n').enumerate() {
+            let (line, cr) = match raw.strip_suffix('\r') {
+                Some(l) => (l, "\r"),
+                None => (raw, ""),
+            };
+            let mut missing = Vec::new();
+            if let Some(inc) = include_re().captures(line) {
+                match &base_dir {
+                    Some(dir) => self.file(&dir.join(&inc[1]), true),
+                    None => self.error(origin, idx + 1, format!("无法解析 #include \"{}\"", &inc[1])),
+                }
+            } else if let Some(def) = definition_re().captures(line) {
+                // Definitions may use earlier variables
+                let value = substitute(&def[2], &self.vars, &mut missing, &mut 0);
+                self.vars.insert(def[1].to_string(), value);
+            } else {
+                let split = comment_start(line).unwrap_or(line.len());
+                let code = substitute(&line[..split], &self.vars, &mut missing, &mut self.expansions);
+                self.out.push(format!("{}{}{}", code, &line[split..], cr));
+            }
+            for name in missing {
+                self.error(origin, idx + 1, format!("未定义的变量 {}", name));
+            }
+        }
+    }
+
+    fn finish(self) -> Result<Expanded, WarlordError> {
+        if !self.errors.is_empty() {
+            return Err(WarlordError::invalid(self.errors.join("\n")));
+        }
+        Ok(Expanded { text: self.out.join("\n"), variables: self.vars.len(), expansions: self.expansions, files: self.files })
+    }
+}
+
+pub struct Expanded {
+    pub text: String,
+    pub variables: usize,
+    pub expansions: usize,
+    pub files: Vec<String>,
+}
+
+/// Expand a `.filtersrc` text without a file of its own (`#include` is not available).
+pub fn expand(src: &str) -> Result<Expanded, WarlordError> {
+    let mut expander = Expander::default();
+    expander.text(src, None);
+    expander.finish()
+}
+
+/// Expand a source file, following `#include "relative/path"` directives.
+pub fn expand_file(path: &str) -> Result<Expanded, WarlordError> {
+    let mut expander = Expander::default();
+    expander.file(Path::new(path), false);
+    expander.finish()
+}
+
+pub fn compile_filter(src: &str, dest: &str) -> Result<CompileReport, WarlordError> {
+    let expanded = expand_file(src)?;
+    encoding::write_preserving(dest, &expanded.text)?;
+    Ok(CompileReport { dest: dest.to_string(), variables: expanded.variables, expansions: expanded.expansions })
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildReport {
+    pub dest: String,
+    /// Source files that went into the build, in include order
+    pub files: Vec<String>,
+    pub blocks: usize,
+    /// Lint findings on the combined filter; the output is written either way
+    pub problems: Vec<String>,
+}
+
+/// Compile a modular filter into one file and lint the result.
+pub fn build_filter(src: &str, dest: &str) -> Result<BuildReport, WarlordError> {
+    let expanded = expand_file(src)?;
+    let doc = FilterDocument::parse(&expanded.text);
+    let problems = pipelines::lint_document(&doc);
+    encoding::write_preserving(dest, &expanded.text)?;
+    Ok(BuildReport { dest: dest.to_string(), files: expanded.files, blocks: doc.blocks.len(), problems })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn expands_variables_outside_comments() {
+        let src = "$Red = 255 0 0 255\r\n$Border = $Red\r\nShow # $tier->t1\r\n    SetTextColor $Red\r\n    SetBorderColor $Border\r\n";
+        let expanded = expand(src).unwrap();
+        let (text, vars, count) = (expanded.text, expanded.variables, expanded.expansions);
+        assert_eq!(text, "Show # $tier->t1\r\n    SetTextColor 255 0 0 255\r\n    SetBorderColor 255 0 0 255\r\n");
+        assert_eq!((vars, count), (2, 2));
+        assert!(expand("Show\n    SetFontSize $Big\n").err().unwrap().to_string().contains("$Big"));
+    }
+
+    #[test]
+    fn follows_includes() {
+        let dir = std::env::temp_dir().join(format!("wt_include_{}", std::process::id()));
+        fs::create_dir_all(dir.join("sections")).unwrap();
+        fs::write(dir.join("sections/colors.filtersrc"), "$Red = 255 0 0\n").unwrap();
+        fs::write(dir.join("sections/currency.filter"), "Show\n    SetTextColor $Red\n").unwrap();
+        fs::write(dir.join("main.filtersrc"), "#include \"sections/colors.filtersrc\"\n#include \"sections/currency.filter\"\n#include \"main.filtersrc\"\n").unwrap();
+
+        let err = expand_file(&dir.join("main.filtersrc").display().to_string()).err().unwrap();
+        assert!(err.to_string().contains("循环引用"));
+        fs::write(dir.join("main.filtersrc"), "#include \"sections/colors.filtersrc\"\n#include \"sections/currency.filter\"\n").unwrap();
+        let expanded = expand_file(&dir.join("main.filtersrc").display().to_string()).unwrap();
+        assert_eq!(expanded.text, "Show\n    SetTextColor 255 0 0\n");
+        assert_eq!(expanded.files.len(), 3);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
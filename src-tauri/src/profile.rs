@@ -0,0 +1,177 @@
+//! Portable profile: the app's settings in one small JSON file, to set up another PC without
+//! copying the library and its sounds (that is `library_export`). It holds the settings
+//! files listed in `PROFILE_FILES` and the library folder as a pointer. Paths that only make
+//! sense on this PC (window placement, the library folder, game folders, read-only roots)
+//! are left out on export and kept on import; secrets such as account tokens and the OBS,
+//! WebDAV or webhook addresses are never in a profile.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+use crate::{app_paths, library, sandbox};
+
+const FORMAT: u32 = 1;
+
+/// Settings and user-made presets; run state and caches stay behind. Tags, notes, source
+/// subscriptions and hotkeys are kept by the frontend in the same folder.
+const PROFILE_FILES: &[&str] = &[
+    "Settings.json",
+    "tags.json",
+    "notes.json",
+    "subscriptions.json",
+    "hotkeys.json",
+    "snippets.json",
+    "sound_profiles.json",
+    "colorblind.json",
+    "quiet_hours.json",
+    "power.json",
+    "backups.json",
+    "versions.json",
+    "discord_rpc.json",
+    "library_git.json",
+    "game_sync.json",
+    "spectator.json",
+];
+
+/// Top-level keys of a profile file describing this PC rather than the user.
+const LOCAL_KEYS: &[(&str, &[&str])] = &[
+    ("Settings.json", &["width", "height", "x", "y", "maximized", "filterStoragePath", "lastSelectedFilter", "backgroundPath"]),
+    ("spectator.json", &["readOnlyRoots"]),
+];
+
+fn local_keys(name: &str) -> &'static [&'static str] {
+    LOCAL_KEYS.iter().find(|(file, _)| *file == name).map_or(&[], |(_, keys)| keys)
+}
+
+/// `value` of the config file `name` without what describes this PC.
+fn strip_local(name: &str, mut value: serde_json::Value) -> serde_json::Value {
+    if let Some(object) = value.as_object_mut() {
+        for key in local_keys(name) {
+            object.remove(*key);
+        }
+    }
+    if name == "game_sync.json" {
+        // An empty game folder is the game's documents folder on whatever PC imports it
+        for profile in value.get_mut("profiles").and_then(|p| p.as_array_mut()).into_iter().flatten() {
+            if let Some(dir) = profile.get_mut("gameDir") {
+                *dir = serde_json::Value::String(String::new());
+            }
+        }
+    }
+    value
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Profile {
+    pub format: u32,
+    pub app_version: String,
+    /// Seconds since the unix epoch
+    pub created_at: u64,
+    /// Library folder on the PC it was exported on
+    pub library_root: Option<String>,
+    /// Contents by config file name
+    pub files: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileImport {
+    pub applied: Vec<String>,
+    /// Not a profile file (from a newer version, or edited by hand)
+    pub skipped: Vec<String>,
+    /// The exported library folder, for the frontend to offer when this PC has none yet
+    pub library_root: Option<String>,
+}
+
+pub fn export_profile(dest: &Path) -> Result<Profile, WarlordError> {
+    let mut files = BTreeMap::new();
+    for name in PROFILE_FILES {
+        let path = app_paths::config_file(name);
+        let Ok(text) = fs::read_to_string(long_path(&path)) else { continue };
+        let value = serde_json::from_str(&text).map_err(|e| WarlordError::invalid(format!("{} 无法读取: {}", name, e)))?;
+        files.insert(name.to_string(), strip_local(name, value));
+    }
+    let profile = Profile {
+        format: FORMAT,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: app_paths::now_secs(),
+        library_root: library::library_root().map(path_utils::short_path),
+        files,
+    };
+    let json = serde_json::to_vec_pretty(&profile).map_err(|e| WarlordError::from(e.to_string()))?;
+    sandbox::write_as(dest, &json, "export")?;
+    eprintln!("[WarlordTools] Exported a profile with {} settings files to {}", profile.files.len(), dest.display());
+    Ok(profile)
+}
+
+/// `incoming` for the config file `name` with what describes this PC taken from `local`: the
+/// `LOCAL_KEYS`, and the game folder of sync profiles this PC has under the same name.
+fn keep_local(name: &str, mut incoming: serde_json::Value, local: &serde_json::Value) -> serde_json::Value {
+    let Some(object) = incoming.as_object_mut() else { return incoming };
+    for key in local_keys(name) {
+        match local.get(key) {
+            Some(value) => object.insert(key.to_string(), value.clone()),
+            None => object.remove(*key),
+        };
+    }
+    if name == "game_sync.json" {
+        let local_profiles = local.get("profiles").and_then(|p| p.as_array()).map_or(&[][..], |p| p.as_slice());
+        for profile in object.get_mut("profiles").and_then(|p| p.as_array_mut()).into_iter().flatten() {
+            let here = local_profiles.iter().find(|l| l.get("name").is_some_and(|n| Some(n) == profile.get("name")));
+            if let (Some(dir), Some(local_dir)) = (profile.get_mut("gameDir"), here.and_then(|l| l.get("gameDir"))) {
+                *dir = local_dir.clone();
+            }
+        }
+    }
+    incoming
+}
+
+pub fn import_profile(path: &Path) -> Result<ProfileImport, WarlordError> {
+    let text = fs::read_to_string(long_path(path)).map_err(|e| WarlordError::io(e, path))?;
+    let profile: Profile = serde_json::from_str(&text).map_err(|e| WarlordError::invalid(format!("{} 不是 WarlordTools 配置文件: {}", path_utils::short_path(path), e)))?;
+    if profile.format == 0 || profile.format > FORMAT {
+        return Err(WarlordError::invalid(format!("配置文件格式 {} 需要更新版本的 WarlordTools", profile.format)));
+    }
+    let (mut applied, mut skipped) = (Vec::new(), Vec::new());
+    for (name, value) in profile.files {
+        if !PROFILE_FILES.contains(&name.as_str()) {
+            skipped.push(name);
+            continue;
+        }
+        let value = keep_local(&name, value, &app_paths::load_json(&name));
+        let json = serde_json::to_vec_pretty(&value).map_err(|e| WarlordError::from(e.to_string()))?;
+        sandbox::write_as(app_paths::config_file(&name), &json, "import")?;
+        applied.push(name);
+    }
+    eprintln!("[WarlordTools] Imported a profile from {}: {} settings files", path.display(), applied.len());
+    Ok(ProfileImport { applied, skipped, library_root: profile.library_root })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_settings_stay() {
+        let incoming = serde_json::json!({ "width": 800, "filterStoragePath": "D:/Filters", "navOrder": ["market"], "x": 5 });
+        let local = serde_json::json!({ "width": 1920, "filterStoragePath": "E:/PoE/Filters", "navOrder": ["filter"] });
+        let merged = keep_local("Settings.json", incoming, &local);
+        assert_eq!(merged, serde_json::json!({ "width": 1920, "filterStoragePath": "E:/PoE/Filters", "navOrder": ["market"] }));
+        assert!(!PROFILE_FILES.iter().any(|f| ["accounts.json", "local_api.json", "webdav.json", "obs.json", "webhooks.json"].contains(f)));
+    }
+
+    #[test]
+    fn local_paths_are_not_exported() {
+        let spectator = serde_json::json!({ "readOnlyRoots": ["D:/Shared"], "networkReadOnly": true });
+        assert_eq!(strip_local("spectator.json", spectator), serde_json::json!({ "networkReadOnly": true }));
+
+        let exported = strip_local("game_sync.json", serde_json::json!({ "profiles": [{ "name": "main", "gameDir": "D:/PoE", "filters": ["a.filter"] }] }));
+        assert_eq!(exported["profiles"][0]["gameDir"], "");
+        let local = serde_json::json!({ "profiles": [{ "name": "main", "gameDir": "E:/Games/PoE", "filters": [] }] });
+        assert_eq!(keep_local("game_sync.json", exported, &local)["profiles"][0]["gameDir"], "E:/Games/PoE");
+    }
+}
@@ -0,0 +1,73 @@
+use crate::app_paths;
+use crate::filter_parser::Block;
+
+const MARKER: &str = "# [WarlordTools:generated]";
+
+/// Where a machine-generated block came from.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    /// Tool/input that produced the block, e.g. "merge:addon.filter"
+    pub source: String,
+    pub tool_version: String,
+    pub timestamp: u64,
+}
+
+impl Provenance {
+    pub fn new(source: &str) -> Provenance {
+        Provenance {
+            source: source.to_string(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            timestamp: app_paths::now_secs(),
+        }
+    }
+
+    /// `source` goes last since it may contain spaces.
+    pub fn to_comment(&self) -> String {
+        format!("{} version={} at={} source={}", MARKER, self.tool_version, self.timestamp, self.source)
+    }
+
+    pub fn from_comment(line: &str) -> Option<Provenance> {
+        let rest = line.trim().strip_prefix(MARKER)?.trim_start();
+        let (version, rest) = rest.strip_prefix("version=")?.split_once(' ')?;
+        let (at, rest) = rest.strip_prefix("at=")?.split_once(' ')?;
+        let source = rest.strip_prefix("source=")?;
+        Some(Provenance {
+            source: source.to_string(),
+            tool_version: version.to_string(),
+            timestamp: at.parse().ok()?,
+        })
+    }
+}
+
+/// Provenance comment of a block, None for hand-written blocks.
+pub fn read(block: &Block) -> Option<Provenance> {
+    block.leading.iter().rev().find_map(|l| Provenance::from_comment(l))
+}
+
+/// Mark `block` as generated by `source`, replacing an older stamp.
+/// The stamp goes above the comment group touching the header, because the editor
+/// reads the last comment line before `Show`/`Hide` as the rule's name.
+pub fn stamp(block: &mut Block, source: &str) {
+    block.leading.retain(|l| Provenance::from_comment(l).is_none());
+    let pos = block.leading.iter().rposition(|l| l.trim().is_empty()).map(|i| i + 1).unwrap_or(0);
+    block.leading.insert(pos, Provenance::new(source).to_comment());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamp_round_trips() {
+        let mut block = Block::new("Show");
+        block.leading.push("# 催化剂".to_string());
+        stamp(&mut block, "merge:my addon.filter");
+        stamp(&mut block, "merge:my addon.filter");
+        assert_eq!(block.leading.len(), 2);
+        assert_eq!(block.leading[1], "# 催化剂");
+        let p = read(&block).unwrap();
+        assert_eq!(p.source, "merge:my addon.filter");
+        assert_eq!(p.tool_version, env!("CARGO_PKG_VERSION"));
+    }
+}
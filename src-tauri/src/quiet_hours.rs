@@ -0,0 +1,130 @@
+//! Quiet hours: a nightly window in which notifications are held back and sound previews are
+//! turned down. A reduced-volume copy of a filter can be compiled for the same window.
+
+use std::path::Path;
+
+use chrono::Timelike;
+
+use crate::error::WarlordError;
+use crate::filter_transforms::{self, FileChange};
+use crate::{app_paths, filter_parser};
+
+const CONFIG_FILE: &str = "quiet_hours.json";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct QuietHours {
+    pub enabled: bool,
+    /// Local time, "HH:MM"; the window may wrap past midnight
+    pub start: String,
+    pub end: String,
+    /// Preview and quiet-variant volume, percent of normal
+    pub volume_percent: u32,
+    pub mute_notifications: bool,
+    pub mute_webhooks: bool,
+}
+
+impl Default for QuietHours {
+    fn default() -> Self {
+        QuietHours {
+            enabled: false,
+            start: "23:00".to_string(),
+            end: "07:00".to_string(),
+            volume_percent: 30,
+            mute_notifications: true,
+            mute_webhooks: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuietStatus {
+    pub quiet: bool,
+    /// Factor to apply to preview playback volume
+    pub volume_scale: f32,
+}
+
+fn parse_time(text: &str) -> Result<u32, WarlordError> {
+    let invalid = || WarlordError::invalid(format!("无效的时间: {} (应为 HH:MM)", text));
+    let (h, m) = text.trim().split_once(':').ok_or_else(invalid)?;
+    let (h, m): (u32, u32) = (h.parse().map_err(|_| invalid())?, m.parse().map_err(|_| invalid())?);
+    if h > 23 || m > 59 {
+        return Err(invalid());
+    }
+    Ok(h * 60 + m)
+}
+
+impl QuietHours {
+    /// Whether `minute` (minutes since local midnight) falls in the window.
+    pub fn contains(&self, minute: u32) -> bool {
+        let (Ok(start), Ok(end)) = (parse_time(&self.start), parse_time(&self.end)) else { return false };
+        if start <= end {
+            (start..end).contains(&minute)
+        } else {
+            minute >= start || minute < end
+        }
+    }
+}
+
+pub fn get_quiet_hours() -> QuietHours {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+pub fn set_quiet_hours(config: &QuietHours) -> Result<(), WarlordError> {
+    parse_time(&config.start)?;
+    parse_time(&config.end)?;
+    app_paths::save_json(CONFIG_FILE, config)
+}
+
+fn active() -> Option<QuietHours> {
+    let config = get_quiet_hours();
+    let now = chrono::Local::now();
+    (config.enabled && config.contains(now.hour() * 60 + now.minute())).then_some(config)
+}
+
+pub fn status() -> QuietStatus {
+    match active() {
+        Some(config) => QuietStatus { quiet: true, volume_scale: config.volume_percent as f32 / 100.0 },
+        None => QuietStatus { quiet: false, volume_scale: 1.0 },
+    }
+}
+
+/// In-app notifications (pipeline messages etc.) should be held back right now.
+pub fn mutes_notifications() -> bool {
+    active().is_some_and(|c| c.mute_notifications)
+}
+
+pub fn mutes_webhooks() -> bool {
+    active().is_some_and(|c| c.mute_webhooks)
+}
+
+/// Write `<stem>.quiet.filter` next to `path` with every alert volume scaled down.
+pub fn compile_quiet_variant(path: &str) -> Result<FileChange, WarlordError> {
+    let config = get_quiet_hours();
+    let mut doc = filter_parser::parse_file(path)?;
+    let lines = filter_transforms::scale_volumes_in(&mut doc, config.volume_percent);
+    let source = Path::new(path);
+    let stem = source.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let dest = source.with_file_name(format!("{}.quiet.filter", stem)).display().to_string();
+    filter_parser::write_file(&dest, &doc)?;
+    Ok(FileChange { path: dest, replacements: lines.len(), lines })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_wraps_past_midnight() {
+        let config = QuietHours { start: "23:00".to_string(), end: "07:30".to_string(), ..Default::default() };
+        assert!(config.contains(23 * 60));
+        assert!(config.contains(3 * 60));
+        assert!(!config.contains(7 * 60 + 30));
+        assert!(!config.contains(12 * 60));
+
+        let mut doc = filter_parser::FilterDocument::parse("Show\n    PlayAlertSound 6 200\n    CustomAlertSound \"a.mp3\"\n");
+        assert_eq!(filter_transforms::scale_volumes_in(&mut doc, 30), vec![1, 2]);
+        assert_eq!(doc.to_text(), "Show\n    PlayAlertSound 6 60\n    CustomAlertSound \"a.mp3\" 30\n");
+    }
+}
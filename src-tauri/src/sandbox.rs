@@ -0,0 +1,466 @@
+//! Write guard for every filesystem change the backend makes on behalf of the user.
+//! Workspaces opened from a network share (or listed as spectator roots) are read-only:
+//! guild members can browse the leader's filters without being able to change them.
+//! Changes are also limited to an allowlist of roots (the filter library, the game's
+//! documents folders, the app's own data and folders the user added), so a buggy or
+//! compromised frontend cannot delete arbitrary folders. Reads are not restricted.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::WarlordError;
+use crate::path_utils::long_path;
+use crate::{app_paths, app_trash, backups, filter_link, journal, library, library_git, path_utils, scan, versions};
+
+const CONFIG_FILE: &str = "spectator.json";
+const ROOTS_FILE: &str = "sandbox.json";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SpectatorConfig {
+    /// Folders that are always opened read-only
+    pub read_only_roots: Vec<String>,
+    /// Treat UNC paths and mapped network drives as read-only
+    pub network_read_only: bool,
+}
+
+impl Default for SpectatorConfig {
+    fn default() -> Self {
+        SpectatorConfig { read_only_roots: Vec::new(), network_read_only: true }
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SandboxConfig {
+    /// Folders added by the user besides the built-in roots
+    pub extra_roots: Vec<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SandboxRoot {
+    pub path: String,
+    /// "library" / "game" / "appData" / "custom"
+    pub kind: String,
+}
+
+/// What the UI may offer for a path.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    pub read_only: bool,
+    pub reason: Option<String>,
+    pub can_edit: bool,
+    pub can_rename: bool,
+    pub can_delete: bool,
+}
+
+pub fn get_config() -> SpectatorConfig {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+pub fn set_config(config: &SpectatorConfig) -> Result<(), WarlordError> {
+    app_paths::save_json(CONFIG_FILE, config)
+}
+
+pub fn get_roots_config() -> SandboxConfig {
+    app_paths::load_json(ROOTS_FILE)
+}
+
+/// The user's Documents folder as Windows knows it, which OneDrive backup and group policy
+/// often move off `%USERPROFILE%\Documents`.
+#[cfg(windows)]
+fn documents_dir() -> Option<PathBuf> {
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStringExt;
+    #[repr(C)]
+    struct Guid {
+        data1: u32,
+        data2: u16,
+        data3: u16,
+        data4: [u8; 8],
+    }
+    const FOLDERID_DOCUMENTS: Guid = Guid { data1: 0xFDD39AD0, data2: 0x238F, data3: 0x46AF, data4: [0xAD, 0xB4, 0x6C, 0x85, 0x48, 0x03, 0x69, 0xC7] };
+    #[link(name = "shell32")]
+    extern "system" {
+        fn SHGetKnownFolderPath(id: *const Guid, flags: u32, token: *mut c_void, path: *mut *mut u16) -> i32;
+    }
+    #[link(name = "ole32")]
+    extern "system" {
+        fn CoTaskMemFree(memory: *mut c_void);
+    }
+    let mut raw: *mut u16 = std::ptr::null_mut();
+    let result = unsafe { SHGetKnownFolderPath(&FOLDERID_DOCUMENTS, 0, std::ptr::null_mut(), &mut raw) };
+    let path = (result == 0 && !raw.is_null()).then(|| unsafe {
+        let len = (0..).take_while(|&i| *raw.add(i) != 0).count();
+        PathBuf::from(std::ffi::OsString::from_wide(std::slice::from_raw_parts(raw, len)))
+    });
+    // Freed on failure too
+    unsafe { CoTaskMemFree(raw.cast()) };
+    path
+}
+
+#[cfg(not(windows))]
+fn documents_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Documents"))
+}
+
+/// `Documents/My Games/Path of Exile` (and PoE 2), where the game reads filters from.
+pub fn game_documents() -> Vec<PathBuf> {
+    let Some(documents) = documents_dir() else { return Vec::new() };
+    let games = documents.join("My Games");
+    vec![games.join("Path of Exile"), games.join("Path of Exile 2")]
+}
+
+/// Every folder changes are allowed under.
+pub fn roots() -> Vec<SandboxRoot> {
+    let root = |path: &Path, kind: &str| SandboxRoot { path: path.display().to_string(), kind: kind.to_string() };
+    let mut roots: Vec<SandboxRoot> = library::library_root().iter().map(|p| root(p, "library")).collect();
+    roots.extend(game_documents().iter().map(|p| root(p, "game")));
+    roots.push(root(&app_paths::config_dir(), "appData"));
+    // Tests work in the temp folder; not built into the app
+    #[cfg(test)]
+    roots.push(root(&std::env::temp_dir(), "custom"));
+    roots.extend(get_roots_config().extra_roots.iter().map(|p| root(Path::new(p), "custom")));
+    roots
+}
+
+fn add_root_allowed(path: &Path) -> Result<(), WarlordError> {
+    if !path.is_dir() {
+        return Err(WarlordError::invalid(format!("{} 不是文件夹", path.display())));
+    }
+    // A drive or the home folder as a root would switch the sandbox off
+    let home = std::env::var_os("USERPROFILE").or_else(|| std::env::var_os("HOME"));
+    let absolute = std::path::absolute(path).map_err(|e| WarlordError::io(e, path))?;
+    if absolute.parent().is_none() || home.is_some_and(|h| path_utils::compare_paths(&absolute.to_string_lossy(), &h.to_string_lossy())) {
+        return Err(WarlordError::invalid(format!("不能把 {} 整个加入允许的文件夹", path.display())));
+    }
+    Ok(())
+}
+
+/// Add a folder to the allowlist. The frontend cannot call this with a path of its choice;
+/// `add_sandbox_root` asks the user with a native folder dialog first.
+pub fn add_root(path: &str) -> Result<(), WarlordError> {
+    add_root_allowed(Path::new(path))?;
+    let mut config = get_roots_config();
+    if !config.extra_roots.iter().any(|r| path_utils::compare_paths(r, path)) {
+        config.extra_roots.push(path.to_string());
+    }
+    app_paths::save_json(ROOTS_FILE, &config)
+}
+
+pub fn remove_root(path: &str) -> Result<(), WarlordError> {
+    let mut config = get_roots_config();
+    config.extra_roots.retain(|r| !path_utils::compare_paths(r, path));
+    app_paths::save_json(ROOTS_FILE, &config)
+}
+
+/// Whether `path` is `root` or below it, after resolving `.` and `..` (so `root/../x` is not).
+fn is_within(path: &Path, root: &Path) -> bool {
+    let absolute = |p: &Path| std::path::absolute(path_utils::short_path(p)).map(|a| a.to_string_lossy().to_string());
+    let (Ok(path), Ok(root)) = (absolute(path), absolute(root)) else { return false };
+    match path_utils::relative_path(&root, &path) {
+        Ok(relative) => relative != ".." && !relative.starts_with("../") && !relative.starts_with("..\\"),
+        Err(_) => false,
+    }
+}
+
+/// Fail with `OutsideSandbox` when `path` is outside every sandbox root.
+pub fn check_allowed(path: impl AsRef<Path>) -> Result<(), WarlordError> {
+    let path = path.as_ref();
+    // A resolved path is compared with the resolved roots too (a root reached through a link)
+    let resolved = |root: &str| fs::canonicalize(long_path(root)).ok().map(|r| PathBuf::from(path_utils::short_path(r)));
+    if roots().iter().any(|r| is_within(path, Path::new(&r.path)) || resolved(&r.path).is_some_and(|root| is_within(path, &root))) {
+        Ok(())
+    } else {
+        Err(WarlordError::OutsideSandbox { path: path_utils::short_path(path) })
+    }
+}
+
+#[cfg(windows)]
+fn is_mapped_network_drive(path: &Path) -> bool {
+    use std::os::windows::ffi::OsStrExt;
+    extern "system" {
+        fn GetDriveTypeW(root: *const u16) -> u32;
+    }
+    const DRIVE_REMOTE: u32 = 4;
+    let text = path.to_string_lossy();
+    let Some(drive) = text.get(..2).filter(|d| d.ends_with(':')) else { return false };
+    let root: Vec<u16> = std::ffi::OsStr::new(&format!("{}\\", drive)).encode_wide().chain(Some(0)).collect();
+    unsafe { GetDriveTypeW(root.as_ptr()) == DRIVE_REMOTE }
+}
+
+#[cfg(not(windows))]
+fn is_mapped_network_drive(_path: &Path) -> bool {
+    false
+}
+
+fn is_network_path(path: &Path) -> bool {
+    let text = path.to_string_lossy();
+    let unc = (text.starts_with("\\\\") || text.starts_with("//")) && !text.starts_with("\\\\?\\") && !text.starts_with("\\\\.\\");
+    unc || is_mapped_network_drive(path)
+}
+
+/// Why `path` is read-only, if it is.
+pub fn read_only_reason(path: &Path, config: &SpectatorConfig) -> Option<String> {
+    // `\\?\UNC\server\share` is a network path too
+    let path = &PathBuf::from(path_utils::short_path(path));
+    if let Some(root) = config.read_only_roots.iter().find(|r| is_within(path, Path::new(r))) {
+        return Some(format!("只读工作区: {}", root));
+    }
+    (config.network_read_only && is_network_path(path)).then(|| "网络共享位置 (只读)".to_string())
+}
+
+pub fn capabilities(path: &str) -> Capabilities {
+    let reason = match check_allowed(path) {
+        Ok(()) => read_only_reason(Path::new(path), &get_config()),
+        Err(outside) => Some(outside.to_string()),
+    };
+    let writable = reason.is_none();
+    Capabilities { read_only: !writable, reason, can_edit: writable, can_rename: writable, can_delete: writable }
+}
+
+/// Fail when `path` may not be created, changed or removed: outside the sandbox roots or
+/// read-only.
+pub fn check_write(path: impl AsRef<Path>) -> Result<(), WarlordError> {
+    let path = path.as_ref();
+    check_allowed(path)?;
+    match read_only_reason(path, &get_config()) {
+        Some(reason) => Err(WarlordError::ReadOnly { path: path_utils::short_path(path), reason }),
+        None => Ok(()),
+    }
+}
+
+/// Atomic write (`app_paths::write_atomic`) behind the write guard, backing up the previous
+/// content first and recording the change in the undo `journal`. Filters also get the new
+/// content recorded in their `versions` history.
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Result<(), WarlordError> {
+    write_as(path, contents, "save").map(|_| ())
+}
+
+/// Where a write to `path` lands: a hard link made by `filter_link` is written through its
+/// library file (replacing the link's own name would cut it off), and symbolic links and
+/// junctions, of the file or a folder above it, are resolved.
+fn write_target(path: &Path) -> PathBuf {
+    if let Some(source) = filter_link::hard_link_source(path) {
+        return source;
+    }
+    let resolved = match fs::canonicalize(long_path(path)) {
+        Ok(resolved) => Some(resolved),
+        // A new file: resolve its folder
+        Err(_) => path.parent().zip(path.file_name()).and_then(|(parent, name)| fs::canonicalize(long_path(parent)).ok().map(|p| p.join(name))),
+    };
+    resolved.map_or_else(|| path.to_path_buf(), |r| PathBuf::from(path_utils::short_path(r)))
+}
+
+/// `write` with the reason recorded in the backup name. Returns the backup, if one was taken
+/// (not for new files, unchanged content or with backups turned off). Both the given path
+/// and where it resolves to must be writable, so a link inside the library cannot lead a
+/// write out of the sandbox.
+pub fn write_as(path: impl AsRef<Path>, contents: impl AsRef<[u8]>, reason: &str) -> Result<Option<PathBuf>, WarlordError> {
+    let (path, contents) = (path.as_ref(), contents.as_ref());
+    check_write(path)?;
+    let target = write_target(path);
+    check_write(&target)?;
+    let path = target.as_path();
+    let previous = fs::read(long_path(path)).ok();
+    let backup = match &previous {
+        Some(previous) if previous != contents && backups::get_config().enabled => Some(backups::snapshot(path, reason)?),
+        _ => None,
+    };
+    app_paths::write_atomic(&long_path(path), contents).map_err(|e| WarlordError::write_failed(e, path))?;
+    if previous.as_deref() != Some(contents) {
+        journal::record_write(path, previous.as_deref(), reason);
+    }
+    if versions::is_versioned(path) {
+        if let Err(e) = versions::record(path, previous.as_deref(), contents, reason) {
+            eprintln!("[WarlordTools] Could not record a version of {}: {}", path.display(), e);
+        }
+    }
+    library_git::auto_commit(path, reason);
+    Ok(backup)
+}
+
+/// Copy a file with `write` semantics (guarded, atomic, previous target backed up and kept
+/// for undo). Returns the bytes copied.
+pub fn copy(src: impl AsRef<Path>, dest: impl AsRef<Path>) -> Result<u64, WarlordError> {
+    let src = src.as_ref();
+    let source = fs::File::open(long_path(src)).map_err(|e| WarlordError::io(e, src))?;
+    write_stream(dest, source, "copy")
+}
+
+/// `write_as` with the content read from `source`; returns the bytes written. Sounds and
+/// packs are streamed into the atomic temporary file, never held in memory; filters go
+/// through `write_as` for their version history, which needs the content.
+pub fn write_stream(dest: impl AsRef<Path>, mut source: impl io::Read, reason: &str) -> Result<u64, WarlordError> {
+    let dest = dest.as_ref();
+    if versions::is_versioned(dest) {
+        let mut contents = Vec::new();
+        source.read_to_end(&mut contents).map_err(|e| WarlordError::io(e, dest))?;
+        write_as(dest, &contents, reason)?;
+        return Ok(contents.len() as u64);
+    }
+    check_write(dest)?;
+    let target = write_target(dest);
+    check_write(&target)?;
+    let existed = long_path(&target).is_file();
+    if existed && backups::get_config().enabled {
+        backups::snapshot(&target, reason)?;
+    }
+    let previous = if existed { journal::stash_file(&target).map(Some) } else { Ok(None) };
+    let copied = app_paths::write_atomic_from(&long_path(&target), &mut source).map_err(|e| WarlordError::write_failed(e, &target))?;
+    match previous {
+        Ok(previous) => journal::record(reason, journal::Change::Write { path: path_utils::short_path(&target), previous }),
+        Err(e) => eprintln!("[WarlordTools] Could not keep the previous content of {} for undo: {}", target.display(), e),
+    }
+    library_git::auto_commit(&target, reason);
+    Ok(copied)
+}
+
+/// Delete a file or folder behind the write guard. With the `app_trash` setting (the
+/// default) it goes to the app's trash and can be restored from there, when it is on the
+/// same drive. Otherwise, with `recycle_bin`, it goes to the Recycle Bin (freedesktop trash
+/// on Linux); without either trash the files are backed up first (folders up to
+/// `backups::TREE_LIMIT`).
+pub fn delete(path: impl AsRef<Path>) -> Result<(), WarlordError> {
+    let path = path.as_ref();
+    check_write(path)?;
+    if !long_path(path).exists() {
+        return Err(WarlordError::not_found(path));
+    }
+    let trash_id = remove(path)?;
+    journal::record("delete", journal::Change::Delete { path: path_utils::short_path(path), trash_id });
+    library_git::auto_commit(path, "delete");
+    Ok(())
+}
+
+/// The removal of a checked `delete`; returns the app trash entry it went to.
+fn remove(path: &Path) -> Result<Option<String>, WarlordError> {
+    let long = long_path(path);
+    let config = backups::get_config();
+    if config.app_trash {
+        if let Some(entry) = app_trash::move_to_trash(path)? {
+            return Ok(Some(entry.id));
+        }
+    }
+    if config.enabled && !config.recycle_bin {
+        if long.is_dir() {
+            backups::snapshot_tree(path, "delete")?;
+        } else {
+            backups::snapshot(path, "delete")?;
+        }
+    }
+    if config.recycle_bin {
+        trash::delete(&long).map_err(|e| WarlordError::Io { path: Some(path_utils::short_path(path)), message: format!("无法移到回收站: {}", e) })?;
+    } else if long.is_dir() {
+        fs::remove_dir_all(&long).map_err(|e| WarlordError::write_failed(e, path))?;
+    } else {
+        fs::remove_file(&long).map_err(|e| WarlordError::write_failed(e, path))?;
+    }
+    Ok(None)
+}
+
+/// `write` only when the file still has SHA-256 `expected_hash` (as from `scan::hash_file`),
+/// so a file re-downloaded by FilterBlade since it was opened is not overwritten. Returns the
+/// hash of what was written; a file that changed or was deleted meanwhile is a `Conflict`
+/// and nothing is written.
+pub fn write_if_unchanged(path: impl AsRef<Path>, contents: impl AsRef<[u8]>, expected_hash: &str) -> Result<String, WarlordError> {
+    let path = path.as_ref();
+    let current_hash = match scan::hash_file(path) {
+        Ok(hash) => Some(hash),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(WarlordError::io(e, path)),
+    };
+    let message = match current_hash {
+        Some(hash) if hash.eq_ignore_ascii_case(expected_hash) => None,
+        Some(_) => Some("在打开后已被其他程序修改, 未保存"),
+        None => Some("在打开后已被删除, 未保存"),
+    };
+    if let Some(message) = message {
+        let path = path_utils::short_path(path);
+        return Err(WarlordError::Conflict { message: format!("{} {}", path, message), path });
+    }
+    write(path, &contents)?;
+    Ok(format!("{:x}", Sha256::digest(contents.as_ref())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_locations_are_read_only() {
+        let config = SpectatorConfig { read_only_roots: vec!["C:/Guild/Filters".to_string()], network_read_only: true };
+        assert!(read_only_reason(Path::new(r"\\nas\poe\leader.filter"), &config).is_some());
+        assert!(read_only_reason(Path::new("C:/Guild/Filters/a.filter"), &config).is_some());
+        assert!(read_only_reason(Path::new("C:/Guild/Other/a.filter"), &config).is_none());
+        assert!(read_only_reason(Path::new("C:/Guild/Other/../Filters/a.filter"), &config).is_some());
+        assert!(read_only_reason(Path::new("C:/Guild/Filters/../Other/a.filter"), &config).is_none());
+        assert!(read_only_reason(Path::new("C:/Guild/FiltersOld/a.filter"), &config).is_none());
+        let off = SpectatorConfig { network_read_only: false, ..config };
+        assert!(read_only_reason(Path::new("//nas/poe/leader.filter"), &off).is_none());
+    }
+
+    #[test]
+    fn sandbox_roots_contain_their_children_only() {
+        let root = Path::new("/lib/filters");
+        assert!(is_within(Path::new("/lib/filters/league/a.filter"), root));
+        assert!(is_within(root, root));
+        assert!(!is_within(Path::new("/lib/filters/../other"), root));
+        assert!(!is_within(Path::new("/lib/filters-old/a.filter"), root));
+        assert!(check_allowed(std::env::temp_dir().join("a.filter")).is_ok());
+        let refused = check_write("/definitely/not/allowed").unwrap_err();
+        assert_eq!(refused.code(), "outsideSandbox");
+    }
+
+    #[test]
+    fn streams_copies_into_place() {
+        let dir = std::env::temp_dir().join("wt-sandbox-copy-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let sound = vec![7u8; 300_000];
+        fs::write(dir.join("alert.ogg"), &sound).unwrap();
+        assert_eq!(copy(dir.join("alert.ogg"), dir.join("copy.ogg")).unwrap(), 300_000);
+        fs::write(dir.join("alert.ogg"), b"short").unwrap();
+        assert_eq!(copy(dir.join("alert.ogg"), dir.join("copy.ogg")).unwrap(), 5);
+        assert_eq!(fs::read(dir.join("copy.ogg")).unwrap(), b"short");
+        assert!(matches!(copy(dir.join("missing.ogg"), dir.join("copy.ogg")), Err(WarlordError::NotFound { .. })));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_overwrite_changed_files() {
+        let file = std::env::temp_dir().join("wt-sandbox-test.filter");
+        fs::write(&file, "Show\n").unwrap();
+        let loaded = scan::hash_file(&file).unwrap();
+        let hash = write_if_unchanged(&file, "Hide\n", &loaded).unwrap();
+        assert_eq!(hash, scan::hash_file(&file).unwrap());
+        assert!(matches!(write_if_unchanged(&file, "Show\n", &loaded), Err(WarlordError::Conflict { .. })));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "Hide\n");
+        fs::remove_file(&file).unwrap();
+        assert_eq!(write_if_unchanged(&file, "Show\n", &hash).unwrap_err().code(), "conflict");
+        assert!(!file.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_through_links_stay_in_the_sandbox() {
+        let dir = std::env::temp_dir().join("wt-sandbox-link-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("real.filter"), "Show\n").unwrap();
+        std::os::unix::fs::symlink(dir.join("real.filter"), dir.join("link.filter")).unwrap();
+        write(dir.join("link.filter"), "Hide\n").unwrap();
+        assert_eq!(fs::read_to_string(dir.join("real.filter")).unwrap(), "Hide\n");
+        assert!(fs::symlink_metadata(dir.join("link.filter")).unwrap().file_type().is_symlink());
+
+        std::os::unix::fs::symlink("/", dir.join("escape")).unwrap();
+        assert!(matches!(write(dir.join("escape/wt-escape-test.filter"), "Show\n"), Err(WarlordError::OutsideSandbox { .. })));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
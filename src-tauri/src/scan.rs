@@ -0,0 +1,510 @@
+//! Library scans with a persistent cache. The last result for each root is kept in the config
+//! folder per directory, so the list can be shown before anything is read from disk. A
+//! refresh only lists directories whose modification time changed (or that the watcher
+//! marked dirty). Content hashes are only computed when `ScanOptions::hash` asks for them,
+//! and then only for files whose size or modification time changed.
+//! Subdirectories and file hashes are processed in parallel, which is what makes large
+//! libraries on network drives usable: most of the time is spent waiting on the share.
+//! Besides filters, the same scan lists sounds or packs through `ScanOptions` patterns; each
+//! set of options is cached separately. Paths matched by the root's `.wtignore` and `.git`
+//! folders are skipped.
+//! Linked installs are marked with their target, and folder links pointing back into the
+//! scanned tree are not followed. Conflict copies made by sync tools are marked with the file
+//! they are a copy of.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::error::WarlordError;
+use crate::filter_link::{self, FilterLink, LinkKind};
+use crate::path_utils::{self, long_path};
+use crate::wtignore::{self, IgnoreRules};
+use crate::{app_paths, cloud_conflicts, glob};
+
+const CACHE_FILE: &str = "scan_cache.json";
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScannedFile {
+    pub path: String,
+    /// Relative to the scan root
+    #[serde(default)]
+    pub relative_path: String,
+    pub size: u64,
+    /// Milliseconds since the unix epoch
+    pub modified: u64,
+    /// SHA-256 of the content, hex; empty when the scan was not asked to hash
+    pub hash: String,
+    /// A symbolic link or junction (itself or a folder above it, below the root), or a hard
+    /// link made by `filter_link::link_filter`: a linked install, not a copy of its own
+    #[serde(default)]
+    pub is_link: bool,
+    /// The file the link resolves to
+    #[serde(default)]
+    pub link_target: Option<String>,
+    /// The original when this is a sync tool's conflict copy of it (`cloud_conflicts`)
+    #[serde(default)]
+    pub conflict_of: Option<String>,
+}
+
+/// What a scan lists.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScanOptions {
+    /// File name patterns (`*`, `?`), e.g. `*.filter`, `*.ruthless.filter`, `*.ogg`, `*.zip`
+    pub patterns: Vec<String>,
+    /// Directory levels below the root to descend into; None for no limit
+    pub max_depth: Option<usize>,
+    /// Hash file contents, for callers that compare them (duplicates, sync)
+    pub hash: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions { patterns: vec!["*.filter".to_string()], max_depth: None, hash: false }
+    }
+}
+
+impl ScanOptions {
+    fn accepts(&self, path: &Path) -> bool {
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        self.patterns.iter().any(|p| glob::matches(p, &name))
+    }
+
+    /// Cache entry for `root`; the default options use the bare root so older caches stay valid.
+    /// Hashing or not shares the entry: a file hashed once keeps its hash while unchanged.
+    fn cache_key(&self, root: &Path) -> String {
+        if (ScanOptions { hash: false, ..self.clone() }) == ScanOptions::default() {
+            return root.display().to_string();
+        }
+        let depth = self.max_depth.map(|d| d.to_string()).unwrap_or_default();
+        format!("{}|{}|{}", root.display(), self.patterns.join(";"), depth)
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct CachedDir {
+    /// Directory mtime in nanoseconds; entries are added/removed exactly when it changes
+    modified_ns: u64,
+    files: Vec<ScannedFile>,
+    dirs: Vec<String>,
+    /// Root entry only: the `.wtignore` text the listings were made with
+    #[serde(skip_serializing_if = "String::is_empty")]
+    ignore: String,
+}
+
+/// root (see `ScanOptions::cache_key`) -> directory -> listing
+type ScanCache = BTreeMap<String, BTreeMap<String, CachedDir>>;
+
+static CACHE: Mutex<Option<ScanCache>> = Mutex::new(None);
+
+fn with_cache<R>(f: impl FnOnce(&mut ScanCache) -> R) -> R {
+    let mut guard = CACHE.lock().unwrap();
+    let cache = guard.get_or_insert_with(|| app_paths::load_json(CACHE_FILE));
+    f(cache)
+}
+
+fn since_epoch(time: io::Result<SystemTime>) -> std::time::Duration {
+    time.ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).unwrap_or_default()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// What scans and `write_file_if_unchanged` use
+    #[default]
+    Sha256,
+    /// Several times faster on large files (sound packs, archives)
+    Blake3,
+}
+
+/// Streamed content hash of `path`, hex.
+pub fn hash_file_with(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut file = fs::File::open(long_path(path))?;
+    match algorithm {
+        HashAlgorithm::Sha256 => {
+            let mut hasher = Sha256::new();
+            io::copy(&mut file, &mut hasher)?;
+            Ok(format!("{:x}", hasher.finalize()))
+        }
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            io::copy(&mut file, &mut hasher)?;
+            Ok(hasher.finalize().to_hex().to_string())
+        }
+    }
+}
+
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    hash_file_with(path, HashAlgorithm::Sha256)
+}
+
+/// Force the next refresh to list `dir` again (for file system watchers).
+pub fn mark_dirty(dir: &Path) {
+    let key = dir.display().to_string();
+    with_cache(|cache| {
+        for listing in cache.values_mut().filter_map(|dirs| dirs.get_mut(&key)) {
+            listing.modified_ns = 0;
+        }
+    });
+}
+
+/// One refresh: the previous listing, cancellation and a callback
+/// that gets each directory's files as soon as they are known.
+struct Walk<'a> {
+    root: &'a Path,
+    options: &'a ScanOptions,
+    ignore: &'a IgnoreRules,
+    old: &'a BTreeMap<String, CachedDir>,
+    cancelled: &'a AtomicBool,
+    on_batch: &'a (dyn Fn(&[ScannedFile]) + Sync),
+    /// Hard links the OS cannot tell from copies
+    hard_links: &'a [FilterLink],
+}
+
+impl Walk<'_> {
+    fn new<'a>(root: &'a Path, options: &'a ScanOptions, old: &'a BTreeMap<String, CachedDir>) -> Walk<'a> {
+        static NOT_CANCELLED: AtomicBool = AtomicBool::new(false);
+        static NO_RULES: IgnoreRules = IgnoreRules::none();
+        Walk { root, options, ignore: &NO_RULES, old, cancelled: &NOT_CANCELLED, on_batch: &|_| {}, hard_links: &[] }
+    }
+}
+
+fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(long_path(path)).is_ok_and(|m| m.file_type().is_symlink())
+}
+
+/// A folder link whose target is inside the root (or holds it) would list the same files
+/// twice, or forever.
+fn links_back_into(root: &Path, dir: &Path) -> bool {
+    match (fs::canonicalize(long_path(root)), fs::canonicalize(long_path(dir))) {
+        (Ok(root), Ok(target)) => target.starts_with(&root) || root.starts_with(&target),
+        _ => false,
+    }
+}
+
+/// (is_link, link_target) of `file`, in a folder reached through a folder link when
+/// `in_linked_dir`.
+fn link_of(walk: &Walk, in_linked_dir: bool, file: &Path) -> (bool, Option<String>) {
+    let shown = file.display().to_string();
+    if let Some(link) = walk.hard_links.iter().find(|l| path_utils::compare_paths(&l.link, &shown)) {
+        return (true, Some(link.source.clone()));
+    }
+    if !in_linked_dir && !is_symlink(file) {
+        return (false, None);
+    }
+    (true, fs::canonicalize(long_path(file)).ok().map(path_utils::short_path))
+}
+
+/// Scan `dir` (`depth` levels below the root, reached through a folder link when `linked`)
+/// and everything below it; returns (directory, listing) pairs.
+fn visit(walk: &Walk, dir: &Path, depth: usize, linked: bool) -> io::Result<Vec<(String, CachedDir)>> {
+    if walk.cancelled.load(Ordering::Relaxed) {
+        return Err(io::ErrorKind::Interrupted.into());
+    }
+    let key = dir.display().to_string();
+    let modified_ns = since_epoch(fs::metadata(long_path(dir))?.modified()).as_nanos() as u64;
+    // `mark_dirty` zeroes modified_ns
+    let cached = walk.old.get(&key).filter(|c| c.modified_ns != 0 && c.modified_ns == modified_ns);
+
+    let (files, dirs): (Vec<PathBuf>, Vec<String>) = match cached {
+        Some(c) => (c.files.iter().map(|f| PathBuf::from(&f.path)).collect(), c.dirs.clone()),
+        None => {
+            let mut files = Vec::new();
+            let mut dirs = Vec::new();
+            for entry in fs::read_dir(long_path(dir))? {
+                let entry = entry?;
+                // Listed through the long form, reported without it
+                let (path, is_dir) = (dir.join(entry.file_name()), entry.path().is_dir());
+                // A library under git (`library_git`) holds its history there
+                if is_dir && entry.file_name() == ".git" {
+                    continue;
+                }
+                if walk.ignore.is_ignored(&path.strip_prefix(walk.root).unwrap_or(&path).to_string_lossy(), is_dir) {
+                    continue;
+                }
+                if is_dir && is_symlink(&path) && links_back_into(walk.root, &path) {
+                    eprintln!("[WarlordTools] Not following {}: links back into the scanned folder", path.display());
+                } else if is_dir {
+                    dirs.push(path.display().to_string());
+                } else if walk.options.accepts(&path) {
+                    files.push(path);
+                }
+            }
+            files.sort();
+            dirs.sort();
+            (files, dirs)
+        }
+    };
+
+    let previous: BTreeMap<&str, &ScannedFile> = walk.old.get(&key).map(|c| c.files.iter().map(|f| (f.path.as_str(), f)).collect()).unwrap_or_default();
+    let scanned: Vec<ScannedFile> = files
+        .par_iter()
+        .filter_map(|file| {
+            // Gone since the directory was listed
+            let meta = fs::metadata(long_path(file)).ok()?;
+            let path = file.display().to_string();
+            let relative_path = file.strip_prefix(walk.root).unwrap_or(file).display().to_string();
+            let size = meta.len();
+            let modified = since_epoch(meta.modified()).as_millis() as u64;
+            let hash = match previous.get(path.as_str()) {
+                Some(prev) if prev.size == size && prev.modified == modified && (!prev.hash.is_empty() || !walk.options.hash) => Ok(prev.hash.clone()),
+                _ if walk.options.hash => hash_file(file),
+                _ => Ok(String::new()),
+            };
+            let (is_link, link_target) = link_of(walk, linked, file);
+            let conflict_of = cloud_conflicts::original_of(file).map(|p| p.display().to_string());
+            Some(hash.map(|hash| ScannedFile { path, relative_path, size, modified, hash, is_link, link_target, conflict_of }))
+        })
+        .collect::<io::Result<_>>()?;
+    if !scanned.is_empty() {
+        (walk.on_batch)(&scanned);
+    }
+    // A subdirectory removed since the listing is skipped; the parent's mtime changed, so it
+    // is listed again next time
+    let descend = walk.options.max_depth.is_none_or(|max| depth < max);
+    let below: Vec<io::Result<Vec<(String, CachedDir)>>> = if descend {
+        dirs.par_iter().map(|sub| visit(walk, Path::new(sub), depth + 1, linked || is_symlink(Path::new(sub)))).collect()
+    } else {
+        Vec::new()
+    };
+    let mut out = vec![(key, CachedDir { modified_ns, files: scanned, dirs, ignore: String::new() })];
+    out.extend(below.into_iter().flatten().flatten());
+    Ok(out)
+}
+
+fn flatten(dirs: &BTreeMap<String, CachedDir>) -> Vec<ScannedFile> {
+    dirs.values().flat_map(|d| d.files.iter().cloned()).collect()
+}
+
+/// Result of the last scan of `root`, without touching the disk.
+pub fn cached(root: &Path, options: &ScanOptions) -> Vec<ScannedFile> {
+    with_cache(|cache| cache.get(&options.cache_key(root)).map(flatten).unwrap_or_default())
+}
+
+fn run(root: &Path, options: &ScanOptions, cancelled: &AtomicBool, on_batch: &(dyn Fn(&[ScannedFile]) + Sync)) -> Result<Vec<ScannedFile>, WarlordError> {
+    if options.patterns.is_empty() {
+        return Err(WarlordError::invalid("扫描模式不能为空"));
+    }
+    let key = options.cache_key(root);
+    let mut old = with_cache(|cache| cache.get(&key).cloned()).unwrap_or_default();
+    let root_key = root.display().to_string();
+    let ignore_text = fs::read_to_string(root.join(wtignore::FILE_NAME)).unwrap_or_default();
+    if old.get(&root_key).is_some_and(|r| r.ignore != ignore_text) {
+        // List everything again; unchanged files keep their hashes
+        for listing in old.values_mut() {
+            listing.modified_ns = 0;
+        }
+    }
+    let ignore = IgnoreRules::parse(&ignore_text);
+    let hard_links: Vec<FilterLink> = filter_link::links().into_iter().filter(|l| l.kind == LinkKind::Hardlink).collect();
+    let walk = Walk { root, options, ignore: &ignore, old: &old, cancelled, on_batch, hard_links: &hard_links };
+    let result = visit(&walk, root, 0, false);
+    if cancelled.load(Ordering::Relaxed) {
+        return Err(WarlordError::invalid("扫描已取消"));
+    }
+    let mut dirs: BTreeMap<String, CachedDir> = result.map_err(|e| WarlordError::io(e, root))?.into_iter().collect();
+    if let Some(listing) = dirs.get_mut(&root_key) {
+        listing.ignore = ignore_text;
+    }
+    let files = flatten(&dirs);
+    with_cache(|cache| {
+        cache.insert(key, dirs);
+        app_paths::save_json(CACHE_FILE, cache)
+    })?;
+    Ok(files)
+}
+
+/// Bring the cached scan of `root` up to date and return it.
+pub fn refresh(root: &Path, options: &ScanOptions) -> Result<Vec<ScannedFile>, WarlordError> {
+    run(root, options, &AtomicBool::new(false), &|_| {})
+}
+
+/// Files found so far by a background scan, or its end.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanBatch {
+    pub id: u64,
+    pub files: Vec<ScannedFile>,
+    pub done: bool,
+    /// Total files, on the final batch
+    pub total: Option<usize>,
+    pub error: Option<String>,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static RUNNING: Mutex<Option<HashMap<u64, Arc<AtomicBool>>>> = Mutex::new(None);
+
+fn with_running<R>(f: impl FnOnce(&mut HashMap<u64, Arc<AtomicBool>>) -> R) -> R {
+    let mut guard = RUNNING.lock().unwrap();
+    f(guard.get_or_insert_with(HashMap::new))
+}
+
+/// Refresh `root` on a background thread, handing each directory's files to `emit` as they
+/// are found. The last batch has `done` set. Returns the scan id for `cancel`.
+pub fn start(root: PathBuf, options: ScanOptions, emit: impl Fn(ScanBatch) + Send + Sync + 'static) -> u64 {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let cancelled = Arc::new(AtomicBool::new(false));
+    with_running(|running| running.insert(id, cancelled.clone()));
+    std::thread::spawn(move || {
+        let on_batch = |files: &[ScannedFile]| emit(ScanBatch { id, files: files.to_vec(), done: false, total: None, error: None });
+        let result = run(&root, &options, &cancelled, &on_batch);
+        let (total, error) = match result {
+            Ok(files) => (Some(files.len()), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        emit(ScanBatch { id, files: Vec::new(), done: true, total, error });
+        with_running(|running| running.remove(&id));
+    });
+    id
+}
+
+/// Stop a background scan; the cache keeps its previous state.
+pub fn cancel(id: u64) {
+    if let Some(cancelled) = with_running(|running| running.remove(&id)) {
+        cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `a.filter`, `sub/b.filter` and `notes.txt` in a fresh folder of the temp dir
+    fn tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a.filter"), "Show\n").unwrap();
+        fs::write(root.join("sub/b.filter"), "Hide\n").unwrap();
+        fs::write(root.join("notes.txt"), "").unwrap();
+        root
+    }
+
+    fn scan(walk: &Walk, root: &Path) -> BTreeMap<String, CachedDir> {
+        visit(walk, root, 0, false).unwrap().into_iter().collect()
+    }
+
+    #[test]
+    fn refresh_picks_up_changes() {
+        let root = tree("wt-scan-test");
+        let options = ScanOptions { hash: true, ..ScanOptions::default() };
+        let old = scan(&Walk::new(&root, &options, &BTreeMap::new()), &root);
+        assert_eq!(flatten(&old).len(), 2);
+
+        fs::write(root.join("a.filter"), "Show\n    SetFontSize 45\n").unwrap();
+        fs::write(root.join("sub/c.filter"), "").unwrap();
+        let mut marked = old.clone();
+        marked.get_mut(&root.join("sub").display().to_string()).unwrap().modified_ns = 0;
+        let files = flatten(&scan(&Walk::new(&root, &options, &marked), &root));
+        assert_eq!(files.len(), 3);
+        assert_ne!(files[0].hash, flatten(&old)[0].hash);
+        assert_eq!(files[1].hash, flatten(&old)[1].hash);
+        assert_eq!(Path::new(&files[2].relative_path), Path::new("sub").join("c.filter"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn hashes_only_when_asked() {
+        let root = tree("wt-scan-hash-test");
+        let hashed = scan(&Walk::new(&root, &ScanOptions { hash: true, ..ScanOptions::default() }, &BTreeMap::new()), &root);
+        let unhashed = scan(&Walk::new(&root, &ScanOptions::default(), &BTreeMap::new()), &root);
+        assert!(flatten(&unhashed).iter().all(|f| f.hash.is_empty()));
+        // A listing keeps the hashes an earlier scan computed
+        let kept = scan(&Walk::new(&root, &ScanOptions::default(), &hashed), &root);
+        assert_eq!(flatten(&kept)[0].hash, flatten(&hashed)[0].hash);
+        assert!(!flatten(&kept)[0].hash.is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn patterns_and_depth_limit() {
+        let root = tree("wt-scan-pattern-test");
+        let options = ScanOptions { patterns: vec!["*.txt".to_string(), "b.*".to_string()], max_depth: Some(0), hash: false };
+        let top = flatten(&scan(&Walk::new(&root, &options, &BTreeMap::new()), &root));
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].relative_path, "notes.txt");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn skips_ignored_folders() {
+        let root = tree("wt-scan-ignore-test");
+        let (options, ignore, empty) = (ScanOptions::default(), IgnoreRules::parse("sub/\n"), BTreeMap::new());
+        let walk = Walk { ignore: &ignore, ..Walk::new(&root, &options, &empty) };
+        let files = flatten(&scan(&walk, &root));
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].relative_path, "a.filter");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn marks_links_and_skips_loops() {
+        let root = std::env::temp_dir().join("wt-scan-link-test");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("library")).unwrap();
+        fs::write(root.join("library/a.filter"), "Show\n").unwrap();
+        std::os::unix::fs::symlink(root.join("library/a.filter"), root.join("linked.filter")).unwrap();
+        std::os::unix::fs::symlink(&root, root.join("library/loop")).unwrap();
+        let options = ScanOptions::default();
+
+        let files = flatten(&visit(&Walk::new(&root, &options, &BTreeMap::new()), &root, 0, false).unwrap().into_iter().collect());
+        assert_eq!(files.len(), 2);
+        let linked = files.iter().find(|f| f.relative_path == "linked.filter").unwrap();
+        assert!(linked.is_link);
+        assert_eq!(linked.link_target.as_deref().map(Path::new), Some(fs::canonicalize(root.join("library/a.filter")).unwrap().as_path()));
+        assert!(!files.iter().find(|f| f.relative_path != "linked.filter").unwrap().is_link);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// Parallel scan against a plain recursive walk (the old `scan_filter_files`) on a
+    /// generated tree: `cargo test --release bench_scan -- --ignored --nocapture`. Both
+    /// only list; the hashing scan and the cached rescan are timed after them.
+    /// Point WT_BENCH_ROOT at an existing library (e.g. on a network share) to measure that.
+    #[test]
+    #[ignore]
+    fn bench_scan() {
+        let (root, generated) = match std::env::var("WT_BENCH_ROOT") {
+            Ok(root) => (PathBuf::from(root), false),
+            Err(_) => {
+                let root = std::env::temp_dir().join("wt-scan-bench");
+                let _ = fs::remove_dir_all(&root);
+                for d in 0..200 {
+                    let dir = root.join(format!("league-{}", d / 20)).join(format!("set-{}", d));
+                    fs::create_dir_all(&dir).unwrap();
+                    for f in 0..50 {
+                        fs::write(dir.join(format!("{}.filter", f)), "Show\n    Class \"Currency\"\n".repeat(20)).unwrap();
+                    }
+                }
+                (root, true)
+            }
+        };
+        let timed = |options: &ScanOptions, cache: &BTreeMap<String, CachedDir>| {
+            let started = std::time::Instant::now();
+            let scanned = visit(&Walk::new(&root, options, cache), &root, 0, false).unwrap();
+            (scanned.into_iter().collect::<BTreeMap<_, _>>(), started.elapsed())
+        };
+        let started = std::time::Instant::now();
+        let sequential = crate::library::filter_files(&root).unwrap().len();
+        let walk = started.elapsed();
+        let (listed, parallel) = timed(&ScanOptions::default(), &BTreeMap::new());
+        let hashing = ScanOptions { hash: true, ..Default::default() };
+        let (hashed, with_hashes) = timed(&hashing, &BTreeMap::new());
+        let (_, warm) = timed(&hashing, &hashed);
+        assert_eq!(flatten(&listed).len(), sequential);
+        eprintln!("{} files: recursive walk {:?}, parallel scan {:?}, parallel scan with hashes {:?}, cached rescan with hashes {:?}", sequential, walk, parallel, with_hashes, warm);
+        if generated {
+            fs::remove_dir_all(&root).unwrap();
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Find and replace across every filter in a library folder. A call without `apply` only
+//! previews the matches; applying backs up each changed file first and writes it back in
+//! the encoding it was read in (GBK, UTF-16 ...). A file that cannot be read is listed as
+//! failed rather than left out. `search_library` is the read-only search, with files read
+//! in parallel.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+
+use crate::error::WarlordError;
+use crate::file_ops::FailedFile;
+use crate::{encoding, library, sandbox};
+
+/// Hits returned by `search_library` before it stops
+pub const MAX_HITS: usize = 10_000;
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchOptions {
+    /// Treat `pattern` as a regex (`$1` etc. work in the replacement); literal text otherwise
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Write the changes; without it the call is a preview
+    pub apply: bool,
+    /// Limit applying to these files (as returned by the preview)
+    pub files: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineMatch {
+    /// 1-based
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileMatches {
+    pub path: String,
+    pub matches: Vec<LineMatch>,
+    /// Backup taken before the file was rewritten
+    pub backup: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchReport {
+    pub files: Vec<FileMatches>,
+    pub total: usize,
+    pub applied: bool,
+    /// Filters that could not be read, so were not searched
+    pub failed: Vec<FailedFile>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub path: String,
+    /// 1-based
+    pub line: usize,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibrarySearch {
+    pub hits: Vec<SearchHit>,
+    /// Files read before the search stopped
+    pub files_searched: usize,
+    /// More than `MAX_HITS` matches; the search stopped there
+    pub truncated: bool,
+}
+
+fn build_regex(pattern: &str, options: &SearchOptions) -> Result<Regex, WarlordError> {
+    if pattern.is_empty() {
+        return Err(WarlordError::invalid("搜索内容不能为空"));
+    }
+    let mut source = if options.regex { pattern.to_string() } else { regex::escape(pattern) };
+    if options.whole_word {
+        source = format!(r"\b(?:{})\b", source);
+    }
+    RegexBuilder::new(&source).case_insensitive(!options.case_sensitive).build().map_err(|e| WarlordError::invalid(format!("无效的正则表达式: {}", e)))
+}
+
+/// Replace in `content` line by line. Returns the new text and the changed lines.
+pub fn replace_in(content: &str, re: &Regex, replacement: &str, literal: bool) -> (String, Vec<LineMatch>) {
+    let mut out = String::with_capacity(content.len());
+    let mut matches = Vec::new();
+    for (i, raw) in content.split_inclusive('\n').enumerate() {
+        let body = raw.trim_end_matches(['\r', '\n']);
+        let ending = &raw[body.len()..];
+        let after = if literal { re.replace_all(body, regex::NoExpand(replacement)) } else { re.replace_all(body, replacement) };
+        if after != body {
+            matches.push(LineMatch { line: i + 1, before: body.to_string(), after: after.to_string() });
+        }
+        out.push_str(&after);
+        out.push_str(ending);
+    }
+    (out, matches)
+}
+
+pub fn search_replace(root: &str, pattern: &str, replacement: &str, options: &SearchOptions) -> Result<SearchReport, WarlordError> {
+    let re = build_regex(pattern, options)?;
+    let mut report = SearchReport { files: Vec::new(), total: 0, applied: options.apply, failed: Vec::new() };
+    for file in library::filter_files(Path::new(root)).map_err(|e| WarlordError::io(e, Path::new(root)))? {
+        let path = file.display().to_string();
+        let decoded = match encoding::read_file(&file) {
+            Ok(decoded) => decoded,
+            Err(error) => {
+                report.failed.push(FailedFile { path, error });
+                continue;
+            }
+        };
+        let (updated, matches) = replace_in(&decoded.text, &re, replacement, !options.regex);
+        if matches.is_empty() {
+            continue;
+        }
+        let mut backup = None;
+        let selected = options.files.as_ref().is_none_or(|files| files.contains(&path));
+        if options.apply && selected {
+            let bytes = encoding::encode(&updated, &decoded.encoding, decoded.bom)?;
+            backup = sandbox::write_as(&file, bytes, "search-replace")?.map(|b| b.display().to_string());
+        }
+        report.total += matches.len();
+        report.files.push(FileMatches { path, matches, backup });
+    }
+    Ok(report)
+}
+
+/// Lines matching `query` in every filter below `root` (`apply` and `files` are ignored).
+/// Stops once one match more than `MAX_HITS` is found.
+pub fn search_library(root: &str, query: &str, options: &SearchOptions) -> Result<LibrarySearch, WarlordError> {
+    let re = build_regex(query, options)?;
+    let files = library::filter_files(Path::new(root)).map_err(|e| WarlordError::io(e, Path::new(root)))?;
+    let (found, searched) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    let per_file: Vec<Vec<SearchHit>> = files
+        .par_iter()
+        .map(|file| {
+            let mut hits = Vec::new();
+            if found.load(Ordering::Relaxed) > MAX_HITS {
+                return hits;
+            }
+            let Ok(content) = encoding::read_to_string(file) else { return hits };
+            searched.fetch_add(1, Ordering::Relaxed);
+            let path = file.display().to_string();
+            for (i, line) in content.lines().enumerate().filter(|(_, line)| re.is_match(line)) {
+                // The match past the limit only tells that there are more
+                if found.fetch_add(1, Ordering::Relaxed) >= MAX_HITS {
+                    break;
+                }
+                hits.push(SearchHit { path: path.clone(), line: i + 1, text: line.to_string() });
+            }
+            hits
+        })
+        .collect();
+    let hits = per_file.into_iter().flatten().collect();
+    Ok(LibrarySearch { hits, files_searched: searched.into_inner(), truncated: found.into_inner() > MAX_HITS })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_per_line_keeping_endings() {
+        let options = SearchOptions { regex: true, ..Default::default() };
+        let re = build_regex(r"SetFontSize (\d+)", &options).unwrap();
+        let (text, matches) = replace_in("Show\r\n    setfontsize 40\r\n    SetTextColor 1 2 3\r\n", &re, "SetFontSize 45 # was $1", false);
+        assert_eq!(text, "Show\r\n    SetFontSize 45 # was 40\r\n    SetTextColor 1 2 3\r\n");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line, 2);
+
+        let literal = build_regex("a.b", &SearchOptions { whole_word: true, ..Default::default() }).unwrap();
+        assert_eq!(replace_in("a.b axb", &literal, "$x", true).0, "$x axb");
+    }
+
+    #[test]
+    fn keeps_the_encoding_of_utf16_filters() {
+        let root = std::env::temp_dir().join("wt-search-replace-test");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let utf16 = |text: &str| -> Vec<u8> { [0xFF, 0xFE].into_iter().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect() };
+        std::fs::write(root.join("notepad.filter"), utf16("Show\r\n    SetFontSize 40\r\n")).unwrap();
+        let root_str = root.display().to_string();
+
+        let preview = search_replace(&root_str, "SetFontSize 40", "SetFontSize 45", &SearchOptions::default()).unwrap();
+        assert_eq!((preview.files.len(), preview.total), (1, 1));
+        search_replace(&root_str, "SetFontSize 40", "SetFontSize 45", &SearchOptions { apply: true, ..Default::default() }).unwrap();
+        assert_eq!(std::fs::read(root.join("notepad.filter")).unwrap(), utf16("Show\r\n    SetFontSize 45\r\n"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
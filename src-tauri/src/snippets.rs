@@ -0,0 +1,146 @@
+//! Reusable blocks ("highlight divines", "hide low-level flasks", ...) kept in the config
+//! folder and inserted into filters by position. Inserted blocks carry a `snippet:<name>`
+//! provenance stamp.
+
+use std::sync::Mutex;
+
+use crate::error::WarlordError;
+use crate::block_edit::{self, edit_file, Side};
+use crate::filter_parser::FilterDocument;
+use crate::{app_paths, provenance};
+
+const STATE_FILE: &str = "snippets.json";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snippet {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// One or more blocks in filter syntax
+    pub text: String,
+    pub created_at: u64,
+}
+
+/// Where `insert_snippet` puts the blocks.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(tag = "at", rename_all = "camelCase")]
+pub enum SnippetPosition {
+    Top,
+    End,
+    /// In front of block `block`
+    Before { block: usize },
+    After { block: usize },
+    /// After the last block of the first section whose marker contains `section`
+    Section { section: String },
+}
+
+static SNIPPETS: Mutex<Option<Vec<Snippet>>> = Mutex::new(None);
+
+fn with_snippets<R>(f: impl FnOnce(&mut Vec<Snippet>) -> R) -> R {
+    let mut guard = SNIPPETS.lock().unwrap();
+    let snippets = guard.get_or_insert_with(|| app_paths::load_json(STATE_FILE));
+    f(snippets)
+}
+
+fn check_text(text: &str) -> Result<(), WarlordError> {
+    if FilterDocument::parse(text).blocks.is_empty() {
+        return Err(WarlordError::invalid("片段中没有 Show/Hide 规则块"));
+    }
+    Ok(())
+}
+
+pub fn list_snippets() -> Vec<Snippet> {
+    with_snippets(|snippets| snippets.clone())
+}
+
+/// Create a snippet (`id` None) or replace an existing one.
+pub fn save_snippet(id: Option<&str>, name: &str, description: &str, text: &str) -> Result<Snippet, WarlordError> {
+    if name.trim().is_empty() {
+        return Err(WarlordError::invalid("片段名称不能为空"));
+    }
+    check_text(text)?;
+    with_snippets(|snippets| {
+        let snippet = match id.and_then(|id| snippets.iter_mut().find(|s| s.id == id)) {
+            Some(existing) => {
+                existing.name = name.to_string();
+                existing.description = description.to_string();
+                existing.text = text.to_string();
+                existing.clone()
+            }
+            None if id.is_some() => return Err(WarlordError::invalid("片段不存在")),
+            None => {
+                let snippet = Snippet {
+                    id: app_paths::new_id(),
+                    name: name.to_string(),
+                    description: description.to_string(),
+                    text: text.to_string(),
+                    created_at: app_paths::now_secs(),
+                };
+                snippets.push(snippet.clone());
+                snippet
+            }
+        };
+        app_paths::save_json(STATE_FILE, snippets)?;
+        Ok(snippet)
+    })
+}
+
+pub fn delete_snippet(id: &str) -> Result<(), WarlordError> {
+    with_snippets(|snippets| {
+        snippets.retain(|s| s.id != id);
+        app_paths::save_json(STATE_FILE, snippets)
+    })
+}
+
+/// Insert the blocks of `snippet` into `doc`. Returns the index of the first inserted block.
+pub fn insert_into(doc: &mut FilterDocument, snippet: &Snippet, position: &SnippetPosition) -> Result<usize, WarlordError> {
+    let count = doc.blocks.len();
+    let check = |block: usize| if block < count { Ok(block) } else { Err(WarlordError::invalid("Block index out of range")) };
+    let (index, side) = match position {
+        SnippetPosition::Top => (0, Side::BeforeNext),
+        SnippetPosition::End => (count, Side::AfterPrev),
+        SnippetPosition::Before { block } => (check(*block)?, Side::BeforeNext),
+        SnippetPosition::After { block } => (check(*block)? + 1, Side::AfterPrev),
+        SnippetPosition::Section { section } => {
+            let last = doc.blocks.iter().rposition(|b| b.section.as_deref().is_some_and(|s| s.contains(section.as_str())));
+            (last.ok_or_else(|| WarlordError::invalid(format!("Section not found: {}", section)))? + 1, Side::AfterPrev)
+        }
+    };
+    let source = format!("snippet:{}", snippet.name);
+    for (offset, mut block) in FilterDocument::parse(&snippet.text).blocks.into_iter().enumerate() {
+        provenance::stamp(&mut block, &source);
+        block_edit::attach(doc, index + offset, block, side);
+    }
+    doc.renumber();
+    Ok(index)
+}
+
+pub fn insert_snippet(filter_path: &str, snippet_id: &str, position: &SnippetPosition) -> Result<usize, WarlordError> {
+    let snippet = with_snippets(|snippets| snippets.iter().find(|s| s.id == snippet_id).cloned()).ok_or_else(|| WarlordError::invalid("片段不存在"))?;
+    edit_file(filter_path, |doc| insert_into(doc, &snippet, position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserts_after_section() {
+        let mut doc = FilterDocument::parse("# [[0100]] Currency\nShow\n    Class \"Currency\"\n\n# [[0200]] Maps\nShow\n    Class \"Waystones\"\n");
+        let snippet = Snippet {
+            id: "1".to_string(),
+            name: "divine".to_string(),
+            description: String::new(),
+            text: "Show\n    BaseType == \"Divine Orb\"\n".to_string(),
+            created_at: 0,
+        };
+        let position = SnippetPosition::Section { section: "0100".to_string() };
+        assert_eq!(insert_into(&mut doc, &snippet, &position).unwrap(), 1);
+        assert_eq!(doc.blocks.len(), 3);
+        assert_eq!(doc.blocks[1].rule("BaseType").unwrap().values, vec!["\"Divine Orb\""]);
+        assert_eq!(provenance::read(&doc.blocks[1]).unwrap().source, "snippet:divine");
+        assert!(doc.blocks[2].leading.iter().any(|l| l.contains("[[0200]]")));
+    }
+}
@@ -0,0 +1,144 @@
+//! Per-area-tier alert volumes. Each alert block gets AreaLevel-conditioned copies with a
+//! scaled volume placed in front of it; the original stays last as the fallback.
+
+use crate::error::WarlordError;
+use crate::app_paths;
+use crate::block_edit::{self, Side};
+use crate::filter_parser::{self, FilterDocument, Rule};
+use crate::filter_transforms::{self, VolumeAdjust};
+use crate::provenance;
+
+const CONFIG_FILE: &str = "sound_profiles.json";
+const SOURCE: &str = "sound-profile";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AreaTier {
+    pub name: String,
+    pub min_area_level: u32,
+    pub max_area_level: u32,
+    /// Percentage applied to each block's own volume
+    pub volume_percent: u32,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SoundProfiles {
+    pub tiers: Vec<AreaTier>,
+}
+
+impl Default for SoundProfiles {
+    fn default() -> Self {
+        let tier = |name: &str, min, max, volume_percent| AreaTier {
+            name: name.to_string(),
+            min_area_level: min,
+            max_area_level: max,
+            volume_percent,
+        };
+        SoundProfiles {
+            tiers: vec![
+                tier("leveling", 1, 67, 60),
+                tier("white", 68, 72, 80),
+                tier("yellow", 73, 77, 100),
+                tier("red", 78, 100, 150),
+            ],
+        }
+    }
+}
+
+pub fn get_sound_profiles() -> SoundProfiles {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+pub fn set_sound_profiles(profiles: &SoundProfiles) -> Result<(), WarlordError> {
+    if let Some(t) = profiles.tiers.iter().find(|t| t.min_area_level > t.max_area_level) {
+        return Err(WarlordError::invalid(format!("区域等级范围无效: {}", t.name)));
+    }
+    app_paths::save_json(CONFIG_FILE, profiles)
+}
+
+/// Drop variants from an earlier compile so compiling twice gives the same output.
+fn remove_variants(doc: &mut FilterDocument) {
+    let mut i = 0;
+    while i < doc.blocks.len() {
+        if provenance::read(&doc.blocks[i]).is_some_and(|p| p.source.starts_with(SOURCE)) {
+            block_edit::remove_attached(doc, i);
+        } else {
+            i += 1;
+        }
+    }
+    doc.renumber();
+}
+
+/// Add AreaLevel variants for every Show block with an alert sound. Blocks that already
+/// test AreaLevel are left alone. Returns how many variants were generated.
+pub fn compile_document(doc: &mut FilterDocument, profiles: &SoundProfiles) -> usize {
+    remove_variants(doc);
+    let mut generated = 0;
+    let mut i = 0;
+    while i < doc.blocks.len() {
+        let block = &doc.blocks[i];
+        let eligible = block.kind == "Show" && block.rule("AreaLevel").is_none() && block.rules().any(filter_transforms::is_alert_sound);
+        if !eligible {
+            i += 1;
+            continue;
+        }
+        for tier in &profiles.tiers {
+            let mut variant = doc.blocks[i].clone();
+            variant.leading.clear();
+            for line in &mut variant.lines {
+                if let Some(rule) = line.rule.as_ref().filter(|r| filter_transforms::is_alert_sound(r)) {
+                    let rule = filter_transforms::with_scaled_volume(rule, &VolumeAdjust { percent: tier.volume_percent, ..VolumeAdjust::default() });
+                    line.set_rule(rule);
+                }
+            }
+            // Conditions go first so the variant reads like a hand-written block
+            let mut min = Rule::new("AreaLevel", vec![tier.min_area_level.to_string()]);
+            min.operator = Some(">=".to_string());
+            let mut max = Rule::new("AreaLevel", vec![tier.max_area_level.to_string()]);
+            max.operator = Some("<=".to_string());
+            variant.lines.insert(0, filter_parser::BlockLine::from_rule(max));
+            variant.lines.insert(0, filter_parser::BlockLine::from_rule(min));
+            provenance::stamp(&mut variant, &format!("{}:{}", SOURCE, tier.name));
+            block_edit::attach(doc, i, variant, Side::BeforeNext);
+            i += 1;
+            generated += 1;
+        }
+        i += 1;
+    }
+    doc.renumber();
+    generated
+}
+
+pub fn compile_sound_profiles(path: &str) -> Result<usize, WarlordError> {
+    let mut doc = filter_parser::parse_file(path)?;
+    let generated = compile_document(&mut doc, &get_sound_profiles());
+    filter_parser::write_file(path, &doc)?;
+    Ok(generated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiles_variants_idempotently() {
+        let src = "# Divine\nShow\n    BaseType \"Divine Orb\"\n    PlayAlertSound 1 200\n";
+        let profiles = SoundProfiles {
+            tiers: vec![AreaTier { name: "red".to_string(), min_area_level: 78, max_area_level: 100, volume_percent: 200 }],
+        };
+        let mut doc = FilterDocument::parse(src);
+        assert_eq!(compile_document(&mut doc, &profiles), 1);
+        // Ignore the stamp lines, their timestamp may tick between the two compiles
+        let text = |doc: &FilterDocument| doc.lines().into_iter().filter(|l| !l.contains("WarlordTools:generated")).collect::<Vec<_>>().join("\n");
+        let once = text(&doc);
+        let variant = &doc.blocks[0];
+        assert_eq!(variant.rule("PlayAlertSound").unwrap().values, vec!["1", "300"]);
+        assert_eq!(variant.lines[0].raw, "    AreaLevel >= 78");
+
+        compile_document(&mut doc, &profiles);
+        assert_eq!(text(&doc), once);
+        remove_variants(&mut doc);
+        assert_eq!(doc.blocks.len(), 1);
+    }
+}
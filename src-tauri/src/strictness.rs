@@ -0,0 +1,109 @@
+//! Raising or lowering the strictness of an installed filter in place. NeverSink/FilterBlade
+//! tag blocks that drop out at stricter levels with `%D<n>` / `%H<n>` in the header comment;
+//! at strictness `n` and above those blocks are commented out, below it they are restored.
+
+
+use crate::error::WarlordError;
+use crate::filter_parser::{block_keyword, tokenize};
+use crate::encoding;
+
+/// Prefix for lines we commented out, so only our own edits are ever uncommented.
+const DISABLED_PREFIX: &str = "#~ ";
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StrictnessReport {
+    pub level: u32,
+    /// Header lines (0-based) of blocks that were commented out
+    pub disabled: Vec<usize>,
+    /// Header lines (0-based) of blocks that were restored
+    pub enabled: Vec<usize>,
+}
+
+/// Strictness tag of a header line, e.g. 5 for `Show # %D5 $type->...`.
+pub fn tag_level(header: &str) -> Option<u32> {
+    let comment = tokenize(header).1?;
+    comment.split_whitespace().find_map(|t| {
+        let digits = t.strip_prefix("%D").or_else(|| t.strip_prefix("%H"))?;
+        digits.parse().ok()
+    })
+}
+
+/// (line index, is currently disabled) for every block header.
+fn headers(lines: &[&str]) -> Vec<(usize, bool)> {
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, l)| match l.strip_prefix(DISABLED_PREFIX) {
+            Some(rest) => block_keyword(rest).map(|_| (i, true)),
+            None => block_keyword(l).map(|_| (i, false)),
+        })
+        .collect()
+}
+
+/// Lines after `start` that belong to its block. Bodies end at a blank line, an unindented
+/// comment or the next header.
+fn body_len(lines: &[&str], start: usize, disabled: bool) -> usize {
+    lines[start + 1..]
+        .iter()
+        .take_while(|l| {
+            let text = if disabled {
+                match l.strip_prefix(DISABLED_PREFIX) {
+                    Some(rest) => rest,
+                    None => return false,
+                }
+            } else {
+                l
+            };
+            !text.trim().is_empty() && block_keyword(text).is_none() && !text.starts_with('#')
+        })
+        .count()
+}
+
+pub fn apply_strictness(content: &str, level: u32) -> (String, StrictnessReport) {
+    let lines: Vec<&str> = content.split('\n').collect();
+    let mut out: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    let mut report = StrictnessReport { level, ..Default::default() };
+    for (start, disabled) in headers(&lines) {
+        let header = if disabled { &lines[start][DISABLED_PREFIX.len()..] } else { lines[start] };
+        let Some(tag) = tag_level(header) else { continue };
+        let want_disabled = level >= tag;
+        if want_disabled == disabled {
+            continue;
+        }
+        for line in &mut out[start..=start + body_len(&lines, start, disabled)] {
+            *line = if want_disabled {
+                format!("{}{}", DISABLED_PREFIX, line)
+            } else {
+                line[DISABLED_PREFIX.len()..].to_string()
+            };
+        }
+        if want_disabled { report.disabled.push(start) } else { report.enabled.push(start) }
+    }
+    (out.join("\n"), report)
+}
+
+pub fn set_strictness(path: &str, level: u32) -> Result<StrictnessReport, WarlordError> {
+    let content = encoding::read_to_string(path)?;
+    let (patched, report) = apply_strictness(&content, level);
+    if !report.disabled.is_empty() || !report.enabled.is_empty() {
+        encoding::write_preserving(path, &patched)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggles_tagged_blocks() {
+        let src = "Show # %D3 $tier->t2\r\n    BaseType \"Chaos Orb\"\r\n\r\nShow # $tier->t1\r\n    BaseType \"Divine Orb\"\r\n";
+        let (strict, report) = apply_strictness(src, 4);
+        assert_eq!(report.disabled, vec![0]);
+        assert!(strict.starts_with("#~ Show # %D3 $tier->t2\r\n#~     BaseType \"Chaos Orb\"\r\n\r\nShow"));
+        let (soft, report) = apply_strictness(&strict, 2);
+        assert_eq!(report.enabled, vec![0]);
+        assert_eq!(soft, src);
+    }
+}
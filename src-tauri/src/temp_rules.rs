@@ -0,0 +1,202 @@
+//! Temporary rules: a block injected at the top of an installed filter between marker
+//! comments, removed again once its time is up. The rules are tracked in temp_rules.json so
+//! a background thread can expire them, also those that ran out while the app was closed.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::error::WarlordError;
+use crate::app_paths;
+use crate::encoding;
+use crate::library;
+use crate::provenance::Provenance;
+
+const STATE_FILE: &str = "temp_rules.json";
+const MARKER_PREFIX: &str = "# [WarlordTools:temp ";
+
+/// A temporary block injected at the top of an installed filter.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct TempRule {
+    pub id: String,
+    /// Filter file the block was injected into
+    pub filter: String,
+    /// Block text as supplied by the caller
+    pub rule: String,
+    pub created_at: u64,
+    /// Unix seconds after which the block is removed automatically
+    pub expires_at: u64,
+}
+
+// In-memory copy of temp_rules.json, loaded lazily
+static TEMP_RULES: Mutex<Option<Vec<TempRule>>> = Mutex::new(None);
+
+fn with_rules<R>(f: impl FnOnce(&mut Vec<TempRule>) -> R) -> R {
+    let mut guard = TEMP_RULES.lock().unwrap();
+    let rules = guard.get_or_insert_with(|| app_paths::load_json(STATE_FILE));
+    f(rules)
+}
+
+fn begin_marker(id: &str, expires_at: u64) -> String {
+    format!("{}{} expires={}] BEGIN", MARKER_PREFIX, id, expires_at)
+}
+
+fn end_marker(id: &str) -> String {
+    format!("{}{}] END", MARKER_PREFIX, id)
+}
+
+/// Insert the marked block at the top of `content` so it wins over every other rule.
+fn inject(content: &str, id: &str, rule: &str, expires_at: u64) -> String {
+    let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut out = String::new();
+    out.push_str(&begin_marker(id, expires_at));
+    out.push_str(newline);
+    out.push_str(&Provenance::new(&format!("temp-rule:{}", id)).to_comment());
+    out.push_str(newline);
+    for line in rule.trim_end().lines() {
+        out.push_str(line.trim_end_matches('\r'));
+        out.push_str(newline);
+    }
+    out.push_str(&end_marker(id));
+    out.push_str(newline);
+    out.push_str(newline);
+    out.push_str(content);
+    out
+}
+
+/// Strip the marked region (and the blank separator line after it) for `id`.
+/// Returns None when the markers are not present.
+fn strip(content: &str, id: &str) -> Option<String> {
+    let begin = format!("{}{} ", MARKER_PREFIX, id);
+    let end = end_marker(id);
+    let start = content.find(&begin)?;
+    let end_pos = start + content[start..].find(&end)? + end.len();
+
+    let mut rest = &content[end_pos..];
+    // Eat the end marker's newline plus one blank separator line
+    for _ in 0..2 {
+        if let Some(r) = rest.strip_prefix("\r\n") {
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix('\n') {
+            rest = r;
+        }
+    }
+    Some(format!("{}{}", &content[..start], rest))
+}
+
+/// Inject `rule` into `filter` for `ttl` seconds and start tracking it.
+pub fn add_temp_rule(filter: &str, rule: &str, ttl: u64) -> Result<TempRule, WarlordError> {
+    if rule.trim().is_empty() {
+        return Err(WarlordError::invalid("规则内容不能为空"));
+    }
+    let content = encoding::read_to_string(filter)?;
+    let now = app_paths::now_secs();
+    let entry = TempRule {
+        id: app_paths::new_id(),
+        filter: filter.to_string(),
+        rule: rule.to_string(),
+        created_at: now,
+        expires_at: now.saturating_add(ttl),
+    };
+    encoding::write_preserving(filter, &inject(&content, &entry.id, rule, entry.expires_at))?;
+
+    with_rules(|rules| {
+        rules.push(entry.clone());
+        app_paths::save_json(STATE_FILE, rules)
+    })?;
+    eprintln!("[WarlordTools] temp rule {} added to {} (ttl {}s)", entry.id, filter, ttl);
+    Ok(entry)
+}
+
+/// Remove a temporary block from its filter and stop tracking it.
+/// A filter that no longer contains the markers (replaced or deleted) is left alone.
+pub fn remove_temp_rule(id: &str) -> Result<(), WarlordError> {
+    with_rules(|rules| {
+        let pos = rules.iter().position(|r| r.id == id).ok_or_else(|| WarlordError::invalid("临时规则不存在"))?;
+        let entry = rules[pos].clone();
+        if let Ok(content) = encoding::read_to_string(&entry.filter) {
+            if let Some(stripped) = strip(&content, &entry.id) {
+                encoding::write_preserving(&entry.filter, &stripped)?;
+            }
+        }
+        rules.remove(pos);
+        app_paths::save_json(STATE_FILE, rules)
+    })
+}
+
+/// Point rules at a renamed/moved filter. Returns the ids that were updated.
+pub fn remap_paths(old: &Path, new: &Path) -> Result<Vec<String>, WarlordError> {
+    with_rules(|rules| {
+        let mut changed = Vec::new();
+        for rule in rules.iter_mut() {
+            if let Some(p) = library::remap(Path::new(&rule.filter), old, new) {
+                rule.filter = p.display().to_string();
+                changed.push(rule.id.clone());
+            }
+        }
+        if !changed.is_empty() {
+            app_paths::save_json(STATE_FILE, rules)?;
+        }
+        Ok(changed)
+    })
+}
+
+pub fn list_temp_rules() -> Vec<TempRule> {
+    with_rules(|rules| rules.clone())
+}
+
+/// Remove every rule whose TTL has passed. Returns the ids that were removed.
+pub fn remove_expired() -> Vec<String> {
+    let now = app_paths::now_secs();
+    let expired: Vec<String> = with_rules(|rules| {
+        rules.iter().filter(|r| r.expires_at <= now).map(|r| r.id.clone()).collect()
+    });
+    let mut removed = Vec::new();
+    for id in expired {
+        match remove_temp_rule(&id) {
+            Ok(()) => removed.push(id),
+            Err(e) => eprintln!("[WarlordTools] failed to expire temp rule {}: {}", id, e),
+        }
+    }
+    removed
+}
+
+/// Background thread that expires temp rules every 30 seconds, less often on battery (also
+/// catches rules that expired while the app was closed).
+pub fn spawn_expiry_watcher() {
+    std::thread::spawn(|| loop {
+        let removed = remove_expired();
+        if !removed.is_empty() {
+            eprintln!("[WarlordTools] expired temp rules: {:?}", removed);
+        }
+        std::thread::sleep(crate::power::interval(std::time::Duration::from_secs(30)));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn inject_and_strip_round_trip() {
+        let original = "Show\n    BaseType \"Divine Orb\"\n";
+        let injected = inject(original, "abc", "Show\n    Class \"Flasks\"", 100);
+        assert!(injected.starts_with("# [WarlordTools:temp abc expires=100] BEGIN\n# [WarlordTools:generated]"));
+        assert_eq!(strip(&injected, "abc").unwrap(), original);
+        assert!(strip(original, "abc").is_none());
+    }
+
+    #[test]
+    fn huge_ttl_never_expires() {
+        let filter = std::env::temp_dir().join("wt-temp-rule-ttl-test.filter");
+        fs::write(&filter, "Show\n").unwrap();
+        let filter = filter.to_string_lossy().to_string();
+        let rule = add_temp_rule(&filter, "Hide\n    Class \"Flasks\"", u64::MAX).unwrap();
+        assert_eq!(rule.expires_at, u64::MAX);
+        assert!(fs::read_to_string(&filter).unwrap().contains(&format!("expires={}", u64::MAX)));
+        remove_temp_rule(&rule.id).unwrap();
+        assert_eq!(fs::read_to_string(&filter).unwrap(), "Show\n");
+        fs::remove_file(&filter).unwrap();
+    }
+}
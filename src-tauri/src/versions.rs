@@ -0,0 +1,437 @@
+//! Version history of filters: every save through `sandbox::write_as` keeps a copy of what
+//! was saved in `versions/<path hash>/<timestamp>-<reason>.ver`, with `path.txt` naming the
+//! filter. Unlike `backups` (the file as it was before a change, few per file), this is the
+//! file after each save and kept for long, so any earlier state can be looked at and
+//! restored. A save that changes nothing adds no version. The contents are stored once per
+//! SHA-256 in `versions/objects/`, and a version file only names its object, so saving a
+//! filter back to an earlier state costs no space. What is kept is set by the
+//! `RetentionPolicy` in `versions.json`, applied to a filter on each of its saves and to the
+//! whole history by `prune_snapshots`, and when a save brings the history over its size.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::WarlordError;
+use crate::path_utils::{self, long_path};
+use crate::{app_paths, encoding, sandbox};
+
+const TIMESTAMP: &str = "%Y%m%d-%H%M%S%3f";
+const CONFIG_FILE: &str = "versions.json";
+const OBJECTS_DIR: &str = "objects";
+/// A version file is this and the hex hash of its object.
+const OBJECT_REF: &str = "object ";
+
+/// Bytes in the history, counted by the first `enforce_size_cap` and kept up to date by
+/// `record`. Held while versions are written or pruned, so an object just stored is not
+/// taken for an unused one before its version file exists.
+static HISTORY: Mutex<Option<u64>> = Mutex::new(None);
+
+/// Longest `keep_daily_days` and largest `max_total_mb` accepted.
+const MAX_DAYS: u32 = 36500;
+const MAX_TOTAL_MB: u64 = 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetentionPolicy {
+    /// Newest versions of each filter that are always kept
+    pub keep_last: usize,
+    /// Older versions are kept this many days, the last one of each day
+    pub keep_daily_days: u32,
+    /// Size of the whole history in MB, 0 for no limit; over it the oldest versions go
+    /// first, the newest of each filter stays
+    pub max_total_mb: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy { keep_last: 100, keep_daily_days: 30, max_total_mb: 1024 }
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneReport {
+    pub removed: usize,
+    pub freed: u64,
+    /// Versions and bytes left
+    pub remaining: usize,
+    pub total_size: u64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionEntry {
+    /// Passed back to `get_version_content` / `restore_version`
+    pub id: String,
+    pub path: String,
+    /// Seconds since the unix epoch
+    pub created_at: i64,
+    /// "save" / "restore-version" / "original" (the file before its first versioned save) ...
+    pub reason: String,
+    pub size: u64,
+}
+
+pub fn get_policy() -> RetentionPolicy {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+/// Takes effect with the next save; `prune_snapshots` applies it to everything now.
+pub fn set_policy(policy: &RetentionPolicy) -> Result<(), WarlordError> {
+    if policy.keep_last == 0 {
+        return Err(WarlordError::invalid("每个过滤器至少保留 1 个版本"));
+    }
+    if policy.keep_daily_days > MAX_DAYS {
+        return Err(WarlordError::invalid(format!("按天保留最多 {} 天", MAX_DAYS)));
+    }
+    if policy.max_total_mb > MAX_TOTAL_MB {
+        return Err(WarlordError::invalid(format!("版本历史上限最大 {} MB", MAX_TOTAL_MB)));
+    }
+    app_paths::save_json(CONFIG_FILE, policy)
+}
+
+/// Filters and filter sources get versions; sounds and other files do not.
+pub fn is_versioned(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("filter") || e.eq_ignore_ascii_case("filtersrc"))
+}
+
+fn versions_root() -> PathBuf {
+    app_paths::config_file("versions")
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Keyed by the path without `\\?\`, like `backups`.
+fn version_dir(path: &Path) -> PathBuf {
+    let digest = Sha256::digest(path_utils::short_path(path).as_bytes());
+    versions_root().join(hex(&digest[..8]))
+}
+
+fn objects_root() -> PathBuf {
+    versions_root().join(OBJECTS_DIR)
+}
+
+/// Store `contents` unless an object has them already; returns their hash and the bytes
+/// that were added.
+fn store_object(contents: &[u8]) -> Result<(String, u64), WarlordError> {
+    let hash = hex(&Sha256::digest(contents));
+    let object = objects_root().join(&hash);
+    if object.is_file() {
+        return Ok((hash, 0));
+    }
+    let temp = object.with_extension("tmp");
+    fs::create_dir_all(objects_root()).map_err(|e| WarlordError::io(e, objects_root()))?;
+    fs::write(&temp, contents).map_err(|e| WarlordError::io(e, &temp))?;
+    fs::rename(&temp, &object).map_err(|e| WarlordError::io(e, &object))?;
+    Ok((hash, contents.len() as u64))
+}
+
+/// The object a version file names; None for the versions older releases saved whole.
+fn object_of(file: &Path) -> Option<PathBuf> {
+    if fs::metadata(file).ok()?.len() != (OBJECT_REF.len() + 64) as u64 {
+        return None;
+    }
+    let text = fs::read_to_string(file).ok()?;
+    let hash = text.strip_prefix(OBJECT_REF).filter(|h| h.bytes().all(|b| b.is_ascii_hexdigit()))?;
+    Some(objects_root().join(hash))
+}
+
+/// Where a version's contents are: its object, or the file itself.
+fn stored(file: &Path) -> PathBuf {
+    object_of(file).unwrap_or_else(|| file.to_path_buf())
+}
+
+fn read_version(file: &Path) -> Result<Vec<u8>, WarlordError> {
+    let stored = stored(file);
+    fs::read(&stored).map_err(|e| WarlordError::io(e, &stored))
+}
+
+fn content_hash(file: &Path) -> Option<String> {
+    match object_of(file) {
+        Some(object) => Some(object.file_name()?.to_string_lossy().to_string()),
+        None => fs::read(file).ok().map(|contents| hex(&Sha256::digest(contents))),
+    }
+}
+
+/// Version files of the filter in `dir`, oldest first (the names start with the timestamp).
+fn version_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "ver")).collect())
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Timestamp of a version file's name.
+fn created(file: &Path) -> Option<chrono::NaiveDateTime> {
+    let name = file.file_stem()?.to_string_lossy().to_string();
+    let split = name.match_indices('-').nth(1)?.0;
+    chrono::NaiveDateTime::parse_from_str(&name[..split], TIMESTAMP).ok()
+}
+
+/// Names sort by time, so a version saved within the same millisecond as the last one is
+/// stamped a millisecond after it. Returns the file and the bytes its object added.
+fn write_version(dir: &Path, last: Option<&PathBuf>, contents: &[u8], reason: &str) -> Result<(PathBuf, u64), WarlordError> {
+    let mut time = chrono::Local::now().naive_local();
+    if let Some(last) = last.and_then(|f| created(f)).filter(|t| *t >= time) {
+        time = last + chrono::Duration::milliseconds(1);
+    }
+    let file = dir.join(format!("{}-{}.ver", time.format(TIMESTAMP), reason));
+    let (hash, added) = store_object(contents)?;
+    fs::write(&file, format!("{}{}", OBJECT_REF, hash)).map_err(|e| WarlordError::io(e, &file))?;
+    Ok((file, added))
+}
+
+/// Record `contents` as the newest version of `path`, just saved over `previous`. The first
+/// time a filter is versioned its previous content is kept too, so that save can be undone.
+pub fn record(path: &Path, previous: Option<&[u8]>, contents: &[u8], reason: &str) -> Result<(), WarlordError> {
+    let mut history = HISTORY.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = version_dir(path);
+    let files = version_files(&dir);
+    if files.last().and_then(|f| content_hash(f)).is_some_and(|last| last == hex(&Sha256::digest(contents))) {
+        return Ok(());
+    }
+    fs::create_dir_all(&dir).map_err(|e| WarlordError::io(e, &dir))?;
+    fs::write(dir.join("path.txt"), path_utils::short_path(path).as_bytes()).map_err(|e| WarlordError::io(e, &dir))?;
+    let (mut last, mut added) = (files.last().cloned(), 0);
+    if let Some(previous) = previous.filter(|p| files.is_empty() && *p != contents) {
+        let (file, bytes) = write_version(&dir, None, previous, "original")?;
+        (last, added) = (Some(file), bytes);
+    }
+    added += write_version(&dir, last.as_ref(), contents, reason)?.1;
+    let policy = get_policy();
+    let mut report = PruneReport::default();
+    prune_dir(&dir, &policy, &mut report);
+    if let Some(total) = history.as_mut() {
+        *total = total.saturating_add(added).saturating_sub(report.freed);
+    }
+    // Objects the pruned versions leave unused are only counted out by a full pass
+    if history.is_none_or(|total| total > size_cap(&policy)) {
+        enforce_size_cap(&policy, &mut report);
+        *history = Some(report.total_size);
+    }
+    Ok(())
+}
+
+/// Indices of the versions (oldest first, by their timestamps) `policy` drops.
+fn expired(times: &[chrono::NaiveDateTime], policy: &RetentionPolicy, now: chrono::NaiveDateTime) -> Vec<usize> {
+    let keep_from = times.len().saturating_sub(policy.keep_last.max(1));
+    let cutoff = now.checked_sub_signed(chrono::Duration::days(policy.keep_daily_days as i64)).unwrap_or(chrono::NaiveDateTime::MIN);
+    // The next version is there for every index below `keep_from`
+    (0..keep_from).filter(|&i| times[i] < cutoff || times[i + 1].date() == times[i].date()).collect()
+}
+
+/// Remove a version file; the object it names stays until `enforce_size_cap` finds it
+/// unused. Returns whether it was removed.
+fn remove_version(file: &Path, report: &mut PruneReport) -> bool {
+    let size = fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+    match fs::remove_file(file) {
+        Ok(()) => {
+            report.removed += 1;
+            report.freed += size;
+            true
+        }
+        Err(e) => {
+            eprintln!("[WarlordTools] Could not remove version {}: {}", file.display(), e);
+            false
+        }
+    }
+}
+
+fn remove_object(object: &Path, report: &mut PruneReport) -> u64 {
+    let size = fs::metadata(object).map(|m| m.len()).unwrap_or(0);
+    match fs::remove_file(object) {
+        Ok(()) => {
+            report.freed += size;
+            size
+        }
+        Err(_) => 0,
+    }
+}
+
+/// Apply the count and age rules to one filter's versions.
+fn prune_dir(dir: &Path, policy: &RetentionPolicy, report: &mut PruneReport) {
+    // A file without a timestamp is not ours to drop
+    let dated: Vec<(PathBuf, chrono::NaiveDateTime)> = version_files(dir).into_iter().filter_map(|f| created(&f).map(|t| (f, t))).collect();
+    let times: Vec<chrono::NaiveDateTime> = dated.iter().map(|(_, t)| *t).collect();
+    for i in expired(&times, policy, chrono::Local::now().naive_local()) {
+        remove_version(&dated[i].0, report);
+    }
+}
+
+fn version_dirs() -> Vec<PathBuf> {
+    fs::read_dir(versions_root())
+        .map(|entries| entries.flatten().filter(|e| e.file_name() != OBJECTS_DIR).map(|e| e.path()).filter(|p| p.is_dir()).collect())
+        .unwrap_or_default()
+}
+
+fn size_cap(policy: &RetentionPolicy) -> u64 {
+    match policy.max_total_mb {
+        0 => u64::MAX,
+        mb => mb.saturating_mul(1024 * 1024),
+    }
+}
+
+/// Drop the objects no version names any more, then the oldest versions of all filters
+/// until the history fits `max_total_mb`, and fill in what is left. An object counts once
+/// however many versions name it.
+fn enforce_size_cap(policy: &RetentionPolicy, report: &mut PruneReport) {
+    let mut candidates = Vec::new();
+    let mut uses: HashMap<PathBuf, usize> = HashMap::new();
+    let mut count = 0;
+    for dir in version_dirs() {
+        let mut files = version_files(&dir);
+        count += files.len();
+        for file in &files {
+            *uses.entry(stored(file)).or_default() += 1;
+        }
+        // The newest of each filter stays
+        files.pop();
+        candidates.extend(files.into_iter().filter_map(|f| created(&f).map(|t| (t, f))));
+    }
+    for object in fs::read_dir(objects_root()).into_iter().flatten().flatten().map(|e| e.path()) {
+        if !uses.contains_key(&object) {
+            remove_object(&object, report);
+        }
+    }
+    let mut total: u64 = uses.keys().map(|f| fs::metadata(f).map(|m| m.len()).unwrap_or(0)).sum();
+    let cap = size_cap(policy);
+    if total > cap {
+        candidates.sort();
+        for (_, file) in candidates {
+            if total <= cap {
+                break;
+            }
+            let contents = stored(&file);
+            let size = fs::metadata(&contents).map(|m| m.len()).unwrap_or(0);
+            if !remove_version(&file, report) {
+                continue;
+            }
+            count -= 1;
+            let left = uses.get_mut(&contents).map(|n| {
+                *n -= 1;
+                *n
+            });
+            if contents == file {
+                total -= size;
+            } else if left == Some(0) {
+                total -= remove_object(&contents, report);
+            }
+        }
+    }
+    (report.remaining, report.total_size) = (count, total);
+}
+
+/// Apply the retention policy to the history of every filter now.
+pub fn prune_snapshots() -> PruneReport {
+    let mut history = HISTORY.lock().unwrap_or_else(|e| e.into_inner());
+    let policy = get_policy();
+    let mut report = PruneReport::default();
+    for dir in version_dirs() {
+        prune_dir(&dir, &policy, &mut report);
+    }
+    enforce_size_cap(&policy, &mut report);
+    *history = Some(report.total_size);
+    eprintln!("[WarlordTools] Pruned {} versions ({} bytes), {} left", report.removed, report.freed, report.remaining);
+    report
+}
+
+fn entry_of(file: &Path, path: &str) -> Option<VersionEntry> {
+    let id = file.file_stem()?.to_string_lossy().to_string();
+    // <date>-<time>-<reason>
+    let split = id.match_indices('-').nth(1)?.0;
+    let created_at = created(file)?.and_local_timezone(chrono::Local).earliest()?.timestamp();
+    Some(VersionEntry {
+        reason: id[split + 1..].to_string(),
+        id,
+        path: path.to_string(),
+        created_at,
+        size: fs::metadata(stored(file)).map(|m| m.len()).unwrap_or(0),
+    })
+}
+
+/// Versions of `path`, newest first.
+pub fn list_versions(path: &Path) -> Vec<VersionEntry> {
+    let shown = path.display().to_string();
+    let mut entries: Vec<VersionEntry> = version_files(&version_dir(path)).iter().filter_map(|f| entry_of(f, &shown)).collect();
+    entries.reverse();
+    entries
+}
+
+fn version_file(path: &Path, id: &str) -> Result<PathBuf, WarlordError> {
+    let file = version_dir(path).join(format!("{}.ver", id));
+    if id.contains(['/', '\\']) || id.contains("..") || !file.is_file() {
+        return Err(WarlordError::invalid(format!("{} 没有版本 {}", path_utils::short_path(path), id)));
+    }
+    Ok(file)
+}
+
+/// Text of a version.
+pub fn get_version_content(path: &Path, id: &str) -> Result<String, WarlordError> {
+    let file = version_file(path, id)?;
+    Ok(encoding::decode(&read_version(&file)?).text)
+}
+
+/// Save a version over the filter. The current content stays in the history (and is backed
+/// up), so restoring can itself be undone.
+pub fn restore_version(path: &Path, id: &str) -> Result<(), WarlordError> {
+    let contents = read_version(&version_file(path, id)?)?;
+    if let Some(parent) = path.parent() {
+        sandbox::check_write(parent)?;
+        fs::create_dir_all(long_path(parent)).map_err(|e| WarlordError::io(e, parent))?;
+    }
+    sandbox::write_as(path, contents, "restore-version")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_become_restorable_versions() {
+        let dir = std::env::temp_dir().join("wt-versions-test");
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::remove_dir_all(version_dir(&dir.join("main.filter")));
+        fs::create_dir_all(&dir).unwrap();
+        let filter = dir.join("main.filter");
+        fs::write(&filter, "Show\n").unwrap();
+
+        sandbox::write(&filter, "Hide\n").unwrap();
+        sandbox::write(&filter, "Hide\n").unwrap();
+        let versions = list_versions(&filter);
+        assert_eq!(versions.iter().map(|v| v.reason.as_str()).collect::<Vec<_>>(), ["save", "original"]);
+        assert_eq!(get_version_content(&filter, &versions[1].id).unwrap(), "Show\n");
+
+        restore_version(&filter, &versions[1].id).unwrap();
+        assert_eq!(fs::read_to_string(&filter).unwrap(), "Show\n");
+        let restored = list_versions(&filter);
+        assert_eq!(restored[0].reason, "restore-version");
+        // The same contents share one object
+        let object = |id: &str| object_of(&version_file(&filter, id).unwrap());
+        assert!(object(&restored[0].id).is_some());
+        assert_eq!(object(&restored[0].id), object(&versions[1].id));
+        assert!(get_version_content(&filter, "../path").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn retention_keeps_recent_and_one_per_day() {
+        let at = |s: &str| chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        let times = [at("2024-01-01 10:00"), at("2024-02-20 09:00"), at("2024-02-20 18:00"), at("2024-02-25 12:00"), at("2024-02-28 08:00"), at("2024-02-28 09:00")];
+        let policy = RetentionPolicy { keep_last: 2, keep_daily_days: 30, max_total_mb: 0 };
+        // Too old, then an earlier one of the same day; the last two are kept anyway
+        assert_eq!(expired(&times, &policy, at("2024-03-01 00:00")), [0, 1]);
+        assert_eq!(expired(&times, &RetentionPolicy { keep_daily_days: 0, ..policy.clone() }, at("2024-03-01 00:00")), [0, 1, 2, 3]);
+        assert!(expired(&times[..2], &policy, at("2025-01-01 00:00")).is_empty());
+        assert!(expired(&times, &RetentionPolicy { keep_daily_days: u32::MAX, ..policy.clone() }, at("2024-03-01 00:00")).contains(&1));
+        assert!(set_policy(&RetentionPolicy { keep_daily_days: u32::MAX, ..policy.clone() }).is_err());
+        assert!(set_policy(&RetentionPolicy { max_total_mb: u64::MAX, ..policy }).is_err());
+        assert_eq!(size_cap(&RetentionPolicy { max_total_mb: 0, ..Default::default() }), u64::MAX);
+    }
+}
@@ -0,0 +1,185 @@
+//! File system watchers on library folders, so the library view follows FilterBlade downloads
+//! and game writes while the app is open. Every change marks the scan cache entry of its
+//! directory dirty and is reported as created / modified / deleted; renames are reported as
+//! a delete of the old path and a create of the new one. Paths in `.wtignore` and partial
+//! downloads or temporary files (`*.tmp`, `*.part`) are not reported, and nothing is while
+//! battery saving is on. Changes are held until the folder has been quiet for `DEBOUNCE`, so
+//! a download or save that fires a burst of events is reported once per path.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::error::WarlordError;
+use crate::{power, scan};
+use crate::wtignore::{self, IgnoreRules};
+
+const DEBOUNCE: Duration = Duration::from_millis(300);
+/// Suffixes of files still being written by a browser, a downloader or an editor
+const TEMPORARY_SUFFIXES: &[&str] = &[".tmp", ".part", ".crdownload"];
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FsChange {
+    pub root: String,
+    /// "created" / "modified" / "deleted"
+    pub kind: String,
+    pub path: String,
+}
+
+static WATCHERS: Mutex<Option<HashMap<String, RecommendedWatcher>>> = Mutex::new(None);
+
+fn with_watchers<R>(f: impl FnOnce(&mut HashMap<String, RecommendedWatcher>) -> R) -> R {
+    let mut guard = WATCHERS.lock().unwrap();
+    f(guard.get_or_insert_with(HashMap::new))
+}
+
+/// (kind, path) pairs for one notify event; access and unknown events are dropped.
+fn changes(event: &Event) -> Vec<(&'static str, &Path)> {
+    let all = |kind: &'static str| event.paths.iter().map(|p| (kind, p.as_path())).collect();
+    match event.kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => all("created"),
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => all("deleted"),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            let mut out = Vec::new();
+            if let [from, to, ..] = event.paths.as_slice() {
+                out.push(("deleted", from.as_path()));
+                out.push(("created", to.as_path()));
+            }
+            out
+        }
+        // Platforms that cannot tell the direction of a rename
+        EventKind::Modify(ModifyKind::Name(_)) => event.paths.iter().map(|p| (if p.exists() { "created" } else { "deleted" }, p.as_path())).collect(),
+        EventKind::Modify(_) => all("modified"),
+        _ => Vec::new(),
+    }
+}
+
+fn is_temporary(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    TEMPORARY_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+/// Fold a change into the ones waiting to be reported, one per path. A file created and
+/// deleted again within the same burst cancels out.
+fn coalesce(pending: &mut Vec<(PathBuf, &'static str)>, kind: &'static str, path: PathBuf) {
+    let Some(i) = pending.iter().position(|(p, _)| *p == path) else {
+        pending.push((path, kind));
+        return;
+    };
+    match (pending[i].1, kind) {
+        ("created", "deleted") => {
+            pending.remove(i);
+        }
+        ("created", _) => {}
+        ("deleted", "created") => pending[i].1 = "modified",
+        _ => pending[i].1 = kind,
+    }
+}
+
+/// Collect changes until none arrived for `DEBOUNCE`, then emit each path once. Ends when
+/// the watcher, and with it the sender, is dropped.
+fn debounce(changes: Receiver<(&'static str, PathBuf)>, root: String, emit: impl Fn(FsChange)) {
+    let mut pending: Vec<(PathBuf, &'static str)> = Vec::new();
+    loop {
+        let received = if pending.is_empty() { changes.recv().map_err(|_| RecvTimeoutError::Disconnected) } else { changes.recv_timeout(DEBOUNCE) };
+        match received {
+            Ok((kind, path)) => coalesce(&mut pending, kind, path),
+            Err(RecvTimeoutError::Timeout) => {
+                let quiet = std::mem::take(&mut pending);
+                if !power::background_allowed() {
+                    continue;
+                }
+                for (path, kind) in quiet {
+                    emit(FsChange { root: root.clone(), kind: kind.to_string(), path: path.display().to_string() });
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+/// Watch `root` recursively, handing each change to `emit` (on a thread of its own).
+/// Watching a root again replaces the earlier watcher.
+pub fn watch(root: &Path, emit: impl Fn(FsChange) + Send + 'static) -> Result<(), WarlordError> {
+    if !root.is_dir() {
+        return Err(WarlordError::invalid("Path does not exist"));
+    }
+    let root_path = root.to_path_buf();
+    let root_name = root.display().to_string();
+    let ignore_file = root.join(wtignore::FILE_NAME);
+    let load_rules = move |file: &Path| IgnoreRules::parse(&fs::read_to_string(file).unwrap_or_default());
+    let mut rules = load_rules(&ignore_file);
+    let name = root_name.clone();
+    let (sender, receiver) = mpsc::channel();
+    let debounced = root_name.clone();
+    std::thread::spawn(move || debounce(receiver, debounced, emit));
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("[WarlordTools] 文件监视出错 {}: {}", name, e);
+                return;
+            }
+        };
+        for (kind, path) in changes(&event) {
+            if path == ignore_file {
+                rules = load_rules(&ignore_file);
+            }
+            let relative = path.strip_prefix(&root_path).unwrap_or(path).to_string_lossy();
+            if is_temporary(path) || rules.is_excluded(&relative, path.is_dir()) {
+                continue;
+            }
+            if let Some(dir) = path.parent() {
+                scan::mark_dirty(dir);
+            }
+            scan::mark_dirty(path);
+            let _ = sender.send((kind, path.to_path_buf()));
+        }
+    })
+    .map_err(|e| WarlordError::Other { message: e.to_string() })?;
+    watcher.watch(root, RecursiveMode::Recursive).map_err(|e| WarlordError::Other { message: format!("无法监视 {}: {}", root.display(), e) })?;
+    with_watchers(|watchers| watchers.insert(root_name, watcher));
+    Ok(())
+}
+
+pub fn unwatch(root: &Path) {
+    with_watchers(|watchers| watchers.remove(&root.display().to_string()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange, RemoveKind};
+
+    #[test]
+    fn classifies_events() {
+        let event = |kind, paths: &[&str]| paths.iter().fold(Event::new(kind), |e, p| e.add_path(PathBuf::from(p)));
+        let kinds = |e: &Event| changes(e).into_iter().map(|(k, p)| format!("{} {}", k, p.display())).collect::<Vec<_>>();
+        assert_eq!(kinds(&event(EventKind::Create(CreateKind::File), &["a.filter"])), vec!["created a.filter"]);
+        assert_eq!(kinds(&event(EventKind::Modify(ModifyKind::Data(DataChange::Content)), &["a.filter"])), vec!["modified a.filter"]);
+        assert_eq!(kinds(&event(EventKind::Remove(RemoveKind::File), &["a.filter"])), vec!["deleted a.filter"]);
+        assert_eq!(
+            kinds(&event(EventKind::Modify(ModifyKind::Name(RenameMode::Both)), &["a.filter", "b.filter"])),
+            vec!["deleted a.filter", "created b.filter"]
+        );
+    }
+
+    #[test]
+    fn coalesces_bursts_and_skips_partial_files() {
+        assert!(is_temporary(Path::new("dl/NeverSink.filter.part")));
+        assert!(is_temporary(Path::new(".a.filter.123.TMP")));
+        assert!(!is_temporary(Path::new("a.filter")));
+        let mut pending = Vec::new();
+        for (kind, path) in [("created", "a.filter"), ("modified", "a.filter"), ("created", "b.filter"), ("deleted", "b.filter"), ("deleted", "c.filter"), ("created", "c.filter")] {
+            coalesce(&mut pending, kind, PathBuf::from(path));
+        }
+        assert_eq!(pending, vec![(PathBuf::from("a.filter"), "created"), (PathBuf::from("c.filter"), "modified")]);
+    }
+}
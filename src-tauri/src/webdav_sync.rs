@@ -0,0 +1,490 @@
+//! Optional sync of the filter library with a WebDAV folder (Nextcloud, Alist, a NAS), so two
+//! PCs share one library. The folder holds the files plus `warlordtools-sync.json`, an index
+//! of their SHA-256 hashes written by every sync. Each side is compared with the hashes of
+//! the last sync (`webdav_state.json`): a file changed on one side only is copied over, one
+//! changed on both is a conflict and is left alone until resolved by `resolve_conflict`.
+//! Local files are replaced and deleted through the sandbox, so both can be undone. Uploads
+//! are conditional on the ETag the index recorded (or on the file not existing yet), so a
+//! file another PC changed in the meantime is not overwritten. When the index is gone from a
+//! server this PC synced with before, nothing is changed until `reset_state` is called:
+//! planning against a wiped or moved folder would delete the whole library.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+use crate::error::WarlordError;
+use crate::file_ops::{self, FailedFile};
+use crate::path_utils::long_path;
+use crate::{app_paths, archive, library, sandbox, scan};
+
+const CONFIG_FILE: &str = "webdav.json";
+const STATE_FILE: &str = "webdav_state.json";
+const REMOTE_INDEX: &str = "warlordtools-sync.json";
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WebDavConfig {
+    pub enabled: bool,
+    /// Folder on the server, e.g. https://cloud.example.com/remote.php/dav/files/me/WarlordTools
+    pub url: String,
+    pub username: String,
+    /// App password; kept out of library exports
+    pub password: String,
+}
+
+/// Hashes by `/`-separated path relative to the library root (local) or the folder (remote).
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct FileIndex {
+    files: BTreeMap<String, String>,
+    /// ETag the server gave each file when it was last uploaded or downloaded
+    etags: BTreeMap<String, String>,
+}
+
+/// The index of the last sync, for the server it was made with.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct SyncState {
+    url: String,
+    files: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncAction {
+    Push,
+    Pull,
+    DeleteRemote,
+    DeleteLocal,
+    /// Changed on both sides since the last sync
+    Conflict,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncItem {
+    pub path: String,
+    pub action: SyncAction,
+    pub local_hash: Option<String>,
+    pub remote_hash: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    /// Everything that differs; conflicts are never applied
+    pub items: Vec<SyncItem>,
+    pub applied: bool,
+    pub failed: Vec<FailedFile>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Resolution {
+    KeepLocal,
+    KeepRemote,
+    /// The server's copy is saved next to the local file as `name (copy).ext`, then both go up
+    KeepBoth,
+}
+
+pub fn get_config() -> WebDavConfig {
+    app_paths::load_json(CONFIG_FILE)
+}
+
+pub fn set_config(config: &WebDavConfig) -> Result<(), WarlordError> {
+    if config.enabled && !config.url.starts_with("https://") && !config.url.starts_with("http://") {
+        return Err(WarlordError::invalid(format!("无效的 WebDAV 地址: {}", config.url)));
+    }
+    app_paths::save_json(CONFIG_FILE, config)
+}
+
+/// Percent-encoded URL of `path` in the folder.
+fn url_of(config: &WebDavConfig, path: &str) -> String {
+    let encoded: Vec<String> = path
+        .split('/')
+        .map(|segment| segment.bytes().map(|b| if b.is_ascii_alphanumeric() || b"-._~".contains(&b) { (b as char).to_string() } else { format!("%{:02X}", b) }).collect())
+        .collect();
+    format!("{}/{}", config.url.trim_end_matches('/'), encoded.join("/"))
+}
+
+fn request(config: &WebDavConfig, method: &str, path: &str) -> ureq::Request {
+    let request = ureq::request(method, &url_of(config, path));
+    if config.username.is_empty() {
+        return request;
+    }
+    let credentials = base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", config.username, config.password));
+    request.set("Authorization", &format!("Basic {}", credentials))
+}
+
+/// A failed precondition (412) is a `Conflict`: another PC changed the file meanwhile.
+fn network_error(path: &str, error: ureq::Error) -> WarlordError {
+    let message = match error {
+        ureq::Error::Status(401, _) => "WebDAV 用户名或密码错误".to_string(),
+        ureq::Error::Status(412, _) => return WarlordError::Conflict { path: path.to_string(), message: "服务器上的文件在同步期间被修改，请重新同步".to_string() },
+        ureq::Error::Status(code, _) => format!("WebDAV 请求 {} 失败: HTTP {}", path, code),
+        e => format!("无法连接 WebDAV 服务器: {}", e),
+    };
+    WarlordError::Network { message }
+}
+
+struct Download {
+    body: Vec<u8>,
+    etag: Option<String>,
+}
+
+/// `path` on the server, None when it does not exist.
+fn download(config: &WebDavConfig, path: &str) -> Result<Option<Download>, WarlordError> {
+    let response = match request(config, "GET", path).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(e) => return Err(network_error(path, e)),
+    };
+    let etag = response.header("ETag").map(String::from);
+    let mut body = Vec::new();
+    response.into_reader().read_to_end(&mut body).map_err(|e| WarlordError::Network { message: format!("下载 {} 失败: {}", path, e) })?;
+    Ok(Some(Download { body, etag }))
+}
+
+/// Precondition of a write: the server's copy is still the one we know, or there is none.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Expect<'a> {
+    Etag(&'a str),
+    Missing,
+    Anything,
+}
+
+fn conditional(request: ureq::Request, expect: Expect) -> ureq::Request {
+    match expect {
+        Expect::Etag(etag) => request.set("If-Match", etag),
+        Expect::Missing => request.set("If-None-Match", "*"),
+        Expect::Anything => request,
+    }
+}
+
+/// PUT `body` at `path`; returns the new ETag when the server sends one.
+fn upload(config: &WebDavConfig, path: &str, body: &[u8], expect: Expect) -> Result<Option<String>, WarlordError> {
+    let response = conditional(request(config, "PUT", path), expect).send_bytes(body).map_err(|e| network_error(path, e))?;
+    Ok(response.header("ETag").map(String::from))
+}
+
+fn delete_remote(config: &WebDavConfig, path: &str, expect: Expect) -> Result<(), WarlordError> {
+    match conditional(request(config, "DELETE", path), expect).call() {
+        Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(()),
+        Err(e) => Err(network_error(path, e)),
+    }
+}
+
+/// Every folder above `path`, outermost first.
+fn parents(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('/').map(move |(i, _)| &path[..i])
+}
+
+/// An index entry: the hash and ETag of a file, None for removed.
+type Entry = Option<(String, Option<String>)>;
+
+fn set_entry(index: &mut FileIndex, path: &str, entry: &Entry) {
+    match entry {
+        Some((hash, etag)) => {
+            index.files.insert(path.to_string(), hash.clone());
+            match etag {
+                Some(etag) => index.etags.insert(path.to_string(), etag.clone()),
+                None => index.etags.remove(path),
+            };
+        }
+        None => {
+            index.files.remove(path);
+            index.etags.remove(path);
+        }
+    }
+}
+
+fn hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// What to do with a file from its local, remote and last synced hashes.
+fn decide(local: Option<&String>, remote: Option<&String>, base: Option<&String>) -> Option<SyncAction> {
+    if local == remote {
+        None
+    } else if remote == base {
+        Some(if local.is_some() { SyncAction::Push } else { SyncAction::DeleteRemote })
+    } else if local == base {
+        Some(if remote.is_some() { SyncAction::Pull } else { SyncAction::DeleteLocal })
+    } else {
+        Some(SyncAction::Conflict)
+    }
+}
+
+struct Session {
+    config: WebDavConfig,
+    root: PathBuf,
+    local: BTreeMap<String, String>,
+    remote: FileIndex,
+    remote_etag: Option<String>,
+    state: SyncState,
+    /// Folders known to exist on the server
+    folders: BTreeSet<String>,
+    /// Index entries changed by this session (None for removed), replayed onto a newer index
+    /// when another PC wrote one meanwhile
+    changed: BTreeMap<String, Entry>,
+}
+
+fn download_index(config: &WebDavConfig) -> Result<Option<(FileIndex, Option<String>)>, WarlordError> {
+    let Some(index) = download(config, REMOTE_INDEX)? else { return Ok(None) };
+    let parsed = serde_json::from_slice(&index.body).map_err(|e| WarlordError::invalid(format!("{} 无法读取: {}", REMOTE_INDEX, e)))?;
+    Ok(Some((parsed, index.etag)))
+}
+
+/// Hashes of the library's files by `/`-separated relative path. The scan cache keeps them
+/// by size and modification time, so only changed files are read.
+fn local_hashes(root: &Path) -> Result<BTreeMap<String, String>, WarlordError> {
+    let options = scan::ScanOptions { patterns: vec!["*".to_string()], max_depth: None, hash: true };
+    let files = scan::refresh(root, &options)?;
+    Ok(files.into_iter().map(|f| (f.relative_path.replace('\\', "/"), f.hash)).filter(|(name, _)| !name.ends_with(REMOTE_INDEX)).collect())
+}
+
+fn open() -> Result<Session, WarlordError> {
+    let config = get_config();
+    if !config.enabled || config.url.is_empty() {
+        return Err(WarlordError::invalid("尚未设置 WebDAV 同步"));
+    }
+    let root = library::library_root().ok_or_else(|| WarlordError::invalid("尚未设置过滤器库文件夹"))?;
+    let mut state: SyncState = app_paths::load_json(STATE_FILE);
+    // Hashes synced with another server say nothing about this one
+    if state.url != config.url {
+        state = SyncState { url: config.url.clone(), files: BTreeMap::new() };
+    }
+    let (remote, remote_etag) = match download_index(&config)? {
+        Some(index) => index,
+        None if !state.files.is_empty() => {
+            return Err(WarlordError::invalid(format!("服务器上找不到 {}，文件夹可能已被清空或移动。为免删除本地文件，同步已停止；确认地址无误后可重置同步状态", REMOTE_INDEX)));
+        }
+        None => (FileIndex::default(), None),
+    };
+    let local = local_hashes(&root)?;
+    let folders = remote.files.keys().flat_map(|path| parents(path)).map(String::from).collect();
+    Ok(Session { config, root, local, remote, remote_etag, state, folders, changed: BTreeMap::new() })
+}
+
+/// Forget what was last synced, so the next sync treats the server as new: nothing is
+/// deleted on either side and files that differ are conflicts.
+pub fn reset_state() -> Result<(), WarlordError> {
+    app_paths::save_json(STATE_FILE, &SyncState { url: get_config().url, files: BTreeMap::new() })
+}
+
+impl Session {
+    fn plan(&self) -> Vec<SyncItem> {
+        let paths: BTreeSet<&String> = self.local.keys().chain(self.remote.files.keys()).chain(self.state.files.keys()).collect();
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let (local, remote) = (self.local.get(path), self.remote.files.get(path));
+                let action = decide(local, remote, self.state.files.get(path))?;
+                Some(SyncItem { path: path.clone(), action, local_hash: local.cloned(), remote_hash: remote.cloned() })
+            })
+            .collect()
+    }
+
+    fn local_path(&self, path: &str) -> Result<PathBuf, WarlordError> {
+        archive::entry_path(path).map(|p| self.root.join(p)).ok_or_else(|| WarlordError::invalid(format!("服务器上的文件名不安全: {}", path)))
+    }
+
+    /// What the server should hold at `path` for a write to go through.
+    fn expect(&self, path: &str) -> Expect<'_> {
+        match (self.remote.files.contains_key(path), self.remote.etags.get(path)) {
+            (false, _) => Expect::Missing,
+            (true, Some(etag)) => Expect::Etag(etag),
+            // Indexed by a version that kept no ETags
+            (true, None) => Expect::Anything,
+        }
+    }
+
+    /// Create the folders above `path` the server does not have yet.
+    fn make_folders(&mut self, path: &str) -> Result<(), WarlordError> {
+        for folder in parents(path) {
+            if self.folders.contains(folder) {
+                continue;
+            }
+            // 405: it exists already
+            match request(&self.config, "MKCOL", folder).call() {
+                Ok(_) | Err(ureq::Error::Status(405, _)) => {}
+                Err(e) => return Err(network_error(folder, e)),
+            }
+            self.folders.insert(folder.to_string());
+        }
+        Ok(())
+    }
+
+    fn set_remote(&mut self, path: &str, entry: Entry) {
+        set_entry(&mut self.remote, path, &entry);
+        self.changed.insert(path.to_string(), entry);
+    }
+
+    fn push(&mut self, path: &str) -> Result<(), WarlordError> {
+        let file = self.local_path(path)?;
+        let bytes = std::fs::read(long_path(&file)).map_err(|e| WarlordError::io(e, &file))?;
+        self.make_folders(path)?;
+        let etag = upload(&self.config, path, &bytes, self.expect(path))?;
+        self.set_remote(path, Some((hash(&bytes), etag)));
+        Ok(())
+    }
+
+    fn delete_remote(&mut self, path: &str) -> Result<(), WarlordError> {
+        delete_remote(&self.config, path, self.expect(path))?;
+        self.set_remote(path, None);
+        Ok(())
+    }
+
+    fn pull(&mut self, path: &str, to: Option<PathBuf>) -> Result<(), WarlordError> {
+        let target = match to {
+            Some(to) => to,
+            None => self.local_path(path)?,
+        };
+        let downloaded = download(&self.config, path)?.ok_or_else(|| WarlordError::not_found(Path::new(path)))?;
+        if let Some(parent) = target.parent() {
+            sandbox::check_write(parent)?;
+            std::fs::create_dir_all(long_path(parent)).map_err(|e| WarlordError::io(e, parent))?;
+        }
+        sandbox::write_as(&target, &downloaded.body, "sync")?;
+        if let (Some(hash), Some(etag)) = (self.remote.files.get(path).cloned(), downloaded.etag) {
+            self.set_remote(path, Some((hash, Some(etag))));
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, item: &SyncItem) -> Result<(), WarlordError> {
+        match item.action {
+            SyncAction::Push => self.push(&item.path)?,
+            SyncAction::Pull => self.pull(&item.path, None)?,
+            SyncAction::DeleteRemote => self.delete_remote(&item.path)?,
+            SyncAction::DeleteLocal => sandbox::delete(self.local_path(&item.path)?)?,
+            SyncAction::Conflict => return Ok(()),
+        }
+        // Both sides now hold the winner
+        match item.action {
+            SyncAction::Push => self.state.files.insert(item.path.clone(), item.local_hash.clone().unwrap_or_default()),
+            SyncAction::Pull => self.state.files.insert(item.path.clone(), item.remote_hash.clone().unwrap_or_default()),
+            _ => self.state.files.remove(&item.path),
+        };
+        Ok(())
+    }
+
+    /// Record files that already match as synced, then write both indexes. When another PC
+    /// wrote the index since it was read, this session's entries are replayed onto that one.
+    fn finish(mut self) -> Result<(), WarlordError> {
+        for (path, local) in &self.local {
+            if self.remote.files.get(path) == Some(local) {
+                self.state.files.insert(path.clone(), local.clone());
+            }
+        }
+        const ATTEMPTS: usize = 3;
+        for attempt in 1..=ATTEMPTS {
+            let body = serde_json::to_vec_pretty(&self.remote).map_err(|e| WarlordError::from(e.to_string()))?;
+            let expect = self.remote_etag.as_deref().map_or(Expect::Missing, Expect::Etag);
+            match upload(&self.config, REMOTE_INDEX, &body, expect) {
+                Ok(_) => break,
+                Err(WarlordError::Conflict { .. }) if attempt < ATTEMPTS => {
+                    let (mut newer, etag) = download_index(&self.config)?.unwrap_or_default();
+                    for (path, entry) in &self.changed {
+                        set_entry(&mut newer, path, entry);
+                    }
+                    (self.remote, self.remote_etag) = (newer, etag);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        app_paths::save_json(STATE_FILE, &self.state)
+    }
+}
+
+/// Compare the library with the server; with `apply`, copy every change that is not a
+/// conflict. Files that fail are reported and the rest still synced.
+pub fn sync(apply: bool) -> Result<SyncReport, WarlordError> {
+    let mut session = open()?;
+    let items = session.plan();
+    if !apply {
+        return Ok(SyncReport { items, applied: false, failed: Vec::new() });
+    }
+    let mut failed = Vec::new();
+    for item in &items {
+        if let Err(error) = session.apply(item) {
+            failed.push(FailedFile { path: item.path.clone(), error });
+        }
+    }
+    session.finish()?;
+    eprintln!("[WarlordTools] WebDAV sync: {} changes, {} failed", items.len(), failed.len());
+    Ok(SyncReport { items, applied: true, failed })
+}
+
+/// Settle a conflict on `path` (as listed by `sync`).
+pub fn resolve_conflict(path: &str, resolution: Resolution) -> Result<(), WarlordError> {
+    let mut session = open()?;
+    let remote = session.remote.files.get(path).cloned();
+    match resolution {
+        Resolution::KeepLocal if session.local.contains_key(path) => session.push(path)?,
+        Resolution::KeepLocal => session.delete_remote(path)?,
+        Resolution::KeepRemote if remote.is_some() => session.pull(path, None)?,
+        Resolution::KeepRemote => sandbox::delete(session.local_path(path)?)?,
+        Resolution::KeepBoth => {
+            if remote.is_some() {
+                let copy = file_ops::copy_name(&session.local_path(path)?);
+                session.pull(path, Some(copy.clone()))?;
+                let name = copy.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                let relative = match path.rsplit_once('/') {
+                    Some((dir, _)) => format!("{}/{}", dir, name),
+                    None => name,
+                };
+                session.push(&relative)?;
+            }
+            if session.local.contains_key(path) {
+                session.push(path)?;
+            }
+        }
+    }
+    // What the server has now is what this side has
+    let now = session.remote.files.get(path).cloned();
+    match now {
+        Some(hash) => session.state.files.insert(path.to_string(), hash),
+        None => session.state.files.remove(path),
+    };
+    session.local.remove(path);
+    session.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decides_by_last_synced_hash() {
+        let (a, b, c) = (Some("a".to_string()), Some("b".to_string()), Some("c".to_string()));
+        assert_eq!(decide(a.as_ref(), a.as_ref(), None), None);
+        assert_eq!(decide(b.as_ref(), a.as_ref(), a.as_ref()), Some(SyncAction::Push));
+        assert_eq!(decide(a.as_ref(), b.as_ref(), a.as_ref()), Some(SyncAction::Pull));
+        assert_eq!(decide(None, a.as_ref(), a.as_ref()), Some(SyncAction::DeleteRemote));
+        assert_eq!(decide(a.as_ref(), None, a.as_ref()), Some(SyncAction::DeleteLocal));
+        assert_eq!(decide(b.as_ref(), c.as_ref(), a.as_ref()), Some(SyncAction::Conflict));
+        // New on both sides with different content
+        assert_eq!(decide(b.as_ref(), c.as_ref(), None), Some(SyncAction::Conflict));
+
+        let config = WebDavConfig { url: "https://dav.example.com/wt/".to_string(), ..Default::default() };
+        assert_eq!(url_of(&config, "强力/loot filter.filter"), "https://dav.example.com/wt/%E5%BC%BA%E5%8A%9B/loot%20filter.filter");
+    }
+
+    #[test]
+    fn tracks_folders_and_etags() {
+        assert_eq!(parents("a/b/c.filter").collect::<Vec<_>>(), ["a", "a/b"]);
+        let mut index = FileIndex::default();
+        set_entry(&mut index, "a.filter", &Some(("h".to_string(), Some("\"1\"".to_string()))));
+        assert_eq!(index.etags.get("a.filter").map(String::as_str), Some("\"1\""));
+        // A server that sent no ETag for the new upload: the old one must not be trusted
+        set_entry(&mut index, "a.filter", &Some(("i".to_string(), None)));
+        assert!(index.etags.is_empty());
+        set_entry(&mut index, "a.filter", &None);
+        assert!(index.files.is_empty());
+    }
+}
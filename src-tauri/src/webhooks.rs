@@ -0,0 +1,90 @@
+//! Outgoing webhooks (Discord-compatible JSON) for pipeline, filter update and backup events.
+//! A filter update is a filter uploaded to the GGG account or installed into the game folder.
+
+use std::thread;
+
+use crate::error::WarlordError;
+use crate::{app_paths, quiet_hours};
+
+const CONFIG_FILE: &str = "webhooks.json";
+
+pub const EVENT_PIPELINE: &str = "pipeline";
+pub const EVENT_FILTER_UPDATE: &str = "filterUpdate";
+pub const EVENT_BACKUP: &str = "backup";
+pub const EVENTS: &[&str] = &[EVENT_PIPELINE, EVENT_FILTER_UPDATE, EVENT_BACKUP];
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub name: String,
+    pub url: String,
+    /// Event types this webhook receives
+    pub events: Vec<String>,
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+/// Events that are no longer sent (`priceAlert`) are dropped, so saving the list again works.
+pub fn get_webhooks() -> Vec<Webhook> {
+    let mut hooks: Vec<Webhook> = app_paths::load_json(CONFIG_FILE);
+    for hook in &mut hooks {
+        hook.events.retain(|e| EVENTS.contains(&e.as_str()));
+    }
+    hooks
+}
+
+pub fn set_webhooks(hooks: &[Webhook]) -> Result<(), WarlordError> {
+    for hook in hooks {
+        if !hook.url.starts_with("https://") && !hook.url.starts_with("http://") {
+            return Err(WarlordError::invalid(format!("无效的 Webhook 地址: {}", hook.url)));
+        }
+        if let Some(e) = hook.events.iter().find(|e| !EVENTS.contains(&e.as_str())) {
+            return Err(WarlordError::invalid(format!("未知的事件类型: {}", e)));
+        }
+    }
+    app_paths::save_json(CONFIG_FILE, &hooks)
+}
+
+/// Discord webhook body, also understood by Discord-compatible services.
+pub fn payload(event: &str, title: &str, description: &str, success: bool) -> serde_json::Value {
+    serde_json::json!({
+        "username": "WarlordTools",
+        "embeds": [{
+            "title": title,
+            "description": description,
+            "color": if success { 0x2ecc71 } else { 0xe74c3c },
+            "footer": { "text": event },
+        }],
+    })
+}
+
+pub fn post(url: &str, body: &serde_json::Value) -> Result<(), WarlordError> {
+    ureq::post(url).send_json(body.clone()).map_err(|e| WarlordError::Network { message: format!("Webhook 发送失败: {}", e) })?;
+    Ok(())
+}
+
+/// Post `event` to every enabled webhook subscribed to it, in the background.
+pub fn notify(event: &str, title: &str, description: &str, success: bool) {
+    if quiet_hours::mutes_webhooks() {
+        return;
+    }
+    let hooks: Vec<Webhook> = get_webhooks()
+        .into_iter()
+        .filter(|h| h.enabled && h.events.iter().any(|e| e == event))
+        .collect();
+    if hooks.is_empty() {
+        return;
+    }
+    let body = payload(event, title, description, success);
+    thread::spawn(move || {
+        for hook in hooks {
+            if let Err(e) = post(&hook.url, &body) {
+                eprintln!("[WarlordTools] webhook {}: {}", hook.name, e);
+            }
+        }
+    });
+}
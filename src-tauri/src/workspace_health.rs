@@ -0,0 +1,122 @@
+//! One pass over a workspace for the startup dashboard: filters that do not lint clean,
+//! alert sounds that point at missing files, patches waiting to be re-applied or whose filter
+//! is gone, and installed pipeline outputs that differ from what the workspace builds.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::WarlordError;
+use crate::filter_parser::{unquote, FilterDocument};
+use crate::manifest::{self, PipelineStep};
+use crate::{encoding, library, patches, pipelines};
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthIssue {
+    /// "read" / "parse" / "sound" / "patch" / "install"
+    pub kind: String,
+    pub path: String,
+    /// 1-based, for issues inside a filter
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    pub workspace: String,
+    /// Filters checked
+    pub files: usize,
+    pub issues: Vec<HealthIssue>,
+}
+
+fn issue(kind: &str, path: &Path, line: Option<usize>, message: String) -> HealthIssue {
+    HealthIssue { kind: kind.to_string(), path: path.display().to_string(), line, message }
+}
+
+/// `CustomAlertSound` files referenced by `doc` that do not exist. Relative paths are
+/// resolved against the filter's folder, like the game does. `CustomAlertSoundOptional` is
+/// left out: the game plays nothing when its file is missing, by design.
+pub fn missing_sounds(doc: &FilterDocument, base_dir: &Path) -> Vec<(usize, String)> {
+    let mut missing = Vec::new();
+    for block in &doc.blocks {
+        for (i, line) in block.lines.iter().enumerate() {
+            let Some(rule) = line.rule.as_ref().filter(|r| r.keyword == "CustomAlertSound") else { continue };
+            let Some(file) = rule.values.first().map(|v| unquote(v)) else { continue };
+            if !base_dir.join(file).exists() {
+                missing.push((block.line + 2 + i, file.to_string()));
+            }
+        }
+    }
+    missing
+}
+
+fn check_filters(root: &Path, report: &mut HealthReport) -> Result<(), WarlordError> {
+    for file in library::filter_files(root).map_err(|e| WarlordError::io(e, root))? {
+        report.files += 1;
+        let content = match encoding::read_to_string(&file) {
+            Ok(content) => content,
+            Err(error) => {
+                report.issues.push(issue("read", &file, None, error.to_string()));
+                continue;
+            }
+        };
+        let doc = FilterDocument::parse(&content);
+        for problem in pipelines::lint_document(&doc) {
+            report.issues.push(issue("parse", &file, None, problem));
+        }
+        for (line, sound) in missing_sounds(&doc, file.parent().unwrap_or(Path::new(""))) {
+            report.issues.push(issue("sound", &file, Some(line), format!("找不到音效文件 {}", sound)));
+        }
+    }
+    Ok(())
+}
+
+fn check_installs(root: &Path, report: &mut HealthReport) -> Result<(), WarlordError> {
+    for (name, pipeline) in manifest::load(root)?.pipelines {
+        let output = root.join(&pipeline.output);
+        for step in &pipeline.steps {
+            let PipelineStep::Install { dest } = step else { continue };
+            let Ok(target) = pipelines::install_target(dest.as_deref(), &output) else { continue };
+            if target == output {
+                continue;
+            }
+            let message = match (fs::read(&output), fs::read(&target)) {
+                (Ok(_), Err(_)) => format!("流程 {} 的输出尚未安装", name),
+                (Ok(built), Ok(installed)) if built != installed => format!("已安装的文件与流程 {} 的输出不一致", name),
+                _ => continue,
+            };
+            report.issues.push(issue("install", &target, None, message));
+        }
+    }
+    Ok(())
+}
+
+pub fn workspace_health(workspace: Option<&str>) -> Result<HealthReport, WarlordError> {
+    let root = manifest::resolve_workspace(workspace)?;
+    let mut report = HealthReport { workspace: root.display().to_string(), files: 0, issues: Vec::new() };
+    check_filters(&root, &mut report)?;
+    for patch in patches::list_patches().into_iter().filter(|p| p.enabled && !Path::new(&p.target).exists()) {
+        report.issues.push(issue("patch", Path::new(&patch.target), None, format!("补丁 {} 的目标过滤器不存在", patch.name)));
+    }
+    for patch in patches::outdated() {
+        report.issues.push(issue("patch", Path::new(&patch.target), None, format!("补丁 {} 需要重新应用", patch.name)));
+    }
+    check_installs(&root, &mut report)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_missing_sounds() {
+        let dir = std::env::temp_dir().join("wt-health-test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("ok.mp3"), b"").unwrap();
+        let doc = FilterDocument::parse("Show\n    CustomAlertSound \"ok.mp3\"\nShow\n    SetFontSize 40\n    CustomAlertSound \"gone.mp3\" 80\nShow\n    CustomAlertSoundOptional \"extra.mp3\"\n");
+        assert_eq!(missing_sounds(&doc, &dir), vec![(5, "gone.mp3".to_string())]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
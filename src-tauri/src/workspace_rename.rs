@@ -0,0 +1,202 @@
+//! Rename a file or folder in the library and follow the rename everywhere it is referenced:
+//! `CustomAlertSound` and `#include` lines in every filter (written back relative or absolute,
+//! with the separator they had), temporary rules, patch targets, the leveling plan and the
+//! remembered paths in Settings.json. Each rewritten reference comes back as a `TouchedRef`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::error::WarlordError;
+use crate::filter_parser::{unquote, Rule};
+use crate::{app_paths, encoding, journal, leveling, library, patches, sandbox, temp_rules};
+
+/// One reference that was rewritten to follow the rename.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TouchedRef {
+    /// "sound" | "include" | "tempRule" | "patch" | "leveling" | "settings"
+    pub kind: String,
+    /// File (or state store) that held the reference
+    pub location: String,
+    /// 1-based line for references inside filters
+    pub line: Option<usize>,
+    pub old_value: String,
+    pub new_value: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameReport {
+    pub old_path: String,
+    pub new_path: String,
+    pub touched: Vec<TouchedRef>,
+}
+
+/// Render `target` the way the original reference was written: relative to the
+/// referencing filter's folder when it was relative, with the same separator.
+fn render_like(original: &str, target: &Path, base_dir: &Path) -> String {
+    let rendered = if Path::new(original).is_absolute() {
+        target.to_path_buf()
+    } else {
+        target.strip_prefix(base_dir).map(Path::to_path_buf).unwrap_or_else(|_| target.to_path_buf())
+    };
+    let s = rendered.display().to_string();
+    if original.contains('\\') { s.replace('/', "\\") } else { s.replace('\\', "/") }
+}
+
+fn resolve(value: &str, base_dir: &Path) -> PathBuf {
+    let p = Path::new(value);
+    if p.is_absolute() { p.to_path_buf() } else { base_dir.join(p) }
+}
+
+/// Rewrite sound and `#include` references in one filter. Only the affected lines change.
+fn rewrite_filter(file: &Path, old: &Path, new: &Path, touched: &mut Vec<TouchedRef>) -> Result<(), WarlordError> {
+    let include_re = Regex::new(r#"^\s*#include\s+"([^"]+)""#).unwrap();
+    let content = encoding::read_to_string(file)?;
+    let base_dir = file.parent().unwrap_or(Path::new(""));
+    let mut changed = false;
+    let mut lines: Vec<String> = Vec::new();
+    for (idx, line) in content.split('\n').enumerate() {
+        let mut out = line.to_string();
+        let (kind, value) = if let Some(c) = include_re.captures(line) {
+            ("include", Some(c[1].to_string()))
+        } else {
+            match Rule::parse(line.trim()) {
+                Some(rule) if rule.keyword.starts_with("CustomAlertSound") => {
+                    ("sound", rule.values.first().map(|v| unquote(v).to_string()))
+                }
+                _ => ("", None),
+            }
+        };
+        if let Some(value) = value {
+            if let Some(target) = library::remap(&resolve(&value, base_dir), old, new) {
+                let new_value = render_like(&value, &target, base_dir);
+                out = line.replacen(&value, &new_value, 1);
+                touched.push(TouchedRef {
+                    kind: kind.to_string(),
+                    location: file.display().to_string(),
+                    line: Some(idx + 1),
+                    old_value: value,
+                    new_value,
+                });
+                changed = true;
+            }
+        }
+        lines.push(out);
+    }
+    if changed {
+        encoding::write_preserving(file, &lines.join("\n"))?;
+    }
+    Ok(())
+}
+
+fn rewrite_settings(old: &Path, new: &Path, touched: &mut Vec<TouchedRef>) -> Result<(), WarlordError> {
+    let mut settings: serde_json::Value = app_paths::load_json("Settings.json");
+    let mut changed = false;
+    for key in ["lastSelectedFilter", "filterStoragePath"] {
+        let Some(value) = settings[key].as_str().map(String::from) else { continue };
+        if let Some(target) = library::remap(Path::new(&value), old, new) {
+            let new_value = target.display().to_string();
+            settings[key] = serde_json::json!(new_value);
+            touched.push(TouchedRef {
+                kind: "settings".to_string(),
+                location: key.to_string(),
+                line: None,
+                old_value: value,
+                new_value,
+            });
+            changed = true;
+        }
+    }
+    if changed {
+        app_paths::save_json("Settings.json", &settings)?;
+    }
+    Ok(())
+}
+
+/// Rename a file or folder in the library and update everything that points at it.
+pub fn rename_managed_file(old: &str, new: &str) -> Result<RenameReport, WarlordError> {
+    let (old_path, new_path) = (Path::new(old), Path::new(new));
+    if new_path.exists() {
+        return Err(WarlordError::invalid("目标文件已存在"));
+    }
+    sandbox::check_write(old_path)?;
+    sandbox::check_write(new_path)?;
+    fs::rename(old_path, new_path).map_err(|e| WarlordError::write_failed(e, old_path))?;
+    journal::record("rename", journal::Change::Rename { from: old.to_string(), to: new.to_string() });
+
+    let mut touched = Vec::new();
+    let root = library::library_root().or_else(|| new_path.parent().map(Path::to_path_buf));
+    if let Some(root) = root {
+        for file in library::filter_files(&root).map_err(|e| WarlordError::io(e, &root))? {
+            if let Err(e) = rewrite_filter(&file, old_path, new_path, &mut touched) {
+                eprintln!("[WarlordTools] rename: skipped {}: {}", file.display(), e);
+            }
+        }
+    }
+    for id in temp_rules::remap_paths(old_path, new_path)? {
+        touched.push(TouchedRef {
+            kind: "tempRule".to_string(),
+            location: id,
+            line: None,
+            old_value: old.to_string(),
+            new_value: new.to_string(),
+        });
+    }
+    for name in patches::remap_paths(old_path, new_path)? {
+        touched.push(TouchedRef {
+            kind: "patch".to_string(),
+            location: name,
+            line: None,
+            old_value: old.to_string(),
+            new_value: new.to_string(),
+        });
+    }
+    if leveling::remap_paths(old_path, new_path)? > 0 {
+        touched.push(TouchedRef {
+            kind: "leveling".to_string(),
+            location: "leveling_plan.json".to_string(),
+            line: None,
+            old_value: old.to_string(),
+            new_value: new.to_string(),
+        });
+    }
+    rewrite_settings(old_path, new_path, &mut touched)?;
+
+    Ok(RenameReport { old_path: old.to_string(), new_path: new.to_string(), touched })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_a_renamed_folder() {
+        let root = std::env::temp_dir().join("wt-rename-test");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("parts")).unwrap();
+        fs::write(root.join("parts/alert.mp3"), b"").unwrap();
+        fs::write(root.join("parts/currency.filter"), "Show\n").unwrap();
+        let absolute = root.join("parts/alert.mp3").display().to_string();
+        let main = root.join("main.filter");
+        fs::write(&main, format!("#include \"parts/currency.filter\"\nShow\n    CustomAlertSound \"parts/alert.mp3\" 80\nShow\n    CustomAlertSoundOptional \"{}\"\n    SetFontSize 40\n", absolute)).unwrap();
+
+        let (old, new) = (root.join("parts"), root.join("pieces"));
+        fs::rename(&old, &new).unwrap();
+        let mut touched = Vec::new();
+        rewrite_filter(&main, &old, &new, &mut touched).unwrap();
+
+        let moved = new.join("alert.mp3").display().to_string();
+        assert_eq!(fs::read_to_string(&main).unwrap(), format!("#include \"pieces/currency.filter\"\nShow\n    CustomAlertSound \"pieces/alert.mp3\" 80\nShow\n    CustomAlertSoundOptional \"{}\"\n    SetFontSize 40\n", moved));
+        let found: Vec<(&str, Option<usize>, &str, &str)> = touched.iter().map(|t| (t.kind.as_str(), t.line, t.old_value.as_str(), t.new_value.as_str())).collect();
+        assert_eq!(found, vec![
+            ("include", Some(1), "parts/currency.filter", "pieces/currency.filter"),
+            ("sound", Some(3), "parts/alert.mp3", "pieces/alert.mp3"),
+            ("sound", Some(5), absolute.as_str(), moved.as_str()),
+        ]);
+        assert!(touched.iter().all(|t| t.location == main.display().to_string()));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
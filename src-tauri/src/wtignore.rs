@@ -0,0 +1,107 @@
+//! `.wtignore` at a scan root, in gitignore syntax: one pattern per line, `#` comments, `!`
+//! to re-include, a trailing `/` for directories only, and a leading or inner `/` to anchor
+//! the pattern at the root. The last matching line wins. Ignored directories are not entered.
+
+use crate::glob;
+
+pub const FILE_NAME: &str = ".wtignore";
+
+#[derive(Clone, Debug)]
+struct Pattern {
+    glob: String,
+    negate: bool,
+    dir_only: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<Pattern>,
+}
+
+impl IgnoreRules {
+    pub fn parse(text: &str) -> IgnoreRules {
+        let mut patterns = Vec::new();
+        for line in text.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negate, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let glob = match line.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if line.contains('/') => line.to_string(),
+                None => format!("**/{}", line),
+            };
+            patterns.push(Pattern { glob, negate, dir_only });
+        }
+        IgnoreRules { patterns }
+    }
+
+    pub const fn none() -> IgnoreRules {
+        IgnoreRules { patterns: Vec::new() }
+    }
+
+    /// `relative` is the path below the root, with `/` or `\` separators.
+    pub fn is_ignored(&self, relative: &str, is_dir: bool) -> bool {
+        let relative = relative.replace('\\', "/");
+        self.patterns
+            .iter()
+            .rev()
+            .find(|p| (is_dir || !p.dir_only) && glob::matches(&p.glob, &relative))
+            .is_some_and(|p| !p.negate)
+    }
+
+    /// Like `is_ignored`, but also true below an ignored directory (for paths that were not
+    /// reached by a walk, e.g. from a file watcher).
+    pub fn is_excluded(&self, relative: &str, is_dir: bool) -> bool {
+        let relative = relative.replace('\\', "/");
+        let mut prefix = String::new();
+        for dir in relative.split('/').rev().skip(1).collect::<Vec<_>>().into_iter().rev() {
+            prefix.push_str(dir);
+            if self.is_ignored(&prefix, true) {
+                return true;
+            }
+            prefix.push('/');
+        }
+        self.is_ignored(&relative, is_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_gitignore_rules() {
+        let rules = IgnoreRules::parse("# archive\nbackup/\n/old-leagues\n*.bak.filter\n!keep.bak.filter\nsub/tmp.filter\n");
+        assert!(rules.is_ignored("backup", true));
+        assert!(rules.is_ignored("a/backup", true));
+        assert!(!rules.is_ignored("backup", false));
+        assert!(rules.is_ignored("old-leagues", true));
+        assert!(!rules.is_ignored("a/old-leagues", true));
+        assert!(rules.is_ignored("x/y.bak.filter", false));
+        assert!(!rules.is_ignored("x/keep.bak.filter", false));
+        assert!(rules.is_ignored("sub\\tmp.filter", false));
+        assert!(!rules.is_ignored("a/sub/tmp.filter", false));
+        assert!(rules.is_excluded("a/backup/x.filter", false));
+        assert!(!rules.is_excluded("a/x.filter", false));
+    }
+
+    #[test]
+    fn directory_patterns_do_not_match_inside_names() {
+        let rules = IgnoreRules::parse("backup/\n*.tmp.filter\n");
+        assert!(rules.is_ignored("a/backup", true));
+        assert!(!rules.is_ignored("oldbackup", true));
+        assert!(!rules.is_ignored("a/oldbackup", true));
+        assert!(!rules.is_excluded("oldbackup/x.filter", false));
+        assert!(rules.is_excluded("backup/x.filter", false));
+        assert!(rules.is_ignored("a/x.tmp.filter", false));
+    }
+}
@@ -0,0 +1,18 @@
+// Errors rejected by backend commands: { code, message, path } (see src-tauri/src/error.rs).
+
+export interface WarlordError {
+    code: string;
+    message: string;
+    path?: string | null;
+}
+
+export function isWarlordError(e: unknown): e is WarlordError {
+    return typeof e === 'object' && e !== null && 'code' in e && 'message' in e;
+}
+
+// Text to show for anything a command (or other code) threw
+export function errorMessage(e: unknown): string {
+    if (isWarlordError(e)) return e.message;
+    if (e instanceof Error) return e.message;
+    return String(e);
+}